@@ -0,0 +1,287 @@
+use anyhow::Result;
+use rusqlite::Connection;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::database;
+use crate::models::BackupSummary;
+
+const MANIFEST_NAME: &str = "manifest.json";
+const DB_ENTRY_NAME: &str = "yuki.db";
+const DOCUMENTS_PREFIX: &str = "documents/";
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Tables an imported database must contain before it is allowed to replace local data
+const REQUIRED_TABLES: [&str; 6] = ["documents", "categories", "accounts", "ledger", "settings", "currencies"];
+
+/// Write `db_path` plus every file in `documents_dir` into an AES-256 encrypted zip at `dest`
+pub fn write_bundle(db_path: &Path, documents_dir: &Path, dest: &Path, passphrase: &str) -> Result<BackupSummary> {
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("A passphrase is required to encrypt the backup"));
+    }
+
+    // Snapshot through SQLite so a concurrent write can't leave a torn copy in the bundle
+    let snapshot_path = std::env::temp_dir().join(format!("yuki-backup-{}.db", uuid::Uuid::new_v4()));
+    let conn = Connection::open(db_path)?;
+    conn.execute("VACUUM INTO ?1", [snapshot_path.to_string_lossy()])?;
+    let transaction_count: i64 = conn.query_row("SELECT COUNT(*) FROM ledger", [], |row| row.get(0))?;
+    drop(conn);
+
+    let result = (|| -> Result<BackupSummary> {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .with_aes_encryption(AesMode::Aes256, passphrase);
+        let mut zip = ZipWriter::new(fs::File::create(dest)?);
+
+        let mut document_count = 0;
+        if documents_dir.is_dir() {
+            for entry in fs::read_dir(documents_dir)? {
+                let path = entry?.path();
+                if !path.is_file() {
+                    continue;
+                }
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                zip.start_file(format!("{}{}", DOCUMENTS_PREFIX, name), options)?;
+                zip.write_all(&fs::read(&path)?)?;
+                document_count += 1;
+            }
+        }
+
+        zip.start_file(DB_ENTRY_NAME, options)?;
+        zip.write_all(&fs::read(&snapshot_path)?)?;
+
+        let summary = BackupSummary {
+            transaction_count,
+            document_count,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let manifest = serde_json::json!({
+            "format_version": BUNDLE_FORMAT_VERSION,
+            "summary": summary,
+        });
+        zip.start_file(MANIFEST_NAME, options)?;
+        zip.write_all(manifest.to_string().as_bytes())?;
+
+        zip.finish()?;
+        Ok(summary)
+    })();
+
+    let _ = fs::remove_file(&snapshot_path);
+    if result.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    result
+}
+
+/// Restore a bundle into `data_dir`, replacing `yuki.db` and `documents/`.
+/// The passphrase and the bundled schema are verified against a staging copy
+/// before anything local is touched, and the swap is rolled back on failure.
+pub fn restore_bundle(src: &Path, passphrase: &str, data_dir: &Path) -> Result<BackupSummary> {
+    let mut archive = ZipArchive::new(fs::File::open(src)?)?;
+
+    let manifest: serde_json::Value = {
+        let mut entry = archive
+            .by_name_decrypt(MANIFEST_NAME, passphrase.as_bytes())
+            .map_err(|e| match e {
+                zip::result::ZipError::InvalidPassword => anyhow::anyhow!("Incorrect backup passphrase"),
+                zip::result::ZipError::FileNotFound => anyhow::anyhow!("Not a Yuki backup bundle"),
+                other => anyhow::anyhow!("Failed to read backup: {}", other),
+            })?;
+        let mut json = String::new();
+        entry.read_to_string(&mut json)?;
+        serde_json::from_str(&json)?
+    };
+    if manifest["format_version"].as_u64() != Some(BUNDLE_FORMAT_VERSION as u64) {
+        return Err(anyhow::anyhow!("Unsupported backup format: {}", manifest["format_version"]));
+    }
+
+    let staging_dir = data_dir.join(".import-staging");
+    let _ = fs::remove_dir_all(&staging_dir);
+    fs::create_dir_all(staging_dir.join("documents"))?;
+
+    let staged = (|| -> Result<BackupSummary> {
+        let mut document_count = 0;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index_decrypt(i, passphrase.as_bytes())?;
+            // enclosed_name rejects absolute paths and ".." so entries can't escape staging
+            let name = match entry.enclosed_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let target = match name.to_string_lossy().as_ref() {
+                DB_ENTRY_NAME => staging_dir.join(DB_ENTRY_NAME),
+                n if n.starts_with(DOCUMENTS_PREFIX) => {
+                    document_count += 1;
+                    staging_dir.join("documents").join(name.file_name().unwrap_or_default())
+                }
+                _ => continue,
+            };
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            fs::write(target, data)?;
+        }
+
+        let staged_db = staging_dir.join(DB_ENTRY_NAME);
+        if !staged_db.exists() {
+            return Err(anyhow::anyhow!("Backup does not contain a database"));
+        }
+
+        let conn = Connection::open(&staged_db)?;
+        for table in REQUIRED_TABLES {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                [table],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Err(anyhow::anyhow!("Backup database is missing the '{}' table", table));
+            }
+        }
+
+        // Bring an older bundle up to the current schema before it goes live
+        database::init_schema(&conn)?;
+
+        // Stored paths point at the old machine's data dir
+        let documents_dir = data_dir.join("documents");
+        let rows: Vec<(String, String)> = {
+            let mut stmt = conn.prepare("SELECT id, filepath FROM documents")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            rows
+        };
+        for (id, old_path) in rows {
+            let file_name = Path::new(&old_path).file_name().unwrap_or_default().to_owned();
+            let new_path = documents_dir.join(file_name);
+            conn.execute(
+                "UPDATE documents SET filepath = ?1 WHERE id = ?2",
+                [new_path.to_string_lossy().as_ref(), id.as_str()],
+            )?;
+        }
+
+        let transaction_count: i64 = conn.query_row("SELECT COUNT(*) FROM ledger", [], |row| row.get(0))?;
+        Ok(BackupSummary {
+            transaction_count,
+            document_count,
+            created_at: manifest["summary"]["created_at"].as_str().unwrap_or_default().to_string(),
+        })
+    })();
+
+    let summary = match staged {
+        Ok(summary) => summary,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(e);
+        }
+    };
+
+    swap_into_place(&staging_dir, data_dir)?;
+    let _ = fs::remove_dir_all(&staging_dir);
+    Ok(summary)
+}
+
+/// Move the staged db and documents over the live ones, keeping the old copies
+/// until both renames have succeeded
+fn swap_into_place(staging_dir: &Path, data_dir: &Path) -> Result<()> {
+    let pairs: [(PathBuf, PathBuf); 2] = [
+        (staging_dir.join(DB_ENTRY_NAME), data_dir.join(DB_ENTRY_NAME)),
+        (staging_dir.join("documents"), data_dir.join("documents")),
+    ];
+
+    let mut moved: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
+    for (staged, live) in &pairs {
+        let previous = if live.exists() {
+            let backup = live.with_extension("pre-import");
+            let _ = fs::remove_dir_all(&backup);
+            let _ = fs::remove_file(&backup);
+            fs::rename(live, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        if let Err(e) = fs::rename(staged, live) {
+            // Put back whatever was already swapped so local data stays consistent
+            if let Some(backup) = &previous {
+                let _ = fs::rename(backup, live);
+            }
+            for (live, backup) in moved.iter().rev() {
+                let _ = fs::remove_dir_all(live);
+                let _ = fs::remove_file(live);
+                if let Some(backup) = backup {
+                    let _ = fs::rename(backup, live);
+                }
+            }
+            return Err(anyhow::anyhow!("Failed to replace local data: {}", e));
+        }
+        moved.push((live.clone(), previous));
+    }
+
+    for (_, backup) in moved {
+        if let Some(backup) = backup {
+            let _ = fs::remove_dir_all(&backup);
+            let _ = fs::remove_file(&backup);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_import_round_trip_preserves_transaction_and_document() {
+        let root = std::env::temp_dir().join(format!("yuki-backup-test-{}", uuid::Uuid::new_v4()));
+        let source_dir = root.join("source");
+        let target_dir = root.join("target");
+        fs::create_dir_all(source_dir.join("documents")).unwrap();
+        fs::create_dir_all(&target_dir).unwrap();
+
+        let doc_path = source_dir.join("documents").join("doc1_statement.pdf");
+        fs::write(&doc_path, b"%PDF-1.4 statement bytes").unwrap();
+
+        let conn = Connection::open(source_dir.join("yuki.db")).unwrap();
+        database::init_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES ('doc1', 'statement.pdf', ?1, 'application/pdf', 'abc', '2025-01-01')",
+            [doc_path.to_string_lossy()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at)
+             VALUES ('t1', 'doc1', 'default', '2025-01-02', 'Coffee', -4.5, 'KES', 'dining', NULL, NULL, 'document', '2025-01-02')",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        let bundle = root.join("backup.yuki");
+        let written = write_bundle(&source_dir.join("yuki.db"), &source_dir.join("documents"), &bundle, "hunter2").unwrap();
+        assert_eq!(written.transaction_count, 1);
+        assert_eq!(written.document_count, 1);
+
+        assert!(restore_bundle(&bundle, "wrong", &target_dir).is_err());
+        assert!(!target_dir.join("yuki.db").exists());
+
+        let restored = restore_bundle(&bundle, "hunter2", &target_dir).unwrap();
+        assert_eq!(restored.transaction_count, 1);
+
+        let conn = Connection::open(target_dir.join("yuki.db")).unwrap();
+        let description: String = conn
+            .query_row("SELECT description FROM ledger WHERE id = 't1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(description, "Coffee");
+        let filepath: String = conn
+            .query_row("SELECT filepath FROM documents WHERE id = 'doc1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(fs::read(&filepath).unwrap(), b"%PDF-1.4 statement bytes");
+        assert!(filepath.starts_with(target_dir.to_string_lossy().as_ref()));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::error::Error;
+use std::path::Path;
+
+use crate::models::ChartContent;
+
+const WIDTH: u32 = 1000;
+const HEIGHT: u32 = 700;
+
+// Same palette as ChartCardContent.tsx so exported charts match the app
+const PALETTE: [RGBColor; 10] = [
+    RGBColor(0x3b, 0x82, 0xf6),
+    RGBColor(0x22, 0xc5, 0x5e),
+    RGBColor(0xf5, 0x9e, 0x0b),
+    RGBColor(0xef, 0x44, 0x44),
+    RGBColor(0x8b, 0x5c, 0xf6),
+    RGBColor(0xec, 0x48, 0x99),
+    RGBColor(0x14, 0xb8, 0xa6),
+    RGBColor(0xf9, 0x73, 0x16),
+    RGBColor(0x06, 0xb6, 0xd4),
+    RGBColor(0x84, 0xcc, 0x16),
+];
+const PRIMARY: RGBColor = PALETTE[0];
+const GRID: RGBColor = RGBColor(0xe4, 0xe4, 0xe7);
+const AXIS_TEXT: RGBColor = RGBColor(0x71, 0x71, 0x7a);
+
+/// Output formats accepted by `render_chart_image`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "svg" => Some(ImageFormat::Svg),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Draw `chart` into `dest` as PNG or SVG
+pub fn render(chart: &ChartContent, format: ImageFormat, dest: &Path) -> Result<()> {
+    if chart.data.is_empty() {
+        return Err(anyhow::anyhow!("Chart '{}' has no data to draw", chart.title));
+    }
+
+    let drawn = match format {
+        ImageFormat::Png => {
+            let root = BitMapBackend::new(dest, (WIDTH, HEIGHT)).into_drawing_area();
+            draw(&root, chart).and_then(|_| root.present().map_err(Into::into))
+        }
+        ImageFormat::Svg => {
+            let root = SVGBackend::new(dest, (WIDTH, HEIGHT)).into_drawing_area();
+            draw(&root, chart).and_then(|_| root.present().map_err(Into::into))
+        }
+    };
+    drawn.map_err(|e| anyhow::anyhow!("Failed to draw chart: {}", e))
+}
+
+fn draw<DB>(root: &DrawingArea<DB, Shift>, chart: &ChartContent) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    let root = root.titled(&chart.title, ("sans-serif", 32))?;
+
+    let labels: Vec<&str> = chart.data.iter().map(|p| p.label.as_str()).collect();
+    let values: Vec<f64> = chart.data.iter().map(|p| p.value).collect();
+
+    match chart.chart_type.as_str() {
+        "pie" => draw_pie(&root, &labels, &values),
+        "bar" => draw_bar(&root, &labels, &values),
+        "line" => draw_line(&root, &labels, &values, false),
+        "area" => draw_line(&root, &labels, &values, true),
+        other => Err(format!("Unsupported chart type '{}'", other).into()),
+    }
+}
+
+fn draw_pie<DB>(root: &DrawingArea<DB, Shift>, labels: &[&str], values: &[f64]) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    // Slices are shares of a whole, so signs (expenses are negative) don't matter
+    let sizes: Vec<f64> = values.iter().map(|v| v.abs()).collect();
+    if sizes.iter().sum::<f64>() <= 0.0 {
+        return Err("A pie chart needs at least one non-zero value".into());
+    }
+    let colors: Vec<RGBColor> = (0..sizes.len()).map(|i| PALETTE[i % PALETTE.len()]).collect();
+
+    let (width, height) = root.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = f64::from(width.min(height)) * 0.35;
+
+    let mut pie = Pie::new(&center, &radius, &sizes, &colors, labels);
+    pie.label_style(("sans-serif", 18).into_font().color(&AXIS_TEXT));
+    pie.percentages(("sans-serif", 16).into_font().color(&WHITE));
+    root.draw(&pie)?;
+    Ok(())
+}
+
+/// Y range covering zero and every value, with a little headroom
+fn value_range(values: &[f64]) -> std::ops::Range<f64> {
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    let min = values.iter().cloned().fold(0.0, f64::min);
+    let pad = ((max - min) * 0.1).max(1.0);
+    (if min < 0.0 { min - pad } else { 0.0 })..(max + pad)
+}
+
+fn draw_bar<DB>(root: &DrawingArea<DB, Shift>, labels: &[&str], values: &[f64]) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    if values.is_empty() {
+        return Err("A bar chart needs at least one value".into());
+    }
+
+    // plotters treats an integer range as inclusive, so 0..len-1 is one
+    // segment per bar (and 0..0 a single one)
+    let last = values.len() - 1;
+    let mut chart = ChartBuilder::on(root)
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(80)
+        .build_cartesian_2d((0..last).into_segmented(), value_range(values))?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .light_line_style(GRID)
+        .label_style(("sans-serif", 16).into_font().color(&AXIS_TEXT))
+        .x_labels(labels.len())
+        .x_label_formatter(&|segment| match segment {
+            SegmentValue::CenterOf(i) => labels.get(*i).map(|l| l.to_string()).unwrap_or_default(),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    chart.draw_series(
+        Histogram::vertical(&chart)
+            .style(PRIMARY.filled())
+            .margin(12)
+            .data(values.iter().enumerate().map(|(i, v)| (i, *v))),
+    )?;
+    Ok(())
+}
+
+fn draw_line<DB>(root: &DrawingArea<DB, Shift>, labels: &[&str], values: &[f64], filled: bool) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    // A single point still needs a non-empty x range
+    let last = (values.len() - 1).max(1);
+    let mut chart = ChartBuilder::on(root)
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(80)
+        .build_cartesian_2d(0..last, value_range(values))?;
+
+    chart
+        .configure_mesh()
+        .light_line_style(GRID)
+        .label_style(("sans-serif", 16).into_font().color(&AXIS_TEXT))
+        .x_labels(labels.len())
+        .x_label_formatter(&|i| labels.get(*i).map(|l| l.to_string()).unwrap_or_default())
+        .draw()?;
+
+    let points = values.iter().enumerate().map(|(i, v)| (i, *v));
+    if filled {
+        chart.draw_series(AreaSeries::new(points.clone(), 0.0, PRIMARY.mix(0.3)).border_style(PRIMARY.stroke_width(3)))?;
+    } else {
+        chart.draw_series(LineSeries::new(points.clone(), PRIMARY.stroke_width(3)))?;
+    }
+    chart.draw_series(points.map(|p| Circle::new(p, 5, PRIMARY.filled())))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChartDataPoint;
+
+    fn spending_chart(chart_type: &str) -> ChartContent {
+        ChartContent {
+            chart_type: chart_type.to_string(),
+            title: "Spending by category".to_string(),
+            data: vec![
+                ChartDataPoint { label: "Groceries".to_string(), value: 12500.0 },
+                ChartDataPoint { label: "Dining".to_string(), value: 4300.0 },
+                ChartDataPoint { label: "Transport".to_string(), value: 2750.0 },
+            ],
+            caption: None,
+        }
+    }
+
+    #[test]
+    fn pie_chart_renders_to_png_and_svg() {
+        let dir = std::env::temp_dir().join(format!("yuki-chart-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for format in [ImageFormat::Png, ImageFormat::Svg] {
+            let dest = dir.join(format!("pie.{}", format.extension()));
+            render(&spending_chart("pie"), format, &dest).unwrap();
+            assert!(std::fs::metadata(&dest).unwrap().len() > 0);
+        }
+        let png = std::fs::read(dir.join("pie.png")).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        let svg = std::fs::read_to_string(dir.join("pie.svg")).unwrap();
+        assert!(svg.contains("<svg") && svg.contains("Groceries"));
+
+        for chart_type in ["bar", "line", "area"] {
+            let dest = dir.join(format!("{}.svg", chart_type));
+            render(&spending_chart(chart_type), ImageFormat::Svg, &dest).unwrap();
+            assert!(std::fs::metadata(&dest).unwrap().len() > 0);
+        }
+        assert!(render(&spending_chart("radar"), ImageFormat::Svg, &dir.join("radar.svg")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn single_bar_chart_draws_its_bar() {
+        let dir = std::env::temp_dir().join(format!("yuki-chart-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut chart = spending_chart("bar");
+        chart.data.truncate(1);
+        let dest = dir.join("bar.svg");
+        render(&chart, ImageFormat::Svg, &dest).unwrap();
+        let svg = std::fs::read_to_string(&dest).unwrap();
+        assert!(svg.contains("Groceries"));
+
+        // The one bar spans the whole plot rather than sharing it with an empty slot
+        let bar = svg.lines().find(|l| l.starts_with("<rect") && l.contains("#3B82F6")).unwrap();
+        let width: u32 = bar.split("width=\"").nth(1).unwrap().split('"').next().unwrap().parse().unwrap();
+        assert!(width > 700, "{}", bar);
+
+        let empty_dest = dir.join("empty.svg");
+        let empty = SVGBackend::new(&empty_dest, (WIDTH, HEIGHT)).into_drawing_area();
+        assert!(draw_bar(&empty, &[], &[]).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
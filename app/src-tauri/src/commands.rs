@@ -1,14 +1,24 @@
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Mutex;
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 
+use crate::backup;
+use crate::chart;
+use crate::csv;
 use crate::database;
+use crate::error::YukiError;
 use crate::llm;
+use crate::logging;
 use crate::models::*;
+use crate::ofx;
+use crate::plaintext;
+use crate::webhook;
 
-// Global conversation state - stores the current session ID and recent messages
+// Conversation state - the current session ID for each window, keyed by window label
+// so two open chat views never write into each other's history
 lazy_static::lazy_static! {
-    static ref CURRENT_SESSION: Mutex<Option<String>> = Mutex::new(None);
+    static ref CURRENT_SESSIONS: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
 }
 
 // ============================================================================
@@ -16,8 +26,8 @@ lazy_static::lazy_static! {
 // ============================================================================
 
 #[tauri::command]
-pub async fn has_llm_provider(app: AppHandle) -> Result<bool, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn has_llm_provider(app: AppHandle) -> Result<bool, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
     let result: Result<String, _> = conn.query_row(
         "SELECT value FROM settings WHERE key = 'provider'",
         [],
@@ -27,9 +37,12 @@ pub async fn has_llm_provider(app: AppHandle) -> Result<bool, String> {
 }
 
 #[tauri::command]
-pub async fn get_settings(app: AppHandle) -> Result<Settings, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn get_settings(app: AppHandle) -> Result<Settings, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    Ok(load_settings(&conn))
+}
 
+fn load_settings(conn: &rusqlite::Connection) -> Settings {
     let provider: Option<LLMProvider> = conn
         .query_row(
             "SELECT value FROM settings WHERE key = 'provider'",
@@ -57,50 +70,238 @@ pub async fn get_settings(app: AppHandle) -> Result<Settings, String> {
         )
         .unwrap_or_else(|_| "system".to_string());
 
-    Ok(Settings {
+    let log_level: String = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'log_level'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| "info".to_string());
+
+    let log_bodies: bool = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'log_bodies'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .map(|v| v != "false")
+        .unwrap_or(true);
+
+    let local_only: bool = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'local_only'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .map(|v| v == "true")
+        .unwrap_or(false);
+
+    let optional_setting = |key: &str| -> Option<String> {
+        conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .ok()
+    };
+
+    Settings {
         provider,
         default_currency,
         theme,
-    })
+        log_level,
+        log_bodies,
+        local_only,
+        import_webhook_url: optional_setting("import_webhook_url"),
+        import_webhook_secret: optional_setting("import_webhook_secret"),
+        import_min_amount: saved_import_min_amount(conn),
+    }
 }
 
+/// Save the settings the frontend sent. Fields it leaves out keep their stored
+/// values, so a partial update (e.g. the setup wizard's provider, currency and
+/// theme) doesn't wipe the rest.
 #[tauri::command]
-pub async fn save_settings(app: AppHandle, settings: Settings) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn save_settings(app: AppHandle, settings: serde_json::Value) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let settings = merge_settings(load_settings(&conn), settings)?;
+    store_settings(&conn, &settings)?;
+
+    logging::apply_settings(&settings.log_level, settings.log_bodies);
+    llm::set_local_only(settings.local_only);
+
+    Ok(())
+}
+
+/// Overlay the fields present in `incoming` on the stored settings. An explicit
+/// `null` clears an optional field; a missing one leaves it as it was.
+fn merge_settings(stored: Settings, incoming: serde_json::Value) -> Result<Settings, YukiError> {
+    let serde_json::Value::Object(incoming) = incoming else {
+        return Err(YukiError::Validation("Settings must be an object".to_string()));
+    };
+    let mut merged = serde_json::to_value(stored)?;
+    if let serde_json::Value::Object(fields) = &mut merged {
+        fields.extend(incoming);
+    }
+    serde_json::from_value(merged).map_err(|e| YukiError::Validation(format!("Invalid settings: {}", e)))
+}
+
+fn store_settings(conn: &rusqlite::Connection, settings: &Settings) -> Result<(), YukiError> {
+    if logging::parse_log_level(&settings.log_level).is_none() {
+        return Err(YukiError::Validation(format!("Invalid log level '{}'", settings.log_level)));
+    }
+    if let Some(provider) = &settings.provider {
+        llm::check_provider_allowed(provider, settings.local_only).map_err(YukiError::validation)?;
+    }
+    if let Some(min) = settings.import_min_amount {
+        if !min.is_finite() || min < 0.0 {
+            return Err(YukiError::Validation(format!("Invalid import minimum amount {}", min)));
+        }
+    }
 
     if let Some(provider) = &settings.provider {
-        let provider_json = serde_json::to_string(provider).map_err(|e| e.to_string())?;
+        let provider_json = serde_json::to_string(provider)?;
         conn.execute(
             "INSERT OR REPLACE INTO settings (key, value) VALUES ('provider', ?1)",
             [&provider_json],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     conn.execute(
         "INSERT OR REPLACE INTO settings (key, value) VALUES ('default_currency', ?1)",
         [&settings.default_currency],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     conn.execute(
         "INSERT OR REPLACE INTO settings (key, value) VALUES ('theme', ?1)",
         [&settings.theme],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('log_level', ?1)",
+        [&settings.log_level],
+    )?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('log_bodies', ?1)",
+        [settings.log_bodies.to_string()],
+    )?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('local_only', ?1)",
+        [settings.local_only.to_string()],
+    )?;
+
+    for (key, value) in [
+        ("import_webhook_url", &settings.import_webhook_url),
+        ("import_webhook_secret", &settings.import_webhook_secret),
+    ] {
+        match value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+            Some(value) => conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                [key, value],
+            )?,
+            None => conn.execute("DELETE FROM settings WHERE key = ?1", [key])?,
+        };
+    }
+
+    match settings.import_min_amount.filter(|min| *min > 0.0) {
+        Some(min) => conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('import_min_amount', ?1)",
+            [min.to_string()],
+        )?,
+        None => conn.execute("DELETE FROM settings WHERE key = 'import_min_amount'", [])?,
+    };
 
     Ok(())
 }
 
+/// The effective settings and the configuration around them as pretty-printed
+/// JSON for a bug report, with the API key and webhook secret masked
+#[tauri::command]
+pub async fn export_settings_redacted(app: AppHandle) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let report = settings_report(&conn)?;
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+fn settings_report(conn: &rusqlite::Connection) -> Result<SettingsReport, YukiError> {
+    let mut settings = load_settings(conn);
+    if let Some(provider) = &mut settings.provider {
+        provider.api_key = provider.api_key.as_deref().map(mask_secret);
+        provider.endpoint = logging::redact(&provider.endpoint);
+    }
+    settings.import_webhook_url = settings.import_webhook_url.as_deref().map(url_origin);
+    settings.import_webhook_secret = settings.import_webhook_secret.as_deref().map(mask_secret);
+
+    let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0));
+    Ok(SettingsReport {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        settings,
+        currencies: all_currencies(conn)?,
+        category_count: count("SELECT COUNT(*) FROM categories")?,
+        custom_category_count: count("SELECT COUNT(*) FROM categories WHERE is_default = 0")?,
+        account_count: count("SELECT COUNT(*) FROM accounts")?,
+        transaction_count: count("SELECT COUNT(*) FROM ledger")?,
+    })
+}
+
+/// Enough of a secret to tell two keys apart: the last four characters of a long
+/// one, nothing of a short one
+/// Just the scheme and host of a URL, since webhook paths and queries often
+/// carry a token (e.g. `https://hooks.slack.com/services/T0/B0/xyz`)
+fn url_origin(url: &str) -> String {
+    match reqwest::Url::parse(url.trim()) {
+        Ok(parsed) => match (parsed.host_str(), parsed.port()) {
+            (Some(host), Some(port)) => format!("{}://{}:{}/…", parsed.scheme(), host, port),
+            (Some(host), None) => format!("{}://{}/…", parsed.scheme(), host),
+            (None, _) => "****".to_string(),
+        },
+        Err(_) => "****".to_string(),
+    }
+}
+
+fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.trim().chars().collect();
+    if chars.len() >= 12 {
+        format!("****{}", chars[chars.len() - 4..].iter().collect::<String>())
+    } else {
+        "****".to_string()
+    }
+}
+
+/// Move the database and documents to `path` (e.g. an encrypted or synced folder)
+/// and keep using them from there. Returns the resolved directory.
+#[tauri::command]
+pub async fn set_data_directory(app: AppHandle, path: String) -> Result<String, YukiError> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(YukiError::Validation("Data directory must not be empty".to_string()));
+    }
+
+    let data_dir = database::set_data_dir(&app, std::path::Path::new(path)).map_err(YukiError::validation)?;
+    Ok(data_dir.to_string_lossy().to_string())
+}
+
+/// Default endpoint, model and capabilities per provider type, for prefilling setup
+#[tauri::command]
+pub async fn get_provider_presets() -> Result<Vec<ProviderPreset>, YukiError> {
+    Ok(llm::provider_presets())
+}
+
+/// What the configured provider supports
+#[tauri::command]
+pub async fn get_provider_capabilities(app: AppHandle) -> Result<ProviderCapabilities, YukiError> {
+    let provider = get_settings(app).await?.provider.ok_or(YukiError::NoProvider)?;
+    Ok(llm::provider_capabilities(&provider.provider_type))
+}
+
 #[tauri::command]
 pub async fn list_models(
     provider_type: String,
     endpoint: String,
     api_key: Option<String>,
-) -> Result<Vec<String>, String> {
-    llm::list_provider_models(&provider_type, &endpoint, api_key.as_deref())
+    search: Option<String>,
+) -> Result<Vec<String>, YukiError> {
+    llm::list_provider_models(&provider_type, &endpoint, api_key.as_deref(), search.as_deref())
         .await
-        .map_err(|e| e.to_string())
+        .map_err(YukiError::llm)
 }
 
 #[tauri::command]
@@ -109,47 +310,173 @@ pub async fn test_llm_connection(
     endpoint: String,
     api_key: Option<String>,
     model: String,
-) -> Result<(), String> {
+) -> Result<(), YukiError> {
     let provider = LLMProvider {
+        is_local: llm::provider_preset(&provider_type).is_some_and(|p| p.is_local),
         provider_type,
         name: "Test".to_string(),
         endpoint,
         api_key,
         model,
-        is_local: false,
+        requests_per_minute: None,
     };
 
-    llm::call_llm(&provider, "Say hello", None)
+    llm::call_llm(&provider, "Say hello", None, None)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(YukiError::llm)?;
 
     Ok(())
 }
 
+/// Check the database, provider, categories, default account and currency in one call
+#[tauri::command]
+pub async fn run_diagnostics(app: AppHandle) -> Result<DiagnosticsReport, YukiError> {
+    let mut checks = match database::get_connection(&app) {
+        Ok(conn) => local_diagnostics(&conn),
+        Err(e) => vec![diagnostic("database", "fail", format!("Cannot open database: {}", e))],
+    };
+
+    // Only reach out to the provider once we know one is configured
+    let provider_configured = checks.iter().any(|c| c.name == "provider" && c.status == "pass");
+    if provider_configured {
+        if let Some(provider) = get_settings(app.clone()).await?.provider {
+            let result = test_llm_connection(
+                provider.provider_type.clone(),
+                provider.endpoint.clone(),
+                provider.api_key.clone(),
+                provider.model.clone(),
+            )
+            .await;
+            if let Some(check) = checks.iter_mut().find(|c| c.name == "provider") {
+                *check = match result {
+                    Ok(()) => diagnostic("provider", "pass", format!("{} ({}) responded", provider.name, provider.model)),
+                    Err(e) => diagnostic("provider", "fail", format!("{} is configured but the connection failed: {}", provider.name, e)),
+                };
+            }
+        }
+    }
+
+    Ok(diagnostics_report(checks))
+}
+
+fn diagnostic(name: &str, status: &str, message: String) -> DiagnosticCheck {
+    DiagnosticCheck {
+        name: name.to_string(),
+        status: status.to_string(),
+        message,
+    }
+}
+
+fn diagnostics_report(checks: Vec<DiagnosticCheck>) -> DiagnosticsReport {
+    let status = ["fail", "warn"]
+        .into_iter()
+        .find(|level| checks.iter().any(|c| c.status == *level))
+        .unwrap_or("pass")
+        .to_string();
+    DiagnosticsReport { status, checks }
+}
+
+/// Checks that only need the database
+fn local_diagnostics(conn: &rusqlite::Connection) -> Vec<DiagnosticCheck> {
+    let count = |sql: &str| -> Result<i64, YukiError> {
+        conn.query_row(sql, [], |row| row.get(0)).map_err(YukiError::from)
+    };
+    let mut checks = Vec::new();
+
+    let missing_tables: Vec<&str> = ["documents", "ledger", "categories", "accounts", "currencies", "settings"]
+        .into_iter()
+        .filter(|table| {
+            count(&format!("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '{}'", table))
+                .map(|n| n == 0)
+                .unwrap_or(true)
+        })
+        .collect();
+    checks.push(if !missing_tables.is_empty() {
+        diagnostic("database", "fail", format!("Missing tables: {}", missing_tables.join(", ")))
+    } else if let missing @ [_, ..] = database::missing_schema(conn).as_slice() {
+        diagnostic(
+            "database",
+            "warn",
+            format!("Database is reachable but not fully migrated (missing {}); restart Yuki", missing.join(", ")),
+        )
+    } else {
+        diagnostic("database", "pass", "Database reachable and up to date".to_string())
+    });
+
+    let provider_json: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = 'provider'", [], |row| row.get(0))
+        .ok();
+    checks.push(match provider_json.map(|json| serde_json::from_str::<LLMProvider>(&json)) {
+        None => diagnostic("provider", "fail", "No LLM provider configured".to_string()),
+        Some(Err(e)) => diagnostic("provider", "fail", format!("Saved provider settings are unreadable: {}", e)),
+        Some(Ok(provider)) => diagnostic("provider", "pass", format!("{} configured", provider.name)),
+    });
+
+    checks.push(match count("SELECT COUNT(*) FROM categories") {
+        Ok(0) => diagnostic("categories", "fail", "No categories exist".to_string()),
+        Ok(n) => diagnostic("categories", "pass", format!("{} categories", n)),
+        Err(e) => diagnostic("categories", "fail", e.to_string()),
+    });
+
+    checks.push(match count("SELECT COUNT(*) FROM accounts WHERE id = 'default' OR is_default = 1") {
+        Ok(0) => diagnostic("default_account", "fail", "No default account".to_string()),
+        Ok(_) => diagnostic("default_account", "pass", "Default account present".to_string()),
+        Err(e) => diagnostic("default_account", "fail", e.to_string()),
+    });
+
+    let default_currency: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = 'default_currency'", [], |row| row.get(0))
+        .ok();
+    checks.push(match default_currency {
+        None => diagnostic("default_currency", "warn", "No default currency set; KES is assumed".to_string()),
+        Some(code) => {
+            let known: bool = conn
+                .query_row("SELECT EXISTS(SELECT 1 FROM currencies WHERE code = ?1)", [&code], |row| row.get(0))
+                .unwrap_or(false);
+            if known {
+                diagnostic("default_currency", "pass", format!("Default currency is {}", code))
+            } else {
+                diagnostic("default_currency", "fail", format!("Default currency {} is not in the currency list", code))
+            }
+        }
+    });
+
+    checks
+}
+
 // ============================================================================
 // Document Commands
 // ============================================================================
 
+/// Store an uploaded file under the documents folder. Fails with
+/// `AlreadyImported` when a stored document has the same bytes, unless
+/// `allow_duplicate` is set to import it again anyway.
 #[tauri::command]
 pub async fn save_uploaded_file(
     app: AppHandle,
     filename: String,
     document_id: String,
     data: Vec<u8>,
-) -> Result<String, String> {
-    let data_dir = database::get_data_dir(&app).map_err(|e| e.to_string())?;
+    allow_duplicate: Option<bool>,
+) -> Result<String, YukiError> {
+    if !allow_duplicate.unwrap_or(false) {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+        check_not_imported(&conn, &data)?;
+    }
+
+    let data_dir = database::get_data_dir(&app).map_err(YukiError::database)?;
     let documents_dir = data_dir.join("documents");
-    fs::create_dir_all(&documents_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&documents_dir)?;
 
     let file_path = documents_dir.join(format!("{}_{}", document_id, filename));
-    fs::write(&file_path, &data).map_err(|e| e.to_string())?;
+    fs::write(&file_path, &data)?;
 
     Ok(file_path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-pub async fn save_document(app: AppHandle, document: Document) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn save_document(app: AppHandle, document: Document) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
 
     conn.execute(
         "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
@@ -161,19 +488,17 @@ pub async fn save_document(app: AppHandle, document: Document) -> Result<(), Str
             &document.hash,
             &document.uploaded_at,
         ],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_all_documents(app: AppHandle) -> Result<Vec<Document>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn get_all_documents(app: AppHandle) -> Result<Vec<Document>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
 
     let mut stmt = conn
-        .prepare("SELECT id, filename, filepath, filetype, hash, uploaded_at FROM documents ORDER BY uploaded_at DESC")
-        .map_err(|e| e.to_string())?;
+        .prepare("SELECT id, filename, filepath, filetype, hash, uploaded_at FROM documents ORDER BY uploaded_at DESC")?;
 
     let documents = stmt
         .query_map([], |row| {
@@ -185,8 +510,7 @@ pub async fn get_all_documents(app: AppHandle) -> Result<Vec<Document>, String>
                 hash: row.get(4)?,
                 uploaded_at: row.get(5)?,
             })
-        })
-        .map_err(|e| e.to_string())?
+        })?
         .filter_map(|r| r.ok())
         .collect();
 
@@ -194,34 +518,477 @@ pub async fn get_all_documents(app: AppHandle) -> Result<Vec<Document>, String>
 }
 
 #[tauri::command]
-pub async fn delete_document(app: AppHandle, document_id: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn delete_document(app: AppHandle, document_id: String) -> Result<(), YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let filepath = delete_document_rows(&mut conn, &document_id)?;
+
+    // Delete file from disk
+    if let Some(path) = filepath {
+        let _ = fs::remove_file(path);
+    }
+
+    Ok(())
+}
+
+/// Remove a document and the ledger rows that came from it, returning its file
+/// path. Rows it was only attached to afterwards are unlinked and kept.
+fn delete_document_rows(conn: &mut rusqlite::Connection, document_id: &str) -> Result<Option<String>, YukiError> {
+    let tx = conn.transaction()?;
+    let filepath: Option<String> = tx
+        .query_row("SELECT filepath FROM documents WHERE id = ?1", [document_id], |row| row.get(0))
+        .ok();
+
+    tx.execute(
+        "UPDATE ledger SET document_id = NULL, document_attached = 0 WHERE document_id = ?1 AND document_attached = 1",
+        [document_id],
+    )?;
+    // Cascades to the ledger rows imported from it
+    tx.execute("DELETE FROM document_text WHERE document_id = ?1", [document_id])?;
+    tx.execute("DELETE FROM documents WHERE id = ?1", [document_id])?;
+    tx.commit()?;
+
+    Ok(filepath)
+}
+
+/// Read institution, account and statement period from a statement's header and
+/// suggest the existing account it belongs to
+#[tauri::command]
+pub async fn detect_statement_metadata(app: AppHandle, path: String) -> Result<StatementMetadata, YukiError> {
+    let lower = path.to_lowercase();
+    let text = if lower.ends_with(".pdf") {
+        // Uploaded documents reuse (or fill) the text cache
+        let extraction = {
+            let conn = database::get_connection(&app).map_err(YukiError::database)?;
+            match document_by_path(&conn, &path)? {
+                Some(document) => cached_pdf_text(&conn, &document, pdf_extraction)?,
+                None => pdf_extraction(&fs::read(&path)?),
+            }
+        };
+        if extraction.is_scanned {
+            None
+        } else {
+            Some(extraction.text)
+        }
+    } else if lower.ends_with(".csv") || lower.ends_with(".txt") {
+        Some(fs::read_to_string(&path)?)
+    } else {
+        None
+    };
+
+    let provider = get_settings(app.clone()).await?.provider;
+
+    let mut metadata = match (&text, &provider) {
+        (Some(text), provider) => {
+            let mut metadata = statement_metadata_from_text(text);
+            let incomplete = metadata.institution.is_none()
+                || metadata.account_last4.is_none()
+                || metadata.period_start.is_none();
+            if let (true, Some(provider)) = (incomplete, provider) {
+                // The header is all that's needed; keep the prompt small
+                let header: String = text.chars().take(3000).collect();
+                match llm::detect_statement_metadata_with_llm(provider, &header).await {
+                    Ok(detected) => fill_missing_metadata(&mut metadata, detected),
+                    Err(e) => log::warn!("Statement metadata LLM fallback failed: {}", e),
+                }
+            }
+            metadata
+        }
+        (None, Some(provider)) => llm::detect_statement_metadata_with_vision(provider, &path)
+            .await
+            .map_err(YukiError::llm)?,
+        (None, None) => return Err(YukiError::NoProvider),
+    };
+
+    if let Some(institution) = &metadata.institution {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+        metadata.suggested_account_id = suggest_account_for_institution(&conn, institution)?;
+    }
+
+    Ok(metadata)
+}
+
+/// Rough size and price of parsing a document with the configured provider, so
+/// the UI can warn before an expensive parse. Text is estimated at four characters
+/// per token; scanned PDFs and images go to the vision model page by page.
+#[tauri::command]
+pub async fn estimate_parse_cost(app: AppHandle, path: String) -> Result<ParseCostEstimate, YukiError> {
+    let lower = path.to_lowercase();
+    let (text, page_count) = if lower.ends_with(".pdf") {
+        let bytes = fs::read(&path)?;
+        let extraction = {
+            let conn = database::get_connection(&app).map_err(YukiError::database)?;
+            match document_by_path(&conn, &path)? {
+                Some(document) => cached_pdf_text(&conn, &document, pdf_extraction)?,
+                None => pdf_extraction(&bytes),
+            }
+        };
+        let page_count = lopdf::Document::load_mem(&bytes).map(|pdf| pdf.get_pages().len()).unwrap_or(1);
+        ((!extraction.is_scanned).then_some(extraction.text), page_count)
+    } else if lower.ends_with(".csv") || lower.ends_with(".txt") {
+        (Some(fs::read_to_string(&path)?), 1)
+    } else {
+        (None, 1)
+    };
+
+    let pricing = get_settings(app).await?.provider.as_ref().and_then(llm::model_pricing);
+    Ok(parse_cost_estimate(text.as_deref(), page_count, pricing))
+}
+
+/// Tokens a vision model spends reading one page image
+const VISION_TOKENS_PER_PAGE: u64 = 1_600;
+/// Transactions JSON written back for one scanned page
+const VISION_OUTPUT_TOKENS_PER_PAGE: u64 = 500;
+/// max_tokens the parsers request; a longer answer comes back truncated
+const PARSE_MAX_OUTPUT_TOKENS: u64 = 16_384;
+
+fn parse_cost_estimate(text: Option<&str>, page_count: usize, pricing: Option<llm::ModelPricing>) -> ParseCostEstimate {
+    let (mode, input_tokens, output_tokens, recommended_chunking) = match text {
+        Some(text) => {
+            let input = (text.chars().count() as u64).div_ceil(4);
+            // The JSON written back is about half the size of the statement lines it came from
+            let output = input / 2;
+            let context_budget = pricing.map(|p| p.context_tokens as u64 / 2).unwrap_or(PARSE_MAX_OUTPUT_TOKENS).max(1);
+            let chunks = input.div_ceil(context_budget).max(output.div_ceil(PARSE_MAX_OUTPUT_TOKENS)).max(1);
+            ("text", input, output, chunks as usize)
+        }
+        None => {
+            let pages = page_count.max(1) as u64;
+            // Same split parse_statement_with_vision_llm makes: whole up to 3 pages, then 2 at a time
+            let chunks = if pages <= 3 { 1 } else { pages.div_ceil(2) };
+            ("vision", pages * VISION_TOKENS_PER_PAGE, pages * VISION_OUTPUT_TOKENS_PER_PAGE, chunks as usize)
+        }
+    };
+
+    ParseCostEstimate {
+        mode: mode.to_string(),
+        page_count,
+        estimated_input_tokens: input_tokens,
+        estimated_output_tokens: output_tokens,
+        estimated_cost_usd: pricing.map(|p| {
+            (input_tokens as f64 * p.input_per_million + output_tokens as f64 * p.output_per_million) / 1_000_000.0
+        }),
+        recommended_chunking,
+    }
+}
+
+// Dates as statements print them: 2025-01-31, 31/01/2025, 31 Jan 2025, January 31, 2025
+const STATEMENT_DATE: &str = r"(\d{4}-\d{2}-\d{2}|\d{1,2}/\d{1,2}/\d{4}|\d{1,2}\s+[A-Za-z]{3,9},?\s+\d{4}|[A-Za-z]{3,9}\s+\d{1,2},?\s+\d{4})";
+
+lazy_static::lazy_static! {
+    static ref STATEMENT_ACCOUNT_NUMBER: regex::Regex = regex::Regex::new(
+        r"(?i)\b(?:account|acct|card)\s*(?:number|no\.?|#)?\s*[:#]?\s*([0-9Xx*•][0-9Xx*• \-]{2,}[0-9]{4})\b"
+    )
+    .unwrap();
+    static ref STATEMENT_PERIOD: regex::Regex = regex::Regex::new(&format!(
+        r"(?i)(?:period|from|dates?)\s*:?\s*{date}\s*(?:-|–|to|through)\s*{date}",
+        date = STATEMENT_DATE
+    ))
+    .unwrap();
+    static ref STATEMENT_DATES: regex::Regex = regex::Regex::new(&format!(r"\b{}\b", STATEMENT_DATE)).unwrap();
+}
+
+/// Institution names are usually among the first lines of a statement
+const INSTITUTION_KEYWORDS: [&str; 6] = ["bank", "credit union", "sacco", "m-pesa", "mpesa", "building society"];
+
+/// Header titles and summary fields that give away the kind of account, strongest
+/// first. Only credit cards print a minimum payment or credit limit, so those win
+/// over a "Savings" promo line or an "Available Balance" a card might also show.
+const ACCOUNT_TYPE_SIGNALS: [(&str, &[&str]); 3] = [
+    (
+        "credit",
+        &["credit card", "minimum payment due", "minimum amount due", "minimum payment", "credit limit", "available credit"],
+    ),
+    ("savings", &["savings"]),
+    ("checking", &["checking", "current account", "available balance", "overdraft limit"]),
+];
+
+/// Account type suggested for a new account from a lowercased statement header
+fn infer_account_type(lower_header: &str) -> Option<&'static str> {
+    ACCOUNT_TYPE_SIGNALS
+        .iter()
+        .find(|(_, signals)| signals.iter().any(|signal| lower_header.contains(signal)))
+        .map(|(account_type, _)| *account_type)
+}
+
+fn statement_metadata_from_text(text: &str) -> StatementMetadata {
+    let header: String = text.lines().take(40).collect::<Vec<_>>().join("\n");
+    let lower = header.to_lowercase();
+
+    let institution = header
+        .lines()
+        .map(str::trim)
+        .find(|line| {
+            let line = line.to_lowercase();
+            line.len() <= 80 && INSTITUTION_KEYWORDS.iter().any(|k| line.contains(k))
+        })
+        .map(|line| line.to_string());
+
+    let account_type = infer_account_type(&lower).map(str::to_string);
+
+    let account_last4 = STATEMENT_ACCOUNT_NUMBER.captures(&header).map(|caps| {
+        let digits: String = caps[1].chars().filter(|c| c.is_ascii_digit()).collect();
+        digits[digits.len() - 4..].to_string()
+    });
+
+    let (period_start, period_end) = match STATEMENT_PERIOD.captures(&header) {
+        Some(caps) => (parse_statement_date(&caps[1]), parse_statement_date(&caps[2])),
+        None => (None, None),
+    };
+
+    StatementMetadata {
+        institution,
+        account_type,
+        account_last4,
+        period_start,
+        period_end,
+        suggested_account_id: None,
+        language: llm::detect_language(text).map(str::to_string),
+    }
+}
+
+/// Normalize the date formats statements commonly print to YYYY-MM-DD
+fn parse_statement_date(raw: &str) -> Option<String> {
+    let cleaned = raw.replace(',', "");
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    ["%Y-%m-%d", "%d/%m/%Y", "%d %b %Y", "%d %B %Y", "%b %d %Y", "%B %d %Y"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(&cleaned, format).ok())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+fn fill_missing_metadata(metadata: &mut StatementMetadata, detected: StatementMetadata) {
+    metadata.institution = metadata.institution.take().or(detected.institution);
+    metadata.account_type = metadata.account_type.take().or(detected.account_type);
+    // Never trust more than four digits back from the model
+    metadata.account_last4 = metadata.account_last4.take().or(detected
+        .account_last4
+        .map(|n| n.chars().filter(|c| c.is_ascii_digit()).collect::<String>())
+        .filter(|n| n.len() >= 4)
+        .map(|n| n[n.len() - 4..].to_string()));
+    metadata.period_start = metadata.period_start.take().or(detected.period_start);
+    metadata.period_end = metadata.period_end.take().or(detected.period_end);
+}
+
+/// Find an account whose institution matches, ignoring case and "Bank"/"Ltd" style suffixes
+fn suggest_account_for_institution(
+    conn: &rusqlite::Connection,
+    institution: &str,
+) -> Result<Option<String>, YukiError> {
+    let detected = normalize_merchant(institution);
+    if detected.is_empty() {
+        return Ok(None);
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT id, institution FROM accounts WHERE institution IS NOT NULL ORDER BY is_default DESC, created_at")?;
+
+    let accounts: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(accounts
+        .into_iter()
+        .find(|(_, existing)| {
+            let existing = normalize_merchant(existing);
+            !existing.is_empty() && (detected.contains(&existing) || existing.contains(&detected))
+        })
+        .map(|(id, _)| id))
+}
+
+/// Re-hash every stored document file and return the ids whose file is missing
+/// or no longer matches the recorded hash
+#[tauri::command]
+pub async fn verify_documents(app: AppHandle) -> Result<Vec<String>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let failed = unverified_documents(&conn)?;
+
+    if !failed.is_empty() {
+        log::warn!("{} document(s) failed integrity check", failed.len());
+    }
+    Ok(failed)
+}
+
+/// SHA-256 of the file as lowercase hex, matching computeFileHash in the frontend
+fn hash_file(path: &str) -> std::io::Result<String> {
+    Ok(hash_bytes(&fs::read(path)?))
+}
+
+fn hash_bytes(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
 
-    // Get the file path first
-    let filepath: Option<String> = conn
+    hex::encode(Sha256::digest(data))
+}
+
+/// Whole-statement dedup: an upload whose bytes match a stored document is the
+/// same statement again, which per-row dedup would only catch row by row
+fn check_not_imported(conn: &rusqlite::Connection, data: &[u8]) -> Result<(), YukiError> {
+    let existing: Option<String> = conn
         .query_row(
-            "SELECT filepath FROM documents WHERE id = ?1",
-            [&document_id],
+            "SELECT id FROM documents WHERE LOWER(hash) = ?1 ORDER BY uploaded_at LIMIT 1",
+            [hash_bytes(data)],
             |row| row.get(0),
         )
-        .ok();
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            other => Err(other),
+        })?;
 
-    // Delete from database (cascades to ledger entries)
-    conn.execute("DELETE FROM documents WHERE id = ?1", [&document_id])
-        .map_err(|e| e.to_string())?;
+    match existing {
+        Some(existing_document_id) => Err(YukiError::AlreadyImported { existing_document_id }),
+        None => Ok(()),
+    }
+}
 
-    // Delete file from disk
-    if let Some(path) = filepath {
-        let _ = fs::remove_file(path);
+fn unverified_documents(conn: &rusqlite::Connection) -> Result<Vec<String>, YukiError> {
+    let mut stmt = conn
+        .prepare("SELECT id, filepath, hash FROM documents ORDER BY uploaded_at")?;
+
+    let documents: Vec<(String, String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(documents
+        .into_iter()
+        .filter(|(_, filepath, hash)| match hash_file(filepath) {
+            Ok(actual) => !actual.eq_ignore_ascii_case(hash),
+            Err(_) => true,
+        })
+        .map(|(id, _, _)| id)
+        .collect())
+}
+
+/// List files in the documents directory that no document row points at, and
+/// document rows whose file has gone missing
+#[tauri::command]
+pub async fn find_orphaned_files(app: AppHandle) -> Result<OrphanReport, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let data_dir = database::get_data_dir(&app).map_err(YukiError::database)?;
+    orphan_report(&conn, &data_dir.join("documents"))
+}
+
+/// Delete the orphaned files found by `find_orphaned_files` and return their paths.
+/// Rows with missing files are left alone; they only show up in the report.
+#[tauri::command]
+pub async fn cleanup_orphaned_files(app: AppHandle) -> Result<Vec<String>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let data_dir = database::get_data_dir(&app).map_err(YukiError::database)?;
+    let report = orphan_report(&conn, &data_dir.join("documents"))?;
+
+    let mut deleted = Vec::new();
+    for path in report.orphaned_files {
+        match fs::remove_file(&path) {
+            Ok(()) => deleted.push(path),
+            Err(e) => log::warn!("Failed to delete orphaned file {}: {}", path, e),
+        }
     }
+    log::info!("Deleted {} orphaned document file(s)", deleted.len());
+    Ok(deleted)
+}
 
-    Ok(())
+fn orphan_report(conn: &rusqlite::Connection, documents_dir: &std::path::Path) -> Result<OrphanReport, YukiError> {
+    let mut stmt = conn
+        .prepare("SELECT id, filepath FROM documents ORDER BY uploaded_at")?;
+    let documents: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Match on file name as well as full path, so a row whose stored path is
+    // spelled differently (or predates a data dir move) still protects its file
+    let referenced: std::collections::HashSet<std::ffi::OsString> = documents
+        .iter()
+        .filter_map(|(_, filepath)| std::path::Path::new(filepath).file_name().map(|n| n.to_owned()))
+        .collect();
+
+    let missing_files = documents
+        .iter()
+        .filter(|(_, filepath)| !std::path::Path::new(filepath).is_file())
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    let mut orphaned_files = Vec::new();
+    let mut orphaned_bytes = 0;
+    if documents_dir.is_dir() {
+        for entry in fs::read_dir(documents_dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || referenced.contains(&entry.file_name()) {
+                continue;
+            }
+            orphaned_bytes += metadata.len();
+            orphaned_files.push(entry.path().to_string_lossy().to_string());
+        }
+    }
+    orphaned_files.sort();
+
+    Ok(OrphanReport {
+        orphaned_files,
+        orphaned_bytes,
+        missing_files,
+    })
+}
+
+#[tauri::command]
+pub async fn extract_pdf_text(data: Vec<u8>) -> Result<PdfExtractionResult, YukiError> {
+    Ok(pdf_extraction(&data))
 }
 
+/// Text of a stored PDF document, extracted once and then served from document_text
 #[tauri::command]
-pub async fn extract_pdf_text(data: Vec<u8>) -> Result<PdfExtractionResult, String> {
+pub async fn extract_document_text(app: AppHandle, document_id: String) -> Result<PdfExtractionResult, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let document = document_by_id(&conn, &document_id)?;
+    if document.filetype != "application/pdf" {
+        return Err(YukiError::Validation(format!("Document '{}' is not a PDF", document.filename)));
+    }
+    cached_pdf_text(&conn, &document, pdf_extraction)
+}
+
+/// Look up the cached extraction for `document`, running `extract` on the stored
+/// file and caching the result the first time
+fn cached_pdf_text(
+    conn: &rusqlite::Connection,
+    document: &Document,
+    extract: impl FnOnce(&[u8]) -> PdfExtractionResult,
+) -> Result<PdfExtractionResult, YukiError> {
+    let cached = conn.query_row(
+        "SELECT text, is_scanned FROM document_text WHERE document_id = ?1",
+        [&document.id],
+        |row| Ok(PdfExtractionResult { text: row.get(0)?, is_scanned: row.get(1)? }),
+    );
+    match cached {
+        Ok(extraction) => {
+            log::debug!("Using cached text for document {}", document.id);
+            return Ok(extraction);
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let data = fs::read(&document.filepath)?;
+    let extraction = extract(&data);
+    let page_count = lopdf::Document::load_mem(&data).ok().map(|pdf| pdf.get_pages().len() as i64);
+
+    conn.execute(
+        "INSERT OR REPLACE INTO document_text (document_id, text, page_count, is_scanned, extracted_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            &document.id,
+            &extraction.text,
+            page_count,
+            extraction.is_scanned,
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(extraction)
+}
+
+fn pdf_extraction(data: &[u8]) -> PdfExtractionResult {
     // Use pdf-extract to get text - handle errors gracefully for scanned PDFs
-    let text = match pdf_extract::extract_text_from_mem(&data) {
+    let text = match pdf_extract::extract_text_from_mem(data) {
         Ok(t) => t,
         Err(e) => {
             // If extraction fails, it's likely a scanned/image-based PDF
@@ -243,10 +1010,10 @@ pub async fn extract_pdf_text(data: Vec<u8>) -> Result<PdfExtractionResult, Stri
         is_scanned
     );
 
-    Ok(PdfExtractionResult {
+    PdfExtractionResult {
         text,
         is_scanned,
-    })
+    }
 }
 
 // ============================================================================
@@ -254,17 +1021,17 @@ pub async fn extract_pdf_text(data: Vec<u8>) -> Result<PdfExtractionResult, Stri
 // ============================================================================
 
 #[tauri::command]
-pub async fn save_ledger_entry(app: AppHandle, entry: LedgerEntry) -> Result<(), String> {
-    log::info!("[save_ledger_entry] Saving entry: {} - {}", entry.description, entry.amount);
+pub async fn save_ledger_entry(app: AppHandle, entry: LedgerEntry) -> Result<String, YukiError> {
+    log::info!("[save_ledger_entry] Saving entry: {} - {}", logging::redact(&entry.description), entry.amount);
 
     let conn = database::get_connection(&app).map_err(|e| {
         log::error!("[save_ledger_entry] Failed to get DB connection: {}", e);
-        e.to_string()
+        YukiError::database(e)
     })?;
 
     conn.execute(
-        "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at, original_amount, original_currency, needs_review)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
         rusqlite::params![
             &entry.id,
             &entry.document_id,
@@ -278,25 +1045,28 @@ pub async fn save_ledger_entry(app: AppHandle, entry: LedgerEntry) -> Result<(),
             &entry.notes,
             &entry.source,
             &entry.created_at,
+            entry.original_amount,
+            &entry.original_currency,
+            entry.needs_review,
         ],
     )
     .map_err(|e| {
         log::error!("[save_ledger_entry] SQL error: {}", e);
-        e.to_string()
+        YukiError::from(e)
     })?;
 
     log::info!("[save_ledger_entry] Entry saved successfully");
-    Ok(())
+    Ok(entry.id)
 }
 
 #[tauri::command]
-pub async fn save_ledger_entries_batch(app: AppHandle, entries: Vec<LedgerEntry>) -> Result<usize, String> {
+pub async fn save_ledger_entries_batch(app: AppHandle, entries: Vec<LedgerEntry>) -> Result<usize, YukiError> {
     log::info!("[save_ledger_entries_batch] Received {} entries to save", entries.len());
 
     // Log first entry details for debugging
     if let Some(first) = entries.first() {
         log::info!("[save_ledger_entries_batch] First entry: id={}, doc_id={:?}, date={}, desc={}, amount={}, currency={}, category={}, source={}",
-            first.id, first.document_id, first.date, first.description, first.amount, first.currency, first.category_id, first.source);
+            first.id, first.document_id, first.date, logging::redact(&first.description), first.amount, first.currency, first.category_id, first.source);
     }
 
     if entries.is_empty() {
@@ -304,9 +1074,9 @@ pub async fn save_ledger_entries_batch(app: AppHandle, entries: Vec<LedgerEntry>
         return Ok(0);
     }
 
-    let conn = database::get_connection(&app).map_err(|e| {
+    let mut conn = database::get_connection(&app).map_err(|e| {
         log::error!("[save_ledger_entries_batch] Failed to get DB connection: {}", e);
-        e.to_string()
+        YukiError::database(e)
     })?;
 
     // Verify document exists (foreign key check)
@@ -321,13 +1091,26 @@ pub async fn save_ledger_entries_batch(app: AppHandle, entries: Vec<LedgerEntry>
         }
     }
 
-    let mut saved_count = 0;
+    insert_ledger_entries(&mut conn, &entries)?;
+    let saved_count = entries.len();
+    log::info!("[save_ledger_entries_batch] Complete: saved {} entries", saved_count);
+
+    notify_import_webhook(&conn, entries);
+
+    Ok(saved_count)
+}
+
+/// Insert every entry under one new import batch, all or nothing: the first
+/// failing row rolls the whole batch back and is returned as the error
+fn insert_ledger_entries(conn: &mut rusqlite::Connection, entries: &[LedgerEntry]) -> Result<(), YukiError> {
+    let import_id = uuid::Uuid::new_v4().to_string();
+    let tx = conn.transaction()?;
     for (idx, entry) in entries.iter().enumerate() {
-        log::debug!("[save_ledger_entries_batch] Saving entry {}/{}: {}", idx + 1, entries.len(), entry.description);
+        log::debug!("[save_ledger_entries_batch] Saving entry {}/{}: {}", idx + 1, entries.len(), logging::redact(&entry.description));
 
-        match conn.execute(
-            "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        tx.execute(
+            "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at, original_amount, original_currency, needs_review, import_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
             rusqlite::params![
                 &entry.id,
                 &entry.document_id,
@@ -341,982 +1124,8872 @@ pub async fn save_ledger_entries_batch(app: AppHandle, entries: Vec<LedgerEntry>
                 &entry.notes,
                 &entry.source,
                 &entry.created_at,
+                entry.original_amount,
+                &entry.original_currency,
+                entry.needs_review,
+                &import_id,
             ],
-        ) {
-            Ok(_) => {
-                saved_count += 1;
-                if saved_count % 10 == 0 {
-                    log::info!("[save_ledger_entries_batch] Progress: saved {}/{}", saved_count, entries.len());
-                }
-            },
-            Err(e) => {
-                log::error!("[save_ledger_entries_batch] FAILED to save entry {}: '{}' - Error: {}",
-                    idx + 1, entry.description, e);
-                log::error!("[save_ledger_entries_batch] Entry details: date={}, amount={}, currency={}, category_id={}",
-                    entry.date, entry.amount, entry.currency, entry.category_id);
-            }
-        }
+        )
+        .map_err(|e| {
+            log::error!("[save_ledger_entries_batch] FAILED to save entry {}: '{}' - Error: {}",
+                idx + 1, logging::redact(&entry.description), e);
+            log::error!("[save_ledger_entries_batch] Entry details: date={}, amount={}, currency={}, category_id={}",
+                entry.date, entry.amount, entry.currency, entry.category_id);
+            YukiError::Database(format!("Entry {} could not be saved, so none were: {}", idx + 1, e))
+        })?;
     }
 
-    log::info!("[save_ledger_entries_batch] Complete: saved {}/{} entries", saved_count, entries.len());
-    Ok(saved_count)
+    if let Some(first) = entries.first() {
+        record_import_batch(&tx, &import_id, &first.source, first.document_id.as_deref(), entries.len())?;
+    }
+    tx.commit()?;
+    Ok(())
 }
 
-#[tauri::command]
-pub async fn get_all_transactions(app: AppHandle) -> Result<Vec<LedgerEntry>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+/// Record an import batch once its rows are in. Imports that saved nothing
+/// leave no batch behind.
+fn record_import_batch(
+    conn: &rusqlite::Connection,
+    import_id: &str,
+    source: &str,
+    document_id: Option<&str>,
+    transaction_count: usize,
+) -> Result<(), YukiError> {
+    if transaction_count > 0 {
+        conn.execute(
+            "INSERT INTO import_batches (id, source, document_id, created_at, transaction_count) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![import_id, source, document_id, chrono::Utc::now().to_rfc3339(), transaction_count as i64],
+        )?;
+    }
+    Ok(())
+}
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at
-             FROM ledger ORDER BY date DESC, created_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
+/// Imports newest first, so a bad one can be found and rolled back
+#[tauri::command]
+pub async fn list_import_batches(app: AppHandle) -> Result<Vec<ImportBatch>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    import_batches(&conn)
+}
 
-    let entries = stmt
+fn import_batches(conn: &rusqlite::Connection) -> Result<Vec<ImportBatch>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source, document_id, created_at, transaction_count FROM import_batches ORDER BY created_at DESC, rowid DESC",
+    )?;
+    let batches = stmt
         .query_map([], |row| {
-            Ok(LedgerEntry {
+            Ok(ImportBatch {
                 id: row.get(0)?,
-                document_id: row.get(1)?,
-                account_id: row.get::<_, Option<String>>(2).unwrap_or(Some("default".to_string())),
-                date: row.get(3)?,
-                description: row.get(4)?,
-                amount: row.get(5)?,
-                currency: row.get(6)?,
-                category_id: row.get(7)?,
-                merchant: row.get(8)?,
-                notes: row.get(9)?,
-                source: row.get(10)?,
-                created_at: row.get(11)?,
+                source: row.get(1)?,
+                document_id: row.get(2)?,
+                created_at: row.get(3)?,
+                transaction_count: row.get(4)?,
             })
-        })
-        .map_err(|e| e.to_string())?
+        })?
         .filter_map(|r| r.ok())
         .collect();
-
-    Ok(entries)
+    Ok(batches)
 }
 
+/// Delete every transaction an import added, and the batch itself, in one
+/// transaction. Returns the number of transactions deleted.
 #[tauri::command]
-pub async fn delete_transaction(app: AppHandle, transaction_id: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn rollback_import(app: AppHandle, import_id: String) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let deleted = rollback_import_rows(&mut conn, &import_id)?;
 
-    conn.execute("DELETE FROM ledger WHERE id = ?1", [&transaction_id])
-        .map_err(|e| e.to_string())?;
+    log::info!("Rolled back import {}: deleted {} transactions", import_id, deleted);
+    Ok(deleted)
+}
 
-    Ok(())
+fn rollback_import_rows(conn: &mut rusqlite::Connection, import_id: &str) -> Result<usize, YukiError> {
+    let tx = conn.transaction()?;
+    if tx.execute("DELETE FROM import_batches WHERE id = ?1", [import_id])? == 0 {
+        return Err(YukiError::NotFound(format!("Import '{}' not found", import_id)));
+    }
+    let deleted = tx.execute("DELETE FROM ledger WHERE import_id = ?1", [import_id])?;
+    tx.commit()?;
+    Ok(deleted)
 }
 
-// ============================================================================
-// Category Commands
-// ============================================================================
+/// Import a JSON array of transactions exported from another tool
+#[tauri::command]
+pub async fn import_transactions_json(
+    app: AppHandle,
+    path: String,
+    account_id: String,
+    convert_to_account_currency: Option<bool>,
+    expected_total: Option<f64>,
+    min_amount: Option<f64>,
+) -> Result<ImportSummary, YukiError> {
+    let json = fs::read_to_string(&path).map_err(|e| file_error("read", &path, e))?;
+    let transactions = parse_transactions_json(&json)?;
+
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let options = ImportOptions {
+        convert_to_account_currency: convert_to_account_currency.unwrap_or(false),
+        expected_total,
+        min_amount,
+    };
+    let summary = import_extracted_transactions(&mut conn, &transactions, &account_id, None, "manual", &options)?;
+
+    log::info!(
+        "[import_transactions_json] Inserted {}, skipped {} duplicates and {} below the minimum amount, {} with unmatched category, {} flagged for review",
+        summary.inserted,
+        summary.skipped_duplicates,
+        summary.below_threshold,
+        summary.category_unmatched,
+        summary.flagged_for_review
+    );
+    Ok(summary)
+}
 
+/// Import an OFX (bank or credit card) statement. Re-importing the same file, or
+/// an overlapping one, adds nothing thanks to the FITID on each transaction.
 #[tauri::command]
-pub async fn get_all_categories(app: AppHandle) -> Result<Vec<Category>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn import_ofx(
+    app: AppHandle,
+    path: String,
+    account_id: String,
+    min_amount: Option<f64>,
+) -> Result<ImportSummary, YukiError> {
+    let bytes = fs::read(&path).map_err(|e| file_error("read", &path, e))?;
 
-    let mut stmt = conn
-        .prepare("SELECT id, name, icon, color, is_default, created_at FROM categories ORDER BY name")
-        .map_err(|e| e.to_string())?;
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let summary = import_ofx_text(&mut conn, &ofx::decode(&bytes), &account_id, min_amount)?;
 
-    let categories = stmt
-        .query_map([], |row| {
-            Ok(Category {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                icon: row.get(2)?,
-                color: row.get(3)?,
-                is_default: row.get::<_, i32>(4)? == 1,
-                created_at: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+    log::info!(
+        "[import_ofx] Inserted {}, skipped {} duplicates and {} below the minimum amount, {} with unmatched category",
+        summary.inserted,
+        summary.skipped_duplicates,
+        summary.below_threshold,
+        summary.category_unmatched
+    );
+    Ok(summary)
+}
 
-    Ok(categories)
+fn import_ofx_text(
+    conn: &mut rusqlite::Connection,
+    text: &str,
+    account_id: &str,
+    min_amount: Option<f64>,
+) -> Result<ImportSummary, YukiError> {
+    let transactions = ofx::parse(text).map_err(YukiError::validation)?;
+    let options = ImportOptions { min_amount, ..Default::default() };
+    import_extracted_transactions(conn, &transactions, account_id, None, "manual", &options)
 }
 
+/// Import a Mint or YNAB CSV export. `format` is "mint" or "ynab"; the columns
+/// of each are mapped automatically.
 #[tauri::command]
-pub async fn get_category_names(app: AppHandle) -> Result<Vec<String>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-
-    let mut stmt = conn
-        .prepare("SELECT name FROM categories ORDER BY name")
-        .map_err(|e| e.to_string())?;
-
-    let names = stmt
-        .query_map([], |row| row.get(0))
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+pub async fn import_known_format(
+    app: AppHandle,
+    path: String,
+    format: String,
+    account_id: String,
+    min_amount: Option<f64>,
+) -> Result<ImportSummary, YukiError> {
+    let format = csv::KnownFormat::from_name(&format)
+        .ok_or_else(|| YukiError::Validation(format!("Unknown export format '{}': expected mint or ynab", format)))?;
+    let bytes = fs::read(&path).map_err(|e| file_error("read", &path, e))?;
+    let transactions = csv::parse(&ofx::decode(&bytes), format).map_err(YukiError::validation)?;
+
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let options = ImportOptions { min_amount, ..Default::default() };
+    let summary = import_extracted_transactions(&mut conn, &transactions, &account_id, None, "manual", &options)?;
 
-    Ok(names)
+    log::info!(
+        "[import_known_format] Inserted {} from a {:?} export, skipped {} duplicates and {} below the minimum amount, {} with unmatched category",
+        summary.inserted,
+        format,
+        summary.skipped_duplicates,
+        summary.below_threshold,
+        summary.category_unmatched
+    );
+    Ok(summary)
 }
 
+/// Import the transactions parsed from an uploaded statement (text, scanned
+/// PDF or image) under its document, as one batch that can be rolled back.
+/// With `convert_to_account_currency`, rows in another currency are stored in
+/// the account's currency. `expected_total` is the statement's net change, which
+/// otherwise comes from the rows' running balances. `min_amount` overrides the
+/// import_min_amount setting.
 #[tauri::command]
-pub async fn add_category(app: AppHandle, name: String, color: Option<String>) -> Result<String, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-    let id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
+pub async fn import_statement_transactions(
+    app: AppHandle,
+    document_id: String,
+    transactions: Vec<ExtractedTransaction>,
+    account_id: Option<String>,
+    convert_to_account_currency: Option<bool>,
+    expected_total: Option<f64>,
+    min_amount: Option<f64>,
+) -> Result<ImportSummary, YukiError> {
+    let account_id = account_id.unwrap_or_else(|| "default".to_string());
+
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let options = ImportOptions {
+        convert_to_account_currency: convert_to_account_currency.unwrap_or(false),
+        expected_total,
+        min_amount,
+    };
+    let summary = import_extracted_transactions(
+        &mut conn,
+        &transactions,
+        &account_id,
+        Some(&document_id),
+        "document",
+        &options,
+    )?;
 
-    conn.execute(
-        "INSERT INTO categories (id, name, icon, color, is_default, created_at) VALUES (?1, ?2, NULL, ?3, 0, ?4)",
-        [&id, &name, &color.unwrap_or_else(|| "#71717a".to_string()), &now],
-    )
-    .map_err(|e| e.to_string())?;
+    log::info!(
+        "[import_statement_transactions] Inserted {} of {} from document {}, skipped {} duplicates and {} below the minimum amount, {} with unmatched category",
+        summary.inserted,
+        transactions.len(),
+        document_id,
+        summary.skipped_duplicates,
+        summary.below_threshold,
+        summary.category_unmatched
+    );
+    Ok(summary)
+}
 
-    Ok(id)
+fn parse_transactions_json(json: &str) -> Result<Vec<ExtractedTransaction>, YukiError> {
+    // serde_json errors already name the line and column of the offending entry
+    serde_json::from_str(json).map_err(|e| YukiError::Validation(format!("Malformed transactions file: {}", e)))
 }
 
-// ============================================================================
-// Receipt Commands
-// ============================================================================
+/// Per-import knobs for import_extracted_transactions
+#[derive(Debug, Default)]
+struct ImportOptions {
+    convert_to_account_currency: bool,
+    expected_total: Option<f64>,
+    min_amount: Option<f64>, // None uses the import_min_amount setting; 0 imports everything
+}
 
-#[tauri::command]
-pub async fn save_receipt(app: AppHandle, receipt: Receipt) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+/// The saved import_min_amount setting, if one is set
+fn saved_import_min_amount(conn: &rusqlite::Connection) -> Option<f64> {
+    conn.query_row("SELECT value FROM settings WHERE key = 'import_min_amount'", [], |row| row.get::<_, String>(0))
+        .ok()
+        .and_then(|v| v.parse().ok())
+}
 
-    let items_json = serde_json::to_string(&receipt.items).map_err(|e| e.to_string())?;
+/// Shared import pipeline: map categories, default currencies, drop rows that
+/// already exist (or repeat within the batch) and insert the rest atomically.
+/// With `convert_to_account_currency`, foreign-currency rows are stored in the
+/// account's currency and keep their statement amount in `original_amount`.
+/// Rows are flagged for review when the LLM marked them uncertain, or all of
+/// them when their sum doesn't reconcile with the statement's `expected_total`
+/// (taken from the rows' running balances when the caller gives none).
+/// Rows smaller than the minimum amount (fees, rounding lines) are left out.
+fn import_extracted_transactions(
+    conn: &mut rusqlite::Connection,
+    transactions: &[ExtractedTransaction],
+    account_id: &str,
+    document_id: Option<&str>,
+    source: &str,
+    options: &ImportOptions,
+) -> Result<ImportSummary, YukiError> {
+    let convert_to_account_currency = options.convert_to_account_currency;
+    let expected_total = options.expected_total.or_else(|| statement_total_from_balances(transactions));
+    let min_amount = options.min_amount.or_else(|| saved_import_min_amount(conn)).unwrap_or(0.0);
+
+    let account_currency = account_currency(conn, account_id)?;
+
+    let rates = currency_rates(conn)?;
+
+    let categories: Vec<(String, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT id, name FROM categories")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        rows
+    };
 
-    conn.execute(
-        "INSERT INTO receipts (id, document_id, ledger_id, merchant, items, tax, total) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        rusqlite::params![
-            &receipt.id,
-            &receipt.document_id,
-            &receipt.ledger_id,
-            &receipt.merchant,
-            &items_json,
-            receipt.tax,
-            receipt.total,
-        ],
-    )
-    .map_err(|e| e.to_string())?;
+    let mut seen: std::collections::HashSet<(String, i64, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT date, amount, description, original_amount FROM ledger")?;
+        let rows: Vec<(String, f64, String, Option<f64>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        // Converted rows are matched on their statement amount too, so re-importing
+        // the same statement is caught either way
+        rows.iter()
+            .flat_map(|(date, amount, description, original)| {
+                std::iter::once(duplicate_key(date, *amount, description))
+                    .chain(original.map(|o| duplicate_key(date, o, description)))
+            })
+            .collect()
+    };
 
-    Ok(())
-}
+    // Bank ids are only unique per account; legacy rows without one count as 'default'
+    let mut seen_external: std::collections::HashSet<String> = {
+        let mut stmt = conn.prepare(
+            "SELECT external_id FROM ledger
+             WHERE COALESCE(account_id, 'default') = ?1 AND external_id IS NOT NULL",
+        )?;
+        let ids = stmt
+            .query_map([account_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        ids
+    };
 
-// ============================================================================
-// Purchased Items Commands
-// ============================================================================
+    let extracted_total: f64 = transactions.iter().map(|t| t.amount).sum();
+    let discrepant = expected_total.is_some_and(|expected| (extracted_total - expected).abs() >= 0.005);
+    if discrepant {
+        log::warn!(
+            "Extracted transactions total {:.2} but the statement says {:.2}; flagging for review",
+            extracted_total,
+            expected_total.unwrap_or_default()
+        );
+    }
 
-#[tauri::command]
-pub async fn save_purchased_item(app: AppHandle, item: PurchasedItem) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+    let import_id = uuid::Uuid::new_v4().to_string();
+    let mut summary = ImportSummary::default();
+    let mut inserted = Vec::new();
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
 
-    conn.execute(
-        "INSERT INTO purchased_items (id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
-        rusqlite::params![
-            &item.id,
-            &item.receipt_id,
-            &item.ledger_id,
-            &item.name,
-            item.quantity,
-            &item.unit,
-            item.unit_price,
-            item.total_price,
-            &item.category,
-            &item.brand,
-            &item.purchased_at,
-            &item.created_at,
-        ],
-    )
-    .map_err(|e| e.to_string())?;
+    for txn in transactions {
+        if txn.amount.abs() < min_amount {
+            summary.below_threshold += 1;
+            continue;
+        }
 
-    Ok(())
-}
+        // A bank id is authoritative: two identical coffees on the same day with
+        // different ids are both real
+        let is_new = match &txn.external_id {
+            Some(id) => seen_external.insert(id.clone()),
+            None => seen.insert(duplicate_key(&txn.date, txn.amount, &txn.description)),
+        };
+        if !is_new {
+            summary.skipped_duplicates += 1;
+            continue;
+        }
 
-#[tauri::command]
-pub async fn save_purchased_items(app: AppHandle, items: Vec<PurchasedItem>) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+        // Same slug the document importer builds, falling back to a name match
+        let slug = txn.category.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("-");
+        let category_id = categories
+            .iter()
+            .find(|(id, name)| *id == slug || name.eq_ignore_ascii_case(txn.category.trim()))
+            .map(|(id, _)| id.clone())
+            .unwrap_or_else(|| {
+                summary.category_unmatched += 1;
+                "other".to_string()
+            });
+
+        let currency = if txn.currency.trim().is_empty() {
+            account_currency.clone()
+        } else {
+            txn.currency.trim().to_uppercase()
+        };
+
+        let (amount, currency, original_amount, original_currency) =
+            if convert_to_account_currency && currency != account_currency {
+                let converted = convert_amount(&rates, txn.amount, &currency, &account_currency)?;
+                (converted, account_currency.clone(), Some(txn.amount), Some(currency))
+            } else {
+                (txn.amount, currency, None, None)
+            };
 
-    for item in items {
-        conn.execute(
-            "INSERT INTO purchased_items (id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        let needs_review = discrepant || txn.needs_review == Some(true);
+
+        let entry = LedgerEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            document_id: document_id.map(str::to_string),
+            account_id: Some(account_id.to_string()),
+            date: txn.date.clone(),
+            description: txn.description.clone(),
+            amount,
+            currency,
+            category_id,
+            merchant: txn.merchant.clone(),
+            notes: None,
+            source: source.to_string(),
+            created_at: now.clone(),
+            original_amount,
+            original_currency,
+            needs_review: needs_review.then_some(true),
+        };
+        tx.execute(
+            "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at, original_amount, original_currency, needs_review, external_id, import_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, NULL, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
             rusqlite::params![
-                &item.id,
-                &item.receipt_id,
-                &item.ledger_id,
-                &item.name,
-                item.quantity,
-                &item.unit,
-                item.unit_price,
-                item.total_price,
-                &item.category,
-                &item.brand,
-                &item.purchased_at,
-                &item.created_at,
+                &entry.id,
+                &entry.document_id,
+                &entry.account_id,
+                &entry.date,
+                &entry.description,
+                entry.amount,
+                &entry.currency,
+                &entry.category_id,
+                &entry.merchant,
+                &entry.source,
+                &entry.created_at,
+                entry.original_amount,
+                &entry.original_currency,
+                entry.needs_review,
+                &txn.external_id,
+                &import_id,
             ],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
+        inserted.push(entry);
+        if needs_review {
+            summary.flagged_for_review += 1;
+        }
     }
 
-    Ok(())
-}
+    summary.inserted = inserted.len();
+    record_import_batch(&tx, &import_id, source, document_id, summary.inserted)?;
+    tx.commit()?;
+    if summary.inserted > 0 {
+        summary.import_id = Some(import_id);
+    }
 
-#[tauri::command]
-pub async fn get_purchased_items(app: AppHandle, ledger_id: Option<String>) -> Result<Vec<PurchasedItem>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+    notify_import_webhook(conn, inserted);
+    Ok(summary)
+}
 
-    let query = if ledger_id.is_some() {
-        "SELECT id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at
-         FROM purchased_items WHERE ledger_id = ?1 ORDER BY purchased_at DESC"
-    } else {
-        "SELECT id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at
-         FROM purchased_items ORDER BY purchased_at DESC"
-    };
+/// Send newly imported rows to the import webhook, if one is set. Called once
+/// the import has committed, so the webhook can't affect it.
+fn notify_import_webhook(conn: &rusqlite::Connection, inserted: Vec<LedgerEntry>) {
+    if let Settings { import_webhook_url: Some(url), import_webhook_secret, local_only, .. } = load_settings(conn) {
+        webhook::notify_imported(url, import_webhook_secret, local_only, inserted);
+    }
+}
 
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+/// The statement's net change, closing balance minus opening balance, when its
+/// first and last rows carry a running balance. Rows may run oldest- or
+/// newest-first; the dates say which.
+fn statement_total_from_balances(transactions: &[ExtractedTransaction]) -> Option<f64> {
+    let (first, last) = (transactions.first()?, transactions.last()?);
+    let (oldest, newest) = if first.date > last.date { (last, first) } else { (first, last) };
+    let opening = oldest.balance? - oldest.amount;
+    let closing = newest.balance?;
+    Some(((closing - opening) * 100.0).round() / 100.0)
+}
 
-    let items: Vec<PurchasedItem> = if let Some(ref lid) = ledger_id {
-        stmt.query_map([lid], |row| {
-            Ok(PurchasedItem {
-                id: row.get(0)?,
-                receipt_id: row.get(1)?,
-                ledger_id: row.get(2)?,
-                name: row.get(3)?,
-                quantity: row.get(4)?,
-                unit: row.get(5)?,
-                unit_price: row.get(6)?,
-                total_price: row.get(7)?,
-                category: row.get(8)?,
-                brand: row.get(9)?,
-                purchased_at: row.get(10)?,
-                created_at: row.get(11)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect()
-    } else {
-        stmt.query_map([], |row| {
-            Ok(PurchasedItem {
-                id: row.get(0)?,
-                receipt_id: row.get(1)?,
-                ledger_id: row.get(2)?,
-                name: row.get(3)?,
-                quantity: row.get(4)?,
-                unit: row.get(5)?,
-                unit_price: row.get(6)?,
-                total_price: row.get(7)?,
-                category: row.get(8)?,
-                brand: row.get(9)?,
-                purchased_at: row.get(10)?,
-                created_at: row.get(11)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
+/// Conversion rates to the primary currency, keyed by currency code
+fn currency_rates(conn: &rusqlite::Connection) -> Result<std::collections::HashMap<String, f64>, YukiError> {
+    let mut stmt = conn
+        .prepare("SELECT code, conversion_rate FROM currencies")?;
+    let rates = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
         .filter_map(|r| r.ok())
-        .collect()
-    };
+        .collect();
+    Ok(rates)
+}
 
-    Ok(items)
+/// Convert via the primary currency: rates convert *to* primary, so divide by the target's rate
+fn convert_amount(
+    rates: &std::collections::HashMap<String, f64>,
+    amount: f64,
+    from: &str,
+    to: &str,
+) -> Result<f64, YukiError> {
+    convert_row_amount(rates, amount, from, None, to)
+}
+
+/// Like `convert_amount`, but a row's own exchange_rate (set_transaction_rate)
+/// replaces the global rate of `from`
+fn convert_row_amount(
+    rates: &std::collections::HashMap<String, f64>,
+    amount: f64,
+    from: &str,
+    row_rate: Option<f64>,
+    to: &str,
+) -> Result<f64, YukiError> {
+    let rate = |code: &str| {
+        rates
+            .get(code)
+            .copied()
+            .filter(|r| *r > 0.0)
+            .ok_or_else(|| YukiError::Validation(format!("No conversion rate for currency '{}'", code)))
+    };
+    let from_rate = match row_rate.filter(|r| *r > 0.0) {
+        Some(r) => r,
+        None => rate(from)?,
+    };
+    let converted = amount * from_rate / rate(to)?;
+    Ok((converted * 100.0).round() / 100.0)
 }
 
 #[tauri::command]
-pub async fn delete_purchased_item(app: AppHandle, item_id: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn get_all_transactions(app: AppHandle) -> Result<Vec<LedgerEntry>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
 
-    conn.execute("DELETE FROM purchased_items WHERE id = ?1", [&item_id])
-        .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at,
+                    original_amount, original_currency, needs_review
+             FROM ledger ORDER BY date DESC, created_at DESC",
+        )?;
 
-    Ok(())
-}
+    let entries = stmt
+        .query_map([], ledger_entry_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
 
-// ============================================================================
-// Conversation Management Commands
-// ============================================================================
+    Ok(entries)
+}
 
-/// Start a new conversation session
 #[tauri::command]
-pub async fn start_conversation(app: AppHandle) -> Result<String, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-    let session_id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
+pub async fn get_transaction(app: AppHandle, id: String) -> Result<LedgerEntry, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    transaction_by_id(&conn, &id)
+}
 
-    conn.execute(
-        "INSERT INTO conversation_sessions (id, created_at, updated_at) VALUES (?1, ?2, ?2)",
-        [&session_id, &now],
+fn transaction_by_id(conn: &rusqlite::Connection, id: &str) -> Result<LedgerEntry, YukiError> {
+    conn.query_row(
+        "SELECT id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at,
+                    original_amount, original_currency, needs_review
+         FROM ledger WHERE id = ?1",
+        [id],
+        ledger_entry_from_row,
     )
-    .map_err(|e| e.to_string())?;
-
-    // Set as current session
-    let mut current = CURRENT_SESSION.lock().map_err(|e| e.to_string())?;
-    *current = Some(session_id.clone());
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => YukiError::NotFound(format!("Transaction '{}' not found", id)),
+        other => other.into(),
+    })
+}
 
-    log::info!("[CONVERSATION] Started new session: {}", session_id);
-    Ok(session_id)
+/// Map a row selected with the ledger's columns in declaration order
+fn ledger_entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<LedgerEntry> {
+    Ok(LedgerEntry {
+        id: row.get(0)?,
+        document_id: row.get(1)?,
+        account_id: row.get::<_, Option<String>>(2).unwrap_or(Some("default".to_string())),
+        date: row.get(3)?,
+        description: row.get(4)?,
+        amount: row.get(5)?,
+        currency: row.get(6)?,
+        category_id: row.get(7)?,
+        merchant: row.get(8)?,
+        notes: row.get(9)?,
+        source: row.get(10)?,
+        created_at: row.get(11)?,
+        original_amount: row.get(12)?,
+        original_currency: row.get(13)?,
+        needs_review: row.get(14)?,
+    })
 }
 
-/// Get or create the current conversation session
 #[tauri::command]
-pub async fn get_or_create_session(app: AppHandle) -> Result<String, String> {
-    // Check if we have a current session
-    {
-        let current = CURRENT_SESSION.lock().map_err(|e| e.to_string())?;
-        if let Some(session_id) = current.as_ref() {
-            return Ok(session_id.clone());
-        }
-    }
+pub async fn delete_transaction(app: AppHandle, transaction_id: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    conn.execute("DELETE FROM ledger WHERE id = ?1", [&transaction_id])?;
 
-    // No current session, create one
-    start_conversation(app).await
+    Ok(())
 }
 
-/// Clear conversation and start fresh
+/// Set or clear (empty/None) the note on an existing transaction
 #[tauri::command]
-pub async fn clear_conversation(app: AppHandle) -> Result<String, String> {
-    // Clear current session reference
-    {
-        let mut current = CURRENT_SESSION.lock().map_err(|e| e.to_string())?;
-        *current = None;
-    }
+pub async fn set_transaction_note(app: AppHandle, id: String, note: Option<String>) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    update_transaction_note(&conn, &id, note.as_deref())
+}
+
+fn update_transaction_note(conn: &rusqlite::Connection, id: &str, note: Option<&str>) -> Result<(), YukiError> {
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    let updated = conn
+        .execute("UPDATE ledger SET notes = ?1 WHERE id = ?2", rusqlite::params![note, id])?;
+    if updated == 0 {
+        return Err(YukiError::NotFound(format!("Transaction '{}' not found", id)));
+    }
+    Ok(())
+}
 
-    // Start a new session
-    start_conversation(app).await
+/// Record the exchange rate actually paid on a foreign-currency transaction
+/// (primary-currency units per unit of the row's currency). Reports and
+/// conversions use it instead of the currency's rate; None clears it.
+#[tauri::command]
+pub async fn set_transaction_rate(app: AppHandle, id: String, rate: Option<f64>) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    update_transaction_rate(&conn, &id, rate)
 }
 
-/// Get conversation history for the current session
-fn get_conversation_history(app: &AppHandle, limit: usize) -> Result<Vec<ConversationMessage>, String> {
-    let session_id = {
-        let current = CURRENT_SESSION.lock().map_err(|e| e.to_string())?;
-        match current.as_ref() {
-            Some(id) => id.clone(),
-            None => return Ok(vec![]),
+fn update_transaction_rate(conn: &rusqlite::Connection, id: &str, rate: Option<f64>) -> Result<(), YukiError> {
+    if let Some(rate) = rate {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(YukiError::Validation("Exchange rate must be a positive number".to_string()));
         }
-    };
+    }
+    let updated = conn.execute("UPDATE ledger SET exchange_rate = ?1 WHERE id = ?2", rusqlite::params![rate, id])?;
+    if updated == 0 {
+        return Err(YukiError::NotFound(format!("Transaction '{}' not found", id)));
+    }
+    Ok(())
+}
 
-    let conn = database::get_connection(app).map_err(|e| e.to_string())?;
+/// Transactions flagged during extraction that haven't been checked yet, newest first
+#[tauri::command]
+pub async fn get_transactions_needing_review(app: AppHandle) -> Result<Vec<LedgerEntry>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    transactions_needing_review(&conn)
+}
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT role, content FROM conversation_messages
-             WHERE session_id = ?1
-             ORDER BY created_at DESC
-             LIMIT ?2",
-        )
-        .map_err(|e| e.to_string())?;
+fn transactions_needing_review(conn: &rusqlite::Connection) -> Result<Vec<LedgerEntry>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at,
+                original_amount, original_currency, needs_review
+         FROM ledger WHERE needs_review = 1 ORDER BY date DESC, created_at DESC",
+    )?;
 
-    let messages: Vec<ConversationMessage> = stmt
-        .query_map(rusqlite::params![&session_id, limit as i64], |row| {
-            Ok(ConversationMessage {
-                role: row.get(0)?,
-                content: row.get(1)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
+    let entries = stmt
+        .query_map([], ledger_entry_from_row)?
         .filter_map(|r| r.ok())
         .collect();
 
-    // Reverse to get chronological order
-    Ok(messages.into_iter().rev().collect())
+    Ok(entries)
 }
 
-/// Save a message to the conversation history
-fn save_message(app: &AppHandle, role: &str, content: &str) -> Result<(), String> {
-    let session_id = {
-        let current = CURRENT_SESSION.lock().map_err(|e| e.to_string())?;
-        match current.as_ref() {
-            Some(id) => id.clone(),
-            None => return Err("No active conversation session".to_string()),
-        }
-    };
-
-    let conn = database::get_connection(app).map_err(|e| e.to_string())?;
-    let msg_id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
-
-    conn.execute(
-        "INSERT INTO conversation_messages (id, session_id, role, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        [&msg_id, &session_id, role, content, &now],
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Update session timestamp
-    conn.execute(
-        "UPDATE conversation_sessions SET updated_at = ?1 WHERE id = ?2",
-        [&now, &session_id],
-    )
-    .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn mark_reviewed(app: AppHandle, id: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    clear_review_flag(&conn, &id)
+}
 
+fn clear_review_flag(conn: &rusqlite::Connection, id: &str) -> Result<(), YukiError> {
+    let updated = conn.execute("UPDATE ledger SET needs_review = NULL WHERE id = ?1", [id])?;
+    if updated == 0 {
+        return Err(YukiError::NotFound(format!("Transaction '{}' not found", id)));
+    }
     Ok(())
 }
 
-// ============================================================================
-// Account Commands
-// ============================================================================
-
+/// Queue of transactions still filed under "other" (or with no category), newest
+/// first, for categorizing by hand. `limit` defaults to 50.
 #[tauri::command]
-pub async fn get_all_accounts(app: AppHandle) -> Result<Vec<Account>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn get_uncategorized_transactions(
+    app: AppHandle,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<LedgerEntry>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    uncategorized_transactions(&conn, limit.unwrap_or(50), offset.unwrap_or(0))
+}
 
-    let mut stmt = conn
-        .prepare("SELECT id, name, account_type, institution, currency, is_default, created_at FROM accounts ORDER BY is_default DESC, name")
-        .map_err(|e| e.to_string())?;
+fn uncategorized_transactions(
+    conn: &rusqlite::Connection,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<LedgerEntry>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at,
+                original_amount, original_currency, needs_review
+         FROM ledger WHERE category_id IS NULL OR category_id IN ('other', '')
+         ORDER BY date DESC, created_at DESC, id
+         LIMIT ?1 OFFSET ?2",
+    )?;
 
-    let accounts = stmt
-        .query_map([], |row| {
-            Ok(Account {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                account_type: row.get(2)?,
-                institution: row.get(3)?,
-                currency: row.get(4)?,
-                is_default: row.get::<_, i32>(5)? == 1,
-                created_at: row.get(6)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
+    let entries = stmt
+        .query_map(rusqlite::params![limit as i64, offset as i64], ledger_entry_from_row)?
         .filter_map(|r| r.ok())
         .collect();
 
-    Ok(accounts)
+    Ok(entries)
 }
 
+/// Move many transactions to one category at once. Either all of them change
+/// or, if any id is unknown, none do. Returns the number updated.
 #[tauri::command]
-pub async fn add_account(
-    app: AppHandle,
-    name: String,
-    account_type: String,
-    institution: Option<String>,
-    currency: String,
-) -> Result<String, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-    let id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
+pub async fn bulk_categorize(app: AppHandle, ids: Vec<String>, category_id: String) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let updated = categorize_rows(&mut conn, &ids, &category_id)?;
 
-    conn.execute(
-        "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at) VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)",
-        rusqlite::params![&id, &name, &account_type, &institution, &currency, &now],
-    )
-    .map_err(|e| e.to_string())?;
+    log::info!("Moved {} transactions to category {}", updated, category_id);
+    Ok(updated)
+}
 
-    Ok(id)
+fn categorize_rows(conn: &mut rusqlite::Connection, ids: &[String], category_id: &str) -> Result<usize, YukiError> {
+    let category_exists: bool =
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM categories WHERE id = ?1)", [category_id], |row| row.get(0))?;
+    if !category_exists {
+        return Err(YukiError::NotFound(format!("Category '{}' does not exist", category_id)));
+    }
+
+    let mut unique: Vec<&String> = ids.iter().collect();
+    unique.sort();
+    unique.dedup();
+
+    let tx = conn.transaction()?;
+    for id in &unique {
+        if tx.execute("UPDATE ledger SET category_id = ?1 WHERE id = ?2", [category_id, id.as_str()])? == 0 {
+            return Err(YukiError::NotFound(format!("Transaction '{}' not found", id)));
+        }
+    }
+    tx.commit()?;
+    Ok(unique.len())
 }
 
+/// Link a previously uploaded document (e.g. a receipt) to a transaction.
+/// This replaces any existing link. Deleting the document later only unlinks
+/// the transaction, unless the transaction was imported from it.
 #[tauri::command]
-pub async fn delete_account(app: AppHandle, account_id: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn attach_document_to_transaction(
+    app: AppHandle,
+    ledger_id: String,
+    document_id: String,
+) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    link_document_to_transaction(&conn, &ledger_id, &document_id)
+}
 
-    // Check if it's the default account
-    let is_default: i32 = conn
+fn link_document_to_transaction(
+    conn: &rusqlite::Connection,
+    ledger_id: &str,
+    document_id: &str,
+) -> Result<(), YukiError> {
+    let previous: Option<String> = conn
+        .query_row("SELECT document_id FROM ledger WHERE id = ?1", [ledger_id], |row| row.get(0))
+        .map_err(row_not_found(format!("Transaction '{}' not found", ledger_id)))?;
+
+    let document_exists: bool = conn
         .query_row(
-            "SELECT is_default FROM accounts WHERE id = ?1",
-            [&account_id],
+            "SELECT EXISTS(SELECT 1 FROM documents WHERE id = ?1)",
+            [document_id],
             |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
+    if !document_exists {
+        return Err(YukiError::NotFound(format!("Document '{}' not found", document_id)));
+    }
 
-    if is_default == 1 {
-        return Err("Cannot delete the default account".to_string());
+    if let Some(previous) = previous.filter(|p| p != document_id) {
+        log::info!("Transaction {} re-linked from document {} to {}", ledger_id, previous, document_id);
     }
 
-    // Set ledger entries to use default account
     conn.execute(
-        "UPDATE ledger SET account_id = 'default' WHERE account_id = ?1",
-        [&account_id],
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Delete the account
-    conn.execute("DELETE FROM accounts WHERE id = ?1", [&account_id])
-        .map_err(|e| e.to_string())?;
+        "UPDATE ledger SET document_id = ?1, document_attached = CASE WHEN document_id IS ?1 THEN document_attached ELSE 1 END
+         WHERE id = ?2",
+        [document_id, ledger_id],
+    )?;
 
     Ok(())
 }
 
-// ============================================================================
-// Currency Commands
-// ============================================================================
-
+/// Re-categorize every transaction whose merchant (or description, when no merchant
+/// was extracted) matches the pattern. Returns the number of rows that changed.
 #[tauri::command]
-pub async fn get_all_currencies(app: AppHandle) -> Result<Vec<Currency>, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn recategorize_by_merchant(
+    app: AppHandle,
+    merchant_pattern: String,
+    new_category_id: String,
+) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let changed = recategorize_merchant_rows(&mut conn, &merchant_pattern, &new_category_id)?;
 
-    let mut stmt = conn
-        .prepare("SELECT code, name, symbol, conversion_rate, is_primary, created_at FROM currencies ORDER BY is_primary DESC, name")
-        .map_err(|e| e.to_string())?;
+    log::info!(
+        "[recategorize_by_merchant] '{}' -> {}: {} rows changed",
+        merchant_pattern,
+        new_category_id,
+        changed
+    );
+    Ok(changed)
+}
 
-    let currencies = stmt
-        .query_map([], |row| {
-            Ok(Currency {
-                code: row.get(0)?,
-                name: row.get(1)?,
-                symbol: row.get(2)?,
-                conversion_rate: row.get(3)?,
-                is_primary: row.get::<_, i32>(4)? == 1,
-                created_at: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+fn recategorize_merchant_rows(
+    conn: &mut rusqlite::Connection,
+    merchant_pattern: &str,
+    new_category_id: &str,
+) -> Result<usize, YukiError> {
+    let pattern = normalize_merchant(merchant_pattern);
+    if pattern.is_empty() {
+        return Err(YukiError::Validation("Merchant pattern must not be empty".to_string()));
+    }
 
-    Ok(currencies)
+    let category_exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM categories WHERE id = ?1)",
+            [new_category_id],
+            |row| row.get(0),
+        )?;
+    if !category_exists {
+        return Err(YukiError::NotFound(format!("Category '{}' does not exist", new_category_id)));
+    }
+
+    let tx = conn.transaction()?;
+
+    // Matching happens in Rust so the same normalization applies to both sides
+    let matching_ids: Vec<String> = {
+        let mut stmt = tx
+            .prepare("SELECT id, COALESCE(merchant, description) FROM ledger WHERE category_id != ?1")?;
+        let rows = stmt
+            .query_map([new_category_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .filter_map(|r| r.ok())
+            .filter(|(_, merchant)| normalize_merchant(merchant).contains(&pattern))
+            .map(|(id, _)| id)
+            .collect();
+        rows
+    };
+
+    for id in &matching_ids {
+        tx.execute(
+            "UPDATE ledger SET category_id = ?1 WHERE id = ?2",
+            [new_category_id, id.as_str()],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(matching_ids.len())
 }
 
+/// Find groups of transactions that look like duplicate imports of each other
 #[tauri::command]
-pub async fn add_currency(
-    app: AppHandle,
-    code: String,
-    name: String,
-    symbol: String,
-    conversion_rate: f64,
-) -> Result<Currency, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-    let now = chrono::Utc::now().to_rfc3339();
+pub async fn find_all_duplicate_groups(app: AppHandle) -> Result<Vec<DuplicateGroup>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let groups = duplicate_groups(&conn)?;
 
-    conn.execute(
-        "INSERT INTO currencies (code, name, symbol, conversion_rate, is_primary, created_at)
-         VALUES (?1, ?2, ?3, ?4, 0, ?5)",
-        rusqlite::params![&code, &name, &symbol, conversion_rate, &now],
-    )
-    .map_err(|e| e.to_string())?;
+    log::info!("[find_all_duplicate_groups] Found {} duplicate groups", groups.len());
+    Ok(groups)
+}
 
-    Ok(Currency {
-        code,
-        name,
-        symbol,
-        conversion_rate,
-        is_primary: false,
-        created_at: now,
-    })
+/// Rows with the same key are treated as the same transaction. Amounts are keyed
+/// on cents so float noise doesn't split a group.
+fn duplicate_key(date: &str, amount: f64, description: &str) -> (String, i64, String) {
+    (date.to_string(), (amount * 100.0).round() as i64, normalize_merchant(description))
 }
 
-#[tauri::command]
-pub async fn update_currency(
-    app: AppHandle,
-    code: String,
-    name: Option<String>,
-    symbol: Option<String>,
-    conversion_rate: Option<f64>,
-) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+fn duplicate_groups(conn: &rusqlite::Connection) -> Result<Vec<DuplicateGroup>, YukiError> {
+    let ignored = ignored_targets(conn, "duplicate")?;
 
-    if let Some(n) = name {
-        conn.execute("UPDATE currencies SET name = ?1 WHERE code = ?2", [&n, &code])
-            .map_err(|e| e.to_string())?;
-    }
-    if let Some(s) = symbol {
-        conn.execute("UPDATE currencies SET symbol = ?1 WHERE code = ?2", [&s, &code])
-            .map_err(|e| e.to_string())?;
-    }
-    if let Some(r) = conversion_rate {
-        conn.execute(
-            "UPDATE currencies SET conversion_rate = ?1 WHERE code = ?2",
-            rusqlite::params![r, &code],
-        )
-        .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, date, amount, description FROM ledger ORDER BY date, created_at, id")?;
+
+    let rows: Vec<(String, String, f64, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut index: std::collections::HashMap<(String, i64, String), usize> =
+        std::collections::HashMap::new();
+
+    for (id, date, amount, description) in rows {
+        let key = duplicate_key(&date, amount, &description);
+        match index.get(&key) {
+            Some(&i) => groups[i].ids.push(id),
+            None => {
+                let group_key = format!("{}|{}|{}", key.0, key.1, key.2);
+                index.insert(key, groups.len());
+                groups.push(DuplicateGroup {
+                    date,
+                    amount,
+                    description,
+                    ids: vec![id],
+                    key: group_key,
+                });
+            }
+        }
     }
 
-    Ok(())
+    groups.retain(|g| g.ids.len() > 1 && !ignored.contains(&g.key));
+    Ok(groups)
 }
 
+/// Collapse a duplicate group: keep one row and delete the rest, moving any
+/// receipts/purchased items linked to the deleted rows over to the kept row first.
+/// Returns the number of rows deleted.
 #[tauri::command]
-pub async fn delete_currency(app: AppHandle, code: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-
-    // Check if it's the primary currency
-    let is_primary: i32 = conn
-        .query_row(
-            "SELECT is_primary FROM currencies WHERE code = ?1",
-            [&code],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+pub async fn merge_duplicates(
+    app: AppHandle,
+    keep_id: String,
+    delete_ids: Vec<String>,
+) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let deleted = merge_duplicate_rows(&mut conn, &keep_id, &delete_ids)?;
+
+    log::info!("[merge_duplicates] Kept {}, deleted {} duplicates", keep_id, deleted);
+    Ok(deleted)
+}
 
-    if is_primary == 1 {
-        return Err("Cannot delete the primary currency".to_string());
+fn merge_duplicate_rows(
+    conn: &mut rusqlite::Connection,
+    keep_id: &str,
+    delete_ids: &[String],
+) -> Result<usize, YukiError> {
+    if delete_ids.iter().any(|id| id == keep_id) {
+        return Err(YukiError::Validation("The kept transaction cannot also be deleted".to_string()));
     }
 
-    // Check if any transactions use this currency
-    let usage_count: i32 = conn
+    let tx = conn.transaction()?;
+
+    let keep_exists: bool = tx
         .query_row(
-            "SELECT COUNT(*) FROM ledger WHERE currency = ?1",
-            [&code],
+            "SELECT EXISTS(SELECT 1 FROM ledger WHERE id = ?1)",
+            [keep_id],
             |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
+    if !keep_exists {
+        return Err(YukiError::NotFound(format!("Transaction '{}' does not exist", keep_id)));
+    }
 
-    if usage_count > 0 {
-        return Err(format!("Cannot delete currency '{}' - {} transactions use it", code, usage_count));
+    let mut deleted = 0;
+    for id in delete_ids {
+        // Reassign before deleting, otherwise ON DELETE CASCADE would drop the items
+        tx.execute(
+            "UPDATE purchased_items SET ledger_id = ?1 WHERE ledger_id = ?2",
+            [keep_id, id.as_str()],
+        )?;
+        tx.execute(
+            "UPDATE receipts SET ledger_id = ?1 WHERE ledger_id = ?2",
+            [keep_id, id.as_str()],
+        )?;
+
+        deleted += tx
+            .execute("DELETE FROM ledger WHERE id = ?1", [id])?;
     }
 
-    conn.execute("DELETE FROM currencies WHERE code = ?1", [&code])
-        .map_err(|e| e.to_string())?;
+    tx.commit()?;
+    Ok(deleted)
+}
+
+/// Flag types that can be dismissed with `ignore_flag`
+const FLAG_TYPES: [&str; 2] = ["anomaly", "duplicate"];
+
+/// Stop showing a reviewed flag. `target_id` is whatever the detection command
+/// reports for the item: `DuplicateGroup::key` for duplicates; for anomalies the
+/// transaction id of a sign error, `RefundMatch::refund_id`, or the `key` of a
+/// price increase or missing recurring charge.
+#[tauri::command]
+pub async fn ignore_flag(app: AppHandle, flag_type: String, target_id: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    insert_ignored_flag(&conn, &flag_type, &target_id)
+}
+
+fn insert_ignored_flag(conn: &rusqlite::Connection, flag_type: &str, target_id: &str) -> Result<(), YukiError> {
+    if !FLAG_TYPES.contains(&flag_type) {
+        return Err(YukiError::Validation(format!(
+            "Unknown flag type '{}', expected one of: {}",
+            flag_type,
+            FLAG_TYPES.join(", ")
+        )));
+    }
+    if target_id.trim().is_empty() {
+        return Err(YukiError::Validation("Flag target must not be empty".to_string()));
+    }
 
+    conn.execute(
+        "INSERT OR IGNORE INTO ignored_flags (flag_type, target_id, created_at) VALUES (?1, ?2, ?3)",
+        [flag_type, target_id, &chrono::Utc::now().to_rfc3339()],
+    )?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn set_primary_currency(app: AppHandle, code: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn list_ignored_flags(app: AppHandle) -> Result<Vec<IgnoredFlag>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let mut stmt = conn
+        .prepare("SELECT flag_type, target_id, created_at FROM ignored_flags ORDER BY created_at DESC")?;
 
-    // Get the current primary currency's conversion rate (for potential future use)
-    let _old_primary_rate: f64 = conn
-        .query_row(
-            "SELECT conversion_rate FROM currencies WHERE is_primary = 1",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(1.0);
+    let flags = stmt
+        .query_map([], |row| {
+            Ok(IgnoredFlag {
+                flag_type: row.get(0)?,
+                target_id: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
 
-    // Get the new primary currency's conversion rate
-    let new_primary_rate: f64 = conn
-        .query_row(
-            "SELECT conversion_rate FROM currencies WHERE code = ?1",
-            [&code],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+    Ok(flags)
+}
 
-    // Clear all primary flags
-    conn.execute("UPDATE currencies SET is_primary = 0", [])
-        .map_err(|e| e.to_string())?;
+fn ignored_targets(conn: &rusqlite::Connection, flag_type: &str) -> Result<std::collections::HashSet<String>, YukiError> {
+    let mut stmt = conn.prepare("SELECT target_id FROM ignored_flags WHERE flag_type = ?1")?;
+    let targets = stmt
+        .query_map([flag_type], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(targets)
+}
 
-    // Set new primary
-    conn.execute("UPDATE currencies SET is_primary = 1, conversion_rate = 1.0 WHERE code = ?1", [&code])
-        .map_err(|e| e.to_string())?;
+/// `flags` without the anomalies the user has dismissed
+fn without_ignored_anomalies<T>(
+    conn: &rusqlite::Connection,
+    flags: Vec<T>,
+    target_id: impl Fn(&T) -> &str,
+) -> Result<Vec<T>, YukiError> {
+    let ignored = ignored_targets(conn, "anomaly")?;
+    Ok(flags.into_iter().filter(|flag| !ignored.contains(target_id(flag))).collect())
+}
 
-    // Recalculate all other currencies' conversion rates relative to new primary
-    // new_rate = old_rate / new_primary_rate * old_primary_rate
-    if new_primary_rate > 0.0 {
-        conn.execute(
-            "UPDATE currencies SET conversion_rate = conversion_rate / ?1 WHERE code != ?2",
-            rusqlite::params![new_primary_rate, &code],
-        )
-        .map_err(|e| e.to_string())?;
+/// Normalize a merchant name so variants of the same merchant compare equal,
+/// e.g. "VISA-NETFLIX.COM 8841" and "Netflix.com" both become "netflix com".
+/// Lowercases, strips card-processor prefixes, replaces punctuation with spaces
+/// and drops reference-number tokens.
+fn normalize_merchant(raw: &str) -> String {
+    const PREFIXES: [&str; 9] = [
+        "pos ",
+        "visa-",
+        "visa ",
+        "sq *",
+        "sq*",
+        "tst*",
+        "paypal *",
+        "card purchase ",
+        "debit card purchase ",
+    ];
+
+    let mut name = raw.trim().to_lowercase();
+    while let Some(rest) = PREFIXES.iter().find_map(|p| name.strip_prefix(p)) {
+        name = rest.trim_start().to_string();
     }
 
-    // Also update the default currency setting
-    conn.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES ('default_currency', ?1)",
-        [&code],
-    )
-    .map_err(|e| e.to_string())?;
+    let spaced: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
 
-    Ok(())
+    spaced
+        .split_whitespace()
+        // Long tokens containing digits are store numbers / references, not the name
+        .filter(|token| token.len() <= 2 || !token.chars().any(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
+/// Distinct merchant names for typeahead, most used first. Variants of the same
+/// merchant collapse to their most used spelling; `prefix` matches the start of the
+/// normalized name, so "naiv" also matches "POS NAIVAS 0231".
 #[tauri::command]
-pub async fn get_default_currency(app: AppHandle) -> Result<String, String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+pub async fn get_merchants(app: AppHandle, prefix: Option<String>, limit: Option<usize>) -> Result<Vec<String>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    merchant_names(&conn, prefix.as_deref(), limit.unwrap_or(10))
+}
 
-    let currency: String = conn
-        .query_row(
-            "SELECT value FROM settings WHERE key = 'default_currency'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or_else(|_| "KES".to_string());
+fn merchant_names(conn: &rusqlite::Connection, prefix: Option<&str>, limit: usize) -> Result<Vec<String>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT merchant FROM ledger
+         WHERE merchant IS NOT NULL AND TRIM(merchant) != ''
+         ORDER BY date, created_at, id",
+    )?;
+    let rows: Vec<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
 
-    Ok(currency)
+    let prefix = prefix.map(normalize_merchant).filter(|p| !p.is_empty());
+    // normalized key -> spellings with their use counts, in first-seen order
+    let mut merchants: Vec<(String, Vec<(String, usize)>)> = Vec::new();
+    for name in rows {
+        let key = normalize_merchant(&name);
+        if key.is_empty() || prefix.as_ref().is_some_and(|p| !key.starts_with(p.as_str())) {
+            continue;
+        }
+        let name = name.trim().to_string();
+        let spellings = match merchants.iter().position(|(k, _)| *k == key) {
+            Some(i) => &mut merchants[i].1,
+            None => {
+                merchants.push((key, Vec::new()));
+                &mut merchants.last_mut().unwrap().1
+            }
+        };
+        match spellings.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, count)) => *count += 1,
+            None => spellings.push((name, 1)),
+        }
+    }
+
+    // Stable sorts keep first-seen order between equally used merchants and spellings
+    merchants.sort_by_key(|(_, spellings)| std::cmp::Reverse(spellings.iter().map(|(_, c)| c).sum::<usize>()));
+    Ok(merchants
+        .into_iter()
+        .take(limit)
+        .filter_map(|(_, mut spellings)| {
+            spellings.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            spellings.into_iter().next().map(|(name, _)| name)
+        })
+        .collect())
 }
 
-#[tauri::command]
-pub async fn set_default_currency(app: AppHandle, code: String) -> Result<(), String> {
-    let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
+// ============================================================================
+// Recurring Transaction Commands
+// ============================================================================
 
-    // Verify the currency exists
-    let exists: i32 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM currencies WHERE code = ?1",
-            [&code],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn add_recurring_template(
+    app: AppHandle,
+    merchant: String,
+    amount: f64,
+    category_id: String,
+    account_id: Option<String>,
+    day_of_month: u32,
+) -> Result<RecurringTemplate, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let template = RecurringTemplate {
+        id: uuid::Uuid::new_v4().to_string(),
+        merchant: merchant.trim().to_string(),
+        amount,
+        category_id,
+        account_id: account_id.unwrap_or_else(|| "default".to_string()),
+        day_of_month,
+        active: true,
+        last_posted_cycle: None,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    insert_recurring_template(&conn, &template)?;
+    Ok(template)
+}
 
-    if exists == 0 {
-        return Err(format!("Currency '{}' does not exist", code));
+fn insert_recurring_template(conn: &rusqlite::Connection, template: &RecurringTemplate) -> Result<(), YukiError> {
+    if template.merchant.is_empty() {
+        return Err(YukiError::Validation("A recurring transaction needs a merchant".to_string()));
+    }
+    if template.amount == 0.0 || !template.amount.is_finite() {
+        return Err(YukiError::Validation("A recurring transaction needs a non-zero amount".to_string()));
+    }
+    if !(1..=31).contains(&template.day_of_month) {
+        return Err(YukiError::Validation(format!(
+            "Day of month must be between 1 and 31, got {}",
+            template.day_of_month
+        )));
+    }
+    account_currency(conn, &template.account_id)?;
+    let category_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM categories WHERE id = ?1)",
+        [&template.category_id],
+        |row| row.get(0),
+    )?;
+    if !category_exists {
+        return Err(YukiError::NotFound(format!("Category '{}' not found", template.category_id)));
     }
 
     conn.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES ('default_currency', ?1)",
-        [&code],
-    )
-    .map_err(|e| e.to_string())?;
-
+        "INSERT INTO recurring_templates (id, merchant, amount, category_id, account_id, day_of_month, active, last_posted_cycle, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![
+            template.id,
+            template.merchant,
+            template.amount,
+            template.category_id,
+            template.account_id,
+            template.day_of_month,
+            template.active,
+            template.last_posted_cycle,
+            template.created_at,
+        ],
+    )?;
     Ok(())
 }
 
-// ============================================================================
-// Query Commands
-// ============================================================================
-
 #[tauri::command]
-pub async fn process_query(app: AppHandle, question: String) -> Result<ResponseData, String> {
-    log::info!("========================================");
-    log::info!("[PIPELINE] Starting query processing");
-    log::info!("[PIPELINE] User question: {}", question);
-    log::info!("========================================");
-
-    // Ensure we have a conversation session
-    let _ = get_or_create_session(app.clone()).await;
-
-    // Get conversation history (last 10 messages for context)
-    let history = get_conversation_history(&app, 10).unwrap_or_default();
-    log::info!("[PIPELINE] Loaded {} messages from conversation history", history.len());
-
-    // Save the user's message
-    let _ = save_message(&app, "user", &question);
-
-    let settings = get_settings(app.clone()).await?;
-
-    let provider = settings
-        .provider
-        .ok_or_else(|| "No LLM provider configured".to_string())?;
-
-    log::info!("[PIPELINE] Using provider: {} ({})", provider.name, provider.provider_type);
+pub async fn get_recurring_templates(app: AppHandle) -> Result<Vec<RecurringTemplate>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    recurring_templates(&conn)
+}
 
-    // Step 1: Determine if this is a data query or conversational query
-    log::info!("[PIPELINE] Step 1: Analyzing query...");
-    let query_analysis = llm::analyze_query(&provider, &question, &history)
-        .await
-        .map_err(|e| e.to_string())?;
+fn recurring_templates(conn: &rusqlite::Connection) -> Result<Vec<RecurringTemplate>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, merchant, amount, category_id, account_id, day_of_month, active, last_posted_cycle, created_at
+         FROM recurring_templates ORDER BY day_of_month, merchant",
+    )?;
+    let templates = stmt
+        .query_map([], |row| {
+            Ok(RecurringTemplate {
+                id: row.get(0)?,
+                merchant: row.get(1)?,
+                amount: row.get(2)?,
+                category_id: row.get(3)?,
+                account_id: row.get(4)?,
+                day_of_month: row.get(5)?,
+                active: row.get(6)?,
+                last_posted_cycle: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(templates)
+}
 
-    log::info!("[PIPELINE] Query analysis result:");
-    log::info!("[PIPELINE]   - needs_data: {}", query_analysis.needs_data);
-    log::info!("[PIPELINE]   - query_type: {}", query_analysis.query_type);
-    log::info!("[PIPELINE]   - sql_query: {:?}", query_analysis.sql_query);
+/// Pause or resume a template; paused templates are skipped by post_due_recurring
+#[tauri::command]
+pub async fn set_recurring_template_active(app: AppHandle, id: String, active: bool) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    if conn.execute("UPDATE recurring_templates SET active = ?1 WHERE id = ?2", rusqlite::params![active, id])? == 0 {
+        return Err(YukiError::NotFound(format!("Recurring transaction '{}' not found", id)));
+    }
+    Ok(())
+}
 
-    // Step 2: If it's a data query, execute SQL and format results
-    if query_analysis.needs_data {
-        let sql = query_analysis.sql_query.clone().unwrap_or_default();
-        log::info!("[PIPELINE] Step 2: Executing SQL query...");
-        log::info!("[PIPELINE] SQL: {}", sql);
+/// Stop posting a template. Rows it already posted stay in the ledger.
+#[tauri::command]
+pub async fn delete_recurring_template(app: AppHandle, id: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    if conn.execute("DELETE FROM recurring_templates WHERE id = ?1", [&id])? == 0 {
+        return Err(YukiError::NotFound(format!("Recurring transaction '{}' not found", id)));
+    }
+    Ok(())
+}
 
-        // Get the connection and execute the query
-        let conn = database::get_connection(&app).map_err(|e| e.to_string())?;
-
-        let query_result = execute_query(&conn, &sql);
-
-        match query_result {
-            Ok(data) => {
-                log::info!("[PIPELINE] SQL execution successful!");
-                log::info!("[PIPELINE] Raw data: {}", data);
-
-                // Check if we got any results
-                let parsed: serde_json::Value = serde_json::from_str(&data).unwrap_or_default();
-                let row_count = parsed["row_count"].as_i64().unwrap_or(0);
-
-                if row_count == 0 {
-                    // No data found - return a helpful message without calling LLM again
-                    log::info!("[PIPELINE] No data returned, skipping LLM formatting");
-                    log::info!("========================================");
-                    return Ok(ResponseData {
-                        cards: vec![ResponseCard::Text(TextContent {
-                            body: "I don't have any data matching that query yet. Try uploading some financial documents or receipts first, and then I can help you analyze your spending!".to_string(),
-                            is_error: Some(false),
-                        })],
-                    });
-                }
+/// Post every active template whose day has come this month and that hasn't been
+/// posted for this month yet. Meant to be called on launch; calling it again in
+/// the same month posts nothing. Missed earlier months are not back-filled.
+/// Returns the number of ledger rows added and the due templates whose account
+/// is gone; those stay unposted for the month so they post once it's fixed.
+#[tauri::command]
+pub async fn post_due_recurring(app: AppHandle) -> Result<RecurringPostSummary, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let summary = post_due_recurring_rows(&mut conn, chrono::Local::now().date_naive())?;
 
-                // Step 3: Format the results with the LLM
-                log::info!("[PIPELINE] Step 3: Formatting results with LLM ({} rows)...", row_count);
-                let response = llm::format_query_results(&provider, &question, &data, &history)
-                    .await
-                    .map_err(|e| e.to_string())?;
-
-                // Save the assistant's response to conversation history
-                if let Some(first_card) = response.cards.first() {
-                    let response_text = match first_card {
-                        ResponseCard::Text(content) => content.body.clone(),
-                        ResponseCard::Chart(content) => format!("[Chart: {}]", content.title),
-                        ResponseCard::Table(content) => format!("[Table: {}]", content.title),
-                        ResponseCard::Mixed(content) => content.body.clone(),
-                    };
-                    let _ = save_message(&app, "assistant", &response_text);
-                }
+    if summary.posted > 0 {
+        log::info!("Posted {} recurring transactions", summary.posted);
+    }
+    for template in &summary.skipped {
+        log::warn!(
+            "Skipped recurring transaction '{}' ({}): account '{}' not found",
+            template.merchant, template.id, template.account_id
+        );
+    }
+    Ok(summary)
+}
 
-                log::info!("[PIPELINE] Final response generated with {} cards", response.cards.len());
-                log::info!("========================================");
-                Ok(response)
-            }
-            Err(e) => {
-                log::error!("[PIPELINE] SQL execution FAILED!");
-                log::error!("[PIPELINE] Error: {}", e);
-                log::error!("[PIPELINE] Failed SQL: {}", sql);
-                log::info!("========================================");
+fn post_due_recurring_rows(
+    conn: &mut rusqlite::Connection,
+    today: chrono::NaiveDate,
+) -> Result<RecurringPostSummary, YukiError> {
+    use chrono::Datelike;
+
+    let cycle = today.format("%Y-%m").to_string();
+    // A template on the 31st posts on the 30th in April and the 28th in February
+    let last_day = chrono::NaiveDate::from_ymd_opt(today.year(), today.month(), 1)
+        .and_then(|first| first.checked_add_months(chrono::Months::new(1)))
+        .map(|next| (next - chrono::Duration::days(1)).day())
+        .unwrap_or(28);
+
+    let due: Vec<RecurringTemplate> = recurring_templates(conn)?
+        .into_iter()
+        .filter(|t| t.active && t.day_of_month.min(last_day) <= today.day())
+        .collect();
 
-                // Return a friendly error message
-                Ok(ResponseData {
-                    cards: vec![ResponseCard::Text(TextContent {
-                        body: format!("I couldn't retrieve that data. Error: {} in {}", e, sql),
-                        is_error: Some(true),
-                    })],
-                })
-            }
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+    let mut summary = RecurringPostSummary::default();
+    for template in due {
+        // Claim the cycle before inserting; a template already posted this month
+        // changes no row. Both happen in one transaction, so two launches racing
+        // can't post the same month twice.
+        let claimed = tx.execute(
+            "UPDATE recurring_templates SET last_posted_cycle = ?1
+             WHERE id = ?2 AND (last_posted_cycle IS NULL OR last_posted_cycle < ?1)",
+            [&cycle, &template.id],
+        )?;
+        if claimed == 0 {
+            continue;
         }
-    } else {
-        // It's a conversational query, respond directly
-        log::info!("[PIPELINE] Step 2: Processing as conversational query (no data needed)");
-        let response = llm::process_conversational_query(&provider, &question, &history)
-            .await
-            .map_err(|e| e.to_string())?;
 
-        // Save the assistant's response to conversation history
-        if let Some(first_card) = response.cards.first() {
-            let response_text = match first_card {
-                ResponseCard::Text(content) => content.body.clone(),
-                ResponseCard::Chart(content) => format!("[Chart: {}]", content.title),
-                ResponseCard::Table(content) => format!("[Table: {}]", content.title),
-                ResponseCard::Mixed(content) => content.body.clone(),
-            };
-            let _ = save_message(&app, "assistant", &response_text);
+        let date = format!("{}-{:02}", cycle, template.day_of_month.min(last_day));
+        let inserted = tx.execute(
+            "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at)
+             SELECT ?1, NULL, ?2, ?3, ?4, ?5, currency, ?6, ?4, NULL, 'recurring', ?7 FROM accounts WHERE id = ?2",
+            rusqlite::params![
+                uuid::Uuid::new_v4().to_string(),
+                template.account_id,
+                date,
+                template.merchant,
+                template.amount,
+                template.category_id,
+                now,
+            ],
+        )?;
+        if inserted == 0 {
+            // The account was deleted; give the cycle back so the month isn't lost
+            tx.execute(
+                "UPDATE recurring_templates SET last_posted_cycle = ?1 WHERE id = ?2",
+                rusqlite::params![template.last_posted_cycle, template.id],
+            )?;
+            summary.skipped.push(template);
+            continue;
         }
-
-        log::info!("[PIPELINE] Conversational response generated");
-        log::info!("========================================");
-        Ok(response)
+        summary.posted += 1;
     }
+    tx.commit()?;
+    Ok(summary)
 }
 
-/// Execute a SQL query and return the results as a JSON string
-fn execute_query(conn: &rusqlite::Connection, sql: &str) -> Result<String, String> {
-    log::info!("Executing SQL: {}", sql);
+// ============================================================================
+// Category Commands
+// ============================================================================
 
-    // Safety check - only allow SELECT queries
-    let sql_upper = sql.trim().to_uppercase();
-    if !sql_upper.starts_with("SELECT") {
-        return Err("Only SELECT queries are allowed".to_string());
-    }
+/// Categories as the list shows them: pinned first, then the custom order, then by name
+#[tauri::command]
+pub async fn get_all_categories(app: AppHandle) -> Result<Vec<Category>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    all_categories(&conn)
+}
 
-    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+fn all_categories(conn: &rusqlite::Connection) -> Result<Vec<Category>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, icon, color, is_default, created_at, sort_order, is_pinned FROM categories
+         ORDER BY is_pinned DESC, sort_order IS NULL, sort_order, name",
+    )?;
 
-    let rows: Vec<Vec<serde_json::Value>> = stmt
+    let categories = stmt
         .query_map([], |row| {
-            let mut values: Vec<serde_json::Value> = Vec::new();
-            for i in 0..column_names.len() {
-                let value: rusqlite::Result<rusqlite::types::Value> = row.get(i);
-                let json_value = match value {
-                    Ok(rusqlite::types::Value::Null) => serde_json::Value::Null,
-                    Ok(rusqlite::types::Value::Integer(i)) => serde_json::json!(i),
-                    Ok(rusqlite::types::Value::Real(f)) => serde_json::json!(f),
-                    Ok(rusqlite::types::Value::Text(s)) => serde_json::json!(s),
-                    Ok(rusqlite::types::Value::Blob(b)) => serde_json::json!(format!("<blob {} bytes>", b.len())),
-                    Err(_) => serde_json::Value::Null,
-                };
-                values.push(json_value);
-            }
-            Ok(values)
-        })
-        .map_err(|e| e.to_string())?
+            Ok(Category {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                icon: row.get(2)?,
+                color: row.get(3)?,
+                is_default: row.get::<_, i32>(4)? == 1,
+                created_at: row.get(5)?,
+                sort_order: row.get(6)?,
+                is_pinned: row.get::<_, i32>(7)? == 1,
+            })
+        })?
         .filter_map(|r| r.ok())
         .collect();
 
-    let result = serde_json::json!({
-        "columns": column_names,
-        "rows": rows,
-        "row_count": rows.len()
-    });
+    Ok(categories)
+}
 
-    log::info!("Query returned {} rows", rows.len());
-    Ok(result.to_string())
+/// Put categories in the given order. Categories left out of `ordered_ids` lose
+/// their position and sort by name after the ordered ones.
+#[tauri::command]
+pub async fn set_category_order(app: AppHandle, ordered_ids: Vec<String>) -> Result<(), YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    apply_category_order(&mut conn, &ordered_ids)
+}
+
+fn apply_category_order(conn: &mut rusqlite::Connection, ordered_ids: &[String]) -> Result<(), YukiError> {
+    let mut seen = std::collections::HashSet::new();
+    if let Some(repeated) = ordered_ids.iter().find(|id| !seen.insert(id.as_str())) {
+        return Err(YukiError::Validation(format!("Category '{}' appears more than once", repeated)));
+    }
+
+    let tx = conn.transaction()?;
+    tx.execute("UPDATE categories SET sort_order = NULL", [])?;
+    for (position, id) in ordered_ids.iter().enumerate() {
+        let updated = tx.execute(
+            "UPDATE categories SET sort_order = ?1 WHERE id = ?2",
+            rusqlite::params![position as i64, id],
+        )?;
+        if updated == 0 {
+            // Dropping the transaction leaves the previous order in place
+            return Err(YukiError::NotFound(format!("Category '{}' not found", id)));
+        }
+    }
+    tx.commit()?;
+    Ok(())
 }
 
+/// Pin a category to the top of the list, or unpin it
 #[tauri::command]
-pub async fn parse_document_text(
-    app: AppHandle,
-    text: String,
-    categories: Vec<String>,
-) -> Result<Vec<ExtractedTransaction>, String> {
-    log::info!("[parse_document_text] ========== COMMAND CALLED ==========");
-    log::info!("[parse_document_text] Text length: {} chars", text.len());
-    log::info!("[parse_document_text] Categories: {:?}", categories);
+pub async fn pin_category(app: AppHandle, id: String, pinned: bool) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    set_category_pinned(&conn, &id, pinned)
+}
 
-    let settings = get_settings(app).await?;
+fn set_category_pinned(conn: &rusqlite::Connection, id: &str, pinned: bool) -> Result<(), YukiError> {
+    let updated = conn.execute(
+        "UPDATE categories SET is_pinned = ?1 WHERE id = ?2",
+        rusqlite::params![pinned as i32, id],
+    )?;
+    if updated == 0 {
+        return Err(YukiError::NotFound(format!("Category '{}' not found", id)));
+    }
+    Ok(())
+}
 
-    let provider = settings
-        .provider
-        .ok_or_else(|| "No LLM provider configured".to_string())?;
+#[tauri::command]
+pub async fn get_category_names(app: AppHandle) -> Result<Vec<String>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
 
-    log::info!("[parse_document_text] Using provider: {} ({})", provider.name, provider.provider_type);
+    let mut stmt = conn
+        .prepare("SELECT name FROM categories ORDER BY name")?;
 
-    let result = llm::parse_document_with_llm(&provider, &text, &categories)
-        .await
-        .map_err(|e| {
-            log::error!("[parse_document_text] LLM parsing failed: {}", e);
-            e.to_string()
-        })?;
+    let names = stmt
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
 
-    log::info!("[parse_document_text] ========== RESULT: {} transactions ==========", result.len());
-    Ok(result)
+    Ok(names)
 }
 
+/// Icon names the category picker can render (lucide icon ids)
+const CATEGORY_ICONS: &[&str] = &[
+    "banknote", "baby", "book", "briefcase", "bus", "car", "circle", "coffee", "credit-card", "dog",
+    "dumbbell", "film", "fuel", "gift", "graduation-cap", "heart-pulse", "home", "music", "piggy-bank",
+    "plane", "receipt", "repeat", "shirt", "shopping-bag", "shopping-cart", "smartphone", "tag", "user",
+    "utensils", "wallet", "wifi", "wrench", "zap",
+];
+
+const DEFAULT_CATEGORY_COLOR: &str = "#71717a";
+
 #[tauri::command]
-pub async fn parse_receipt_image(
+pub async fn add_category(
     app: AppHandle,
-    image_path: String,
-    categories: Vec<String>,
-) -> Result<ParsedReceipt, String> {
-    let settings = get_settings(app).await?;
+    name: String,
+    color: Option<String>,
+    icon: Option<String>,
+) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    insert_category(&conn, &name, color.as_deref(), icon.as_deref())
+}
 
-    let provider = settings
-        .provider
-        .ok_or_else(|| "No LLM provider configured".to_string())?;
+fn insert_category(
+    conn: &rusqlite::Connection,
+    name: &str,
+    color: Option<&str>,
+    icon: Option<&str>,
+) -> Result<String, YukiError> {
+    let color = validate_category_color(color.unwrap_or(DEFAULT_CATEGORY_COLOR))?;
+    let icon = icon.map(validate_category_icon).transpose()?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
 
-    llm::parse_receipt_with_llm(&provider, &image_path, &categories)
-        .await
-        .map_err(|e| e.to_string())
+    conn.execute(
+        "INSERT INTO categories (id, name, icon, color, is_default, created_at) VALUES (?1, ?2, ?3, ?4, 0, ?5)",
+        rusqlite::params![&id, name, icon, &color, &now],
+    )?;
+
+    Ok(id)
 }
 
+/// Change a category's name, color or icon; an empty `icon` clears it
 #[tauri::command]
-pub async fn parse_statement_image(
+pub async fn update_category(
     app: AppHandle,
-    image_path: String,
-    categories: Vec<String>,
-) -> Result<Vec<ExtractedTransaction>, String> {
-    log::info!("[parse_statement_image] Starting for: {}", image_path);
+    id: String,
+    name: Option<String>,
+    color: Option<String>,
+    icon: Option<String>,
+) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    update_category_fields(&conn, &id, name.as_deref(), color.as_deref(), icon.as_deref())
+}
 
-    let settings = get_settings(app).await?;
+fn update_category_fields(
+    conn: &rusqlite::Connection,
+    id: &str,
+    name: Option<&str>,
+    color: Option<&str>,
+    icon: Option<&str>,
+) -> Result<(), YukiError> {
+    // Validate everything before writing anything
+    let color = color.map(validate_category_color).transpose()?;
+    let icon = match icon.map(str::trim) {
+        Some("") => Some(None),
+        Some(icon) => Some(Some(validate_category_icon(icon)?)),
+        None => None,
+    };
+    let name = name.map(str::trim);
+    if name == Some("") {
+        return Err(YukiError::Validation("Category name must not be empty".to_string()));
+    }
 
-    let provider = settings
-        .provider
-        .ok_or_else(|| "No LLM provider configured".to_string())?;
+    let exists: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM categories WHERE id = ?1)", [id], |row| row.get(0))?;
+    if !exists {
+        return Err(YukiError::NotFound(format!("Category '{}' not found", id)));
+    }
 
-    log::info!("[parse_statement_image] Calling parse_statement_with_vision_llm...");
+    if let Some(name) = name {
+        conn.execute("UPDATE categories SET name = ?1 WHERE id = ?2", [name, id])?;
+    }
+    if let Some(color) = color {
+        conn.execute("UPDATE categories SET color = ?1 WHERE id = ?2", [&color, id])?;
+    }
+    if let Some(icon) = icon {
+        conn.execute("UPDATE categories SET icon = ?1 WHERE id = ?2", rusqlite::params![icon, id])?;
+    }
 
-    let result = llm::parse_statement_with_vision_llm(&provider, &image_path, &categories)
-        .await
-        .map_err(|e| {
-            log::error!("[parse_statement_image] LLM parsing failed: {}", e);
-            e.to_string()
-        })?;
+    Ok(())
+}
 
-    log::info!("[parse_statement_image] SUCCESS: Got {} transactions, returning to frontend", result.len());
+/// `#rrggbb`, returned lowercased
+fn validate_category_color(color: &str) -> Result<String, YukiError> {
+    let color = color.trim();
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        return Err(YukiError::Validation(format!("Invalid color '{}', expected #rrggbb", color)));
+    }
+    Ok(color.to_lowercase())
+}
 
-    Ok(result)
+fn validate_category_icon(icon: &str) -> Result<String, YukiError> {
+    let icon = icon.trim().to_lowercase();
+    if !CATEGORY_ICONS.contains(&icon.as_str()) {
+        return Err(YukiError::Validation(format!("Unknown category icon '{}'", icon)));
+    }
+    Ok(icon)
 }
 
+/// Give custom categories still on the default gray (or with no color) their
+/// own color, so they can be told apart in charts. Returns how many changed.
 #[tauri::command]
-pub async fn parse_receipt_text(
-    app: AppHandle,
-    text: String,
-    categories: Vec<String>,
-) -> Result<ParsedReceipt, String> {
-    let settings = get_settings(app).await?;
+pub async fn assign_category_colors(app: AppHandle) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    recolor_gray_categories(&mut conn)
+}
 
-    let provider = settings
-        .provider
-        .ok_or_else(|| "No LLM provider configured".to_string())?;
+fn recolor_gray_categories(conn: &mut rusqlite::Connection) -> Result<usize, YukiError> {
+    // The seeded "Other" category is gray on purpose
+    let categories: Vec<(String, Option<String>, bool)> = conn
+        .prepare("SELECT id, color, is_default FROM categories ORDER BY created_at, id")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    let is_gray = |color: &Option<String>| color.as_deref().map_or(true, |c| c.eq_ignore_ascii_case(DEFAULT_CATEGORY_COLOR));
+    let (gray, colored): (Vec<_>, Vec<_>) =
+        categories.into_iter().partition(|(_, color, is_default)| !is_default && is_gray(color));
+
+    let mut used_hues: Vec<f64> = colored.iter().filter_map(|(_, color, _)| color.as_deref().and_then(hex_hue)).collect();
+    let tx = conn.transaction()?;
+    for (id, _, _) in &gray {
+        let hue = most_distant_hue(&used_hues);
+        used_hues.push(hue);
+        tx.execute("UPDATE categories SET color = ?1 WHERE id = ?2", [&hsl_hex(hue, 0.65, 0.5), id])?;
+    }
+    tx.commit()?;
+    Ok(gray.len())
+}
 
-    llm::parse_receipt_text_with_llm(&provider, &text, &categories)
-        .await
-        .map_err(|e| e.to_string())
+/// Whole-degree hue furthest (around the color wheel) from every hue in
+/// `used`, the lowest such hue on a tie so the result is deterministic
+fn most_distant_hue(used: &[f64]) -> f64 {
+    let distance = |a: f64, b: f64| {
+        let d = (a - b).rem_euclid(360.0);
+        d.min(360.0 - d)
+    };
+    let mut best = (0.0, f64::MIN);
+    for hue in (0..360).map(f64::from) {
+        let nearest = used.iter().map(|u| distance(hue, *u)).fold(f64::MAX, f64::min);
+        if nearest > best.1 {
+            best = (hue, nearest);
+        }
+    }
+    best.0
 }
 
-#[tauri::command]
-pub async fn detect_expense(app: AppHandle, message: String) -> Result<ExpenseDetectionResult, String> {
-    let settings = get_settings(app).await?;
+/// Hue in degrees of a `#rrggbb` color, or None for grays, which have none
+fn hex_hue(color: &str) -> Option<f64> {
+    let channel = |i: usize| u8::from_str_radix(color.get(i..i + 2)?, 16).ok().map(|v| f64::from(v) / 255.0);
+    let (r, g, b) = (channel(1)?, channel(3)?, channel(5)?);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta < 0.05 {
+        return None;
+    }
+    let hue = if max == r {
+        (g - b) / delta
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    Some((hue * 60.0).rem_euclid(360.0))
+}
 
-    let provider = settings
-        .provider
-        .ok_or_else(|| "No LLM provider configured".to_string())?;
+/// `#rrggbb` for a hue in degrees and saturation and lightness in 0..=1
+fn hsl_hex(hue: f64, saturation: f64, lightness: f64) -> String {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let byte = |v: f64| ((v + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b))
+}
 
-    llm::detect_expense_with_llm(&provider, &message)
-        .await
-        .map_err(|e| e.to_string())
+// ============================================================================
+// Receipt Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn save_receipt(app: AppHandle, receipt: Receipt) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    let items_json = serde_json::to_string(&receipt.items)?;
+
+    conn.execute(
+        "INSERT INTO receipts (id, document_id, ledger_id, merchant, items, tax, total) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            &receipt.id,
+            &receipt.document_id,
+            &receipt.ledger_id,
+            &receipt.merchant,
+            &items_json,
+            receipt.tax,
+            receipt.total,
+        ],
+    )?;
+
+    Ok(receipt.id)
+}
+
+// ============================================================================
+// Purchased Items Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn save_purchased_item(app: AppHandle, item: PurchasedItem) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    conn.execute(
+        "INSERT INTO purchased_items (id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        rusqlite::params![
+            &item.id,
+            &item.receipt_id,
+            &item.ledger_id,
+            &item.name,
+            item.quantity,
+            &item.unit,
+            item.unit_price,
+            item.total_price,
+            &item.category,
+            &item.brand,
+            &item.purchased_at,
+            &item.created_at,
+        ],
+    )?;
+
+    Ok(item.id)
+}
+
+#[tauri::command]
+pub async fn save_purchased_items(app: AppHandle, items: Vec<PurchasedItem>) -> Result<Vec<String>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    let mut ids = Vec::with_capacity(items.len());
+    for item in items {
+        conn.execute(
+            "INSERT INTO purchased_items (id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            rusqlite::params![
+                &item.id,
+                &item.receipt_id,
+                &item.ledger_id,
+                &item.name,
+                item.quantity,
+                &item.unit,
+                item.unit_price,
+                item.total_price,
+                &item.category,
+                &item.brand,
+                &item.purchased_at,
+                &item.created_at,
+            ],
+        )?;
+        ids.push(item.id);
+    }
+
+    Ok(ids)
+}
+
+#[tauri::command]
+pub async fn get_purchased_items(app: AppHandle, ledger_id: Option<String>) -> Result<Vec<PurchasedItem>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    let query = if ledger_id.is_some() {
+        "SELECT id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at
+         FROM purchased_items WHERE ledger_id = ?1 ORDER BY purchased_at DESC"
+    } else {
+        "SELECT id, receipt_id, ledger_id, name, quantity, unit, unit_price, total_price, category, brand, purchased_at, created_at
+         FROM purchased_items ORDER BY purchased_at DESC"
+    };
+
+    let mut stmt = conn.prepare(query)?;
+
+    let items: Vec<PurchasedItem> = if let Some(ref lid) = ledger_id {
+        stmt.query_map([lid], |row| {
+            Ok(PurchasedItem {
+                id: row.get(0)?,
+                receipt_id: row.get(1)?,
+                ledger_id: row.get(2)?,
+                name: row.get(3)?,
+                quantity: row.get(4)?,
+                unit: row.get(5)?,
+                unit_price: row.get(6)?,
+                total_price: row.get(7)?,
+                category: row.get(8)?,
+                brand: row.get(9)?,
+                purchased_at: row.get(10)?,
+                created_at: row.get(11)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect()
+    } else {
+        stmt.query_map([], |row| {
+            Ok(PurchasedItem {
+                id: row.get(0)?,
+                receipt_id: row.get(1)?,
+                ledger_id: row.get(2)?,
+                name: row.get(3)?,
+                quantity: row.get(4)?,
+                unit: row.get(5)?,
+                unit_price: row.get(6)?,
+                total_price: row.get(7)?,
+                category: row.get(8)?,
+                brand: row.get(9)?,
+                purchased_at: row.get(10)?,
+                created_at: row.get(11)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect()
+    };
+
+    Ok(items)
+}
+
+#[tauri::command]
+pub async fn delete_purchased_item(app: AppHandle, item_id: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    conn.execute("DELETE FROM purchased_items WHERE id = ?1", [&item_id])?;
+
+    Ok(())
+}
+
+/// Map item names matching `pattern` (e.g. "coke" or "coca-cola*") onto
+/// `canonical` in get_item_spending_normalized. Re-adding a pattern replaces it.
+#[tauri::command]
+pub async fn add_item_alias(app: AppHandle, pattern: String, canonical: String) -> Result<ItemAlias, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    insert_item_alias(&conn, &pattern, &canonical)
+}
+
+fn insert_item_alias(conn: &rusqlite::Connection, pattern: &str, canonical: &str) -> Result<ItemAlias, YukiError> {
+    let pattern = normalize_item_name(pattern);
+    let canonical = canonical.split_whitespace().collect::<Vec<_>>().join(" ");
+    if pattern.trim_matches('*').is_empty() {
+        return Err(YukiError::Validation("Alias pattern must contain more than '*'".to_string()));
+    }
+    if canonical.is_empty() {
+        return Err(YukiError::Validation("Canonical item name must not be empty".to_string()));
+    }
+
+    let alias = ItemAlias {
+        pattern,
+        canonical,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    conn.execute(
+        "INSERT OR REPLACE INTO item_aliases (pattern, canonical, created_at) VALUES (?1, ?2, ?3)",
+        [&alias.pattern, &alias.canonical, &alias.created_at],
+    )?;
+    Ok(alias)
+}
+
+/// Purchased-item spending with aliased names folded together, largest first
+#[tauri::command]
+pub async fn get_item_spending_normalized(app: AppHandle) -> Result<Vec<NormalizedItemSpending>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    item_spending_normalized(&conn)
+}
+
+fn item_spending_normalized(conn: &rusqlite::Connection) -> Result<Vec<NormalizedItemSpending>, YukiError> {
+    let mut aliases: Vec<(String, String)> = {
+        let mut stmt = conn.prepare("SELECT pattern, canonical FROM item_aliases")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        rows
+    };
+    // Exact names beat wildcards, then the longest (most specific) pattern wins
+    aliases.sort_by_key(|(pattern, _)| (pattern.contains('*'), std::cmp::Reverse(pattern.replace('*', "").len())));
+
+    let mut stmt = conn.prepare("SELECT name, quantity, total_price FROM purchased_items")?;
+    let items: Vec<(String, f64, f64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut groups: HashMap<String, NormalizedItemSpending> = HashMap::new();
+    for (name, quantity, total_price) in items {
+        let normalized = normalize_item_name(&name);
+        let canonical = aliases
+            .iter()
+            .find(|(pattern, _)| glob_matches(pattern, &normalized))
+            .map(|(_, canonical)| canonical.clone())
+            .unwrap_or(normalized);
+
+        let group = groups.entry(canonical.to_lowercase()).or_insert_with(|| NormalizedItemSpending {
+            name: canonical,
+            quantity: 0.0,
+            total_price: 0.0,
+            purchase_count: 0,
+            variants: Vec::new(),
+        });
+        group.quantity += quantity;
+        group.total_price += total_price;
+        group.purchase_count += 1;
+        let variant = name.trim().to_string();
+        if !group.variants.contains(&variant) {
+            group.variants.push(variant);
+        }
+    }
+
+    let mut spending: Vec<NormalizedItemSpending> = groups.into_values().collect();
+    for group in &mut spending {
+        group.variants.sort();
+    }
+    spending.sort_by(|a, b| b.total_price.total_cmp(&a.total_price).then_with(|| a.name.cmp(&b.name)));
+    Ok(spending)
+}
+
+fn normalize_item_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// The priciest single purchased items, by line total or, with
+/// `sort_by = "unit_price"`, by price per unit. `limit` defaults to 10.
+#[tauri::command]
+pub async fn get_most_expensive_items(
+    app: AppHandle,
+    limit: Option<usize>,
+    sort_by: Option<String>,
+) -> Result<Vec<ExpensiveItem>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    most_expensive_items(&conn, limit.unwrap_or(10), sort_by.as_deref().unwrap_or("total_price"))
+}
+
+fn most_expensive_items(conn: &rusqlite::Connection, limit: usize, sort_by: &str) -> Result<Vec<ExpensiveItem>, YukiError> {
+    // Items saved without a unit price cost their line total spread over the quantity
+    let order = match sort_by {
+        "total_price" => "p.total_price",
+        "unit_price" => "COALESCE(p.unit_price, p.total_price / NULLIF(p.quantity, 0), p.total_price)",
+        other => {
+            return Err(YukiError::Validation(format!(
+                "Unknown sort '{}', expected 'total_price' or 'unit_price'",
+                other
+            )))
+        }
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT p.id, p.name, p.quantity, p.unit_price, p.total_price, p.brand,
+                COALESCE(r.merchant, NULLIF(l.merchant, ''), l.description), date(p.purchased_at)
+         FROM purchased_items p
+         LEFT JOIN receipts r ON p.receipt_id = r.id
+         LEFT JOIN ledger l ON p.ledger_id = l.id
+         ORDER BY {} DESC, p.purchased_at DESC, p.id
+         LIMIT ?1",
+        order
+    ))?;
+
+    let items = stmt
+        .query_map([limit as i64], |row| {
+            let raw_name: String = row.get(1)?;
+            Ok(ExpensiveItem {
+                id: row.get(0)?,
+                name: display_item_name(&raw_name),
+                raw_name,
+                quantity: row.get(2)?,
+                unit_price: row.get(3)?,
+                total_price: row.get(4)?,
+                brand: row.get(5)?,
+                merchant: row.get(6)?,
+                purchased_at: row.get(7)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(items)
+}
+
+/// Readable form of an extracted item name: "organic-whole-milk" becomes
+/// "Organic whole milk"
+fn display_item_name(name: &str) -> String {
+    let words = name.replace(['-', '_'], " ");
+    let words = normalize_item_name(&words);
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+/// Whole-string match where `*` in `pattern` matches any run of characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+// ============================================================================
+// Conversation Management Commands
+// ============================================================================
+
+/// Start a new conversation session for the calling window
+#[tauri::command]
+pub async fn start_conversation(app: AppHandle, window: tauri::Window) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    start_session(&conn, window.label())
+}
+
+/// Get or create the calling window's conversation session
+#[tauri::command]
+pub async fn get_or_create_session(app: AppHandle, window: tauri::Window) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    session_for_window(&conn, window.label())
+}
+
+/// Clear the calling window's conversation and start fresh
+#[tauri::command]
+pub async fn clear_conversation(app: AppHandle, window: tauri::Window) -> Result<String, YukiError> {
+    // Start a new session; the old one stays in the database but is no longer current
+    start_conversation(app, window).await
+}
+
+/// Messages of any session, oldest first, for reopening a past conversation.
+/// `offset` counts from the first message; no `limit` returns the rest.
+#[tauri::command]
+pub async fn get_session_messages(
+    app: AppHandle,
+    session_id: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<ConversationMessage>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    session_messages(&conn, &session_id, limit, offset.unwrap_or(0))
+}
+
+fn session_messages(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+    limit: Option<usize>,
+    offset: usize,
+) -> Result<Vec<ConversationMessage>, YukiError> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM conversation_sessions WHERE id = ?1)",
+        [session_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(YukiError::NotFound(format!("Conversation session '{}' not found", session_id)));
+    }
+
+    // LIMIT -1 is SQLite for "no limit"
+    let limit = limit.map(|l| l as i64).unwrap_or(-1);
+    let mut stmt = conn.prepare(
+        "SELECT role, content, created_at FROM conversation_messages
+         WHERE session_id = ?1
+         ORDER BY created_at, rowid
+         LIMIT ?2 OFFSET ?3",
+    )?;
+    let messages = stmt
+        .query_map(rusqlite::params![session_id, limit, offset as i64], |row| {
+            Ok(ConversationMessage {
+                role: row.get(0)?,
+                content: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
+fn current_session(window_label: &str) -> Result<Option<String>, YukiError> {
+    let sessions = CURRENT_SESSIONS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    Ok(sessions.get(window_label).cloned())
+}
+
+fn start_session(conn: &rusqlite::Connection, window_label: &str) -> Result<String, YukiError> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT INTO conversation_sessions (id, created_at, updated_at) VALUES (?1, ?2, ?2)",
+        [&session_id, &now],
+    )?;
+
+    // Set as current session for this window
+    let mut sessions = CURRENT_SESSIONS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    sessions.insert(window_label.to_string(), session_id.clone());
+
+    log::info!("[CONVERSATION] Started new session for window '{}': {}", window_label, session_id);
+    Ok(session_id)
+}
+
+fn session_for_window(conn: &rusqlite::Connection, window_label: &str) -> Result<String, YukiError> {
+    match current_session(window_label)? {
+        Some(session_id) => Ok(session_id),
+        None => start_session(conn, window_label),
+    }
+}
+
+/// Get conversation history for the window's current session
+fn get_conversation_history(
+    conn: &rusqlite::Connection,
+    window_label: &str,
+    limit: usize,
+) -> Result<Vec<ConversationMessage>, YukiError> {
+    let session_id = match current_session(window_label)? {
+        Some(id) => id,
+        None => return Ok(vec![]),
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT role, content, created_at FROM conversation_messages
+             WHERE session_id = ?1
+             ORDER BY created_at DESC
+             LIMIT ?2",
+        )?;
+
+    let messages: Vec<ConversationMessage> = stmt
+        .query_map(rusqlite::params![&session_id, limit as i64], |row| {
+            Ok(ConversationMessage {
+                role: row.get(0)?,
+                content: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Reverse to get chronological order
+    Ok(messages.into_iter().rev().collect())
+}
+
+/// The conversation history block process_query would add to the prompt for
+/// this window's next question. Read-only: no session is started and nothing
+/// is saved.
+#[tauri::command]
+pub async fn get_current_context(app: AppHandle, window: tauri::Window) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    current_context(&conn, window.label())
+}
+
+fn current_context(conn: &rusqlite::Connection, window_label: &str) -> Result<String, YukiError> {
+    let history = get_conversation_history(conn, window_label, 10)?;
+    Ok(llm::build_conversation_context(&history))
+}
+
+/// Save a message to the window's conversation history
+fn save_message(conn: &rusqlite::Connection, window_label: &str, role: &str, content: &str) -> Result<(), YukiError> {
+    let session_id = current_session(window_label)?
+        .ok_or_else(|| YukiError::NotFound("No active conversation session".to_string()))?;
+
+    let msg_id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT INTO conversation_messages (id, session_id, role, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        [&msg_id, &session_id, role, content, &now],
+    )?;
+
+    // Update session timestamp
+    conn.execute(
+        "UPDATE conversation_sessions SET updated_at = ?1 WHERE id = ?2",
+        [&now, &session_id],
+    )?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Account Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_all_accounts(app: AppHandle) -> Result<Vec<Account>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, name, account_type, institution, currency, is_default, created_at FROM accounts ORDER BY is_default DESC, name")?;
+
+    let accounts = stmt
+        .query_map([], |row| {
+            Ok(Account {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                account_type: row.get(2)?,
+                institution: row.get(3)?,
+                currency: row.get(4)?,
+                is_default: row.get::<_, i32>(5)? == 1,
+                created_at: row.get(6)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(accounts)
+}
+
+#[tauri::command]
+pub async fn add_account(
+    app: AppHandle,
+    name: String,
+    account_type: String,
+    institution: Option<String>,
+    currency: String,
+) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at) VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)",
+        rusqlite::params![&id, &name, &account_type, &institution, &currency, &now],
+    )?;
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn delete_account(app: AppHandle, account_id: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    // Check if it's the default account
+    let is_default: i32 = conn
+        .query_row(
+            "SELECT is_default FROM accounts WHERE id = ?1",
+            [&account_id],
+            |row| row.get(0),
+        )?;
+
+    if is_default == 1 {
+        return Err(YukiError::Validation("Cannot delete the default account".to_string()));
+    }
+
+    // Set ledger entries to use default account
+    conn.execute(
+        "UPDATE ledger SET account_id = 'default' WHERE account_id = ?1",
+        [&account_id],
+    )?;
+    conn.execute(
+        "UPDATE recurring_templates SET account_id = 'default' WHERE account_id = ?1",
+        [&account_id],
+    )?;
+
+    // Delete the account
+    conn.execute("DELETE FROM accounts WHERE id = ?1", [&account_id])?;
+
+    Ok(())
+}
+
+/// Move ledger rows from one account to another, optionally only those dated
+/// within `date_filter`. Returns the number of rows moved.
+#[tauri::command]
+pub async fn reassign_account(
+    app: AppHandle,
+    from_account_id: String,
+    to_account_id: String,
+    date_filter: Option<DateRange>,
+) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let moved = reassign_account_rows(&mut conn, &from_account_id, &to_account_id, &date_filter.unwrap_or_default())?;
+
+    log::info!("Moved {} transactions from account {} to {}", moved, from_account_id, to_account_id);
+    Ok(moved)
+}
+
+fn reassign_account_rows(
+    conn: &mut rusqlite::Connection,
+    from_account_id: &str,
+    to_account_id: &str,
+    date_filter: &DateRange,
+) -> Result<usize, YukiError> {
+    for account_id in [from_account_id, to_account_id] {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM accounts WHERE id = ?1)",
+            [account_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(YukiError::NotFound(format!("Account '{}' not found", account_id)));
+        }
+    }
+    if from_account_id == to_account_id {
+        return Ok(0);
+    }
+
+    let tx = conn.transaction()?;
+    // Rows imported before accounts existed have no account and belong to the default one
+    let moved = tx.execute(
+        "UPDATE ledger SET account_id = ?1
+         WHERE (account_id = ?2 OR (account_id IS NULL AND ?2 = 'default'))
+           AND (?3 IS NULL OR date >= ?3)
+           AND (?4 IS NULL OR date <= ?4)",
+        rusqlite::params![to_account_id, from_account_id, date_filter.start_date, date_filter.end_date],
+    )?;
+    tx.commit()?;
+    Ok(moved)
+}
+
+/// Give ledger rows imported before accounts existed the default account they
+/// are already treated as belonging to. Returns the number of rows updated.
+#[tauri::command]
+pub async fn backfill_account_ids(app: AppHandle) -> Result<usize, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let updated = backfill_null_account_ids(&conn)?;
+
+    log::info!("Assigned {} legacy transactions to the default account", updated);
+    Ok(updated)
+}
+
+fn backfill_null_account_ids(conn: &rusqlite::Connection) -> Result<usize, YukiError> {
+    Ok(conn.execute("UPDATE ledger SET account_id = 'default' WHERE account_id IS NULL", [])?)
+}
+
+/// Balance of an account at the end of `date` (YYYY-MM-DD), converting rows in
+/// other currencies to the account's currency
+#[tauri::command]
+pub async fn get_balance_as_of(app: AppHandle, account_id: String, date: String) -> Result<AccountBalance, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    balance_as_of(&conn, &account_id, &date)
+}
+
+fn balance_as_of(conn: &rusqlite::Connection, account_id: &str, date: &str) -> Result<AccountBalance, YukiError> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| YukiError::Validation(format!("Invalid date '{}', expected YYYY-MM-DD", date)))?;
+
+    let currency = account_currency(conn, account_id)?;
+    let rates = currency_rates(conn)?;
+
+    // Rows imported before accounts existed have no account and belong to the default one
+    let mut stmt = conn.prepare(
+        "SELECT amount, currency, exchange_rate FROM ledger
+         WHERE COALESCE(account_id, 'default') = ?1 AND date(date) <= ?2",
+    )?;
+    let rows: Vec<(f64, String, Option<f64>)> = stmt
+        .query_map([account_id, date], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut balance = 0.0;
+    for (amount, row_currency, row_rate) in &rows {
+        balance += if *row_currency == currency {
+            *amount
+        } else {
+            convert_row_amount(&rates, *amount, row_currency, *row_rate, &currency)?
+        };
+    }
+
+    Ok(AccountBalance {
+        account_id: account_id.to_string(),
+        date: date.to_string(),
+        balance: (balance * 100.0).round() / 100.0,
+        currency,
+        transaction_count: rows.len() as i64,
+    })
+}
+
+fn account_currency(conn: &rusqlite::Connection, account_id: &str) -> Result<String, YukiError> {
+    conn.query_row("SELECT currency FROM accounts WHERE id = ?1", [account_id], |row| row.get(0))
+        .map_err(row_not_found(format!("Account '{}' not found", account_id)))
+}
+
+/// For `map_err` on a single-row query: no row is NotFound with `message`, any
+/// other failure stays a database error
+fn row_not_found(message: String) -> impl FnOnce(rusqlite::Error) -> YukiError {
+    move |e| match e {
+        rusqlite::Error::QueryReturnedNoRows => YukiError::NotFound(message),
+        other => other.into(),
+    }
+}
+
+/// An I/O failure naming the file. The kind is kept, so a missing file is still NotFound.
+fn file_error(action: &str, path: &str, e: std::io::Error) -> YukiError {
+    std::io::Error::new(e.kind(), format!("Failed to {} {}: {}", action, path, e)).into()
+}
+
+/// Money in and out of an account for each of the last `months` calendar months
+/// (including the current one), oldest first, in the account's currency
+#[tauri::command]
+pub async fn get_cash_flow(app: AppHandle, account_id: String, months: u32) -> Result<Vec<CashFlowMonth>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    cash_flow(&conn, &account_id, months, chrono::Local::now().date_naive())
+}
+
+fn cash_flow(
+    conn: &rusqlite::Connection,
+    account_id: &str,
+    months: u32,
+    today: chrono::NaiveDate,
+) -> Result<Vec<CashFlowMonth>, YukiError> {
+    let labels = recent_months(months, today)?;
+    let first_day = format!("{}-01", labels.first().cloned().unwrap_or_default());
+    let currency = account_currency(conn, account_id)?;
+    let rates = currency_rates(conn)?;
+
+    // Rows imported before accounts existed have no account and belong to the default one
+    let mut stmt = conn.prepare(
+        "SELECT strftime('%Y-%m', date), amount, currency, exchange_rate FROM ledger
+         WHERE COALESCE(account_id, 'default') = ?1 AND date >= ?2",
+    )?;
+    let rows: Vec<(String, f64, String, Option<f64>)> = stmt
+        .query_map([account_id, first_day.as_str()], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut flow: Vec<CashFlowMonth> = labels
+        .into_iter()
+        .map(|month| CashFlowMonth { month, inflow: 0.0, outflow: 0.0, net: 0.0 })
+        .collect();
+    for (month, amount, row_currency, row_rate) in rows {
+        let Some(entry) = flow.iter_mut().find(|m| m.month == month) else { continue };
+        let amount = if row_currency == currency {
+            amount
+        } else {
+            convert_row_amount(&rates, amount, &row_currency, row_rate, &currency)?
+        };
+        if amount >= 0.0 {
+            entry.inflow += amount;
+        } else {
+            entry.outflow += amount.abs();
+        }
+    }
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    for entry in &mut flow {
+        entry.inflow = round(entry.inflow);
+        entry.outflow = round(entry.outflow);
+        entry.net = round(entry.inflow - entry.outflow);
+    }
+    Ok(flow)
+}
+
+// ============================================================================
+// Currency Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_all_currencies(app: AppHandle) -> Result<Vec<Currency>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    all_currencies(&conn)
+}
+
+fn all_currencies(conn: &rusqlite::Connection) -> Result<Vec<Currency>, YukiError> {
+    let mut stmt = conn
+        .prepare("SELECT code, name, symbol, conversion_rate, is_primary, created_at FROM currencies ORDER BY is_primary DESC, name")?;
+
+    let currencies = stmt
+        .query_map([], |row| {
+            Ok(Currency {
+                code: row.get(0)?,
+                name: row.get(1)?,
+                symbol: row.get(2)?,
+                conversion_rate: row.get(3)?,
+                is_primary: row.get::<_, i32>(4)? == 1,
+                created_at: row.get(5)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(currencies)
+}
+
+#[tauri::command]
+pub async fn add_currency(
+    app: AppHandle,
+    code: String,
+    name: String,
+    symbol: String,
+    conversion_rate: f64,
+) -> Result<Currency, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT INTO currencies (code, name, symbol, conversion_rate, is_primary, created_at)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5)",
+        rusqlite::params![&code, &name, &symbol, conversion_rate, &now],
+    )?;
+
+    Ok(Currency {
+        code,
+        name,
+        symbol,
+        conversion_rate,
+        is_primary: false,
+        created_at: now,
+    })
+}
+
+#[tauri::command]
+pub async fn update_currency(
+    app: AppHandle,
+    code: String,
+    name: Option<String>,
+    symbol: Option<String>,
+    conversion_rate: Option<f64>,
+) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    if let Some(n) = name {
+        conn.execute("UPDATE currencies SET name = ?1 WHERE code = ?2", [&n, &code])?;
+    }
+    if let Some(s) = symbol {
+        conn.execute("UPDATE currencies SET symbol = ?1 WHERE code = ?2", [&s, &code])?;
+    }
+    if let Some(r) = conversion_rate {
+        conn.execute(
+            "UPDATE currencies SET conversion_rate = ?1 WHERE code = ?2",
+            rusqlite::params![r, &code],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_currency(app: AppHandle, code: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    // Check if it's the primary currency
+    let is_primary: i32 = conn
+        .query_row(
+            "SELECT is_primary FROM currencies WHERE code = ?1",
+            [&code],
+            |row| row.get(0),
+        )?;
+
+    if is_primary == 1 {
+        return Err(YukiError::Validation("Cannot delete the primary currency".to_string()));
+    }
+
+    // Check if any transactions use this currency
+    let usage_count: i32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM ledger WHERE currency = ?1",
+            [&code],
+            |row| row.get(0),
+        )?;
+
+    if usage_count > 0 {
+        return Err(YukiError::Validation(format!(
+            "Cannot delete currency '{}' - {} transactions use it",
+            code, usage_count
+        )));
+    }
+
+    conn.execute("DELETE FROM currencies WHERE code = ?1", [&code])?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_primary_currency(app: AppHandle, code: String) -> Result<(), YukiError> {
+    change_primary_currency(app, code).await
+}
+
+/// Make `new_code` the primary currency, rebasing every stored conversion rate
+/// onto it and updating the default currency, in one transaction
+#[tauri::command]
+pub async fn change_primary_currency(app: AppHandle, new_code: String) -> Result<(), YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    rebase_primary_currency(&mut conn, &new_code)?;
+
+    log::info!("Primary currency changed to {}", new_code);
+    Ok(())
+}
+
+fn rebase_primary_currency(conn: &mut rusqlite::Connection, new_code: &str) -> Result<(), YukiError> {
+    let tx = conn.transaction()?;
+
+    // Rates are "this currency -> old primary"; dividing by the new primary's
+    // old rate re-expresses them as "this currency -> new primary"
+    let new_primary_rate: f64 = tx
+        .query_row(
+            "SELECT conversion_rate FROM currencies WHERE code = ?1",
+            [new_code],
+            |row| row.get(0),
+        )
+        .map_err(row_not_found(format!("Currency '{}' does not exist", new_code)))?;
+    if new_primary_rate <= 0.0 {
+        return Err(YukiError::Validation(format!("Currency '{}' has no usable conversion rate", new_code)));
+    }
+
+    tx.execute(
+        "UPDATE currencies SET conversion_rate = conversion_rate / ?1, is_primary = 0 WHERE code != ?2",
+        rusqlite::params![new_primary_rate, new_code],
+    )?;
+    // Per-transaction rates are into the old primary too
+    tx.execute(
+        "UPDATE ledger SET exchange_rate = exchange_rate / ?1 WHERE exchange_rate IS NOT NULL",
+        [new_primary_rate],
+    )?;
+
+    // Set exactly rather than dividing so float error can't leave it at 0.9999...
+    tx.execute(
+        "UPDATE currencies SET conversion_rate = 1.0, is_primary = 1 WHERE code = ?1",
+        [new_code],
+    )?;
+
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('default_currency', ?1)",
+        [new_code],
+    )?;
+
+    tx.commit().map_err(YukiError::from)
+}
+
+#[tauri::command]
+pub async fn get_default_currency(app: AppHandle) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    let currency: String = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'default_currency'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| "KES".to_string());
+
+    Ok(currency)
+}
+
+#[tauri::command]
+pub async fn set_default_currency(app: AppHandle, code: String) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+    // Verify the currency exists
+    let exists: i32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM currencies WHERE code = ?1",
+            [&code],
+            |row| row.get(0),
+        )?;
+
+    if exists == 0 {
+        return Err(YukiError::NotFound(format!("Currency '{}' does not exist", code)));
+    }
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('default_currency', ?1)",
+        [&code],
+    )?;
+
+    Ok(())
+}
+
+/// Repair ledger rows whose currency isn't in the currencies table: symbols such
+/// as "$" or "€" and lowercase codes become the matching code, blanks become the
+/// default currency. Returns how many rows were fixed; anything unrecognised is
+/// left as it was.
+#[tauri::command]
+pub async fn fix_invalid_currencies(app: AppHandle) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    fix_invalid_currency_rows(&mut conn)
+}
+
+/// Symbols statements print in place of a code, for currencies whose symbol in
+/// the currencies table is something else or is shared
+const CURRENCY_SYMBOLS: [(&str, &str); 8] = [
+    ("$", "USD"),
+    ("US$", "USD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₹", "INR"),
+    ("₦", "NGN"),
+    ("KSH", "KES"),
+];
+
+fn fix_invalid_currency_rows(conn: &mut rusqlite::Connection) -> Result<usize, YukiError> {
+    let default_currency: String = conn
+        .query_row("SELECT value FROM settings WHERE key = 'default_currency'", [], |row| row.get(0))
+        .unwrap_or_else(|_| "KES".to_string());
+
+    let known: Vec<(String, String)> = conn
+        .prepare("SELECT code, symbol FROM currencies")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    let invalid: Vec<String> = conn
+        .prepare("SELECT DISTINCT l.currency FROM ledger l LEFT JOIN currencies c ON l.currency = c.code WHERE c.code IS NULL")?
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let resolve = |value: &str| -> Option<String> {
+        let value = value.trim();
+        let upper = value.to_uppercase();
+        let code = if value.is_empty() {
+            default_currency.clone()
+        } else if let Some((_, code)) = CURRENCY_SYMBOLS.iter().find(|(symbol, _)| *symbol == upper) {
+            code.to_string()
+        } else if let Some((code, _)) = known.iter().find(|(code, symbol)| *code == upper || *symbol == value) {
+            code.clone()
+        } else {
+            return None;
+        };
+        known.iter().any(|(known_code, _)| *known_code == code).then_some(code)
+    };
+
+    let tx = conn.transaction()?;
+    let mut fixed = 0;
+    for value in invalid {
+        match resolve(&value) {
+            Some(code) => {
+                fixed += tx.execute("UPDATE ledger SET currency = ?1 WHERE currency = ?2", [&code, &value])?;
+            }
+            None => log::warn!("[fix_invalid_currencies] No currency matches '{}', leaving it", value),
+        }
+    }
+    tx.commit()?;
+
+    log::info!("[fix_invalid_currencies] Fixed the currency of {} transactions", fixed);
+    Ok(fixed)
+}
+
+/// Format `amount` for display in `currency_code` using the symbol from the
+/// currencies table, e.g. "$1,234.56", "1.234,56 €", "¥1,235", "KSh 1,234.56"
+#[tauri::command]
+pub async fn format_amount(app: AppHandle, amount: f64, currency_code: String) -> Result<String, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    format_amount_in(&conn, amount, &currency_code)
+}
+
+fn format_amount_in(conn: &rusqlite::Connection, amount: f64, currency_code: &str) -> Result<String, YukiError> {
+    let symbol: String = conn
+        .query_row("SELECT symbol FROM currencies WHERE code = ?1", [currency_code], |row| row.get(0))
+        .map_err(row_not_found(format!("Currency '{}' does not exist", currency_code)))?;
+    Ok(format_money(amount, currency_code, &symbol))
+}
+
+/// Display rules per currency. Currencies without minor units show no decimals;
+/// the euro follows the continental "1.234,56 €" style; letter symbols such as
+/// "KSh" are separated from the number by a space.
+fn format_money(amount: f64, code: &str, symbol: &str) -> String {
+    const NO_DECIMALS: [&str; 7] = ["JPY", "KRW", "VND", "CLP", "ISK", "UGX", "RWF"];
+    let decimals = if NO_DECIMALS.contains(&code) { 0 } else { 2 };
+    let (thousands_sep, decimal_sep, symbol_after) = match code {
+        "EUR" => ('.', ',', true),
+        _ => (',', '.', false),
+    };
+
+    let fixed = format!("{:.*}", decimals, amount.abs());
+    let (whole, fraction) = fixed.split_once('.').unwrap_or((fixed.as_str(), ""));
+
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(thousands_sep);
+        }
+        grouped.push(digit);
+    }
+    if !fraction.is_empty() {
+        grouped.push(decimal_sep);
+        grouped.push_str(fraction);
+    }
+
+    // Rounding can turn a tiny negative into zero; don't show "-$0.00"
+    let sign = if amount < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+    let spacer = if symbol.chars().any(|c| c.is_alphabetic()) { " " } else { "" };
+    if symbol_after {
+        format!("{}{} {}", sign, grouped, symbol)
+    } else {
+        format!("{}{}{}{}", sign, symbol, spacer, grouped)
+    }
+}
+
+/// Effective rate paid on each converted transaction (one with an original
+/// amount in another currency) against the stored rate. Rows more than
+/// `threshold_percent` (default 1%) off are flagged, which usually means FX fees.
+#[tauri::command]
+pub async fn get_effective_exchange_rates(
+    app: AppHandle,
+    threshold_percent: Option<f64>,
+) -> Result<Vec<EffectiveExchangeRate>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    effective_exchange_rates(&conn, threshold_percent.unwrap_or(1.0))
+}
+
+fn effective_exchange_rates(
+    conn: &rusqlite::Connection,
+    threshold_percent: f64,
+) -> Result<Vec<EffectiveExchangeRate>, YukiError> {
+    let rates = currency_rates(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, date, description, original_amount, original_currency, amount, currency
+         FROM ledger
+         WHERE original_amount IS NOT NULL AND original_amount != 0
+           AND original_currency IS NOT NULL AND original_currency != currency
+         ORDER BY date DESC, created_at DESC",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, f64>(5)?,
+                row.get::<_, String>(6)?,
+            ))
+        })?
+        .filter_map(|r| r.ok());
+
+    let mut result = Vec::new();
+    for (ledger_id, date, description, original_amount, original_currency, amount, currency) in rows {
+        // Same direction as convert_amount: via the primary currency
+        let (Some(from), Some(to)) = (rates.get(&original_currency), rates.get(&currency)) else {
+            continue;
+        };
+        if *from <= 0.0 || *to <= 0.0 {
+            continue;
+        }
+        let stored_rate = from / to;
+        let implied_rate = amount / original_amount;
+        let deviation_percent = (implied_rate / stored_rate - 1.0) * 100.0;
+
+        result.push(EffectiveExchangeRate {
+            ledger_id,
+            date,
+            description,
+            original_amount,
+            original_currency,
+            amount,
+            currency,
+            implied_rate,
+            stored_rate,
+            deviation_percent: (deviation_percent * 100.0).round() / 100.0,
+            flagged: deviation_percent.abs() > threshold_percent,
+        });
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// Analytics Commands
+// ============================================================================
+
+const WEEKDAY_LABELS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// Expenses per day of the week, always seven buckets starting on Sunday.
+/// Ledger dates carry no time of day, so there is no hourly breakdown.
+#[tauri::command]
+pub async fn get_spending_by_weekday(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<WeekdaySpending>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    spending_by_weekday(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn spending_by_weekday(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<Vec<WeekdaySpending>, YukiError> {
+    let mut buckets: Vec<WeekdaySpending> = WEEKDAY_LABELS
+        .iter()
+        .enumerate()
+        .map(|(i, label)| WeekdaySpending {
+            weekday: i as u32,
+            label: label.to_string(),
+            total: 0.0,
+            count: 0,
+        })
+        .collect();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT CAST(strftime('%w', l.date) AS INTEGER), SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)), COUNT(*)
+             FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+             WHERE l.amount < 0
+               AND strftime('%w', l.date) IS NOT NULL
+               AND (?1 IS NULL OR l.date >= ?1)
+               AND (?2 IS NULL OR l.date <= ?2)
+             GROUP BY 1",
+        )?;
+
+    let rows = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?, row.get::<_, i64>(2)?))
+        })?
+        .filter_map(|r| r.ok());
+
+    for (weekday, total, count) in rows {
+        if let Some(bucket) = buckets.get_mut(weekday as usize) {
+            bucket.total = total;
+            bucket.count = count;
+        }
+    }
+
+    Ok(buckets)
+}
+
+/// Merchants ranked by number of expense transactions, most frequent first
+#[tauri::command]
+pub async fn get_spending_by_merchant_frequency(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<MerchantFrequency>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    merchant_frequency(&conn, start_date.as_deref(), end_date.as_deref(), limit.unwrap_or(20))
+}
+
+fn merchant_frequency(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    limit: usize,
+) -> Result<Vec<MerchantFrequency>, YukiError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(l.merchant, l.description), ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)
+             FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+             WHERE l.amount < 0
+               AND (?1 IS NULL OR l.date >= ?1)
+               AND (?2 IS NULL OR l.date <= ?2)",
+        )?;
+
+    let rows: Vec<(String, f64)> = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Group on the normalized name so "POS NAIVAS 0231" and "Naivas" count as one merchant,
+    // reporting the first spelling seen
+    let mut merchants: Vec<(String, MerchantFrequency)> = Vec::new();
+    for (name, amount) in rows {
+        let key = normalize_merchant(&name);
+        if key.is_empty() {
+            continue;
+        }
+        match merchants.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, entry)) => {
+                entry.visits += 1;
+                entry.total += amount;
+            }
+            None => merchants.push((
+                key,
+                MerchantFrequency {
+                    merchant: name.trim().to_string(),
+                    visits: 1,
+                    total: amount,
+                },
+            )),
+        }
+    }
+
+    let mut ranked: Vec<MerchantFrequency> = merchants.into_iter().map(|(_, m)| m).collect();
+    ranked.sort_by(|a, b| {
+        b.visits
+            .cmp(&a.visits)
+            .then(b.total.partial_cmp(&a.total).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    ranked.truncate(limit);
+
+    Ok(ranked)
+}
+
+/// Per-merchant visit counts and spend in a period, biggest total first
+#[tauri::command]
+pub async fn get_merchant_stats(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<MerchantStats>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    merchant_stats(&conn, start_date.as_deref(), end_date.as_deref(), limit.unwrap_or(20))
+}
+
+fn merchant_stats(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    limit: usize,
+) -> Result<Vec<MerchantStats>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(l.merchant, l.description), ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0), l.date
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount < 0
+           AND (?1 IS NULL OR l.date >= ?1)
+           AND (?2 IS NULL OR l.date <= ?2)
+         ORDER BY l.date, l.created_at, l.id",
+    )?;
+    let rows: Vec<(String, f64, String)> = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Same grouping as merchant_frequency: normalized name, first spelling seen.
+    // Rows come oldest first, so the first row sets first_seen and the last last_seen.
+    let mut merchants: Vec<(String, MerchantStats)> = Vec::new();
+    for (name, amount, date) in rows {
+        let key = normalize_merchant(&name);
+        if key.is_empty() {
+            continue;
+        }
+        match merchants.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, stats)) => {
+                stats.visit_count += 1;
+                stats.total_spent += amount;
+                stats.last_seen = date;
+            }
+            None => merchants.push((
+                key,
+                MerchantStats {
+                    merchant: name.trim().to_string(),
+                    visit_count: 1,
+                    total_spent: amount,
+                    avg_per_visit: 0.0,
+                    first_seen: date.clone(),
+                    last_seen: date,
+                },
+            )),
+        }
+    }
+
+    let mut ranked: Vec<MerchantStats> = merchants
+        .into_iter()
+        .map(|(_, mut stats)| {
+            stats.avg_per_visit = stats.total_spent / stats.visit_count as f64;
+            stats
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.total_spent
+            .partial_cmp(&a.total_spent)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.visit_count.cmp(&a.visit_count))
+    });
+    ranked.truncate(limit);
+
+    Ok(ranked)
+}
+
+/// Largest transactions in a period by size in the primary currency.
+/// `income_or_expense` is "income", "expense" or None for both.
+#[tauri::command]
+pub async fn get_top_transactions(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    limit: Option<usize>,
+    income_or_expense: Option<String>,
+) -> Result<Vec<RankedTransaction>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    top_transactions(
+        &conn,
+        start_date.as_deref(),
+        end_date.as_deref(),
+        limit.unwrap_or(10),
+        income_or_expense.as_deref(),
+    )
+}
+
+fn top_transactions(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    limit: usize,
+    income_or_expense: Option<&str>,
+) -> Result<Vec<RankedTransaction>, YukiError> {
+    let sign_filter = match income_or_expense {
+        Some("income") => "AND l.amount > 0",
+        Some("expense") => "AND l.amount < 0",
+        None => "",
+        Some(other) => {
+            return Err(YukiError::Validation(format!(
+                "Invalid filter '{}', expected 'income' or 'expense'",
+                other
+            )))
+        }
+    };
+
+    let sql = format!(
+        "SELECT l.id, l.document_id, l.account_id, l.date, l.description, l.amount, l.currency, l.category_id,
+                l.merchant, l.notes, l.source, l.created_at, l.original_amount, l.original_currency, l.needs_review,
+                l.amount * COALESCE(l.exchange_rate, c.conversion_rate, 1.0) AS amount_in_primary
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE (?1 IS NULL OR l.date >= ?1)
+           AND (?2 IS NULL OR l.date <= ?2)
+           {}
+         ORDER BY ABS(amount_in_primary) DESC, l.date DESC
+         LIMIT ?3",
+        sign_filter
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let transactions = stmt
+        .query_map(rusqlite::params![start_date, end_date, limit as i64], |row| {
+            Ok(RankedTransaction {
+                entry: ledger_entry_from_row(row)?,
+                amount_in_primary: row.get(15)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(transactions)
+}
+
+/// Spending in a category for `month` (YYYY-MM) compared with the previous month
+#[tauri::command]
+pub async fn get_category_month_comparison(
+    app: AppHandle,
+    category_id: String,
+    month: String,
+) -> Result<CategoryMonthComparison, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    category_month_comparison(&conn, &category_id, &month)
+}
+
+fn category_month_comparison(
+    conn: &rusqlite::Connection,
+    category_id: &str,
+    month: &str,
+) -> Result<CategoryMonthComparison, YukiError> {
+    let first_day = chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| YukiError::Validation(format!("Invalid month '{}', expected YYYY-MM", month)))?;
+    let previous_month = (first_day - chrono::Duration::days(1)).format("%Y-%m").to_string();
+
+    // Closed months come from their snapshots
+    let totals = monthly_category_totals(conn, &[previous_month.clone(), month.to_string()])?;
+    let month_total = |m: &str| -> f64 {
+        totals.iter().filter(|(tm, category, _)| tm == m && category == category_id).map(|(_, _, total)| total).sum()
+    };
+
+    let current_total = month_total(month);
+    let previous_total = month_total(&previous_month);
+
+    Ok(CategoryMonthComparison {
+        category_id: category_id.to_string(),
+        month: month.to_string(),
+        previous_month,
+        current_total,
+        previous_total,
+        change: current_total - previous_total,
+        percent_change: percent_change(current_total, previous_total),
+    })
+}
+
+/// Expenses so far in the current `period` ("month" or "quarter") against the
+/// same stretch of days one year earlier
+#[tauri::command]
+pub async fn get_year_over_year(app: AppHandle, period: String) -> Result<YearOverYear, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    year_over_year(&conn, &period, chrono::Local::now().date_naive())
+}
+
+fn year_over_year(conn: &rusqlite::Connection, period: &str, today: chrono::NaiveDate) -> Result<YearOverYear, YukiError> {
+    use chrono::Datelike;
+
+    let start_month = match period {
+        "month" => today.month(),
+        "quarter" => (today.month() - 1) / 3 * 3 + 1,
+        other => {
+            return Err(YukiError::Validation(format!(
+                "Unknown period '{}': expected month or quarter",
+                other
+            )))
+        }
+    };
+    let current_start = chrono::NaiveDate::from_ymd_opt(today.year(), start_month, 1)
+        .unwrap_or(today)
+        .format("%Y-%m-%d")
+        .to_string();
+    let current_end = today.format("%Y-%m-%d").to_string();
+
+    // SQLite's own date arithmetic, so Feb 29 shifts the same way everywhere else does
+    let (previous_start, previous_end): (String, String) = conn.query_row(
+        "SELECT date(?1, '-1 year'), date(?2, '-1 year')",
+        [&current_start, &current_end],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let total = |start: &str, end: &str| -> Result<f64, YukiError> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)), 0)
+             FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+             WHERE l.amount < 0 AND date(l.date) BETWEEN ?1 AND ?2",
+            [start, end],
+            |row| row.get(0),
+        )
+        .map_err(YukiError::from)
+    };
+    let current_total = total(&current_start, &current_end)?;
+    let previous_total = total(&previous_start, &previous_end)?;
+
+    Ok(YearOverYear {
+        period: period.to_string(),
+        current_start,
+        current_end,
+        previous_start,
+        previous_end,
+        current_total,
+        previous_total,
+        percent_change: percent_change(current_total, previous_total),
+    })
+}
+
+/// Spending per category for each of the last `months` calendar months (including
+/// the current one), oldest first
+#[tauri::command]
+pub async fn get_category_trend(app: AppHandle, months: u32) -> Result<Vec<CategoryTrendMonth>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    category_trend(&conn, months, chrono::Local::now().date_naive())
+}
+
+fn category_trend(
+    conn: &rusqlite::Connection,
+    months: u32,
+    today: chrono::NaiveDate,
+) -> Result<Vec<CategoryTrendMonth>, YukiError> {
+    let labels = recent_months(months, today)?;
+    let rows = monthly_category_totals(conn, &labels)?;
+
+    // Zero-fill so every month carries the same keys and stacks line up
+    let empty: std::collections::BTreeMap<String, f64> =
+        rows.iter().map(|(_, category, _)| (category.clone(), 0.0)).collect();
+    let mut trend: Vec<CategoryTrendMonth> = labels
+        .into_iter()
+        .map(|month| CategoryTrendMonth { month, totals: empty.clone() })
+        .collect();
+    for (month, category, total) in rows {
+        if let Some(entry) = trend.iter_mut().find(|m| m.month == month) {
+            entry.totals.insert(category, total);
+        }
+    }
+
+    Ok(trend)
+}
+
+/// Whether monthly spending is rising, falling or holding steady over the last
+/// `months` full months, from a least-squares line through the monthly totals
+#[tauri::command]
+pub async fn get_spending_trend_direction(app: AppHandle, months: u32) -> Result<SpendingTrend, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    spending_trend_direction(&conn, months, chrono::Local::now().date_naive())
+}
+
+/// A slope smaller than this share of the average month reads as "flat"
+const FLAT_TREND_PERCENT: f64 = 2.0;
+
+/// The current month is left out: half a month of spending would look like a drop
+fn spending_trend_direction(
+    conn: &rusqlite::Connection,
+    months: u32,
+    today: chrono::NaiveDate,
+) -> Result<SpendingTrend, YukiError> {
+    // One more month than asked for is listed so the current one can be dropped
+    if !(2..=119).contains(&months) {
+        return Err(YukiError::Validation(format!("months must be between 2 and 119, got {}", months)));
+    }
+    let mut labels = recent_months(months + 1, today)?;
+    labels.pop();
+
+    let mut totals: Vec<(String, f64)> = labels.iter().map(|month| (month.clone(), 0.0)).collect();
+    for (month, _, total) in monthly_category_totals(conn, &labels)? {
+        if let Some(entry) = totals.iter_mut().find(|(m, _)| *m == month) {
+            entry.1 += total;
+        }
+    }
+
+    // Least squares over x = 0, 1, 2, ... for each month in order
+    let n = totals.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = totals.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (i, (_, y)) in totals.iter().enumerate() {
+        let (dx, dy) = (i as f64 - mean_x, y - mean_y);
+        sxy += dx * dy;
+        sxx += dx * dx;
+        syy += dy * dy;
+    }
+    let slope = sxy / sxx;
+    // Undefined when every month cost the same: there is nothing to explain
+    let r_squared = (syy > 0.0).then(|| sxy * sxy / (sxx * syy));
+
+    let direction = if slope.abs() <= mean_y.abs() * FLAT_TREND_PERCENT / 100.0 {
+        "flat"
+    } else if slope > 0.0 {
+        "up"
+    } else {
+        "down"
+    };
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(SpendingTrend {
+        direction: direction.to_string(),
+        slope_per_month: round(slope),
+        r_squared: r_squared.map(|r| (r * 1000.0).round() / 1000.0),
+        average_monthly: round(mean_y),
+        months: totals
+            .into_iter()
+            .map(|(label, total)| ChartDataPoint { label, value: round(total) })
+            .collect(),
+    })
+}
+
+/// (month, category, expenses) for each of `months` (YYYY-MM, oldest first).
+/// Months with a snapshot are read from it; the ledger is only scanned from the
+/// first month without one, which is normally just the current month.
+fn monthly_category_totals(conn: &rusqlite::Connection, months: &[String]) -> Result<Vec<(String, String, f64)>, YukiError> {
+    let built: std::collections::HashSet<String> = conn
+        .prepare("SELECT month FROM snapshot_months")?
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    let live_from = months.iter().find(|m| !built.contains(*m)).cloned();
+    let is_live = |month: &str| live_from.as_deref().is_some_and(|from| month >= from);
+
+    let mut rows: Vec<(String, String, f64)> = conn
+        .prepare("SELECT month, category_id, total FROM monthly_snapshots WHERE month >= ?1 ORDER BY month, category_id")?
+        .query_map([months.first().cloned().unwrap_or_default()], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .filter(|(month, _, _)| months.contains(month) && !is_live(month))
+        .collect();
+
+    if let Some(from) = &live_from {
+        let mut stmt = conn.prepare(
+            "SELECT strftime('%Y-%m', l.date) AS month, COALESCE(l.category_id, 'other'),
+                    SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0))
+             FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+             WHERE l.amount < 0 AND l.date >= ?1
+             GROUP BY month, COALESCE(l.category_id, 'other')",
+        )?;
+        let live: Vec<(String, String, f64)> = stmt
+            .query_map([format!("{}-01", from)], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .filter(|(month, _, _)| months.contains(month))
+            .collect();
+        rows.extend(live);
+    }
+
+    Ok(rows)
+}
+
+/// Precompute per-category expense totals for every closed month that doesn't
+/// have a snapshot yet, so trends don't rescan the whole ledger. Returns the
+/// number of months built.
+#[tauri::command]
+pub async fn build_snapshots(app: AppHandle) -> Result<usize, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let built = build_month_snapshots(&mut conn, chrono::Local::now().date_naive())?;
+
+    if built > 0 {
+        log::info!("Built spending snapshots for {} months", built);
+    }
+    Ok(built)
+}
+
+fn build_month_snapshots(conn: &mut rusqlite::Connection, today: chrono::NaiveDate) -> Result<usize, YukiError> {
+    let current_month = today.format("%Y-%m").to_string();
+    let first_month: Option<String> =
+        conn.query_row("SELECT strftime('%Y-%m', MIN(date)) FROM ledger", [], |row| row.get(0))?;
+    let Some(first_month) = first_month else {
+        return Ok(0);
+    };
+    let built: std::collections::HashSet<String> = conn
+        .prepare("SELECT month FROM snapshot_months")?
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Every closed month since the first transaction, empty ones included
+    let mut pending = Vec::new();
+    let mut month = chrono::NaiveDate::parse_from_str(&format!("{}-01", first_month), "%Y-%m-%d")
+        .map_err(|_| YukiError::Database(format!("Unreadable ledger date in month '{}'", first_month)))?;
+    loop {
+        let label = month.format("%Y-%m").to_string();
+        if label >= current_month {
+            break;
+        }
+        if !built.contains(&label) {
+            pending.push(label);
+        }
+        month = match month.checked_add_months(chrono::Months::new(1)) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+    for month in &pending {
+        tx.execute(
+            "INSERT OR REPLACE INTO monthly_snapshots (month, category_id, total, count)
+             SELECT ?1, COALESCE(l.category_id, 'other'), SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)), COUNT(*)
+             FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+             WHERE l.amount < 0 AND strftime('%Y-%m', l.date) = ?1
+             GROUP BY COALESCE(l.category_id, 'other')",
+            [month],
+        )?;
+        tx.execute("INSERT OR REPLACE INTO snapshot_months (month, built_at) VALUES (?1, ?2)", [month, &now])?;
+    }
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+/// YYYY-MM labels for the last `months` calendar months up to `today`'s, oldest first
+fn recent_months(months: u32, today: chrono::NaiveDate) -> Result<Vec<String>, YukiError> {
+    if months == 0 || months > 120 {
+        return Err(YukiError::Validation(format!("months must be between 1 and 120, got {}", months)));
+    }
+
+    let this_month = chrono::Datelike::with_day(&today, 1).unwrap_or(today);
+    Ok((0..months)
+        .rev()
+        .filter_map(|back| this_month.checked_sub_months(chrono::Months::new(back)))
+        .map(|d| d.format("%Y-%m").to_string())
+        .collect())
+}
+
+/// Percent change from `previous` to `current`; undefined from a zero baseline
+fn percent_change(current: f64, previous: f64) -> Option<f64> {
+    if previous.abs() < 0.005 {
+        return None;
+    }
+    Some((current - previous) / previous * 100.0)
+}
+
+/// Purchased-item spending grouped by item category (produce, dairy, ...), largest
+/// first. Items without a category are grouped under "other".
+#[tauri::command]
+pub async fn get_item_category_spending(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<ChartDataPoint>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    item_category_spending(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn item_category_spending(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<Vec<ChartDataPoint>, YukiError> {
+    // purchased_at may carry a time, so compare on the date part only
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(NULLIF(LOWER(TRIM(category)), ''), 'other') AS item_category, SUM(total_price) AS total
+         FROM purchased_items
+         WHERE (?1 IS NULL OR date(purchased_at) >= ?1)
+           AND (?2 IS NULL OR date(purchased_at) <= ?2)
+         GROUP BY item_category
+         ORDER BY total DESC, item_category",
+    )?;
+
+    let points = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            Ok(ChartDataPoint {
+                label: row.get(0)?,
+                value: row.get(1)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(points)
+}
+
+/// Positive ledger amounts grouped into income types (payroll, refund, interest,
+/// transfer, other) with totals in the primary currency, largest first
+#[tauri::command]
+pub async fn get_income_breakdown(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<IncomeTypeTotal>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    income_breakdown(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn income_breakdown(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<Vec<IncomeTypeTotal>, YukiError> {
+    // All income, not just the requested period: payroll is spotted by recurrence,
+    // which a one-month window can't show
+    let mut stmt = conn.prepare(
+        "SELECT l.date, l.description, COALESCE(l.merchant, l.description), l.amount * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount > 0",
+    )?;
+    let rows: Vec<(String, String, String, f64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let payroll_sources = recurring_large_sources(&rows);
+
+    let mut totals: Vec<IncomeTypeTotal> = Vec::new();
+    for (date, description, source, amount) in &rows {
+        if start_date.is_some_and(|s| date.as_str() < s) || end_date.is_some_and(|e| date.as_str() > e) {
+            continue;
+        }
+        let income_type = classify_income(description, source, &payroll_sources);
+        match totals.iter_mut().find(|t| t.income_type == income_type) {
+            Some(entry) => {
+                entry.total += amount;
+                entry.count += 1;
+            }
+            None => totals.push(IncomeTypeTotal {
+                income_type: income_type.to_string(),
+                total: *amount,
+                count: 1,
+            }),
+        }
+    }
+
+    totals.sort_by(|a, b| b.total.partial_cmp(&a.total).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(totals)
+}
+
+/// Normalized sources that paid in at least two different months with an average
+/// at or above the median income amount. That pattern is almost always a salary.
+fn recurring_large_sources(rows: &[(String, String, String, f64)]) -> std::collections::HashSet<String> {
+    let mut amounts: Vec<f64> = rows.iter().map(|(_, _, _, amount)| *amount).collect();
+    if amounts.is_empty() {
+        return std::collections::HashSet::new();
+    }
+    amounts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = amounts[amounts.len() / 2];
+
+    let mut by_source: HashMap<String, (std::collections::HashSet<&str>, f64, usize)> = HashMap::new();
+    for (date, _, source, amount) in rows {
+        let key = normalize_merchant(source);
+        if key.is_empty() {
+            continue;
+        }
+        let entry = by_source.entry(key).or_default();
+        entry.0.insert(date.get(..7).unwrap_or(date));
+        entry.1 += amount;
+        entry.2 += 1;
+    }
+
+    by_source
+        .into_iter()
+        .filter(|(_, (months, total, count))| months.len() >= 2 && total / *count as f64 >= median)
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Income type for one positive ledger row. Explicit keywords win over the
+/// recurrence heuristic, so a monthly "Transfer from savings" stays a transfer.
+fn classify_income(description: &str, source: &str, payroll_sources: &std::collections::HashSet<String>) -> &'static str {
+    const RULES: [(&str, &[&str]); 4] = [
+        ("refund", &["refund", "reversal", "chargeback", "cashback"]),
+        ("interest", &["interest", "dividend"]),
+        ("payroll", &["salary", "payroll", "wage", "payslip"]),
+        ("transfer", &["transfer", "trf", "xfer"]),
+    ];
+
+    let text = format!("{} {}", description, source).to_lowercase();
+    let words: Vec<&str> = text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    for (income_type, keywords) in RULES {
+        // Prefix match so "refunded" and "wages" still count
+        if words.iter().any(|w| keywords.iter().any(|k| w.starts_with(k))) {
+            return income_type;
+        }
+    }
+
+    if payroll_sources.contains(&normalize_merchant(source)) {
+        return "payroll";
+    }
+    "other"
+}
+
+/// Suggested links between refunds and the purchases they reverse, newest refund
+/// first, so the UI can net the pair or move the refund into the purchase's category
+#[tauri::command]
+pub async fn detect_refunds(app: AppHandle) -> Result<Vec<RefundMatch>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    refund_matches(&conn)
+}
+
+/// How long after a purchase money back is still taken to be a refund of it
+const REFUND_WINDOW_DAYS: i64 = 90;
+
+/// A positive row matches an earlier expense from the same merchant in the same
+/// currency, dated up to `REFUND_WINDOW_DAYS` before it, whose amount is between
+/// the refund (allowing a cent of rounding) and twice it, so partial refunds of
+/// up to half still count. The closest amount wins, then the most recent
+/// purchase; each purchase backs at most one refund. Income that names itself
+/// payroll, interest or a transfer is never a refund.
+fn refund_matches(conn: &rusqlite::Connection) -> Result<Vec<RefundMatch>, YukiError> {
+    without_ignored_anomalies(conn, refund_pairs(conn)?, |m| &m.refund_id)
+}
+
+/// Every refund and purchase pair, dismissed or not
+fn refund_pairs(conn: &rusqlite::Connection) -> Result<Vec<RefundMatch>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, date, description, COALESCE(NULLIF(merchant, ''), description), amount, currency, category_id
+         FROM ledger
+         ORDER BY date, created_at, id",
+    )?;
+    type Row = (String, String, String, String, f64, String, String);
+    let rows: Vec<Row> = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // "REFUND - Jumia order 5521" should key the same as "Jumia"
+    let merchant_key = |source: &str| {
+        normalize_merchant(source)
+            .split(' ')
+            .filter(|w| !["refund", "refunded", "reversal", "reversed", "chargeback", "return"].contains(w))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let same_merchant = |a: &str, b: &str| {
+        !a.is_empty()
+            && !b.is_empty()
+            && (a == b || a.starts_with(&format!("{} ", b)) || b.starts_with(&format!("{} ", a)))
+    };
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d.get(..10).unwrap_or(d), "%Y-%m-%d").ok();
+
+    let purchases: Vec<(&Row, String)> = rows
+        .iter()
+        .filter(|row| row.4 < 0.0)
+        .map(|row| (row, merchant_key(&row.3)))
+        .collect();
+    let mut claimed = std::collections::HashSet::new();
+    let no_payroll = std::collections::HashSet::new();
+
+    let mut matches = Vec::new();
+    for refund in rows.iter().filter(|row| row.4 > 0.0) {
+        let (id, date, description, source, amount, currency, category_id) = refund;
+        if !matches!(classify_income(description, source, &no_payroll), "refund" | "other") {
+            continue;
+        }
+        let Some(refund_date) = parse(date) else { continue };
+        let key = merchant_key(source);
+
+        let best = purchases
+            .iter()
+            .filter(|(purchase, purchase_key)| {
+                let spent = purchase.4.abs();
+                let days = parse(&purchase.1).map(|d| (refund_date - d).num_days());
+                !claimed.contains(&purchase.0)
+                    && purchase.5 == *currency
+                    && days.is_some_and(|d| (0..=REFUND_WINDOW_DAYS).contains(&d))
+                    && spent >= amount - 0.01
+                    && spent <= amount * 2.0
+                    && same_merchant(&key, purchase_key)
+            })
+            // Equal distances fall back to the later purchase date
+            .min_by(|(a, _), (b, _)| {
+                let distance = |p: &Row| (p.4.abs() - amount).abs();
+                distance(a).partial_cmp(&distance(b)).unwrap_or(std::cmp::Ordering::Equal).then(b.1.cmp(&a.1))
+            });
+        let Some((purchase, _)) = best else { continue };
+        claimed.insert(&purchase.0);
+
+        let purchase_amount = purchase.4.abs();
+        matches.push(RefundMatch {
+            refund_id: id.clone(),
+            refund_date: date.clone(),
+            refund_amount: *amount,
+            refund_category_id: category_id.clone(),
+            purchase_id: purchase.0.clone(),
+            purchase_date: purchase.1.clone(),
+            purchase_amount,
+            purchase_category_id: purchase.6.clone(),
+            merchant: purchase.3.clone(),
+            currency: currency.clone(),
+            days_after: parse(&purchase.1).map(|d| (refund_date - d).num_days()).unwrap_or_default(),
+            full_refund: (purchase_amount - amount).abs() < 0.005,
+        });
+    }
+
+    matches.reverse();
+    Ok(matches)
+}
+
+/// Rows whose sign contradicts their category, for review: money in under a
+/// typical expense category, or money out under income. Oldest first.
+#[tauri::command]
+pub async fn detect_sign_errors(app: AppHandle) -> Result<Vec<SignError>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    sign_errors(&conn)
+}
+
+/// Categories that only ever see money going out. Gifts, personal and other can
+/// legitimately go either way, so they are left alone.
+const TYPICAL_EXPENSE_CATEGORIES: [&str; 11] = [
+    "housing",
+    "utilities",
+    "groceries",
+    "dining",
+    "transportation",
+    "entertainment",
+    "shopping",
+    "healthcare",
+    "subscriptions",
+    "travel",
+    "education",
+];
+
+/// A positive expense-category row is left out when it reads as a refund or
+/// detect_refunds pairs it with the purchase it reverses
+fn sign_errors(conn: &rusqlite::Connection) -> Result<Vec<SignError>, YukiError> {
+    let expense_categories = TYPICAL_EXPENSE_CATEGORIES.map(|c| format!("'{}'", c)).join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at,
+                original_amount, original_currency, needs_review
+         FROM ledger
+         WHERE (amount > 0 AND category_id IN ({})) OR (amount < 0 AND category_id = 'income')
+         ORDER BY date, created_at, id",
+        expense_categories
+    ))?;
+    let rows: Vec<LedgerEntry> = stmt
+        .query_map([], ledger_entry_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let refunds: std::collections::HashSet<String> =
+        refund_pairs(conn)?.into_iter().map(|m| m.refund_id).collect();
+    let no_payroll = std::collections::HashSet::new();
+    let ignored = ignored_targets(conn, "anomaly")?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|entry| !ignored.contains(&entry.id))
+        .filter_map(|entry| {
+            let expected_sign = if entry.amount > 0.0 {
+                let source = entry.merchant.as_deref().unwrap_or(&entry.description);
+                if refunds.contains(&entry.id) || classify_income(&entry.description, source, &no_payroll) == "refund" {
+                    return None;
+                }
+                "negative"
+            } else {
+                "positive"
+            };
+            Some(SignError {
+                expected_sign: expected_sign.to_string(),
+                transaction: entry,
+            })
+        })
+        .collect())
+}
+
+/// Share of income not spent in a period: (income - expenses) / income, in the
+/// primary currency. The rate is null when nothing came in.
+#[tauri::command]
+pub async fn get_savings_rate(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<SavingsRate, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    savings_rate(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn savings_rate(conn: &rusqlite::Connection, start_date: Option<&str>, end_date: Option<&str>) -> Result<SavingsRate, YukiError> {
+    let (income, expenses): (f64, f64) = conn.query_row(
+        "SELECT
+            COALESCE(SUM(CASE WHEN l.amount > 0 THEN l.amount * COALESCE(l.exchange_rate, c.conversion_rate, 1.0) END), 0),
+            COALESCE(SUM(CASE WHEN l.amount < 0 THEN ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0) END), 0)
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE (?1 IS NULL OR l.date >= ?1)
+           AND (?2 IS NULL OR l.date <= ?2)",
+        rusqlite::params![start_date, end_date],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    let savings = income - expenses;
+    Ok(SavingsRate {
+        income: round(income),
+        expenses: round(expenses),
+        savings: round(savings),
+        savings_rate_percent: (income > 0.0).then(|| round(savings / income * 100.0)),
+    })
+}
+
+/// Each expense category's spending as a percentage of all income in the period,
+/// biggest first, in the primary currency. Percentages are null without income.
+#[tauri::command]
+pub async fn get_spending_to_income_ratios(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<CategoryIncomeRatio>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    spending_to_income_ratios(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn spending_to_income_ratios(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<Vec<CategoryIncomeRatio>, YukiError> {
+    let income = savings_rate(conn, start_date, end_date)?.income;
+
+    let mut stmt = conn.prepare(
+        "SELECT l.category_id, COALESCE(cat.name, l.category_id),
+                SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)) AS total
+         FROM ledger l
+         LEFT JOIN currencies c ON l.currency = c.code
+         LEFT JOIN categories cat ON l.category_id = cat.id
+         WHERE l.amount < 0
+           AND (?1 IS NULL OR l.date >= ?1)
+           AND (?2 IS NULL OR l.date <= ?2)
+         GROUP BY l.category_id
+         ORDER BY total DESC, l.category_id",
+    )?;
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    let ratios = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| {
+            let total: f64 = row.get(2)?;
+            Ok(CategoryIncomeRatio {
+                category_id: row.get(0)?,
+                category_name: row.get(1)?,
+                total: round(total),
+                percent_of_income: (income > 0.0).then(|| round(total / income * 100.0)),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(ratios)
+}
+
+/// Months the balance of liquid accounts would last at the recent spending rate
+#[tauri::command]
+pub async fn get_financial_runway(app: AppHandle) -> Result<FinancialRunway, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    financial_runway(&conn, chrono::Local::now().date_naive())
+}
+
+/// Account types whose money can be spent straight away. M-Pesa and similar
+/// wallets count alongside checking, savings and cash.
+const LIQUID_ACCOUNT_TYPES: [&str; 4] = ["checking", "savings", "cash", "mobile_money"];
+
+/// Liquid balance over the average monthly expenses of the three full months
+/// before `today`'s; the current month is left out since it is only partly spent.
+fn financial_runway(conn: &rusqlite::Connection, today: chrono::NaiveDate) -> Result<FinancialRunway, YukiError> {
+    let liquid_types = LIQUID_ACCOUNT_TYPES.map(|t| format!("'{}'", t)).join(", ");
+    // Rows imported before accounts existed belong to the default account
+    let (liquid_balance, account_count): (f64, i64) = conn.query_row(
+        &format!(
+            "SELECT
+                COALESCE((SELECT SUM(l.amount * COALESCE(l.exchange_rate, c.conversion_rate, 1.0))
+                          FROM ledger l
+                          JOIN accounts a ON COALESCE(l.account_id, 'default') = a.id
+                          LEFT JOIN currencies c ON l.currency = c.code
+                          WHERE a.account_type IN ({types}) AND date(l.date) <= ?1), 0),
+                (SELECT COUNT(*) FROM accounts WHERE account_type IN ({types}))",
+            types = liquid_types
+        ),
+        [today.to_string()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let mut months = recent_months(4, today)?;
+    months.pop();
+    let expenses: f64 = monthly_category_totals(conn, &months)?.iter().map(|(_, _, total)| total).sum();
+    let avg_monthly_expenses = expenses / 3.0;
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(FinancialRunway {
+        liquid_balance: round(liquid_balance),
+        avg_monthly_expenses: round(avg_monthly_expenses),
+        runway_months: (avg_monthly_expenses > 0.0)
+            .then(|| (liquid_balance.max(0.0) / avg_monthly_expenses * 10.0).round() / 10.0),
+        account_count,
+    })
+}
+
+fn savings_goals(conn: &rusqlite::Connection) -> HashMap<String, SavingsGoal> {
+    conn.query_row("SELECT value FROM settings WHERE key = 'savings_goals'", [], |row| row.get::<_, String>(0))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Savings goals by id
+#[tauri::command]
+pub async fn get_savings_goals(app: AppHandle) -> Result<HashMap<String, SavingsGoal>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    Ok(savings_goals(&conn))
+}
+
+/// Create or replace a savings goal; None removes it
+#[tauri::command]
+pub async fn set_savings_goal(app: AppHandle, goal_id: String, goal: Option<SavingsGoal>) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    save_savings_goal(&conn, &goal_id, goal)
+}
+
+fn save_savings_goal(conn: &rusqlite::Connection, goal_id: &str, goal: Option<SavingsGoal>) -> Result<(), YukiError> {
+    if let Some(goal) = &goal {
+        if !goal.target_amount.is_finite() || goal.target_amount <= 0.0 {
+            return Err(YukiError::Validation("Goal target must be more than zero".to_string()));
+        }
+        if !goal.saved_amount.is_finite() || goal.saved_amount < 0.0 {
+            return Err(YukiError::Validation("Amount saved must be zero or more".to_string()));
+        }
+        if chrono::NaiveDate::parse_from_str(&goal.target_date, "%Y-%m-%d").is_err() {
+            return Err(YukiError::Validation(format!("Invalid target date '{}'", goal.target_date)));
+        }
+    }
+
+    let mut goals = savings_goals(conn);
+    match goal {
+        Some(goal) => goals.insert(goal_id.to_string(), goal),
+        None => goals.remove(goal_id),
+    };
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('savings_goals', ?1)",
+        [serde_json::to_string(&goals)?],
+    )?;
+    Ok(())
+}
+
+/// How much a purchase of `amount` (primary currency) would push back a savings
+/// goal at the current rate of saving
+#[tauri::command]
+pub async fn evaluate_purchase_impact(app: AppHandle, amount: f64, goal_id: String) -> Result<PurchaseImpact, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    purchase_impact(&conn, amount, &goal_id, chrono::Local::now().date_naive())
+}
+
+/// The saving rate is net income (income minus expenses) averaged over the last
+/// three full months, spread evenly over the days of a year. The goal is reached
+/// once the rest of its target has been saved at that rate, and the purchase adds
+/// its own amount to what is left to save.
+fn purchase_impact(
+    conn: &rusqlite::Connection,
+    amount: f64,
+    goal_id: &str,
+    today: chrono::NaiveDate,
+) -> Result<PurchaseImpact, YukiError> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err(YukiError::Validation("Purchase amount must be more than zero".to_string()));
+    }
+    let goal = savings_goals(conn)
+        .remove(goal_id)
+        .ok_or_else(|| YukiError::NotFound(format!("Savings goal '{}' does not exist", goal_id)))?;
+    let target_date = chrono::NaiveDate::parse_from_str(&goal.target_date, "%Y-%m-%d")
+        .map_err(|_| YukiError::Validation(format!("Invalid target date '{}'", goal.target_date)))?;
+
+    let months = recent_months(4, today)?;
+    let net: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(l.amount * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)), 0)
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.date >= ?1 AND l.date < ?2",
+        [format!("{}-01", months[0]), format!("{}-01", months[3])],
+        |row| row.get(0),
+    )?;
+    let monthly_savings = net / 3.0;
+    let daily_savings = monthly_savings * 12.0 / 365.0;
+
+    let remaining = (goal.target_amount - goal.saved_amount).max(0.0);
+    // None when nothing is being saved: the goal is never reached at this rate
+    let reached_on = |to_save: f64| {
+        (daily_savings > 0.0).then(|| today + chrono::Duration::days((to_save / daily_savings).ceil() as i64))
+    };
+    let projected = reached_on(remaining);
+    let projected_with_purchase = reached_on(remaining + amount);
+    let delay_days = projected.zip(projected_with_purchase).map(|(before, after)| (after - before).num_days());
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(PurchaseImpact {
+        goal_id: goal_id.to_string(),
+        goal_name: goal.name,
+        target_date: goal.target_date,
+        remaining: round(remaining),
+        monthly_savings: round(monthly_savings),
+        projected_date: projected.map(|d| d.to_string()),
+        projected_date_with_purchase: projected_with_purchase.map(|d| d.to_string()),
+        delay_days,
+        delay_weeks: delay_days.map(|days| (days as f64 / 7.0 * 10.0).round() / 10.0),
+        on_track: projected.is_some_and(|d| d <= target_date),
+        on_track_with_purchase: projected_with_purchase.is_some_and(|d| d <= target_date),
+    })
+}
+
+const DEFAULT_SPENDING_BUCKETS: [(&str, &str); 8] = [
+    ("housing", "essential"),
+    ("utilities", "essential"),
+    ("groceries", "essential"),
+    ("healthcare", "essential"),
+    ("dining", "discretionary"),
+    ("entertainment", "discretionary"),
+    ("shopping", "discretionary"),
+    ("travel", "discretionary"),
+];
+
+/// Category to bucket mapping: the defaults with the user's overrides applied.
+/// Overrides are a JSON object in the spending_buckets setting, where an empty
+/// bucket takes a category out of both.
+fn spending_buckets(conn: &rusqlite::Connection) -> HashMap<String, String> {
+    let mut buckets: HashMap<String, String> = DEFAULT_SPENDING_BUCKETS
+        .iter()
+        .map(|(category, bucket)| (category.to_string(), bucket.to_string()))
+        .collect();
+    let overrides = saved_bucket_overrides(conn);
+    buckets.extend(overrides);
+    buckets.retain(|_, bucket| !bucket.is_empty());
+    buckets
+}
+
+/// The spending_buckets setting: category id to bucket, empty for neither
+fn saved_bucket_overrides(conn: &rusqlite::Connection) -> HashMap<String, String> {
+    conn.query_row("SELECT value FROM settings WHERE key = 'spending_buckets'", [], |row| row.get::<_, String>(0))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Put a category in the "essential" or "discretionary" bucket, or in neither
+/// with an empty `bucket`. None restores the default for the category.
+#[tauri::command]
+pub async fn set_category_bucket(app: AppHandle, category_id: String, bucket: Option<String>) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    save_category_bucket(&conn, &category_id, bucket.as_deref())
+}
+
+fn save_category_bucket(conn: &rusqlite::Connection, category_id: &str, bucket: Option<&str>) -> Result<(), YukiError> {
+    if let Some(bucket) = bucket.filter(|b| !["essential", "discretionary", ""].contains(b)) {
+        return Err(YukiError::Validation(format!(
+            "Unknown bucket '{}': expected essential, discretionary or empty",
+            bucket
+        )));
+    }
+
+    let mut overrides = saved_bucket_overrides(conn);
+    match bucket {
+        Some(bucket) => overrides.insert(category_id.to_string(), bucket.to_string()),
+        None => overrides.remove(category_id),
+    };
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('spending_buckets', ?1)",
+        [serde_json::to_string(&overrides)?],
+    )?;
+    Ok(())
+}
+
+/// Expenses in a period split into essentials (housing, utilities, groceries,
+/// healthcare by default) and discretionary spending (dining, entertainment,
+/// shopping, travel), in the primary currency
+#[tauri::command]
+pub async fn get_essential_vs_discretionary(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<SpendingSplit, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    spending_split(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn spending_split(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<SpendingSplit, YukiError> {
+    let buckets = spending_buckets(conn);
+    let mut stmt = conn.prepare(
+        "SELECT l.category_id, SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0))
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount < 0
+           AND (?1 IS NULL OR l.date >= ?1)
+           AND (?2 IS NULL OR l.date <= ?2)
+         GROUP BY l.category_id",
+    )?;
+    let totals: Vec<(String, f64)> = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let (mut essential, mut discretionary, mut unclassified) = (0.0, 0.0, 0.0);
+    for (category_id, total) in totals {
+        match buckets.get(&category_id).map(String::as_str) {
+            Some("essential") => essential += total,
+            Some("discretionary") => discretionary += total,
+            _ => unclassified += total,
+        }
+    }
+
+    let total = essential + discretionary + unclassified;
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    let percent = |v: f64| if total > 0.0 { round(v / total * 100.0) } else { 0.0 };
+    Ok(SpendingSplit {
+        essential_total: round(essential),
+        essential_percent: percent(essential),
+        discretionary_total: round(discretionary),
+        discretionary_percent: percent(discretionary),
+        unclassified_total: round(unclassified),
+        unclassified_percent: percent(unclassified),
+        total: round(total),
+    })
+}
+
+/// The category_budgets setting: category id to monthly budget in the primary currency
+fn category_budgets(conn: &rusqlite::Connection) -> HashMap<String, f64> {
+    conn.query_row("SELECT value FROM settings WHERE key = 'category_budgets'", [], |row| row.get::<_, String>(0))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Set a category's monthly budget in the primary currency; None removes it
+#[tauri::command]
+pub async fn set_category_budget(app: AppHandle, category_id: String, amount: Option<f64>) -> Result<(), YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    save_category_budget(&conn, &category_id, amount)
+}
+
+fn save_category_budget(conn: &rusqlite::Connection, category_id: &str, amount: Option<f64>) -> Result<(), YukiError> {
+    if amount.is_some_and(|a| !a.is_finite() || a < 0.0) {
+        return Err(YukiError::Validation("Budget must be zero or more".to_string()));
+    }
+    let category_exists: bool =
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM categories WHERE id = ?1)", [category_id], |row| row.get(0))?;
+    if !category_exists {
+        return Err(YukiError::NotFound(format!("Category '{}' does not exist", category_id)));
+    }
+
+    let mut budgets = category_budgets(conn);
+    match amount {
+        Some(amount) => budgets.insert(category_id.to_string(), amount),
+        None => budgets.remove(category_id),
+    };
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('category_budgets', ?1)",
+        [serde_json::to_string(&budgets)?],
+    )?;
+    Ok(())
+}
+
+/// How much can still be spent this week, and today, while staying within the
+/// month's category budgets
+#[tauri::command]
+pub async fn get_weekly_allowance(app: AppHandle) -> Result<WeeklyAllowance, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    weekly_allowance(&conn, chrono::Local::now().date_naive())
+}
+
+/// The budget left after month-to-date spending in the budgeted categories, spread
+/// evenly over the rest of the month counting today: a week's share is seven days'
+/// worth, so the last few days of a month get a partial week rather than a whole one.
+fn weekly_allowance(conn: &rusqlite::Connection, today: chrono::NaiveDate) -> Result<WeeklyAllowance, YukiError> {
+    let budgets = category_budgets(conn);
+    let monthly_budget: f64 = budgets.values().sum();
+
+    let (month_start, next_month) = month_bounds(today)?;
+    let days_remaining = (next_month - today).num_days();
+
+    let spent: f64 = month_to_date_spending(conn, month_start, today)?
+        .into_iter()
+        .filter(|(category_id, _)| budgets.contains_key(category_id))
+        .map(|(_, total)| total)
+        .sum();
+
+    let remaining = monthly_budget - spent;
+    let daily = remaining.max(0.0) / days_remaining as f64;
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(WeeklyAllowance {
+        monthly_budget: round(monthly_budget),
+        spent_this_month: round(spent),
+        remaining: round(remaining),
+        days_remaining,
+        weeks_remaining: round(days_remaining as f64 / 7.0),
+        per_week: round(daily * 7.0),
+        today: round(daily),
+    })
+}
+
+/// First day of `today`'s month and of the month after
+fn month_bounds(today: chrono::NaiveDate) -> Result<(chrono::NaiveDate, chrono::NaiveDate), YukiError> {
+    use chrono::Datelike;
+
+    let month_start = today.with_day(1).unwrap_or(today);
+    let next_month = month_start
+        .checked_add_months(chrono::Months::new(1))
+        .ok_or_else(|| YukiError::Validation(format!("No month follows {}", today)))?;
+    Ok((month_start, next_month))
+}
+
+/// Expenses per category from `month_start` through `today`, in the primary currency
+fn month_to_date_spending(
+    conn: &rusqlite::Connection,
+    month_start: chrono::NaiveDate,
+    today: chrono::NaiveDate,
+) -> Result<HashMap<String, f64>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT l.category_id, SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0))
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount < 0 AND l.date >= ?1 AND date(l.date) <= ?2
+         GROUP BY l.category_id",
+    )?;
+    let spending = stmt
+        .query_map([month_start.to_string(), today.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(spending)
+}
+
+/// Budgeted categories that are over their monthly budget, on course to go over,
+/// or close to it, most severe first
+#[tauri::command]
+pub async fn get_budget_alerts(app: AppHandle) -> Result<Vec<BudgetAlert>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    budget_alerts(&conn, chrono::Local::now().date_naive())
+}
+
+/// Share of a budget spent before an on-track category still gets an "info" alert
+const BUDGET_NEAR_LIMIT_PERCENT: f64 = 80.0;
+
+/// Days into the month before the spending pace is trusted for a projection;
+/// one purchase on the 1st would otherwise project to thirty of them
+const BUDGET_PROJECTION_MIN_DAYS: i64 = 5;
+
+/// Severity per budgeted category: "critical" once month-to-date spending is over
+/// the budget, "warning" when spending at the same daily rate for the rest of the
+/// month would exceed it, "info" when at least 80% is used but the pace fits.
+fn budget_alerts(conn: &rusqlite::Connection, today: chrono::NaiveDate) -> Result<Vec<BudgetAlert>, YukiError> {
+    let budgets = category_budgets(conn);
+    let (month_start, next_month) = month_bounds(today)?;
+    let days_elapsed = (today - month_start).num_days() + 1;
+    let days_in_month = (next_month - month_start).num_days();
+    let spending = month_to_date_spending(conn, month_start, today)?;
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    let mut alerts = Vec::new();
+    for (category_id, budget) in budgets {
+        if budget <= 0.0 {
+            continue;
+        }
+        let spent = spending.get(&category_id).copied().unwrap_or(0.0);
+        let projected = spent / days_elapsed as f64 * days_in_month as f64;
+        let percent_used = spent / budget * 100.0;
+
+        let severity = if spent > budget {
+            "critical"
+        } else if days_elapsed >= BUDGET_PROJECTION_MIN_DAYS && projected > budget {
+            "warning"
+        } else if percent_used >= BUDGET_NEAR_LIMIT_PERCENT {
+            "info"
+        } else {
+            continue;
+        };
+
+        let category_name = conn
+            .query_row("SELECT name FROM categories WHERE id = ?1", [&category_id], |row| row.get(0))
+            .unwrap_or_else(|_| category_id.clone());
+        alerts.push(BudgetAlert {
+            category_id,
+            category_name,
+            severity: severity.to_string(),
+            budget: round(budget),
+            spent: round(spent),
+            projected: round(projected),
+            percent_used: round(percent_used),
+        });
+    }
+
+    let rank = |severity: &str| ["critical", "warning", "info"].iter().position(|s| *s == severity);
+    alerts.sort_by(|a, b| {
+        rank(&a.severity)
+            .cmp(&rank(&b.severity))
+            .then(b.percent_used.partial_cmp(&a.percent_used).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    Ok(alerts)
+}
+
+/// Expenses in `deductible_category_ids` during calendar year `tax_year`, per
+/// category and in total, in the primary currency
+#[tauri::command]
+pub async fn get_deductible_summary(
+    app: AppHandle,
+    tax_year: i32,
+    deductible_category_ids: Vec<String>,
+) -> Result<DeductibleSummary, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    deductible_summary(&conn, tax_year, &deductible_category_ids)
+}
+
+fn deductible_summary(
+    conn: &rusqlite::Connection,
+    tax_year: i32,
+    category_ids: &[String],
+) -> Result<DeductibleSummary, YukiError> {
+    if !(1900..=9999).contains(&tax_year) {
+        return Err(YukiError::Validation(format!("Invalid tax year {}", tax_year)));
+    }
+
+    let mut categories = Vec::new();
+    if !category_ids.is_empty() {
+        // ?1 is the year, the category ids follow
+        let placeholders: Vec<String> = (2..category_ids.len() + 2).map(|i| format!("?{}", i)).collect();
+        let sql = format!(
+            "SELECT l.category_id, COALESCE(cat.name, l.category_id),
+                    SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)) AS total, COUNT(*)
+             FROM ledger l
+             LEFT JOIN currencies c ON l.currency = c.code
+             LEFT JOIN categories cat ON l.category_id = cat.id
+             WHERE l.amount < 0
+               AND strftime('%Y', l.date) = ?1
+               AND l.category_id IN ({})
+             GROUP BY l.category_id
+             ORDER BY total DESC, l.category_id",
+            placeholders.join(", ")
+        );
+
+        let year = format!("{:04}", tax_year);
+        let params = std::iter::once(&year).chain(category_ids.iter());
+        let mut stmt = conn.prepare(&sql)?;
+        categories = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| {
+                Ok(DeductibleCategoryTotal {
+                    category_id: row.get(0)?,
+                    category_name: row.get(1)?,
+                    total: row.get(2)?,
+                    count: row.get(3)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+    }
+
+    let total = categories.iter().map(|c| c.total).sum();
+    Ok(DeductibleSummary {
+        tax_year,
+        categories,
+        total,
+    })
+}
+
+/// Expenses per day of `year` in the primary currency, keyed `YYYY-MM-DD`, with
+/// every day of the year present so a calendar heatmap has no holes
+#[tauri::command]
+pub async fn get_daily_spending_calendar(
+    app: AppHandle,
+    year: i32,
+) -> Result<std::collections::BTreeMap<String, f64>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    daily_spending_calendar(&conn, year)
+}
+
+fn daily_spending_calendar(
+    conn: &rusqlite::Connection,
+    year: i32,
+) -> Result<std::collections::BTreeMap<String, f64>, YukiError> {
+    use chrono::Datelike;
+
+    let first_day = chrono::NaiveDate::from_ymd_opt(year, 1, 1)
+        .filter(|_| (1900..=9999).contains(&year))
+        .ok_or_else(|| YukiError::Validation(format!("Invalid year {}", year)))?;
+    let mut calendar: std::collections::BTreeMap<String, f64> = first_day
+        .iter_days()
+        .take_while(|day| day.year() == year)
+        .map(|day| (day.to_string(), 0.0))
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT date(l.date) AS day, SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0))
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount < 0 AND strftime('%Y', l.date) = ?1
+         GROUP BY day",
+    )?;
+    let totals = stmt
+        .query_map([format!("{:04}", year)], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?
+        .filter_map(|r| r.ok());
+    for (day, total) in totals {
+        if let Some(entry) = calendar.get_mut(&day) {
+            *entry = (total * 100.0).round() / 100.0;
+        }
+    }
+    Ok(calendar)
+}
+
+/// Average daily spend, average expense size and the biggest spending day in a window.
+/// An open bound falls back to the first/last expense date.
+#[tauri::command]
+pub async fn get_spending_metrics(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<SpendingMetrics, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    spending_metrics(&conn, start_date.as_deref(), end_date.as_deref())
+}
+
+fn spending_metrics(
+    conn: &rusqlite::Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<SpendingMetrics, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT date(l.date) AS day, SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)), COUNT(*)
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount < 0
+           AND (?1 IS NULL OR l.date >= ?1)
+           AND (?2 IS NULL OR l.date <= ?2)
+         GROUP BY day
+         ORDER BY day",
+    )?;
+    let days: Vec<(String, f64, i64)> = stmt
+        .query_map(rusqlite::params![start_date, end_date], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let total: f64 = days.iter().map(|(_, total, _)| total).sum();
+    let transaction_count: i64 = days.iter().map(|(_, _, count)| count).sum();
+    let largest_day = days
+        .iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(date, total, _)| DayTotal {
+            date: date.clone(),
+            total: *total,
+        });
+
+    // Days in the window, counting quiet days too so the daily average isn't inflated
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d.get(..10).unwrap_or(d), "%Y-%m-%d").ok();
+    let first = start_date.and_then(parse).or_else(|| days.first().and_then(|(d, _, _)| parse(d)));
+    let last = end_date.and_then(parse).or_else(|| days.last().and_then(|(d, _, _)| parse(d)));
+    let window_days = match (first, last) {
+        (Some(first), Some(last)) if last >= first => (last - first).num_days() + 1,
+        _ => 0,
+    };
+
+    Ok(SpendingMetrics {
+        avg_daily_spend: if window_days > 0 { total / window_days as f64 } else { 0.0 },
+        avg_transaction: if transaction_count > 0 { total / transaction_count as f64 } else { 0.0 },
+        largest_day,
+        transaction_count,
+        days_with_spending: days.len() as i64,
+    })
+}
+
+/// Median, 75th and 90th percentile, smallest and largest expense, optionally in
+/// one category, in the primary currency. Unlike an average these aren't pulled
+/// up by the odd big purchase.
+#[tauri::command]
+pub async fn get_amount_percentiles(
+    app: AppHandle,
+    category_id: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<AmountPercentiles, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    amount_percentiles(&conn, category_id.as_deref(), start_date.as_deref(), end_date.as_deref())
+}
+
+fn amount_percentiles(
+    conn: &rusqlite::Connection,
+    category_id: Option<&str>,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<AmountPercentiles, YukiError> {
+    // SQLite has no percentile aggregate, so the amounts are sorted here
+    let mut stmt = conn.prepare(
+        "SELECT ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)
+         FROM ledger l LEFT JOIN currencies c ON l.currency = c.code
+         WHERE l.amount < 0
+           AND (?1 IS NULL OR l.category_id = ?1)
+           AND (?2 IS NULL OR l.date >= ?2)
+           AND (?3 IS NULL OR l.date <= ?3)",
+    )?;
+    let mut amounts: Vec<f64> = stmt
+        .query_map(rusqlite::params![category_id, start_date, end_date], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    amounts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    Ok(AmountPercentiles {
+        median: round(percentile(&amounts, 50.0)),
+        p75: round(percentile(&amounts, 75.0)),
+        p90: round(percentile(&amounts, 90.0)),
+        max: round(amounts.last().copied().unwrap_or(0.0)),
+        min: round(amounts.first().copied().unwrap_or(0.0)),
+        count: amounts.len() as i64,
+    })
+}
+
+/// `p`th percentile of ascending `sorted`, interpolating linearly between the two
+/// nearest ranks (so the 50th of an even count is the mean of the middle pair)
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let Some(last) = sorted.len().checked_sub(1) else { return 0.0 };
+    let rank = p / 100.0 * last as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+/// Recurring charges whose latest price is more than `threshold_percent` above
+/// the amount they were charged before
+#[tauri::command]
+pub async fn detect_price_increases(app: AppHandle, threshold_percent: f64) -> Result<Vec<PriceIncrease>, YukiError> {
+    if !threshold_percent.is_finite() || threshold_percent < 0.0 {
+        return Err(YukiError::Validation("threshold_percent must be zero or more".to_string()));
+    }
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    price_increases(&conn, threshold_percent)
+}
+
+/// A merchant is treated as recurring when it has charged at least twice at a
+/// steady price (within 5% of the median) followed by one or more charges at a new
+/// price, with charges at least a few days apart so daily habits don't qualify.
+fn price_increases(conn: &rusqlite::Connection, threshold_percent: f64) -> Result<Vec<PriceIncrease>, YukiError> {
+    let mut increases = Vec::new();
+    for ((merchant_key, currency), (merchant, charges)) in merchant_charges(conn)? {
+        if charges.len() < 3 {
+            continue;
+        }
+
+        let dates: Vec<chrono::NaiveDate> = charges.iter().filter_map(|(d, _)| parse_ledger_date(d)).collect();
+        let gaps: Vec<f64> = dates.windows(2).map(|w| (w[1] - w[0]).num_days() as f64).collect();
+        if gaps.len() + 1 != charges.len() || median(&gaps) < 6.0 {
+            continue;
+        }
+
+        // The run of charges at the latest price, allowing for cents of rounding
+        let new_amount = charges[charges.len() - 1].1;
+        let new_start = charges
+            .iter()
+            .rposition(|(_, amount)| (amount - new_amount).abs() > new_amount * 0.005)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let prior: Vec<f64> = charges[..new_start].iter().map(|(_, amount)| *amount).collect();
+        if prior.len() < 2 {
+            continue;
+        }
+
+        let old_amount = median(&prior);
+        if old_amount <= 0.0 || prior.iter().any(|amount| (amount - old_amount).abs() > old_amount * 0.05) {
+            continue;
+        }
+
+        let percent_increase = (new_amount - old_amount) / old_amount * 100.0;
+        if percent_increase > threshold_percent {
+            increases.push(PriceIncrease {
+                merchant,
+                old_amount,
+                new_amount,
+                percent_increase,
+                effective_date: charges[new_start].0.clone(),
+                key: format!("price|{}|{}|{}", merchant_key, currency, charges[new_start].0),
+                currency,
+            });
+        }
+    }
+
+    increases.sort_by(|a, b| b.effective_date.cmp(&a.effective_date));
+    without_ignored_anomalies(conn, increases, |i| &i.key)
+}
+
+/// Display name and dated positive amounts of one merchant's charges in one currency
+type MerchantCharges = (String, Vec<(String, f64)>);
+
+/// Every expense grouped by normalized merchant and currency, oldest charge first.
+/// The first spelling seen is kept for display.
+fn merchant_charges(
+    conn: &rusqlite::Connection,
+) -> Result<std::collections::BTreeMap<(String, String), MerchantCharges>, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT date, COALESCE(NULLIF(merchant, ''), description), ABS(amount), currency
+         FROM ledger
+         WHERE amount < 0
+         ORDER BY date, created_at, id",
+    )?;
+    let rows: Vec<(String, String, f64, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut groups: std::collections::BTreeMap<(String, String), MerchantCharges> = std::collections::BTreeMap::new();
+    for (date, merchant, amount, currency) in rows {
+        let key = normalize_merchant(&merchant);
+        if key.is_empty() {
+            continue;
+        }
+        groups
+            .entry((key, currency))
+            .or_insert_with(|| (merchant.clone(), Vec::new()))
+            .1
+            .push((date, amount));
+    }
+    Ok(groups)
+}
+
+/// Middle value, or the mean of the middle pair for an even count
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+        _ => sorted[mid],
+    }
+}
+
+/// Date part of a ledger date, which may carry a time
+fn parse_ledger_date(date: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date.get(..10).unwrap_or(date), "%Y-%m-%d").ok()
+}
+
+/// Recurring charges whose next charge should already have posted
+#[tauri::command]
+pub async fn detect_missing_recurring(app: AppHandle) -> Result<Vec<MissingRecurring>, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    missing_recurring(&conn, chrono::Local::now().date_naive())
+}
+
+/// Cadences a charge series can follow, as (label, shortest gap, longest gap) in days
+const RECURRING_CADENCES: [(&str, i64, i64); 4] =
+    [("weekly", 6, 8), ("biweekly", 13, 16), ("monthly", 27, 33), ("yearly", 355, 375)];
+
+/// A series counts as recurring when it has at least three charges within 20% of
+/// their median amount, every gap sits in the same cadence band, and the typical
+/// gap is the band's. The next charge is expected one median gap after the last;
+/// it is overdue once the band's slack has also passed without a charge.
+fn missing_recurring(conn: &rusqlite::Connection, today: chrono::NaiveDate) -> Result<Vec<MissingRecurring>, YukiError> {
+    let mut missing = Vec::new();
+    for ((merchant_key, currency), (merchant, charges)) in merchant_charges(conn)? {
+        if charges.len() < 3 {
+            continue;
+        }
+        let dates: Vec<chrono::NaiveDate> = charges.iter().filter_map(|(d, _)| parse_ledger_date(d)).collect();
+        if dates.len() != charges.len() {
+            continue;
+        }
+
+        let gaps: Vec<i64> = dates.windows(2).map(|w| (w[1] - w[0]).num_days()).collect();
+        let Some((_, _, longest)) = cadence_of(&gaps) else {
+            continue;
+        };
+
+        let amounts: Vec<f64> = charges.iter().map(|(_, amount)| *amount).collect();
+        let typical_amount = median(&amounts);
+        if typical_amount <= 0.0 || amounts.iter().any(|amount| (amount - typical_amount).abs() > typical_amount * 0.2) {
+            continue;
+        }
+
+        let last = dates[dates.len() - 1];
+        let typical_gap = median(&gaps.iter().map(|gap| *gap as f64).collect::<Vec<_>>()).round() as i64;
+        let expected_around = last + chrono::Duration::days(typical_gap);
+        if today <= last + chrono::Duration::days(longest) {
+            continue;
+        }
+
+        missing.push(MissingRecurring {
+            merchant,
+            expected_around: expected_around.to_string(),
+            days_overdue: (today - expected_around).num_days(),
+            typical_amount: (typical_amount * 100.0).round() / 100.0,
+            key: format!("missing|{}|{}|{}", merchant_key, currency, expected_around),
+            currency,
+        });
+    }
+
+    missing.sort_by_key(|m| std::cmp::Reverse(m.days_overdue));
+    without_ignored_anomalies(conn, missing, |m| &m.key)
+}
+
+/// The recurring cadence every gap fits, if any
+fn cadence_of(gaps: &[i64]) -> Option<(&'static str, i64, i64)> {
+    RECURRING_CADENCES
+        .into_iter()
+        .find(|(_, shortest, longest)| gaps.iter().all(|gap| gap >= shortest && gap <= longest))
+}
+
+/// Days between consecutive charges at merchants matching `merchant_pattern`,
+/// with the frequency they imply
+#[tauri::command]
+pub async fn get_merchant_cadence(app: AppHandle, merchant_pattern: String) -> Result<MerchantCadence, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    merchant_cadence(&conn, &merchant_pattern)
+}
+
+/// Charges on the same day count once, so two coffees don't read as a zero-day gap.
+/// The frequency is "daily" when no gap is over two days, one of the recurring
+/// cadences when every gap fits it, and "irregular" otherwise or with fewer than
+/// two charge days.
+fn merchant_cadence(conn: &rusqlite::Connection, merchant_pattern: &str) -> Result<MerchantCadence, YukiError> {
+    let pattern = normalize_merchant(merchant_pattern);
+    if pattern.is_empty() {
+        return Err(YukiError::Validation("Merchant pattern must not be empty".to_string()));
+    }
+
+    // Matching happens in Rust so the same normalization applies to both sides
+    let mut stmt = conn.prepare("SELECT date, COALESCE(NULLIF(merchant, ''), description) FROM ledger WHERE amount < 0")?;
+    let mut dates: Vec<chrono::NaiveDate> = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .filter_map(|r| r.ok())
+        .filter(|(_, merchant)| normalize_merchant(merchant).contains(&pattern))
+        .filter_map(|(date, _)| parse_ledger_date(&date))
+        .collect();
+    dates.sort();
+    dates.dedup();
+
+    let gaps: Vec<i64> = dates.windows(2).map(|w| (w[1] - w[0]).num_days()).collect();
+    let frequency = if gaps.is_empty() {
+        "irregular"
+    } else if gaps.iter().all(|gap| *gap <= 2) {
+        "daily"
+    } else {
+        cadence_of(&gaps).map(|(label, _, _)| label).unwrap_or("irregular")
+    };
+
+    Ok(MerchantCadence {
+        merchant_pattern: merchant_pattern.to_string(),
+        charge_days: dates.len(),
+        average_days: (!gaps.is_empty())
+            .then(|| (gaps.iter().sum::<i64>() as f64 / gaps.len() as f64 * 10.0).round() / 10.0),
+        min_days: gaps.iter().min().copied(),
+        max_days: gaps.iter().max().copied(),
+        frequency: frequency.to_string(),
+        first_charge: dates.first().map(|d| d.to_string()),
+        last_charge: dates.last().map(|d| d.to_string()),
+    })
+}
+
+/// Draw a chart card to a PNG or SVG under the exports folder and return its path
+#[tauri::command]
+pub async fn render_chart_image(app: AppHandle, chart: ChartContent, format: String) -> Result<String, YukiError> {
+    let format = chart::ImageFormat::parse(&format)
+        .ok_or_else(|| YukiError::Validation(format!("Unsupported image format '{}', expected 'png' or 'svg'", format)))?;
+
+    let exports_dir = database::get_data_dir(&app).map_err(YukiError::database)?.join("exports");
+    fs::create_dir_all(&exports_dir)?;
+    let dest = exports_dir.join(format!("chart-{}.{}", uuid::Uuid::new_v4(), format.extension()));
+
+    chart::render(&chart, format, &dest).map_err(YukiError::validation)?;
+    log::info!("Rendered {} chart to {}", chart.chart_type, dest.display());
+    Ok(dest.to_string_lossy().to_string())
+}
+
+// ============================================================================
+// Backup Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn export_backup_bundle(
+    app: AppHandle,
+    dest_path: String,
+    passphrase: String,
+) -> Result<BackupSummary, YukiError> {
+    let data_dir = database::get_data_dir(&app).map_err(YukiError::database)?;
+    let db_path = database::get_db_path(&app).map_err(YukiError::database)?;
+
+    let summary = backup::write_bundle(
+        &db_path,
+        &data_dir.join("documents"),
+        std::path::Path::new(&dest_path),
+        &passphrase,
+    )
+    .map_err(YukiError::validation)?;
+
+    log::info!(
+        "Exported backup with {} transactions and {} documents",
+        summary.transaction_count,
+        summary.document_count
+    );
+    Ok(summary)
+}
+
+/// Replace local data with the contents of a backup bundle
+#[tauri::command]
+pub async fn import_backup_bundle(
+    app: AppHandle,
+    src_path: String,
+    passphrase: String,
+) -> Result<BackupSummary, YukiError> {
+    let data_dir = database::get_data_dir(&app).map_err(YukiError::database)?;
+
+    let summary = backup::restore_bundle(std::path::Path::new(&src_path), &passphrase, &data_dir)
+        .map_err(YukiError::validation)?;
+
+    // The restored db carries its own conversation history
+    if let Ok(mut sessions) = CURRENT_SESSIONS.lock() {
+        sessions.clear();
+    }
+
+    log::info!(
+        "Imported backup with {} transactions and {} documents",
+        summary.transaction_count,
+        summary.document_count
+    );
+    Ok(summary)
+}
+
+/// Write every ledger row, with its category name, to `path` as newline-delimited
+/// JSON for data tools. Returns the number of rows written.
+#[tauri::command]
+pub async fn export_ledger_ndjson(app: AppHandle, path: String) -> Result<usize, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let file = fs::File::create(&path).map_err(|e| file_error("create", &path, e))?;
+    let count = write_ledger_ndjson(&conn, std::io::BufWriter::new(file))?;
+
+    log::info!("Exported {} transactions as NDJSON to {}", count, path);
+    Ok(count)
+}
+
+/// Rows are serialized one at a time as the query steps, so the ledger is never
+/// held in memory whole
+fn write_ledger_ndjson(conn: &rusqlite::Connection, mut out: impl std::io::Write) -> Result<usize, YukiError> {
+    let mut stmt = conn.prepare(
+        "SELECT l.id, l.document_id, l.account_id, l.date, l.description, l.amount, l.currency, l.category_id, l.merchant,
+                l.notes, l.source, l.created_at, l.original_amount, l.original_currency, l.needs_review, cat.name
+         FROM ledger l LEFT JOIN categories cat ON l.category_id = cat.id
+         ORDER BY l.date, l.created_at, l.id",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        let entry = ExportedLedgerEntry {
+            entry: ledger_entry_from_row(row)?,
+            category_name: row.get(15)?,
+        };
+        serde_json::to_writer(&mut out, &entry)?;
+        out.write_all(b"\n")?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+const CONFIG_FORMAT_VERSION: u32 = 1;
+
+/// Write categories, item aliases and spending buckets to a JSON file that
+/// import_config can apply on another machine. No transactions are included.
+#[tauri::command]
+pub async fn export_config(app: AppHandle, path: String) -> Result<ConfigExport, YukiError> {
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let config = config_snapshot(&conn)?;
+    fs::write(&path, serde_json::to_string_pretty(&config)?).map_err(|e| file_error("write", &path, e))?;
+
+    log::info!(
+        "Exported {} categories and {} item aliases to {}",
+        config.categories.len(),
+        config.item_aliases.len(),
+        path
+    );
+    Ok(config)
+}
+
+fn config_snapshot(conn: &rusqlite::Connection) -> Result<ConfigExport, YukiError> {
+    let item_aliases = conn
+        .prepare("SELECT pattern, canonical, created_at FROM item_aliases ORDER BY pattern")?
+        .query_map([], |row| {
+            Ok(ItemAlias {
+                pattern: row.get(0)?,
+                canonical: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(ConfigExport {
+        format_version: CONFIG_FORMAT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        categories: all_categories(conn)?,
+        item_aliases,
+        spending_buckets: saved_bucket_overrides(conn),
+        category_budgets: category_budgets(conn),
+    })
+}
+
+/// Apply a file written by export_config. With `merge`, only what's missing
+/// locally is added and existing settings win. Without it, the file wins:
+/// matching categories are overwritten, aliases, buckets and budgets are replaced, and
+/// custom categories the file doesn't have are removed unless transactions
+/// still use them. A category with the same name under another id is treated
+/// as the same category and keeps its local id.
+#[tauri::command]
+pub async fn import_config(app: AppHandle, path: String, merge: bool) -> Result<ConfigImportSummary, YukiError> {
+    let json = fs::read_to_string(&path).map_err(|e| file_error("read", &path, e))?;
+    let config: ConfigExport =
+        serde_json::from_str(&json).map_err(|e| YukiError::Validation(format!("Malformed config file: {}", e)))?;
+
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    apply_config(&mut conn, &config, merge)
+}
+
+fn apply_config(
+    conn: &mut rusqlite::Connection,
+    config: &ConfigExport,
+    merge: bool,
+) -> Result<ConfigImportSummary, YukiError> {
+    if config.format_version != CONFIG_FORMAT_VERSION {
+        return Err(YukiError::Validation(format!(
+            "Unsupported config format version {}",
+            config.format_version
+        )));
+    }
+    if let Some(category) = config.categories.iter().find(|c| c.name.trim().is_empty()) {
+        return Err(YukiError::Validation(format!("Category '{}' has no name", category.id)));
+    }
+    if let Some((category_id, _)) = config.category_budgets.iter().find(|(_, a)| !a.is_finite() || **a < 0.0) {
+        return Err(YukiError::Validation(format!("Budget for '{}' must be zero or more", category_id)));
+    }
+
+    let local = all_categories(conn)?;
+    let mut summary = ConfigImportSummary::default();
+    // File category id to the id it has locally
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+
+    for category in &config.categories {
+        let existing = local.iter().find(|c| c.id == category.id).or_else(|| {
+            local.iter().find(|c| c.name.trim().eq_ignore_ascii_case(category.name.trim()))
+        });
+        // An invalid color or icon aborts the whole import; the transaction is rolled back
+        let color = category.color.as_deref().map(validate_category_color).transpose()?;
+        let icon = category.icon.as_deref().map(validate_category_icon).transpose()?;
+
+        match existing {
+            Some(existing) => {
+                if existing.id != category.id {
+                    summary.categories_matched_by_name += 1;
+                }
+                ids.insert(category.id.clone(), existing.id.clone());
+                if !merge {
+                    tx.execute(
+                        "UPDATE categories SET name = ?1, icon = ?2, color = ?3, sort_order = ?4, is_pinned = ?5 WHERE id = ?6",
+                        rusqlite::params![category.name.trim(), icon, color, category.sort_order, category.is_pinned, existing.id],
+                    )?;
+                    summary.categories_updated += 1;
+                }
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO categories (id, name, icon, color, is_default, created_at, sort_order, is_pinned)
+                     VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7)",
+                    rusqlite::params![category.id, category.name.trim(), icon, color, now, category.sort_order, category.is_pinned],
+                )?;
+                ids.insert(category.id.clone(), category.id.clone());
+                summary.categories_added += 1;
+            }
+        }
+    }
+
+    if !merge {
+        let kept: std::collections::HashSet<&String> = ids.values().collect();
+        for category in local.iter().filter(|c| !c.is_default && !kept.contains(&c.id)) {
+            tx.execute(
+                "DELETE FROM categories WHERE id = ?1
+                   AND NOT EXISTS (SELECT 1 FROM ledger WHERE category_id = ?1)
+                   AND NOT EXISTS (SELECT 1 FROM recurring_templates WHERE category_id = ?1)",
+                [&category.id],
+            )?;
+        }
+        tx.execute("DELETE FROM item_aliases", [])?;
+    }
+
+    for alias in &config.item_aliases {
+        summary.item_aliases += tx.execute(
+            "INSERT OR IGNORE INTO item_aliases (pattern, canonical, created_at) VALUES (?1, ?2, ?3)",
+            [&alias.pattern, &alias.canonical, &alias.created_at],
+        )?;
+    }
+
+    let mut buckets = if merge { saved_bucket_overrides(&tx) } else { HashMap::new() };
+    for (category_id, bucket) in &config.spending_buckets {
+        let category_id = ids.get(category_id).unwrap_or(category_id);
+        if !buckets.contains_key(category_id) {
+            buckets.insert(category_id.clone(), bucket.clone());
+            summary.spending_buckets += 1;
+        }
+    }
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('spending_buckets', ?1)",
+        [serde_json::to_string(&buckets)?],
+    )?;
+
+    let mut budgets = if merge { category_budgets(&tx) } else { HashMap::new() };
+    for (category_id, amount) in &config.category_budgets {
+        let category_id = ids.get(category_id).unwrap_or(category_id);
+        if !budgets.contains_key(category_id) {
+            budgets.insert(category_id.clone(), *amount);
+            summary.category_budgets += 1;
+        }
+    }
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('category_budgets', ?1)",
+        [serde_json::to_string(&budgets)?],
+    )?;
+
+    tx.commit()?;
+    Ok(summary)
+}
+
+/// Delete every transaction, receipt, document and conversation for a fresh start.
+/// With `keep_settings`, settings (including the provider), categories, accounts,
+/// currencies and item aliases stay; otherwise they go back to the defaults.
+#[tauri::command]
+pub async fn reset_data(app: AppHandle, keep_settings: bool) -> Result<ResetSummary, YukiError> {
+    let mut conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let (mut summary, files) = reset_data_rows(&mut conn, keep_settings)?;
+
+    // Files go only after the rows are committed, so a failed reset loses nothing
+    for path in files {
+        match fs::remove_file(&path) {
+            Ok(()) => summary.files_deleted += 1,
+            Err(e) => log::warn!("Could not delete document file {}: {}", path, e),
+        }
+    }
+
+    if let Ok(mut sessions) = CURRENT_SESSIONS.lock() {
+        sessions.clear();
+    }
+
+    log::info!(
+        "Reset data: {} transactions, {} documents, {} files (settings kept: {})",
+        summary.transactions,
+        summary.documents,
+        summary.files_deleted,
+        keep_settings
+    );
+    Ok(summary)
+}
+
+/// Delete the rows for reset_data in one transaction, children before parents,
+/// and return the document file paths to remove afterwards
+fn reset_data_rows(conn: &mut rusqlite::Connection, keep_settings: bool) -> Result<(ResetSummary, Vec<String>), YukiError> {
+    let tx = conn.transaction()?;
+
+    let files: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT filepath FROM documents")?;
+        let paths = stmt.query_map([], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+        paths
+    };
+
+    let summary = ResetSummary {
+        purchased_items: tx.execute("DELETE FROM purchased_items", [])?,
+        receipts: tx.execute("DELETE FROM receipts", [])?,
+        transactions: tx.execute("DELETE FROM ledger", [])?,
+        documents: {
+            tx.execute("DELETE FROM document_text", [])?;
+            tx.execute("DELETE FROM documents", [])?
+        },
+        conversation_messages: {
+            let messages = tx.execute("DELETE FROM conversation_messages", [])?;
+            tx.execute("DELETE FROM conversation_sessions", [])?;
+            messages
+        },
+        chat_history: tx.execute("DELETE FROM chat_history", [])?,
+        files_deleted: 0,
+    };
+    // Dismissed flags and import batches point at rows that no longer exist
+    tx.execute("DELETE FROM ignored_flags", [])?;
+    tx.execute("DELETE FROM import_batches", [])?;
+
+    if !keep_settings {
+        for table in ["settings", "item_aliases", "recurring_templates", "categories", "accounts", "currencies"] {
+            tx.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+        // Seed the default categories, account and currencies again
+        database::init_schema(&tx).map_err(YukiError::database)?;
+    }
+
+    tx.commit()?;
+    Ok((summary, files))
+}
+
+// ============================================================================
+// Query Commands
+// ============================================================================
+
+/// With `plaintext`, the cards come back flattened into one markdown-free text
+/// card for plaintext export and screen readers
+#[tauri::command]
+pub async fn process_query(
+    app: AppHandle,
+    window: tauri::Window,
+    question: String,
+    plaintext: Option<bool>,
+) -> Result<ResponseData, YukiError> {
+    log::info!("========================================");
+    log::info!("[PIPELINE] Starting query processing");
+    log::info!("[PIPELINE] User question: {}", logging::body(&question));
+    log::info!("========================================");
+
+    let window_label = window.label();
+    let history = {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+
+        // Ensure this window has a conversation session
+        let _ = session_for_window(&conn, window_label);
+
+        // Get conversation history (last 10 messages for context)
+        let history = get_conversation_history(&conn, window_label, 10).unwrap_or_default();
+        log::info!("[PIPELINE] Loaded {} messages from conversation history", history.len());
+
+        // Save the user's message
+        let _ = save_message(&conn, window_label, "user", &question);
+        history
+    };
+
+    let response = answer_question(&app, window_label, &question, &history).await?;
+    if !plaintext.unwrap_or(false) {
+        return Ok(response);
+    }
+
+    let is_error = response
+        .cards
+        .iter()
+        .any(|card| matches!(card, ResponseCard::Text(TextContent { is_error: Some(true), .. })));
+    Ok(ResponseData {
+        cards: vec![ResponseCard::Text(TextContent {
+            body: plaintext::render_plaintext(&response),
+            is_error: Some(is_error),
+        })],
+    })
+}
+
+/// Answer a question whose user turn is already saved, saving the assistant turn
+async fn answer_question(
+    app: &AppHandle,
+    window_label: &str,
+    question: &str,
+    history: &[ConversationMessage],
+) -> Result<ResponseData, YukiError> {
+    let settings = get_settings(app.clone()).await?;
+
+    let provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+
+    log::info!("[PIPELINE] Using provider: {} ({})", provider.name, provider.provider_type);
+
+    // Step 1: Determine if this is a data query or conversational query
+    log::info!("[PIPELINE] Step 1: Analyzing query...");
+    let query_analysis = llm::analyze_query(&provider, question, history)
+        .await
+        .map_err(YukiError::llm)?;
+
+    log::info!("[PIPELINE] Query analysis result:");
+    log::info!("[PIPELINE]   - needs_data: {}", query_analysis.needs_data);
+    log::info!("[PIPELINE]   - query_type: {}", query_analysis.query_type);
+    log::info!("[PIPELINE]   - sql_query: {:?}", query_analysis.sql_query);
+
+    // Step 2: If it's a data query, execute SQL and format results
+    if query_analysis.needs_data {
+        let sql = query_analysis.sql_query.clone().unwrap_or_default();
+        log::info!("[PIPELINE] Step 2: Executing SQL query...");
+        log::info!("[PIPELINE] SQL: {}", sql);
+
+        // Get the connection and execute the query
+        let conn = database::get_connection(app).map_err(YukiError::database)?;
+        Ok(answer_from_sql(conn, &provider, window_label, question, &sql, history).await)
+    } else {
+        // It's a conversational query, respond directly
+        log::info!("[PIPELINE] Step 2: Processing as conversational query (no data needed)");
+        let response = llm::process_conversational_query(&provider, question, history)
+            .await
+            .map_err(YukiError::llm)?;
+
+        // Save the assistant's response to conversation history
+        if let Some(first_card) = response.cards.first() {
+            let response_text = match first_card {
+                ResponseCard::Text(content) => content.body.clone(),
+                ResponseCard::Chart(content) => format!("[Chart: {}]", content.title),
+                ResponseCard::Table(content) => format!("[Table: {}]", content.title),
+                ResponseCard::Mixed(content) => content.body.clone(),
+            };
+            if let Ok(conn) = database::get_connection(app) {
+                let _ = save_message(&conn, window_label, "assistant", &response_text);
+            }
+        }
+
+        log::info!("[PIPELINE] Conversational response generated");
+        log::info!("========================================");
+        Ok(response)
+    }
+}
+
+/// Run a read-only query and turn its rows into response cards, saving the
+/// assistant turn. Shared by process_query and execute_approved_query.
+async fn answer_from_sql(
+    conn: rusqlite::Connection,
+    provider: &LLMProvider,
+    window_label: &str,
+    question: &str,
+    sql: &str,
+    history: &[ConversationMessage],
+) -> ResponseData {
+    match execute_query(&conn, sql) {
+        Ok(data) => {
+            log::info!("[PIPELINE] SQL execution successful!");
+            log::info!("[PIPELINE] Raw data: {}", logging::body(&data));
+
+            // Check if we got any results
+            let parsed: serde_json::Value = serde_json::from_str(&data).unwrap_or_default();
+            let row_count = parsed["row_count"].as_i64().unwrap_or(0);
+
+            if row_count == 0 {
+                // No data found - return a helpful message without calling LLM again
+                log::info!("[PIPELINE] No data returned, skipping LLM formatting");
+                log::info!("========================================");
+                return ResponseData {
+                    cards: vec![ResponseCard::Text(TextContent {
+                        body: "I don't have any data matching that query yet. Try uploading some financial documents or receipts first, and then I can help you analyze your spending!".to_string(),
+                        is_error: Some(false),
+                    })],
+                };
+            }
+
+            // Step 3: Format the results with the LLM
+            log::info!("[PIPELINE] Step 3: Formatting results with LLM ({} rows)...", row_count);
+            let response = format_results_or_raw_table(provider, question, &data, history).await;
+
+            // Save the assistant's response to conversation history
+            if let Some(first_card) = response.cards.first() {
+                let response_text = match first_card {
+                    ResponseCard::Text(content) => content.body.clone(),
+                    ResponseCard::Chart(content) => format!("[Chart: {}]", content.title),
+                    ResponseCard::Table(content) => format!("[Table: {}]", content.title),
+                    ResponseCard::Mixed(content) => content.body.clone(),
+                };
+                let _ = save_message(&conn, window_label, "assistant", &response_text);
+            }
+
+            log::info!("[PIPELINE] Final response generated with {} cards", response.cards.len());
+            log::info!("========================================");
+            response
+        }
+        Err(e) => {
+            log::error!("[PIPELINE] SQL execution FAILED!");
+            log::error!("[PIPELINE] Error: {}", e);
+            log::error!("[PIPELINE] Failed SQL: {}", sql);
+            log::info!("========================================");
+
+            // Return a friendly error message
+            ResponseData {
+                cards: vec![ResponseCard::Text(TextContent {
+                    body: format!("I couldn't retrieve that data. Error: {} in {}", e, sql),
+                    is_error: Some(true),
+                })],
+            }
+        }
+    }
+}
+
+/// Generate the SQL for a question without running it, so the user can review
+/// or edit it before calling execute_approved_query. Nothing is saved.
+#[tauri::command]
+pub async fn preview_query_sql(app: AppHandle, window: tauri::Window, question: String) -> Result<llm::QueryAnalysis, YukiError> {
+    let settings = get_settings(app.clone()).await?;
+    let provider = settings.provider.ok_or(YukiError::NoProvider)?;
+
+    // Earlier turns still inform the SQL ("and last month?"), but only current_session
+    // is consulted so previewing never creates a session
+    let history = match current_session(window.label())? {
+        Some(_) => {
+            let conn = database::get_connection(&app).map_err(YukiError::database)?;
+            get_conversation_history(&conn, window.label(), 10).unwrap_or_default()
+        }
+        None => Vec::new(),
+    };
+
+    preview_sql(&provider, &question, &history).await
+}
+
+/// The analysis preview_query_sql returns. SQL that execution would refuse is
+/// rejected here already, so it is never offered for approval.
+async fn preview_sql(
+    provider: &LLMProvider,
+    question: &str,
+    history: &[ConversationMessage],
+) -> Result<llm::QueryAnalysis, YukiError> {
+    let analysis = llm::analyze_query(provider, question, history)
+        .await
+        .map_err(YukiError::llm)?;
+    log::info!("[preview_query_sql] Generated SQL: {:?}", analysis.sql_query);
+    if let Some(sql) = &analysis.sql_query {
+        ensure_select(sql)?;
+    }
+    Ok(analysis)
+}
+
+/// Run SQL the user approved (possibly edited) from preview_query_sql through the
+/// same read-only execution and formatting as process_query
+#[tauri::command]
+pub async fn execute_approved_query(
+    app: AppHandle,
+    window: tauri::Window,
+    sql: String,
+    question: String,
+) -> Result<ResponseData, YukiError> {
+    let settings = get_settings(app.clone()).await?;
+    let provider = settings.provider.ok_or(YukiError::NoProvider)?;
+
+    let window_label = window.label();
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    let _ = session_for_window(&conn, window_label);
+    let history = get_conversation_history(&conn, window_label, 10).unwrap_or_default();
+    let _ = save_message(&conn, window_label, "user", &question);
+
+    log::info!("[execute_approved_query] Running approved SQL: {}", sql);
+    Ok(answer_from_sql(conn, &provider, window_label, &question, &sql, &history).await)
+}
+
+/// Most rows shown when the formatter fails and the raw result is shown instead
+const RAW_TABLE_MAX_ROWS: usize = 100;
+
+/// Format query results with the LLM, falling back to a plain table of the raw rows
+/// when formatting fails. The SQL already succeeded, so the user still sees their data.
+async fn format_results_or_raw_table(
+    provider: &LLMProvider,
+    question: &str,
+    data: &str,
+    history: &[ConversationMessage],
+) -> ResponseData {
+    match llm::format_query_results(provider, question, data, history).await {
+        Ok(response) => response,
+        Err(e) => {
+            log::warn!(
+                "[PIPELINE] Formatting failed, showing raw results: {}",
+                logging::redact(&e.to_string())
+            );
+            raw_results_table(data)
+        }
+    }
+}
+
+/// A table card built straight from execute_query's `{columns, rows}` JSON
+fn raw_results_table(data: &str) -> ResponseData {
+    let parsed: serde_json::Value = serde_json::from_str(data).unwrap_or_default();
+    let columns: Vec<String> = parsed["columns"]
+        .as_array()
+        .map(|cols| cols.iter().map(|c| c.as_str().unwrap_or_default().to_string()).collect())
+        .unwrap_or_default();
+    let all_rows = parsed["rows"].as_array().cloned().unwrap_or_default();
+
+    let cell = |value: &serde_json::Value| match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => format!("{:.2}", f),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    };
+    let rows: Vec<Vec<String>> = all_rows
+        .iter()
+        .take(RAW_TABLE_MAX_ROWS)
+        .map(|row| row.as_array().map(|values| values.iter().map(cell).collect()).unwrap_or_default())
+        .collect();
+
+    let mut summary = "I couldn't summarize these results, so here is the data as it came back.".to_string();
+    if all_rows.len() > rows.len() {
+        summary.push_str(&format!(" Showing the first {} of {} rows.", rows.len(), all_rows.len()));
+    }
+
+    ResponseData {
+        cards: vec![ResponseCard::Table(TableContent {
+            title: "Query results".to_string(),
+            columns,
+            rows,
+            summary: Some(summary),
+        })],
+    }
+}
+
+/// Re-answer the last question in the window's current session, replacing the previous
+/// assistant turn. The user turn is reused rather than saved again.
+#[tauri::command]
+pub async fn regenerate_last_response(app: AppHandle, window: tauri::Window) -> Result<ResponseData, YukiError> {
+    let window_label = window.label();
+    let session_id = current_session(window_label)?
+        .ok_or_else(|| YukiError::NotFound("No active conversation session".to_string()))?;
+
+    let exchange = {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+        last_exchange(&conn, &session_id, 10)?
+    };
+
+    log::info!("[PIPELINE] Regenerating response to: {}", logging::body(&exchange.question));
+    let response = answer_question(&app, window_label, &exchange.question, &exchange.history).await?;
+
+    // Only drop the old answer once a new one exists
+    let conn = database::get_connection(&app).map_err(YukiError::database)?;
+    delete_messages(&conn, &exchange.stale_answer_ids)?;
+
+    Ok(response)
+}
+
+/// Remove conversation messages by id, e.g. the answers a regenerated one replaces
+fn delete_messages(conn: &rusqlite::Connection, ids: &[String]) -> Result<(), YukiError> {
+    for id in ids {
+        conn.execute("DELETE FROM conversation_messages WHERE id = ?1", [id])?;
+    }
+    Ok(())
+}
+
+/// The most recent user turn of a session and what surrounds it
+struct LastExchange {
+    question: String,
+    history: Vec<ConversationMessage>, // Up to `limit` turns before the question
+    stale_answer_ids: Vec<String>,     // Assistant turns after the question
+}
+
+fn last_exchange(conn: &rusqlite::Connection, session_id: &str, limit: usize) -> Result<LastExchange, YukiError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, role, content FROM conversation_messages
+             WHERE session_id = ?1
+             ORDER BY created_at, rowid",
+        )?;
+
+    let messages: Vec<(String, String, String)> = stmt
+        .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let question_index = messages
+        .iter()
+        .rposition(|(_, role, _)| role == "user")
+        .ok_or_else(|| YukiError::NotFound("There is no question to regenerate an answer for".to_string()))?;
+
+    let history = messages[..question_index]
+        .iter()
+        .skip(question_index.saturating_sub(limit))
+        .map(|(_, role, content)| ConversationMessage {
+            role: role.clone(),
+            content: content.clone(),
+            created_at: None,
+        })
+        .collect();
+
+    let stale_answer_ids = messages[question_index + 1..]
+        .iter()
+        .filter(|(_, role, _)| role == "assistant")
+        .map(|(id, _, _)| id.clone())
+        .collect();
+
+    Ok(LastExchange {
+        question: messages[question_index].2.clone(),
+        history,
+        stale_answer_ids,
+    })
+}
+
+/// Safety check - only allow SELECT queries
+fn ensure_select(sql: &str) -> Result<(), YukiError> {
+    if !sql.trim().to_uppercase().starts_with("SELECT") {
+        return Err(YukiError::Validation("Only SELECT queries are allowed".to_string()));
+    }
+    Ok(())
+}
+
+/// Execute a SQL query and return the results as a JSON string
+fn execute_query(conn: &rusqlite::Connection, sql: &str) -> Result<String, YukiError> {
+    log::info!("Executing SQL: {}", sql);
+
+    ensure_select(sql)?;
+
+    let mut stmt = conn.prepare(sql)?;
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+
+    let rows: Vec<Vec<serde_json::Value>> = stmt
+        .query_map([], |row| {
+            let mut values: Vec<serde_json::Value> = Vec::new();
+            for i in 0..column_names.len() {
+                let value: rusqlite::Result<rusqlite::types::Value> = row.get(i);
+                let json_value = match value {
+                    Ok(rusqlite::types::Value::Null) => serde_json::Value::Null,
+                    Ok(rusqlite::types::Value::Integer(i)) => serde_json::json!(i),
+                    Ok(rusqlite::types::Value::Real(f)) => serde_json::json!(f),
+                    Ok(rusqlite::types::Value::Text(s)) => serde_json::json!(s),
+                    Ok(rusqlite::types::Value::Blob(b)) => serde_json::json!(format!("<blob {} bytes>", b.len())),
+                    Err(_) => serde_json::Value::Null,
+                };
+                values.push(json_value);
+            }
+            Ok(values)
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let result = serde_json::json!({
+        "columns": column_names,
+        "rows": rows,
+        "row_count": rows.len()
+    });
+
+    log::info!("Query returned {} rows", rows.len());
+    Ok(result.to_string())
+}
+
+#[tauri::command]
+pub async fn parse_document_text(
+    app: AppHandle,
+    text: String,
+    categories: Vec<String>,
+    only_after_date: Option<String>,
+    period_start: Option<String>,
+    period_end: Option<String>,
+) -> Result<Vec<ExtractedTransaction>, YukiError> {
+    log::info!("[parse_document_text] ========== COMMAND CALLED ==========");
+    validate_cutoff_date(only_after_date.as_deref())?;
+    let period = statement_period(period_start.as_deref(), period_end.as_deref())?;
+    log::info!("[parse_document_text] Text length: {} chars", text.len());
+    log::info!("[parse_document_text] Categories: {:?}", categories);
+
+    let settings = get_settings(app).await?;
+
+    let provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+
+    log::info!("[parse_document_text] Using provider: {} ({})", provider.name, provider.provider_type);
+
+    let period = statement_period_from_text(period, &text);
+    let period = period.as_ref().map(|(start, end)| (start.as_str(), end.as_str()));
+    let result = llm::parse_document_with_llm(&provider, &text, &categories, only_after_date.as_deref(), period)
+        .await
+        .map_err(|e| {
+            log::error!("[parse_document_text] LLM parsing failed: {}", e);
+            YukiError::llm(e)
+        })?;
+
+    log::info!("[parse_document_text] ========== RESULT: {} transactions ==========", result.len());
+    Ok(result)
+}
+
+/// Parse a few transactions pasted as plain text, e.g. from an email or SMS
+#[tauri::command]
+pub async fn parse_pasted_text(
+    app: AppHandle,
+    text: String,
+    categories: Vec<String>,
+) -> Result<Vec<ExtractedTransaction>, YukiError> {
+    if text.trim().is_empty() {
+        return Err(YukiError::Validation("Nothing to parse: the pasted text is empty".to_string()));
+    }
+    let provider = get_settings(app).await?.provider.ok_or(YukiError::NoProvider)?;
+
+    llm::parse_pasted_text_with_llm(&provider, &text, &categories, chrono::Local::now().date_naive())
+        .await
+        .map_err(YukiError::llm)
+}
+
+/// One entry per receipt found in the image, so a page of several receipts isn't merged
+#[tauri::command]
+pub async fn parse_receipt_image(
+    app: AppHandle,
+    image_path: String,
+    categories: Vec<String>,
+) -> Result<Vec<ParsedReceipt>, YukiError> {
+    let settings = get_settings(app).await?;
+
+    let provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+
+    llm::parse_receipt_with_llm(&provider, &image_path, &categories)
+        .await
+        .map_err(YukiError::llm)
+}
+
+/// File types parse_receipt_folder picks up
+const RECEIPT_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "pdf"];
+
+/// Receipts sent to the provider at once; its rate limit still applies on top
+const RECEIPT_FOLDER_CONCURRENCY: usize = 3;
+
+/// Parse every receipt image or PDF directly inside `dir_path`, a few at a time.
+/// Each file gets its own result, so one unreadable scan doesn't lose the rest,
+/// and a receipt-folder-progress event is emitted as each one finishes.
+#[tauri::command]
+pub async fn parse_receipt_folder(
+    app: AppHandle,
+    dir_path: String,
+    categories: Vec<String>,
+) -> Result<Vec<ReceiptFileResult>, YukiError> {
+    let files = receipt_files_in(std::path::Path::new(&dir_path))?;
+    let provider = get_settings(app.clone()).await?.provider.ok_or(YukiError::NoProvider)?;
+
+    log::info!("[parse_receipt_folder] Parsing {} receipt files in {}", files.len(), dir_path);
+    let results = parse_receipt_files(provider, files, categories, move |progress| {
+        if let Err(e) = app.emit("receipt-folder-progress", progress) {
+            log::warn!("[parse_receipt_folder] Failed to emit progress: {}", e);
+        }
+    })
+    .await;
+    Ok(results)
+}
+
+/// Receipt files directly inside `dir`, by name. Hidden files and anything that
+/// isn't an image or PDF are skipped.
+fn receipt_files_in(dir: &std::path::Path) -> Result<Vec<std::path::PathBuf>, YukiError> {
+    if !dir.is_dir() {
+        return Err(YukiError::NotFound(format!("Folder '{}' not found", dir.display())));
+    }
+    let mut files: Vec<std::path::PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| RECEIPT_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Parse `files` with at most RECEIPT_FOLDER_CONCURRENCY in flight, calling
+/// `on_progress` as each finishes. Results come back in the order of `files`.
+async fn parse_receipt_files(
+    provider: LLMProvider,
+    files: Vec<std::path::PathBuf>,
+    categories: Vec<String>,
+    on_progress: impl Fn(ReceiptFolderProgress),
+) -> Vec<ReceiptFileResult> {
+    let provider = std::sync::Arc::new(provider);
+    let categories = std::sync::Arc::new(categories);
+    let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(RECEIPT_FOLDER_CONCURRENCY));
+    let filename = |path: &std::path::Path| path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, path) in files.iter().cloned().enumerate() {
+        let (provider, categories, permits) = (provider.clone(), categories.clone(), permits.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = llm::parse_receipt_with_llm(&provider, &path.to_string_lossy(), &categories).await;
+            (index, result)
+        });
+    }
+
+    let total = files.len();
+    let mut results: Vec<Option<ReceiptFileResult>> = vec![None; total];
+    let mut completed = 0;
+    while let Some(joined) = tasks.join_next().await {
+        // A task that panicked has no index to report; its slot is filled below
+        let Ok((index, result)) = joined else { continue };
+        let (receipts, error) = match result {
+            Ok(receipts) => (receipts, None),
+            Err(e) => {
+                log::warn!("[parse_receipt_folder] {} failed: {}", files[index].display(), e);
+                (Vec::new(), Some(e.to_string()))
+            }
+        };
+        completed += 1;
+        on_progress(ReceiptFolderProgress { filename: filename(&files[index]), completed, total, error: error.clone() });
+        results[index] = Some(ReceiptFileResult { filename: filename(&files[index]), receipts, error });
+    }
+
+    results
+        .into_iter()
+        .zip(&files)
+        .map(|(result, path)| {
+            result.unwrap_or_else(|| ReceiptFileResult {
+                filename: filename(path),
+                receipts: Vec::new(),
+                error: Some("Parsing stopped unexpectedly".to_string()),
+            })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub async fn parse_statement_image(
+    app: AppHandle,
+    image_path: String,
+    categories: Vec<String>,
+    only_after_date: Option<String>,
+    period_start: Option<String>,
+    period_end: Option<String>,
+) -> Result<Vec<ExtractedTransaction>, YukiError> {
+    log::info!("[parse_statement_image] Starting for: {}", image_path);
+    validate_cutoff_date(only_after_date.as_deref())?;
+    let period = statement_period(period_start.as_deref(), period_end.as_deref())?;
+
+    let settings = get_settings(app).await?;
+
+    let provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+
+    log::info!("[parse_statement_image] Calling parse_statement_with_vision_llm...");
+
+    let result = llm::parse_statement_with_vision_llm(&provider, &image_path, &categories, only_after_date.as_deref(), period)
+        .await
+        .map_err(|e| {
+            log::error!("[parse_statement_image] LLM parsing failed: {}", e);
+            YukiError::llm(e)
+        })?;
+
+    log::info!("[parse_statement_image] SUCCESS: Got {} transactions, returning to frontend", result.len());
+
+    Ok(result)
+}
+
+/// `only_after_date` for the statement parsers: YYYY-MM-DD when given
+fn validate_cutoff_date(only_after_date: Option<&str>) -> Result<(), YukiError> {
+    if let Some(date) = only_after_date {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| YukiError::Validation(format!("Invalid only_after_date '{}', expected YYYY-MM-DD", date)))?;
+    }
+    Ok(())
+}
+
+/// The statement period (e.g. from detect_statement_metadata) the parsers fix
+/// years against. Both ends are needed; with either missing there is no period.
+fn statement_period<'a>(
+    period_start: Option<&'a str>,
+    period_end: Option<&'a str>,
+) -> Result<Option<(&'a str, &'a str)>, YukiError> {
+    let (Some(start), Some(end)) = (period_start, period_end) else {
+        return Ok(None);
+    };
+    let parse = |date: &str| {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| YukiError::Validation(format!("Invalid statement period date '{}', expected YYYY-MM-DD", date)))
+    };
+    if parse(start)? > parse(end)? {
+        return Err(YukiError::Validation(format!("Statement period {} to {} ends before it starts", start, end)));
+    }
+    Ok(Some((start, end)))
+}
+
+/// The period a text statement's row years are fixed against: `explicit` when
+/// the caller gave one, else the period printed in the header, else the span of
+/// the full dates printed anywhere in it. None when neither pins down a year.
+fn statement_period_from_text(explicit: Option<(&str, &str)>, text: &str) -> Option<(String, String)> {
+    if let Some((start, end)) = explicit {
+        return Some((start.to_string(), end.to_string()));
+    }
+    let metadata = statement_metadata_from_text(text);
+    if let (Some(start), Some(end)) = (metadata.period_start, metadata.period_end) {
+        if start <= end {
+            return Some((start, end));
+        }
+    }
+
+    let dates: Vec<chrono::NaiveDate> = STATEMENT_DATES
+        .find_iter(text)
+        .filter_map(|m| parse_statement_date(m.as_str()))
+        .filter_map(|date| chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok())
+        .collect();
+    let (start, end) = (dates.iter().min()?, dates.iter().max()?);
+    // An account opening date or a "customer since" line would stretch the span
+    // over years, and then it says nothing about the rows
+    ((*end - *start).num_days() <= 366)
+        .then(|| (start.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string()))
+}
+
+#[tauri::command]
+pub async fn parse_receipt_text(
+    app: AppHandle,
+    text: String,
+    categories: Vec<String>,
+) -> Result<ParsedReceipt, YukiError> {
+    let settings = get_settings(app).await?;
+
+    let provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+
+    llm::parse_receipt_text_with_llm(&provider, &text, &categories)
+        .await
+        .map_err(YukiError::llm)
+}
+
+/// Re-run transaction extraction on a stored document without re-uploading it,
+/// optionally with a different provider than the configured one. Nothing is saved.
+#[tauri::command]
+pub async fn reparse_document(
+    app: AppHandle,
+    document_id: String,
+    provider_override: Option<LLMProvider>,
+    only_after_date: Option<String>,
+    period_start: Option<String>,
+    period_end: Option<String>,
+) -> Result<Vec<ExtractedTransaction>, YukiError> {
+    validate_cutoff_date(only_after_date.as_deref())?;
+    let period = statement_period(period_start.as_deref(), period_end.as_deref())?;
+    let (document, text) = {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+        let document = document_by_id(&conn, &document_id)?;
+        let text = stored_document_text(&conn, &document)?;
+        (document, text)
+    };
+    let categories = get_category_names(app.clone()).await?;
+
+    let provider = match provider_override {
+        Some(provider) => provider,
+        None => get_settings(app)
+            .await?
+            .provider
+            .ok_or(YukiError::NoProvider)?,
+    };
+
+    log::info!(
+        "[reparse_document] Re-parsing {} ({}) with {} ({})",
+        document.filename,
+        document.filetype,
+        provider.name,
+        provider.provider_type
+    );
+    let transactions =
+        reparse_stored_document(&provider, &document, text, &categories, only_after_date.as_deref(), period).await?;
+    log::info!("[reparse_document] Extracted {} transactions", transactions.len());
+    Ok(transactions)
+}
+
+fn document_by_id(conn: &rusqlite::Connection, id: &str) -> Result<Document, YukiError> {
+    conn.query_row(
+        "SELECT id, filename, filepath, filetype, hash, uploaded_at FROM documents WHERE id = ?1",
+        [id],
+        |row| {
+            Ok(Document {
+                id: row.get(0)?,
+                filename: row.get(1)?,
+                filepath: row.get(2)?,
+                filetype: row.get(3)?,
+                hash: row.get(4)?,
+                uploaded_at: row.get(5)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => YukiError::NotFound(format!("Document '{}' not found", id)),
+        other => other.into(),
+    })
+}
+
+fn document_by_path(conn: &rusqlite::Connection, path: &str) -> Result<Option<Document>, YukiError> {
+    let id: Option<String> = match conn.query_row("SELECT id FROM documents WHERE filepath = ?1", [path], |row| row.get(0)) {
+        Ok(id) => Some(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+    id.map(|id| document_by_id(conn, &id)).transpose()
+}
+
+/// Text to parse for a stored document, or None when it has to go to vision
+/// (images and scanned PDFs)
+fn stored_document_text(conn: &rusqlite::Connection, document: &Document) -> Result<Option<String>, YukiError> {
+    match document.filetype.as_str() {
+        "application/pdf" => {
+            let extraction = cached_pdf_text(conn, document, pdf_extraction)?;
+            Ok((!extraction.is_scanned).then_some(extraction.text))
+        }
+        "text/plain" | "text/csv" => Ok(Some(fs::read_to_string(&document.filepath)?)),
+        t if t.starts_with("image/") => Ok(None),
+        other => Err(YukiError::Validation(format!("Cannot re-parse documents of type {}", other))),
+    }
+}
+
+/// Route a stored file to text or vision parsing the same way the upload flow does
+async fn reparse_stored_document(
+    provider: &LLMProvider,
+    document: &Document,
+    text: Option<String>,
+    categories: &[String],
+    only_after_date: Option<&str>,
+    period: Option<(&str, &str)>,
+) -> Result<Vec<ExtractedTransaction>, YukiError> {
+    match text {
+        Some(text) => {
+            let period = statement_period_from_text(period, &text);
+            let period = period.as_ref().map(|(start, end)| (start.as_str(), end.as_str()));
+            llm::parse_document_with_llm(provider, &text, categories, only_after_date, period).await
+        }
+        None => {
+            llm::parse_statement_with_vision_llm(provider, &document.filepath, categories, only_after_date, period).await
+        }
+    }
+    .map_err(YukiError::llm)
+}
+
+/// Vision-parse a stored PDF statement chunk by chunk, with each page range's
+/// transactions or error, so a failed or empty chunk can be retried with
+/// reparse_chunk. Nothing is saved.
+#[tauri::command]
+pub async fn parse_statement_chunks(
+    app: AppHandle,
+    document_id: String,
+    only_after_date: Option<String>,
+    period_start: Option<String>,
+    period_end: Option<String>,
+) -> Result<Vec<StatementChunk>, YukiError> {
+    validate_cutoff_date(only_after_date.as_deref())?;
+    let period = statement_period(period_start.as_deref(), period_end.as_deref())?;
+    let document = {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+        document_by_id(&conn, &document_id)?
+    };
+    stored_pdf_page_count(&document)?;
+    let categories = get_category_names(app.clone()).await?;
+    let provider = get_settings(app).await?.provider.ok_or(YukiError::NoProvider)?;
+
+    llm::parse_statement_chunks_with_vision_llm(
+        &provider,
+        &document.filepath,
+        &categories,
+        only_after_date.as_deref(),
+        period,
+    )
+    .await
+    .map_err(YukiError::llm)
+}
+
+/// Re-run pages `start_page`..=`end_page` of a stored PDF statement on their own,
+/// for the UI to merge in place of a chunk that failed. Nothing is saved.
+#[tauri::command]
+pub async fn reparse_chunk(
+    app: AppHandle,
+    document_id: String,
+    start_page: usize,
+    end_page: usize,
+    only_after_date: Option<String>,
+    period_start: Option<String>,
+    period_end: Option<String>,
+) -> Result<StatementChunk, YukiError> {
+    validate_cutoff_date(only_after_date.as_deref())?;
+    let period = statement_period(period_start.as_deref(), period_end.as_deref())?;
+    let document = {
+        let conn = database::get_connection(&app).map_err(YukiError::database)?;
+        document_by_id(&conn, &document_id)?
+    };
+    validate_page_range(start_page, end_page, stored_pdf_page_count(&document)?)?;
+    let categories = get_category_names(app.clone()).await?;
+    let provider = get_settings(app).await?.provider.ok_or(YukiError::NoProvider)?;
+
+    log::info!("[reparse_chunk] Re-parsing pages {}-{} of {}", start_page, end_page, document.filename);
+    let transactions = llm::parse_statement_pages_with_vision_llm(
+        &provider,
+        &document.filepath,
+        &categories,
+        start_page,
+        end_page,
+        only_after_date.as_deref(),
+        period,
+    )
+    .await
+    .map_err(YukiError::llm)?;
+    Ok(llm::statement_chunk(start_page, end_page, Ok(transactions)))
+}
+
+/// Pages in a stored document, which has to be a PDF
+fn stored_pdf_page_count(document: &Document) -> Result<usize, YukiError> {
+    if document.filetype != "application/pdf" {
+        return Err(YukiError::Validation(format!("'{}' is not a PDF statement", document.filename)));
+    }
+    let pdf = lopdf::Document::load_mem(&fs::read(&document.filepath)?)
+        .map_err(|e| YukiError::Validation(format!("Failed to read PDF '{}': {}", document.filename, e)))?;
+    Ok(pdf.get_pages().len())
+}
+
+/// 1-indexed, inclusive page range within a document of `page_count` pages
+fn validate_page_range(start_page: usize, end_page: usize, page_count: usize) -> Result<(), YukiError> {
+    if start_page == 0 || start_page > end_page || end_page > page_count {
+        return Err(YukiError::Validation(format!(
+            "Invalid page range {}-{} for a {}-page document",
+            start_page, end_page, page_count
+        )));
+    }
+    Ok(())
+}
+
+/// Embed texts with the configured provider, optionally using a separate embedding model
+#[tauri::command]
+pub async fn embed_texts(
+    app: AppHandle,
+    texts: Vec<String>,
+    model: Option<String>,
+) -> Result<Vec<Vec<f32>>, YukiError> {
+    let settings = get_settings(app).await?;
+
+    let mut provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+    if let Some(model) = model {
+        provider.model = model;
+    }
+
+    llm::embed_texts(&provider, texts)
+        .await
+        .map_err(YukiError::llm)
+}
+
+#[tauri::command]
+pub async fn detect_expense(app: AppHandle, message: String) -> Result<ExpenseDetectionResult, YukiError> {
+    let settings = get_settings(app).await?;
+
+    let provider = settings
+        .provider
+        .ok_or(YukiError::NoProvider)?;
+
+    llm::detect_expense_with_llm(&provider, &message)
+        .await
+        .map_err(YukiError::llm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        database::init_schema(&conn).unwrap();
+        conn
+    }
+
+    fn insert_txn(
+        conn: &Connection,
+        id: &str,
+        date: &str,
+        description: &str,
+        amount: f64,
+        category_id: &str,
+        merchant: Option<&str>,
+    ) {
+        conn.execute(
+            "INSERT INTO ledger (id, document_id, account_id, date, description, amount, currency, category_id, merchant, notes, source, created_at)
+             VALUES (?1, NULL, 'default', ?2, ?3, ?4, 'KES', ?5, ?6, NULL, 'manual', '2025-01-01T00:00:00Z')",
+            rusqlite::params![id, date, description, amount, category_id, merchant],
+        )
+        .unwrap();
+    }
+
+    fn category_of(conn: &Connection, id: &str) -> String {
+        conn.query_row("SELECT category_id FROM ledger WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn recategorize_updates_matching_merchants_only() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "a", "2025-01-03", "VISA-NETFLIX.COM 8841", -15.0, "other", Some("VISA-NETFLIX.COM 8841"));
+        insert_txn(&conn, "b", "2025-02-03", "Netflix monthly", -15.0, "other", None);
+        insert_txn(&conn, "c", "2025-02-04", "Spotify", -10.0, "other", Some("Spotify"));
+
+        let changed = recategorize_merchant_rows(&mut conn, "netflix", "subscriptions").unwrap();
+
+        assert_eq!(changed, 2);
+        assert_eq!(category_of(&conn, "a"), "subscriptions");
+        assert_eq!(category_of(&conn, "b"), "subscriptions");
+        assert_eq!(category_of(&conn, "c"), "other");
+    }
+
+    #[test]
+    fn recategorize_rejects_unknown_category() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "a", "2025-01-03", "Netflix", -15.0, "other", None);
+
+        assert!(recategorize_merchant_rows(&mut conn, "netflix", "no-such-category").is_err());
+        assert_eq!(category_of(&conn, "a"), "other");
+    }
+
+    #[test]
+    fn three_way_duplicate_group_collapses_to_one() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-01", "UBER *TRIP", -12.5, "transportation", None);
+        insert_txn(&conn, "b", "2025-03-01", "Uber trip", -12.5, "transportation", None);
+        insert_txn(&conn, "c", "2025-03-01", "uber  trip.", -12.5, "transportation", None);
+        insert_txn(&conn, "d", "2025-03-02", "Uber trip", -12.5, "transportation", None);
+        conn.execute(
+            "INSERT INTO purchased_items (id, receipt_id, ledger_id, name, quantity, total_price, purchased_at, created_at)
+             VALUES ('item', NULL, 'c', 'ride', 1, 12.5, '2025-03-01', '2025-03-01')",
+            [],
+        )
+        .unwrap();
+
+        let groups = duplicate_groups(&conn).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].ids, vec!["a", "b", "c"]);
+
+        let deleted = merge_duplicate_rows(&mut conn, "a", &["b".to_string(), "c".to_string()]).unwrap();
+        assert_eq!(deleted, 2);
+        assert!(duplicate_groups(&conn).unwrap().is_empty());
+
+        let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM ledger", [], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 2);
+        let item_ledger: String = conn
+            .query_row("SELECT ledger_id FROM purchased_items WHERE id = 'item'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(item_ledger, "a");
+    }
+
+    #[test]
+    fn ignored_duplicate_group_stops_being_reported() {
+        let conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-01", "Uber trip", -12.5, "transportation", None);
+        insert_txn(&conn, "b", "2025-03-01", "UBER TRIP", -12.5, "transportation", None);
+        insert_txn(&conn, "c", "2025-03-04", "Netflix", -15.0, "subscriptions", None);
+        insert_txn(&conn, "d", "2025-03-04", "NETFLIX", -15.0, "subscriptions", None);
+
+        let groups = duplicate_groups(&conn).unwrap();
+        assert_eq!(groups.len(), 2);
+        let uber = groups.iter().find(|g| g.ids == vec!["a", "b"]).unwrap();
+
+        insert_ignored_flag(&conn, "duplicate", &uber.key).unwrap();
+        // Ignoring twice is harmless
+        insert_ignored_flag(&conn, "duplicate", &uber.key).unwrap();
+
+        let groups = duplicate_groups(&conn).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].ids, vec!["c", "d"]);
+
+        assert!(insert_ignored_flag(&conn, "typo", "x").is_err());
+    }
+
+    #[test]
+    fn verify_documents_flags_mutated_and_missing_files() {
+        let conn = test_conn();
+        let dir = std::env::temp_dir().join(format!("yuki-verify-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let insert_doc = |id: &str, contents: Option<&[u8]>, stored: &[u8]| {
+            let path = dir.join(id);
+            if let Some(contents) = contents {
+                fs::write(&path, contents).unwrap();
+            }
+            let hash = {
+                use sha2::{Digest, Sha256};
+                hex::encode(Sha256::digest(stored))
+            };
+            conn.execute(
+                "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES (?1, ?1, ?2, 'application/pdf', ?3, ?1)",
+                [id, path.to_string_lossy().as_ref(), hash.as_str()],
+            )
+            .unwrap();
+        };
+        insert_doc("a-intact", Some(b"original"), b"original");
+        insert_doc("b-mutated", Some(b"tampered"), b"original");
+        insert_doc("c-missing", None, b"original");
+
+        let failed = unverified_documents(&conn).unwrap();
+        assert_eq!(failed, vec!["b-mutated".to_string(), "c-missing".to_string()]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn statement_metadata_from_text_header() {
+        let conn = test_conn();
+        conn.execute(
+            "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at) VALUES ('equity-savings', 'Savings', 'savings', 'Equity Bank', 'KES', 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+
+        let fixture = "EQUITY BANK (KENYA) LIMITED\n\
+            Moi Avenue Branch, Nairobi\n\
+            STATEMENT OF ACCOUNT - SAVINGS\n\
+            Account Name: JANE WANJIKU\n\
+            Account Number: 0170 2934 5678\n\
+            Statement Period: 01 Jan 2025 to 31 Jan 2025\n\
+            Date Description Debit Credit Balance\n\
+            02/01/2025 POS NAIVAS WESTLANDS 1,250.00 48,750.00\n";
+
+        let metadata = statement_metadata_from_text(fixture);
+        assert_eq!(metadata.institution.as_deref(), Some("EQUITY BANK (KENYA) LIMITED"));
+        assert_eq!(metadata.account_type.as_deref(), Some("savings"));
+        assert_eq!(metadata.account_last4.as_deref(), Some("5678"));
+        assert_eq!(metadata.period_start.as_deref(), Some("2025-01-01"));
+        assert_eq!(metadata.period_end.as_deref(), Some("2025-01-31"));
+
+        // The parsers fix row years against the printed period unless given one
+        let period = |explicit, text| statement_period_from_text(explicit, text);
+        let expected = |start: &str, end: &str| Some((start.to_string(), end.to_string()));
+        assert_eq!(period(None, fixture), expected("2025-01-01", "2025-01-31"));
+        assert_eq!(period(Some(("2024-12-15", "2025-01-14")), fixture), expected("2024-12-15", "2025-01-14"));
+        // Without a printed period, the full dates in it make the span
+        let no_period = "Opening balance 15/12/2024\n12/20 NAIVAS -2150.00\n01/05 UBER -640.00\nClosing balance 14/01/2025";
+        assert_eq!(period(None, no_period), expected("2024-12-15", "2025-01-14"));
+        assert_eq!(period(None, "Member since 01/03/2019\nClosing balance 14/01/2025"), None);
+        assert_eq!(period(None, "12/20 NAIVAS -2150.00"), None);
+
+        let suggested = suggest_account_for_institution(&conn, metadata.institution.as_deref().unwrap()).unwrap();
+        assert_eq!(suggested.as_deref(), Some("equity-savings"));
+    }
+
+    #[test]
+    fn credit_card_statement_is_inferred_as_credit() {
+        let fixture = "NCBA BANK KENYA PLC\n\
+            VISA CREDIT CARD STATEMENT\n\
+            Card Number: 4532 XXXX XXXX 9012\n\
+            Statement Period: 01 Feb 2025 to 28 Feb 2025\n\
+            Credit Limit: 150,000.00  Available Balance: 112,400.00\n\
+            Minimum Payment Due: 3,760.00  Payment Due Date: 20 Mar 2025\n\
+            05/02/2025 CARREFOUR TRM 4,300.00\n";
+        let metadata = statement_metadata_from_text(fixture);
+        assert_eq!(metadata.account_type.as_deref(), Some("credit"));
+        assert_eq!(metadata.account_last4.as_deref(), Some("9012"));
+
+        // No title, but only a card asks for a minimum payment
+        assert_eq!(infer_account_type("minimum payment due: 1,200.00"), Some("credit"));
+        assert_eq!(infer_account_type("available balance: 48,750.00"), Some("checking"));
+        assert_eq!(infer_account_type("statement of account"), None);
+    }
+
+    #[test]
+    fn weekday_buckets_follow_sqlite_sunday_zero() {
+        let conn = test_conn();
+        insert_txn(&conn, "sun", "2025-01-05", "Brunch", -30.0, "dining", None);
+        insert_txn(&conn, "fri1", "2025-01-03", "Bar", -20.0, "dining", None);
+        insert_txn(&conn, "fri2", "2025-01-10", "Cinema", -15.0, "entertainment", None);
+        insert_txn(&conn, "wed", "2025-01-08", "Salary", 1000.0, "income", None);
+
+        let buckets = spending_by_weekday(&conn, None, None).unwrap();
+        assert_eq!(buckets.len(), 7);
+        assert_eq!(buckets[0].label, "Sunday");
+        assert_eq!(buckets[0].total, 30.0);
+        assert_eq!(buckets[5].label, "Friday");
+        assert_eq!(buckets[5].total, 35.0);
+        assert_eq!(buckets[5].count, 2);
+        // Income is not spending
+        assert_eq!(buckets[3].count, 0);
+
+        let january_first_week = spending_by_weekday(&conn, Some("2025-01-01"), Some("2025-01-07")).unwrap();
+        assert_eq!(january_first_week[5].total, 20.0);
+    }
+
+    #[test]
+    fn json_import_maps_categories_and_skips_duplicates() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "existing", "2025-02-01", "NAIVAS WESTLANDS", -1250.0, "groceries", None);
+
+        let json = r#"[
+            {"date": "2025-02-01", "description": "Naivas Westlands", "amount": -1250.0, "currency": "KES", "category": "Groceries", "merchant": "Naivas"},
+            {"date": "2025-02-02", "description": "Uber trip", "amount": -640.5, "category": "Transportation"},
+            {"date": "2025-02-02", "description": "Uber trip", "amount": -640.5, "category": "Transportation"},
+            {"date": "2025-02-03", "description": "Mystery", "amount": -10.0, "currency": "usd", "category": "Crypto"}
+        ]"#;
+
+        let transactions = parse_transactions_json(json).unwrap();
+        let summary = import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &ImportOptions::default()).unwrap();
+        assert_eq!(summary.inserted, 2);
+        assert_eq!(summary.skipped_duplicates, 2);
+        assert_eq!(summary.category_unmatched, 1);
+
+        let (category, currency): (String, String) = conn
+            .query_row("SELECT category_id, currency FROM ledger WHERE description = 'Uber trip'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(category, "transportation");
+        assert_eq!(currency, "KES");
+        let (category, currency): (String, String) = conn
+            .query_row("SELECT category_id, currency FROM ledger WHERE description = 'Mystery'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(category, "other");
+        assert_eq!(currency, "USD");
+    }
+
+    #[test]
+    fn import_batch_rolls_back_only_its_own_rows() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "manual", "2025-02-01", "Rent", -30000.0, "housing", None);
+
+        let transactions = parse_transactions_json(
+            r#"[
+                {"date": "2025-02-02", "description": "Naivas", "amount": -1250.0, "category": "Groceries"},
+                {"date": "2025-02-03", "description": "Uber trip", "amount": -640.5, "category": "Transportation"}
+            ]"#,
+        )
+        .unwrap();
+        let summary = import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &ImportOptions::default()).unwrap();
+        let import_id = summary.import_id.unwrap();
+
+        let batches = import_batches(&conn).unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].id, import_id);
+        assert_eq!(batches[0].transaction_count, 2);
+
+        // Re-importing adds nothing, so it leaves no empty batch behind
+        let again = import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &ImportOptions::default()).unwrap();
+        assert_eq!(again.import_id, None);
+        assert_eq!(import_batches(&conn).unwrap().len(), 1);
+        let no_account = import_extracted_transactions(&mut conn, &transactions, "gone", None, "manual", &ImportOptions::default());
+        assert!(matches!(no_account, Err(YukiError::NotFound(_))));
+
+        assert_eq!(rollback_import_rows(&mut conn, &import_id).unwrap(), 2);
+        let remaining: Vec<String> = conn
+            .prepare("SELECT id FROM ledger")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(remaining, vec!["manual"]);
+        assert!(import_batches(&conn).unwrap().is_empty());
+        assert!(matches!(rollback_import_rows(&mut conn, &import_id), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn import_skips_rows_below_the_minimum_amount() {
+        let mut conn = test_conn();
+        let transactions = parse_transactions_json(
+            r#"[
+                {"date": "2025-03-01", "description": "Naivas", "amount": -2150.5, "category": "groceries"},
+                {"date": "2025-03-01", "description": "M-Pesa fee", "amount": -0.5, "category": "other"},
+                {"date": "2025-03-02", "description": "Rounding", "amount": 0.03, "category": "other"},
+                {"date": "2025-03-02", "description": "Java House", "amount": -900.0, "category": "dining"}
+            ]"#,
+        )
+        .unwrap();
+
+        // The saved setting applies when the import doesn't override it
+        conn.execute("INSERT INTO settings (key, value) VALUES ('import_min_amount', '1')", []).unwrap();
+        let summary =
+            import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &ImportOptions::default()).unwrap();
+        assert_eq!((summary.inserted, summary.below_threshold), (2, 2));
+        let descriptions: Vec<String> = conn
+            .prepare("SELECT description FROM ledger ORDER BY date, description")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(descriptions, vec!["Naivas", "Java House"]);
+
+        // A per-import override of 0 brings the small rows in
+        let everything = ImportOptions { min_amount: Some(0.0), ..Default::default() };
+        let summary = import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &everything).unwrap();
+        assert_eq!((summary.inserted, summary.skipped_duplicates, summary.below_threshold), (2, 2, 0));
+    }
+
+    #[test]
+    fn ofx_reimport_is_deduplicated_by_fitid() {
+        let mut conn = test_conn();
+        // Two identical coffees on one day are distinct transactions with their own FITIDs
+        let statement = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>KES<BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20250304<TRNAMT>-350.00<FITID>A1<NAME>Java House</STMTTRN>
+<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20250304<TRNAMT>-350.00<FITID>A2<NAME>Java House</STMTTRN>
+<STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20250325<TRNAMT>85000.00<FITID>A3<NAME>ACME LTD<MEMO>SALARY</STMTTRN>
+</BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+        let summary = import_ofx_text(&mut conn, statement, "default", None).unwrap();
+        assert_eq!(summary.inserted, 3);
+        assert_eq!(summary.skipped_duplicates, 0);
+
+        let again = import_ofx_text(&mut conn, statement, "default", None).unwrap();
+        assert_eq!(again.inserted, 0);
+        assert_eq!(again.skipped_duplicates, 3);
+
+        let ids: Vec<String> = conn
+            .prepare("SELECT external_id FROM ledger ORDER BY external_id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(ids, vec!["A1", "A2", "A3"]);
+        let salary: String = conn
+            .query_row("SELECT description FROM ledger WHERE external_id = 'A3'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(salary, "ACME LTD - SALARY");
+    }
+
+    #[test]
+    fn json_import_reports_position_of_malformed_entry() {
+        let json = "[\n  {\"date\": \"2025-02-01\", \"description\": \"Coffee\", \"amount\": -4.5},\n  {\"date\": \"2025-02-02\", \"description\": \"Tea\", \"amount\": \"three\"}\n]";
+
+        let err = parse_transactions_json(json).unwrap_err().to_string();
+        assert!(err.starts_with("Malformed transactions file"));
+        assert!(err.contains("line 3"), "{}", err);
+    }
+
+    #[test]
+    fn get_transaction_returns_entry_or_not_found() {
+        let conn = test_conn();
+        insert_txn(&conn, "t1", "2025-03-01", "Rent", -45000.0, "housing", Some("Landlord"));
+
+        let entry = transaction_by_id(&conn, "t1").unwrap();
+        assert_eq!(entry.description, "Rent");
+        assert_eq!(entry.merchant.as_deref(), Some("Landlord"));
+        assert_eq!(entry.account_id.as_deref(), Some("default"));
+
+        let err = transaction_by_id(&conn, "missing").unwrap_err();
+        assert!(matches!(err, YukiError::NotFound(_)));
+        assert_eq!(err.to_string(), "Transaction 'missing' not found");
+    }
+
+    #[test]
+    fn category_trend_pivots_and_zero_fills_months() {
+        let conn = test_conn();
+        insert_txn(&conn, "old", "2024-12-20", "Naivas", -5000.0, "groceries", None);
+        insert_txn(&conn, "jan1", "2025-01-10", "Naivas", -1000.0, "groceries", None);
+        insert_txn(&conn, "jan2", "2025-01-22", "Carrefour", -500.0, "groceries", None);
+        insert_txn(&conn, "jan3", "2025-01-15", "Uber", -300.0, "transportation", None);
+        insert_txn(&conn, "mar1", "2025-03-02", "Java House", -900.0, "dining", None);
+        insert_txn(&conn, "mar2", "2025-03-05", "Salary", 85000.0, "income", None);
+
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 3, 18).unwrap();
+        let trend = category_trend(&conn, 3, today).unwrap();
+
+        let months: Vec<&str> = trend.iter().map(|m| m.month.as_str()).collect();
+        assert_eq!(months, vec!["2025-01", "2025-02", "2025-03"]);
+        for month in &trend {
+            let keys: Vec<&str> = month.totals.keys().map(|k| k.as_str()).collect();
+            assert_eq!(keys, vec!["dining", "groceries", "transportation"]);
+        }
+        assert_eq!(trend[0].totals["groceries"], 1500.0);
+        assert_eq!(trend[0].totals["transportation"], 300.0);
+        assert_eq!(trend[0].totals["dining"], 0.0);
+        assert!(trend[1].totals.values().all(|v| *v == 0.0));
+        assert_eq!(trend[2].totals["dining"], 900.0);
+
+        assert!(category_trend(&conn, 0, today).is_err());
+    }
+
+    #[test]
+    fn subscription_price_increase_is_flagged_above_threshold() {
+        let conn = test_conn();
+        insert_txn(&conn, "n1", "2025-01-05", "NETFLIX.COM", -1000.0, "subscriptions", Some("Netflix"));
+        insert_txn(&conn, "n2", "2025-02-05", "NETFLIX.COM", -1000.0, "subscriptions", Some("Netflix"));
+        insert_txn(&conn, "n3", "2025-03-05", "NETFLIX.COM", -1000.0, "subscriptions", Some("Netflix"));
+        insert_txn(&conn, "n4", "2025-04-05", "NETFLIX.COM", -1200.0, "subscriptions", Some("Netflix"));
+        // Varying amounts are a habit, not a subscription
+        insert_txn(&conn, "j1", "2025-01-10", "Java House", -400.0, "dining", None);
+        insert_txn(&conn, "j2", "2025-02-10", "Java House", -900.0, "dining", None);
+        insert_txn(&conn, "j3", "2025-03-10", "Java House", -1500.0, "dining", None);
+
+        let flagged = price_increases(&conn, 10.0).unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].merchant, "Netflix");
+        assert_eq!(flagged[0].old_amount, 1000.0);
+        assert_eq!(flagged[0].new_amount, 1200.0);
+        assert!((flagged[0].percent_increase - 20.0).abs() < 1e-9);
+        assert_eq!(flagged[0].effective_date, "2025-04-05");
+
+        assert!(price_increases(&conn, 30.0).unwrap().is_empty());
+
+        // Once dismissed it stays dismissed
+        insert_ignored_flag(&conn, "anomaly", &flagged[0].key).unwrap();
+        assert!(price_increases(&conn, 10.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn merchants_rank_by_frequency_and_filter_by_prefix() {
+        let conn = test_conn();
+        insert_txn(&conn, "j1", "2025-01-03", "Coffee", -400.0, "dining", Some("Java House"));
+        insert_txn(&conn, "n1", "2025-01-04", "Groceries", -2000.0, "groceries", Some("POS NAIVAS 0231"));
+        insert_txn(&conn, "n2", "2025-01-11", "Groceries", -1800.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "n3", "2025-01-18", "Groceries", -2500.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "na", "2025-01-20", "Lunch", -700.0, "dining", Some("Nandos"));
+        insert_txn(&conn, "x1", "2025-01-21", "Transfer", -100.0, "other", None);
+
+        assert_eq!(merchant_names(&conn, None, 10).unwrap(), vec!["Naivas", "Java House", "Nandos"]);
+        assert_eq!(merchant_names(&conn, Some("Na"), 10).unwrap(), vec!["Naivas", "Nandos"]);
+        assert_eq!(merchant_names(&conn, Some("nand"), 10).unwrap(), vec!["Nandos"]);
+        assert_eq!(merchant_names(&conn, None, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn balance_as_of_sums_rows_through_the_cutoff() {
+        let conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "salary", "2025-06-01", "Salary", 85000.0, "income", None);
+        insert_txn(&conn, "rent", "2025-06-05", "Rent", -30000.0, "housing", None);
+        insert_txn(&conn, "usd", "2025-06-30", "Domain renewal", -10.0, "subscriptions", None);
+        conn.execute("UPDATE ledger SET currency = 'USD' WHERE id = 'usd'", []).unwrap();
+        insert_txn(&conn, "july", "2025-07-01", "Naivas", -2000.0, "groceries", None);
+        conn.execute("UPDATE ledger SET account_id = NULL WHERE id = 'rent'", []).unwrap();
+
+        let balance = balance_as_of(&conn, "default", "2025-06-30").unwrap();
+        assert_eq!(balance.balance, 85000.0 - 30000.0 - 1300.0);
+        assert_eq!(balance.transaction_count, 3);
+        assert_eq!(balance.currency, "KES");
+
+        assert_eq!(balance_as_of(&conn, "default", "2025-06-04").unwrap().balance, 85000.0);
+        assert_eq!(balance_as_of(&conn, "default", "2025-07-31").unwrap().transaction_count, 4);
+        assert!(matches!(balance_as_of(&conn, "nope", "2025-06-30"), Err(YukiError::NotFound(_))));
+        assert!(balance_as_of(&conn, "default", "30/06/2025").is_err());
+    }
+
+    #[test]
+    fn cash_flow_splits_inflow_and_outflow_per_month() {
+        let conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "salary", "2025-02-25", "Salary", 85000.0, "income", None);
+        insert_txn(&conn, "rent", "2025-02-05", "Rent", -30000.0, "housing", None);
+        insert_txn(&conn, "usd", "2025-02-12", "Domain renewal", -10.0, "subscriptions", None);
+        conn.execute("UPDATE ledger SET currency = 'USD' WHERE id = 'usd'", []).unwrap();
+        conn.execute("UPDATE ledger SET account_id = NULL WHERE id = 'rent'", []).unwrap();
+        insert_txn(&conn, "march", "2025-03-03", "Naivas", -2000.0, "groceries", None);
+        insert_txn(&conn, "old", "2024-11-03", "Naivas", -999.0, "groceries", None);
+
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 3, 18).unwrap();
+        let flow = cash_flow(&conn, "default", 3, today).unwrap();
+        let months: Vec<&str> = flow.iter().map(|m| m.month.as_str()).collect();
+        assert_eq!(months, vec!["2025-01", "2025-02", "2025-03"]);
+
+        assert_eq!((flow[0].inflow, flow[0].outflow, flow[0].net), (0.0, 0.0, 0.0));
+        assert_eq!(flow[1].inflow, 85000.0);
+        assert_eq!(flow[1].outflow, 31300.0);
+        assert_eq!(flow[1].net, 53700.0);
+        assert_eq!((flow[2].inflow, flow[2].outflow, flow[2].net), (0.0, 2000.0, -2000.0));
+
+        assert!(matches!(cash_flow(&conn, "nope", 3, today), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn merchant_stats_aggregate_repeat_visits() {
+        let conn = test_conn();
+        insert_txn(&conn, "n1", "2025-01-04", "POS NAIVAS 0231", -2000.0, "groceries", None);
+        insert_txn(&conn, "n2", "2025-01-18", "Naivas", -1000.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "n3", "2025-02-01", "Naivas Westlands", -3000.0, "groceries", Some("NAIVAS"));
+        insert_txn(&conn, "j1", "2025-01-10", "Java House", -900.0, "dining", None);
+        insert_txn(&conn, "refund", "2025-01-20", "Naivas refund", 500.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "late", "2025-03-01", "Naivas", -7000.0, "groceries", Some("Naivas"));
+
+        let stats = merchant_stats(&conn, Some("2025-01-01"), Some("2025-02-28"), 10).unwrap();
+        assert_eq!(stats.len(), 2);
+        let naivas = &stats[0];
+        assert_eq!(naivas.merchant, "POS NAIVAS 0231");
+        assert_eq!(naivas.visit_count, 3);
+        assert_eq!(naivas.total_spent, 6000.0);
+        assert_eq!(naivas.avg_per_visit, 2000.0);
+        assert_eq!(naivas.first_seen, "2025-01-04");
+        assert_eq!(naivas.last_seen, "2025-02-01");
+        assert_eq!(stats[1].merchant, "Java House");
+
+        assert_eq!(merchant_stats(&conn, None, None, 1).unwrap()[0].total_spent, 13000.0);
+    }
+
+    #[test]
+    fn savings_rate_is_null_without_income() {
+        let conn = test_conn();
+        insert_txn(&conn, "t1", "2025-03-01", "Salary", 100000.0, "income", None);
+        insert_txn(&conn, "t2", "2025-03-05", "Rent", -45000.0, "housing", None);
+        insert_txn(&conn, "t3", "2025-03-09", "Naivas", -15000.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "t4", "2025-04-02", "Java House", -1200.0, "dining", None);
+
+        let march = savings_rate(&conn, Some("2025-03-01"), Some("2025-03-31")).unwrap();
+        assert_eq!(march.income, 100000.0);
+        assert_eq!(march.expenses, 60000.0);
+        assert_eq!(march.savings, 40000.0);
+        assert_eq!(march.savings_rate_percent, Some(40.0));
+
+        // Spending with nothing coming in: negative savings, no rate
+        let april = savings_rate(&conn, Some("2025-04-01"), Some("2025-04-30")).unwrap();
+        assert_eq!(april.savings, -1200.0);
+        assert_eq!(april.savings_rate_percent, None);
+
+        let empty = savings_rate(&conn, Some("2026-01-01"), None).unwrap();
+        assert_eq!((empty.income, empty.expenses, empty.savings_rate_percent), (0.0, 0.0, None));
+    }
+
+    #[test]
+    fn year_over_year_compares_the_same_days_a_year_earlier() {
+        let conn = test_conn();
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 5, 20).unwrap();
+        insert_txn(&conn, "now", "2025-05-02", "Naivas", -3000.0, "groceries", None);
+        insert_txn(&conn, "apr", "2025-04-28", "Naivas", -900.0, "groceries", None);
+        // Exactly one year ago is the last day of the prior window
+        insert_txn(&conn, "ayearago", "2024-05-20", "Naivas", -2000.0, "groceries", None);
+        // Later in May last year than today is outside it
+        insert_txn(&conn, "late", "2024-05-21", "Naivas", -700.0, "groceries", None);
+        insert_txn(&conn, "salary", "2024-05-05", "Salary", 90000.0, "income", None);
+
+        let month = year_over_year(&conn, "month", today).unwrap();
+        assert_eq!((month.current_start.as_str(), month.current_end.as_str()), ("2025-05-01", "2025-05-20"));
+        assert_eq!((month.previous_start.as_str(), month.previous_end.as_str()), ("2024-05-01", "2024-05-20"));
+        assert_eq!(month.current_total, 3000.0);
+        assert_eq!(month.previous_total, 2000.0);
+        assert_eq!(month.percent_change, Some(50.0));
+
+        let quarter = year_over_year(&conn, "quarter", today).unwrap();
+        assert_eq!(quarter.current_start, "2025-04-01");
+        assert_eq!(quarter.current_total, 3900.0);
+        assert_eq!(quarter.previous_total, 2000.0);
+
+        assert!(year_over_year(&conn, "week", today).is_err());
+    }
+
+    #[test]
+    fn invalid_currencies_are_mapped_to_known_codes() {
+        let mut conn = test_conn();
+        for (id, currency) in [("dollar", "$"), ("blank", ""), ("lower", "eur"), ("shilling", "Ksh"), ("odd", "XYZ"), ("fine", "KES")] {
+            insert_txn(&conn, id, "2025-03-01", "Purchase", -100.0, "other", None);
+            conn.execute("UPDATE ledger SET currency = ?1 WHERE id = ?2", [currency, id]).unwrap();
+        }
+        conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES ('default_currency', 'GBP')", []).unwrap();
+
+        assert_eq!(fix_invalid_currency_rows(&mut conn).unwrap(), 4);
+        let currency = |id: &str| -> String {
+            conn.query_row("SELECT currency FROM ledger WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(currency("dollar"), "USD");
+        assert_eq!(currency("blank"), "GBP");
+        assert_eq!(currency("lower"), "EUR");
+        assert_eq!(currency("shilling"), "KES");
+        assert_eq!(currency("odd"), "XYZ");
+
+        assert_eq!(fix_invalid_currency_rows(&mut conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn implied_exchange_rate_reveals_an_fx_fee() {
+        let conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        // $20 charged as KSh 2,678 rather than 2,600: a 3% fee
+        insert_txn(&conn, "netflix", "2025-03-02", "Netflix", -2678.0, "subscriptions", Some("Netflix"));
+        insert_txn(&conn, "spotify", "2025-03-05", "Spotify", -1300.0, "subscriptions", Some("Spotify"));
+        insert_txn(&conn, "local", "2025-03-06", "Naivas", -900.0, "groceries", None);
+        conn.execute("UPDATE ledger SET original_amount = -20.0, original_currency = 'USD' WHERE id = 'netflix'", []).unwrap();
+        conn.execute("UPDATE ledger SET original_amount = -10.0, original_currency = 'USD' WHERE id = 'spotify'", []).unwrap();
+
+        let rates = effective_exchange_rates(&conn, 2.0).unwrap();
+        assert_eq!(rates.len(), 2);
+
+        let netflix = &rates[1];
+        assert_eq!(netflix.ledger_id, "netflix");
+        assert!((netflix.implied_rate - 133.9).abs() < 1e-9);
+        assert!((netflix.stored_rate - 130.0).abs() < 1e-9);
+        assert_eq!(netflix.deviation_percent, 3.0);
+        assert!(netflix.flagged);
+
+        let spotify = &rates[0];
+        assert_eq!(spotify.deviation_percent, 0.0);
+        assert!(!spotify.flagged);
+
+        assert!(!effective_exchange_rates(&conn, 5.0).unwrap()[1].flagged);
+    }
+
+    #[test]
+    fn transaction_rate_overrides_the_currency_rate() {
+        let conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "hotel", "2025-03-02", "Hotel", -10.0, "travel", None);
+        insert_txn(&conn, "taxi", "2025-03-03", "Taxi", -10.0, "transportation", None);
+        conn.execute("UPDATE ledger SET currency = 'USD'", []).unwrap();
+
+        update_transaction_rate(&conn, "hotel", Some(140.0)).unwrap();
+        assert_eq!(savings_rate(&conn, None, None).unwrap().expenses, 2700.0);
+        assert_eq!(balance_as_of(&conn, "default", "2025-03-31").unwrap().balance, -2700.0);
+
+        update_transaction_rate(&conn, "hotel", None).unwrap();
+        assert_eq!(savings_rate(&conn, None, None).unwrap().expenses, 2600.0);
+
+        assert!(matches!(update_transaction_rate(&conn, "hotel", Some(0.0)), Err(YukiError::Validation(_))));
+        assert!(matches!(update_transaction_rate(&conn, "missing", Some(140.0)), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn weekly_allowance_spreads_the_remaining_budget_over_the_month() {
+        let conn = test_conn();
+        save_category_budget(&conn, "groceries", Some(30000.0)).unwrap();
+        save_category_budget(&conn, "dining", Some(10000.0)).unwrap();
+        save_category_budget(&conn, "travel", Some(5000.0)).unwrap();
+        save_category_budget(&conn, "travel", None).unwrap();
+        assert!(matches!(save_category_budget(&conn, "pets", Some(100.0)), Err(YukiError::NotFound(_))));
+
+        insert_txn(&conn, "naivas", "2025-04-03", "Naivas", -12000.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "java", "2025-04-15", "Java House", -6500.0, "dining", None);
+        // Unbudgeted, last month and not yet happened: none of them count
+        insert_txn(&conn, "uber", "2025-04-10", "Uber", -5000.0, "transportation", None);
+        insert_txn(&conn, "march", "2025-03-28", "Naivas", -9000.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "later", "2025-04-20", "Naivas", -2000.0, "groceries", Some("Naivas"));
+
+        // April 16th: 15 days left counting today
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 4, 16).unwrap();
+        let allowance = weekly_allowance(&conn, today).unwrap();
+        assert_eq!(allowance.monthly_budget, 40000.0);
+        assert_eq!(allowance.spent_this_month, 18500.0);
+        assert_eq!(allowance.remaining, 21500.0);
+        assert_eq!(allowance.days_remaining, 15);
+        assert_eq!(allowance.per_week, 10033.33);
+        assert_eq!(allowance.today, 1433.33);
+
+        // Over budget on the last day of the year
+        insert_txn(&conn, "party", "2025-12-31", "Party", -50000.0, "dining", None);
+        let allowance = weekly_allowance(&conn, chrono::NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()).unwrap();
+        assert_eq!(allowance.days_remaining, 1);
+        assert_eq!(allowance.remaining, -10000.0);
+        assert_eq!(allowance.per_week, 0.0);
+    }
+
+    #[test]
+    fn amount_percentiles_match_a_hand_computed_set() {
+        let conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "a", "2025-03-01", "Naivas", -400.0, "groceries", None);
+        insert_txn(&conn, "b", "2025-03-02", "Naivas", -100.0, "groceries", None);
+        insert_txn(&conn, "c", "2025-03-03", "Carrefour", -1000.0, "groceries", None);
+        insert_txn(&conn, "d", "2025-03-04", "Quickmart", -300.0, "groceries", None);
+        insert_txn(&conn, "e", "2025-03-05", "Amazon", -200.0 / 130.0, "groceries", None);
+        conn.execute("UPDATE ledger SET currency = 'USD' WHERE id = 'e'", []).unwrap();
+        // Other categories, income and other months are left out when filtered
+        insert_txn(&conn, "rent", "2025-03-01", "Rent", -40000.0, "housing", None);
+        insert_txn(&conn, "refund", "2025-03-06", "Naivas refund", 100.0, "groceries", None);
+        insert_txn(&conn, "april", "2025-04-01", "Naivas", -5000.0, "groceries", None);
+
+        // Sorted: 100, 200, 300, 400, 1000
+        let groceries = amount_percentiles(&conn, Some("groceries"), Some("2025-03-01"), Some("2025-03-31")).unwrap();
+        assert_eq!(groceries.count, 5);
+        assert_eq!(groceries.median, 300.0);
+        assert_eq!(groceries.p75, 400.0);
+        // Rank 3.6: 400 + 0.6 * (1000 - 400)
+        assert_eq!(groceries.p90, 760.0);
+        assert_eq!((groceries.min, groceries.max), (100.0, 1000.0));
+
+        // An even count takes the mean of the middle pair
+        assert_eq!(percentile(&[100.0, 200.0, 300.0, 400.0], 50.0), 250.0);
+
+        let none = amount_percentiles(&conn, Some("travel"), None, None).unwrap();
+        assert_eq!((none.count, none.median, none.max), (0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn category_spending_is_a_share_of_income() {
+        let conn = test_conn();
+        insert_txn(&conn, "salary", "2025-03-25", "Salary", 90000.0, "income", None);
+        insert_txn(&conn, "side", "2025-03-28", "Consulting", 10000.0, "income", None);
+        insert_txn(&conn, "rent", "2025-03-01", "Rent", -40000.0, "housing", None);
+        insert_txn(&conn, "naivas", "2025-03-08", "Naivas", -9000.0, "groceries", None);
+        insert_txn(&conn, "carrefour", "2025-03-20", "Carrefour", -3500.0, "groceries", None);
+        insert_txn(&conn, "java", "2025-03-09", "Java House", -2250.0, "dining", None);
+        insert_txn(&conn, "april", "2025-04-01", "Rent", -40000.0, "housing", None);
+
+        let ratios = spending_to_income_ratios(&conn, Some("2025-03-01"), Some("2025-03-31")).unwrap();
+        let summary: Vec<(&str, f64, Option<f64>)> =
+            ratios.iter().map(|r| (r.category_id.as_str(), r.total, r.percent_of_income)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("housing", 40000.0, Some(40.0)),
+                ("groceries", 12500.0, Some(12.5)),
+                ("dining", 2250.0, Some(2.25)),
+            ]
+        );
+        assert_eq!(ratios[0].category_name, "Housing");
+
+        // No income in April: the spending is still listed, without a ratio
+        let april = spending_to_income_ratios(&conn, Some("2025-04-01"), Some("2025-04-30")).unwrap();
+        assert_eq!(april.len(), 1);
+        assert_eq!(april[0].percent_of_income, None);
+    }
+
+    #[test]
+    fn runway_divides_liquid_balance_by_recent_spending() {
+        let conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        conn.execute_batch(
+            "INSERT INTO accounts (id, name, account_type, currency, is_default, created_at) VALUES
+                ('savings', 'Savings', 'savings', 'USD', 0, '2025-01-01'),
+                ('card', 'Credit card', 'credit', 'KES', 0, '2025-01-01'),
+                ('stocks', 'Brokerage', 'investment', 'KES', 0, '2025-01-01');",
+        )
+        .unwrap();
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 5, 10).unwrap();
+        assert_eq!(financial_runway(&conn, today).unwrap().runway_months, None);
+
+        insert_txn(&conn, "salary", "2025-01-25", "Salary", 300000.0, "income", None);
+        insert_txn(&conn, "feb", "2025-02-05", "Rent", -60000.0, "housing", None);
+        insert_txn(&conn, "mar", "2025-03-05", "Rent", -40000.0, "housing", None);
+        insert_txn(&conn, "apr", "2025-04-05", "Rent", -50000.0, "housing", None);
+        // Too recent or too old for the average, but still part of the balance
+        insert_txn(&conn, "may", "2025-05-02", "Naivas", -20000.0, "groceries", None);
+        insert_txn(&conn, "jan", "2025-01-05", "Naivas", -10000.0, "groceries", None);
+        insert_txn(&conn, "usd", "2025-03-01", "Transfer in", 1000.0, "income", None);
+        conn.execute("UPDATE ledger SET account_id = 'savings', currency = 'USD' WHERE id = 'usd'", []).unwrap();
+        // Neither a credit card nor investments are spendable cash
+        insert_txn(&conn, "stocks", "2025-03-01", "Shares", 500000.0, "income", None);
+        conn.execute("UPDATE ledger SET account_id = 'stocks' WHERE id = 'stocks'", []).unwrap();
+        insert_txn(&conn, "card", "2025-04-10", "Card payment", -5000.0, "shopping", None);
+        conn.execute("UPDATE ledger SET account_id = 'card' WHERE id = 'card'", []).unwrap();
+
+        let runway = financial_runway(&conn, today).unwrap();
+        // Default 300,000 - 180,000 = 120,000, plus $1,000 at 130
+        assert_eq!(runway.liquid_balance, 250000.0);
+        assert_eq!(runway.account_count, 2);
+        // February to April: (60,000 + 40,000 + 55,000) / 3
+        assert_eq!(runway.avg_monthly_expenses, 51666.67);
+        assert_eq!(runway.runway_months, Some(4.8));
+    }
+
+    #[test]
+    fn budget_alerts_flag_overspend_and_projected_overspend() {
+        let conn = test_conn();
+        save_category_budget(&conn, "dining", Some(10000.0)).unwrap();
+        save_category_budget(&conn, "groceries", Some(30000.0)).unwrap();
+        save_category_budget(&conn, "transportation", Some(10000.0)).unwrap();
+        save_category_budget(&conn, "entertainment", Some(5000.0)).unwrap();
+
+        // Ten days into April (30 days)
+        insert_txn(&conn, "java", "2025-04-03", "Java House", -12000.0, "dining", None);
+        insert_txn(&conn, "naivas", "2025-04-08", "Naivas", -12000.0, "groceries", None);
+        insert_txn(&conn, "uber", "2025-04-09", "Uber", -2000.0, "transportation", None);
+        insert_txn(&conn, "imax", "2025-04-02", "IMAX", -1000.0, "entertainment", None);
+        insert_txn(&conn, "march", "2025-03-30", "Java House", -9000.0, "dining", None);
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 4, 10).unwrap();
+
+        let alerts = budget_alerts(&conn, today).unwrap();
+        let summary: Vec<(&str, &str, f64)> =
+            alerts.iter().map(|a| (a.category_id.as_str(), a.severity.as_str(), a.projected)).collect();
+        // Groceries: 12,000 in 10 days runs to 36,000; transport's 6,000 and
+        // entertainment's 3,000 fit their budgets
+        assert_eq!(summary, vec![("dining", "critical", 36000.0), ("groceries", "warning", 36000.0)]);
+        assert_eq!(alerts[0].percent_used, 120.0);
+        assert_eq!(alerts[0].category_name, "Dining");
+
+        // Too early in the month for the pace to mean much
+        insert_txn(&conn, "may", "2025-05-02", "Naivas", -25000.0, "groceries", None);
+        let early = budget_alerts(&conn, chrono::NaiveDate::from_ymd_opt(2025, 5, 2).unwrap()).unwrap();
+        let summary: Vec<(&str, &str)> = early.iter().map(|a| (a.category_id.as_str(), a.severity.as_str())).collect();
+        assert_eq!(summary, vec![("groceries", "info")]);
+    }
+
+    #[test]
+    fn skipped_monthly_charge_is_reported_missing() {
+        let conn = test_conn();
+        for (id, date) in [("n1", "2025-01-05"), ("n2", "2025-02-05"), ("n3", "2025-03-06"), ("n4", "2025-04-05")] {
+            insert_txn(&conn, id, date, "NETFLIX.COM", -1100.0, "subscriptions", Some("Netflix"));
+        }
+        for (id, date) in [("s1", "2025-02-10"), ("s2", "2025-03-10"), ("s3", "2025-04-10"), ("s4", "2025-05-10")] {
+            insert_txn(&conn, id, date, "Spotify", -300.0, "subscriptions", Some("Spotify"));
+        }
+        // Irregular gaps are shopping, not a subscription
+        for (id, date) in [("g1", "2025-01-03"), ("g2", "2025-01-20"), ("g3", "2025-03-01")] {
+            insert_txn(&conn, id, date, "Naivas", -4000.0, "groceries", None);
+        }
+
+        // Netflix should have charged around 5 May; Spotify's June charge is not due yet
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let missing = missing_recurring(&conn, today).unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].merchant, "Netflix");
+        assert_eq!(missing[0].expected_around, "2025-05-05");
+        assert_eq!(missing[0].days_overdue, 27);
+        assert_eq!(missing[0].typical_amount, 1100.0);
+
+        // Within the cadence's slack it is only late, not missing
+        let early = chrono::NaiveDate::from_ymd_opt(2025, 5, 7).unwrap();
+        assert!(missing_recurring(&conn, early).unwrap().is_empty());
+
+        // Dismissing the missed May charge hides it
+        insert_ignored_flag(&conn, "anomaly", &missing[0].key).unwrap();
+        assert!(missing_recurring(&conn, today).unwrap().is_empty());
+    }
+
+    #[test]
+    fn daily_spending_calendar_fills_every_day_of_the_year() {
+        let conn = test_conn();
+        insert_txn(&conn, "a", "2024-03-14", "Naivas", -1200.0, "groceries", None);
+        insert_txn(&conn, "b", "2024-03-14 18:30:00", "Java House", -800.0, "dining", None);
+        insert_txn(&conn, "salary", "2024-03-14", "Salary", 90000.0, "income", None);
+        insert_txn(&conn, "usd", "2024-12-31", "Domain renewal", -10.0, "subscriptions", None);
+        conn.execute("UPDATE ledger SET currency = 'USD' WHERE id = 'usd'", []).unwrap();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "other", "2025-01-01", "Naivas", -500.0, "groceries", None);
+
+        let calendar = daily_spending_calendar(&conn, 2024).unwrap();
+        // 2024 is a leap year
+        assert_eq!(calendar.len(), 366);
+        assert_eq!(calendar["2024-03-14"], 2000.0);
+        assert_eq!(calendar["2024-03-15"], 0.0);
+        assert_eq!(calendar["2024-02-29"], 0.0);
+        assert_eq!(calendar["2024-12-31"], 1300.0);
+        assert!(!calendar.contains_key("2025-01-01"));
+    }
+
+    #[test]
+    fn purchase_delays_a_savings_goal_at_the_current_rate() {
+        let conn = test_conn();
+        let goal = SavingsGoal {
+            name: "Emergency fund".to_string(),
+            target_amount: 200_000.0,
+            saved_amount: 80_000.0,
+            target_date: "2025-12-31".to_string(),
+        };
+        save_savings_goal(&conn, "emergency", Some(goal)).unwrap();
+
+        // Saving 36,500 a month, 1,200 a day. April's spending isn't a full month yet.
+        for month in ["01", "02", "03"] {
+            insert_txn(&conn, &format!("pay-{}", month), &format!("2025-{}-25", month), "Salary", 100_000.0, "income", None);
+            insert_txn(&conn, &format!("rent-{}", month), &format!("2025-{}-01", month), "Rent", -63_500.0, "housing", None);
+        }
+        insert_txn(&conn, "april", "2025-04-03", "Naivas", -50_000.0, "groceries", None);
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 4, 10).unwrap();
+
+        let impact = purchase_impact(&conn, 42_000.0, "emergency", today).unwrap();
+        assert_eq!(impact.monthly_savings, 36_500.0);
+        // 120,000 left takes 100 days; the purchase adds 35
+        assert_eq!(impact.projected_date.as_deref(), Some("2025-07-19"));
+        assert_eq!(impact.projected_date_with_purchase.as_deref(), Some("2025-08-23"));
+        assert_eq!(impact.delay_days, Some(35));
+        assert_eq!(impact.delay_weeks, Some(5.0));
+        assert!(impact.on_track && impact.on_track_with_purchase);
+
+        // A purchase big enough to miss the target date
+        let big = purchase_impact(&conn, 250_000.0, "emergency", today).unwrap();
+        assert!(!big.on_track_with_purchase);
+
+        assert!(matches!(purchase_impact(&conn, 1.0, "car", today), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn positive_dining_row_is_a_likely_sign_error() {
+        let conn = test_conn();
+        insert_txn(&conn, "lunch", "2025-03-04", "Lunch at Java House", 850.0, "dining", None);
+        insert_txn(&conn, "salary", "2025-03-25", "Salary", 90000.0, "income", None);
+        insert_txn(&conn, "bonus", "2025-03-26", "Bonus", -15000.0, "income", None);
+        // Money back from a shop is a refund, not a mistake
+        insert_txn(&conn, "shoes", "2025-03-02", "Bata", -4000.0, "shopping", Some("Bata"));
+        insert_txn(&conn, "return", "2025-03-09", "Bata", 4000.0, "shopping", Some("Bata"));
+        insert_txn(&conn, "reversal", "2025-03-10", "Card reversal", 300.0, "groceries", None);
+        insert_txn(&conn, "gift", "2025-03-12", "Birthday money", 2000.0, "gifts", None);
+
+        let flagged: Vec<(String, String)> = sign_errors(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.transaction.id, e.expected_sign))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![("lunch".to_string(), "negative".to_string()), ("bonus".to_string(), "positive".to_string())]
+        );
+
+        // A reviewed row is no longer flagged
+        insert_ignored_flag(&conn, "anomaly", "lunch").unwrap();
+        let flagged: Vec<String> = sign_errors(&conn).unwrap().into_iter().map(|e| e.transaction.id).collect();
+        assert_eq!(flagged, vec!["bonus"]);
+    }
+
+    #[test]
+    fn merchant_cadence_labels_monthly_and_irregular_series() {
+        let conn = test_conn();
+        for (id, date) in [("n1", "2025-01-05"), ("n2", "2025-02-04"), ("n3", "2025-03-06"), ("n4", "2025-04-05")] {
+            insert_txn(&conn, id, date, "NETFLIX.COM", -1100.0, "subscriptions", Some("Netflix"));
+        }
+        for (id, date) in [("c1", "2025-01-03"), ("c2", "2025-01-10"), ("c3", "2025-01-10"), ("c4", "2025-03-01")] {
+            insert_txn(&conn, id, date, "Carrefour Junction", -4000.0, "groceries", None);
+        }
+
+        let netflix = merchant_cadence(&conn, "netflix").unwrap();
+        assert_eq!(netflix.charge_days, 4);
+        assert_eq!(netflix.average_days, Some(30.0));
+        assert_eq!((netflix.min_days, netflix.max_days), (Some(30), Some(30)));
+        assert_eq!(netflix.frequency, "monthly");
+
+        // Two trips on 10 Jan are one charge day
+        let carrefour = merchant_cadence(&conn, "Carrefour").unwrap();
+        assert_eq!(carrefour.charge_days, 3);
+        assert_eq!((carrefour.min_days, carrefour.max_days), (Some(7), Some(50)));
+        assert_eq!(carrefour.average_days, Some(28.5));
+        assert_eq!(carrefour.frequency, "irregular");
+
+        assert_eq!(merchant_cadence(&conn, "spotify").unwrap().average_days, None);
+    }
+
+    #[test]
+    fn ledger_ndjson_has_one_entry_per_line() {
+        let conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-04", "Naivas", -1250.5, "groceries", Some("Naivas"));
+        insert_txn(&conn, "b", "2025-03-05", "Salary, \"March\"\nbonus incl.", 90000.0, "income", None);
+
+        let mut out = Vec::new();
+        assert_eq!(write_ledger_ndjson(&conn, &mut out).unwrap(), 2);
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let entries: Vec<ExportedLedgerEntry> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        assert_eq!(entries[0].entry.id, "a");
+        assert_eq!(entries[0].entry.amount, -1250.5);
+        assert_eq!(entries[0].entry.merchant.as_deref(), Some("Naivas"));
+        assert_eq!(entries[0].category_name.as_deref(), Some("Groceries"));
+        // Newlines inside a field stay escaped on their own line
+        assert_eq!(entries[1].entry.description, "Salary, \"March\"\nbonus incl.");
+        assert_eq!(entries[1].entry.account_id.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn spending_trend_direction_fits_monthly_totals() {
+        let series = |amounts: [f64; 4]| {
+            let conn = test_conn();
+            for (i, amount) in amounts.iter().enumerate() {
+                let date = format!("2025-0{}-10", i + 1);
+                insert_txn(&conn, &format!("rent-{}", i), &date, "Rent", -amount, "housing", None);
+            }
+            // The month in progress never counts
+            insert_txn(&conn, "may", "2025-05-02", "Naivas", -1.0, "groceries", None);
+            spending_trend_direction(&conn, 4, chrono::NaiveDate::from_ymd_opt(2025, 5, 15).unwrap()).unwrap()
+        };
+
+        let up = series([10000.0, 11000.0, 12000.0, 13000.0]);
+        assert_eq!(up.direction, "up");
+        assert_eq!(up.slope_per_month, 1000.0);
+        assert_eq!(up.r_squared, Some(1.0));
+        assert_eq!(up.average_monthly, 11500.0);
+        let labels: Vec<&str> = up.months.iter().map(|m| m.label.as_str()).collect();
+        assert_eq!(labels, vec!["2025-01", "2025-02", "2025-03", "2025-04"]);
+
+        let down = series([20000.0, 16000.0, 17000.0, 11000.0]);
+        assert_eq!(down.direction, "down");
+        assert_eq!(down.slope_per_month, -2600.0);
+        assert_eq!(down.r_squared, Some(0.805));
+
+        // Small wobbles around a steady level are flat
+        let flat = series([15000.0, 15100.0, 14900.0, 15050.0]);
+        assert_eq!(flat.direction, "flat");
+        assert_eq!(series([15000.0; 4]).r_squared, None);
+
+        // The error names the bounds of what was asked for, even at the extremes
+        let conn = test_conn();
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 5, 15).unwrap();
+        assert_eq!(spending_trend_direction(&conn, 119, today).unwrap().months.len(), 119);
+        for months in [0, 1, 120, u32::MAX] {
+            match spending_trend_direction(&conn, months, today) {
+                Err(YukiError::Validation(message)) => assert!(message.ends_with(&format!("got {}", months)), "{}", message),
+                other => panic!("{} months: {:?}", months, other.map(|t| t.direction)),
+            }
+        }
+    }
+
+    #[test]
+    fn spending_is_split_into_essential_and_discretionary() {
+        let conn = test_conn();
+        insert_txn(&conn, "rent", "2025-03-01", "Rent", -40000.0, "housing", None);
+        insert_txn(&conn, "kplc", "2025-03-03", "KPLC", -5000.0, "utilities", None);
+        insert_txn(&conn, "naivas", "2025-03-08", "Naivas", -15000.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "java", "2025-03-09", "Java House", -10000.0, "dining", None);
+        insert_txn(&conn, "imax", "2025-03-15", "IMAX", -5000.0, "entertainment", None);
+        insert_txn(&conn, "uber", "2025-03-16", "Uber", -25000.0, "transportation", None);
+        insert_txn(&conn, "salary", "2025-03-25", "Salary", 150000.0, "income", None);
+        insert_txn(&conn, "april", "2025-04-01", "Rent", -40000.0, "housing", None);
+
+        let march = spending_split(&conn, Some("2025-03-01"), Some("2025-03-31")).unwrap();
+        assert_eq!(march.total, 100000.0);
+        assert_eq!((march.essential_total, march.essential_percent), (60000.0, 60.0));
+        assert_eq!((march.discretionary_total, march.discretionary_percent), (15000.0, 15.0));
+        assert_eq!((march.unclassified_total, march.unclassified_percent), (25000.0, 25.0));
+
+        // Commuting counts as essential for this user; dining is neither
+        save_category_bucket(&conn, "transportation", Some("essential")).unwrap();
+        save_category_bucket(&conn, "dining", Some("")).unwrap();
+        let march = spending_split(&conn, Some("2025-03-01"), Some("2025-03-31")).unwrap();
+        assert_eq!(march.essential_total, 85000.0);
+        assert_eq!(march.discretionary_total, 5000.0);
+        assert_eq!(march.unclassified_total, 10000.0);
+
+        // None restores the default
+        save_category_bucket(&conn, "dining", None).unwrap();
+        assert_eq!(spending_split(&conn, Some("2025-03-01"), Some("2025-03-31")).unwrap().discretionary_total, 15000.0);
+        assert!(save_category_bucket(&conn, "dining", Some("fun")).is_err());
+
+        let empty = spending_split(&conn, Some("2026-01-01"), None).unwrap();
+        assert_eq!((empty.total, empty.essential_percent), (0.0, 0.0));
+    }
+
+    #[test]
+    fn snapshots_match_a_live_scan_and_are_invalidated_by_edits() {
+        let mut conn = test_conn();
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 4, 12).unwrap();
+        insert_txn(&conn, "jan", "2025-01-05", "Naivas", -2150.0, "groceries", None);
+        insert_txn(&conn, "feb1", "2025-02-03", "Java House", -900.0, "dining", None);
+        insert_txn(&conn, "feb2", "2025-02-20", "Carrefour", -1350.0, "groceries", None);
+        insert_txn(&conn, "salary", "2025-02-25", "Salary", 90000.0, "income", None);
+        insert_txn(&conn, "apr", "2025-04-02", "Uber", -450.0, "transportation", None);
+        let live = category_trend(&conn, 4, today).unwrap();
+
+        // January to March are closed; March is built even though nothing was spent
+        assert_eq!(build_month_snapshots(&mut conn, today).unwrap(), 3);
+        assert_eq!(build_month_snapshots(&mut conn, today).unwrap(), 0);
+        let snapshot_total: f64 = conn
+            .query_row("SELECT total FROM monthly_snapshots WHERE month = '2025-02' AND category_id = 'groceries'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(snapshot_total, 1350.0);
+
+        let from_snapshots = category_trend(&conn, 4, today).unwrap();
+        assert_eq!(
+            serde_json::to_value(&from_snapshots).unwrap(),
+            serde_json::to_value(&live).unwrap()
+        );
+
+        // Month comparisons and the runway read closed months from the snapshots too
+        assert_eq!(category_month_comparison(&conn, "groceries", "2025-02").unwrap().previous_total, 2150.0);
+        conn.execute("UPDATE monthly_snapshots SET total = 1400.0 WHERE month = '2025-02' AND category_id = 'groceries'", [])
+            .unwrap();
+        assert_eq!(category_month_comparison(&conn, "groceries", "2025-03").unwrap().previous_total, 1400.0);
+        assert_eq!(financial_runway(&conn, today).unwrap().avg_monthly_expenses, 1483.33);
+        conn.execute("UPDATE monthly_snapshots SET total = 1350.0 WHERE month = '2025-02' AND category_id = 'groceries'", [])
+            .unwrap();
+
+        // Editing a February row drops February's snapshot only
+        conn.execute("UPDATE ledger SET amount = -1500.0 WHERE id = 'feb2'", []).unwrap();
+        let built: Vec<String> = conn
+            .prepare("SELECT month FROM snapshot_months ORDER BY month")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(built, vec!["2025-01", "2025-03"]);
+        let february = &category_trend(&conn, 4, today).unwrap()[1];
+        assert_eq!(february.totals["groceries"], 1500.0);
+
+        assert_eq!(build_month_snapshots(&mut conn, today).unwrap(), 1);
+        assert_eq!(category_trend(&conn, 4, today).unwrap()[1].totals["groceries"], 1500.0);
+
+        // A database from before per-transaction rates has an update trigger that
+        // ignores them; starting up replaces it
+        conn.execute_batch(
+            "DROP TRIGGER ledger_update_invalidates_snapshot;
+             CREATE TRIGGER ledger_update_invalidates_snapshot
+                 AFTER UPDATE OF date, amount, currency, category_id ON ledger BEGIN
+                 DELETE FROM snapshot_months WHERE month IN (strftime('%Y-%m', OLD.date), strftime('%Y-%m', NEW.date));
+                 DELETE FROM monthly_snapshots WHERE month IN (strftime('%Y-%m', OLD.date), strftime('%Y-%m', NEW.date));
+             END;",
+        )
+        .unwrap();
+        database::init_schema(&conn).unwrap();
+        update_transaction_rate(&conn, "jan", Some(1.1)).unwrap();
+        let built_months = |conn: &Connection| conn.query_row("SELECT COUNT(*) FROM snapshot_months", [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(built_months(&conn), 2);
+
+        // A rate change affects every month
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        assert_eq!(built_months(&conn), 0);
+    }
+
+    #[test]
+    fn category_month_comparison_handles_zero_baseline() {
+        let conn = test_conn();
+        insert_txn(&conn, "jan1", "2025-01-10", "Naivas", -1000.0, "groceries", None);
+        insert_txn(&conn, "feb1", "2025-02-03", "Naivas", -800.0, "groceries", None);
+        insert_txn(&conn, "feb2", "2025-02-20", "Carrefour", -350.0, "groceries", None);
+        insert_txn(&conn, "feb3", "2025-02-21", "Java House", -500.0, "dining", None);
+
+        let feb = category_month_comparison(&conn, "groceries", "2025-02").unwrap();
+        assert_eq!(feb.previous_month, "2025-01");
+        assert_eq!(feb.current_total, 1150.0);
+        assert_eq!(feb.previous_total, 1000.0);
+        assert_eq!(feb.change, 150.0);
+        assert!((feb.percent_change.unwrap() - 15.0).abs() < 1e-9);
+
+        let jan = category_month_comparison(&conn, "groceries", "2025-01").unwrap();
+        assert_eq!(jan.previous_month, "2024-12");
+        assert_eq!(jan.previous_total, 0.0);
+        assert_eq!(jan.percent_change, None);
+
+        assert_eq!(percent_change(50.0, 200.0), Some(-75.0));
+        assert!(category_month_comparison(&conn, "groceries", "Feb 2025").is_err());
+    }
+
+    #[test]
+    fn note_and_document_can_be_added_after_insert() {
+        let conn = test_conn();
+        insert_txn(&conn, "t1", "2025-03-04", "Hardware store", -2300.0, "shopping", None);
+        conn.execute(
+            "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES ('r1', 'receipt.jpg', '/tmp/receipt.jpg', 'image/jpeg', 'abc', '2025-03-05')",
+            [],
+        )
+        .unwrap();
+
+        update_transaction_note(&conn, "t1", Some("Shelf brackets for the study")).unwrap();
+        link_document_to_transaction(&conn, "t1", "r1").unwrap();
+
+        let entry = transaction_by_id(&conn, "t1").unwrap();
+        assert_eq!(entry.notes.as_deref(), Some("Shelf brackets for the study"));
+        assert_eq!(entry.document_id.as_deref(), Some("r1"));
+
+        update_transaction_note(&conn, "t1", Some("  ")).unwrap();
+        assert_eq!(transaction_by_id(&conn, "t1").unwrap().notes, None);
+
+        assert!(update_transaction_note(&conn, "missing", Some("x")).is_err());
+        assert_eq!(
+            link_document_to_transaction(&conn, "t1", "nope").unwrap_err().to_string(),
+            "Document 'nope' not found"
+        );
+        assert!(matches!(link_document_to_transaction(&conn, "missing", "r1"), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn a_failed_row_saves_none_of_its_batch() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "t1", "2025-03-04", "Naivas", -1200.0, "groceries", None);
+        let existing = transaction_by_id(&conn, "t1").unwrap();
+        let new_entry = LedgerEntry { id: "b1".to_string(), description: "Java House".to_string(), ..existing.clone() };
+
+        // The second row reuses an id that's already taken
+        assert!(insert_ledger_entries(&mut conn, &[new_entry.clone(), existing]).is_err());
+        assert!(transaction_by_id(&conn, "b1").is_err());
+        assert!(import_batches(&conn).unwrap().is_empty());
+
+        insert_ledger_entries(&mut conn, &[new_entry]).unwrap();
+        assert_eq!(import_batches(&conn).unwrap()[0].transaction_count, 1);
+    }
+
+    #[test]
+    fn deleting_an_attached_document_keeps_the_transaction() {
+        let mut conn = test_conn();
+        for (id, filename) in [("r1", "receipt.jpg"), ("s1", "statement.pdf")] {
+            conn.execute(
+                "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES (?1, ?2, ?2, 'image/jpeg', ?1, '2025-03-05')",
+                [id, filename],
+            )
+            .unwrap();
+        }
+        insert_txn(&conn, "t1", "2025-03-04", "Hardware store", -2300.0, "shopping", None);
+        insert_txn(&conn, "imported", "2025-03-06", "Naivas", -1200.0, "groceries", None);
+        conn.execute("UPDATE ledger SET document_id = 's1', source = 'document' WHERE id = 'imported'", []).unwrap();
+        link_document_to_transaction(&conn, "t1", "r1").unwrap();
+        // Attaching the statement a row came from doesn't make it an attachment
+        link_document_to_transaction(&conn, "imported", "s1").unwrap();
+
+        assert_eq!(delete_document_rows(&mut conn, "r1").unwrap().as_deref(), Some("receipt.jpg"));
+        assert_eq!(transaction_by_id(&conn, "t1").unwrap().document_id, None);
+
+        delete_document_rows(&mut conn, "s1").unwrap();
+        assert!(transaction_by_id(&conn, "imported").is_err());
+    }
+
+    #[test]
+    fn regenerate_replaces_assistant_turn_without_duplicating_question() {
+        let conn = test_conn();
+        conn.execute(
+            "INSERT INTO conversation_sessions (id, created_at, updated_at) VALUES ('s1', '2025-01-01T00:00:00Z', '2025-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        let add = |id: &str, role: &str, content: &str, at: &str| {
+            conn.execute(
+                "INSERT INTO conversation_messages (id, session_id, role, content, created_at) VALUES (?1, 's1', ?2, ?3, ?4)",
+                [id, role, content, at],
+            )
+            .unwrap();
+        };
+        add("m1", "user", "How much on groceries?", "2025-01-01T00:00:01Z");
+        add("m2", "assistant", "KES 12,000", "2025-01-01T00:00:02Z");
+        add("m3", "user", "And dining?", "2025-01-01T00:00:03Z");
+        add("m4", "assistant", "A bad answer", "2025-01-01T00:00:04Z");
+
+        let exchange = last_exchange(&conn, "s1", 10).unwrap();
+        assert_eq!(exchange.question, "And dining?");
+        assert_eq!(exchange.history.len(), 2);
+        assert_eq!(exchange.history[1].content, "KES 12,000");
+        assert_eq!(exchange.stale_answer_ids, vec!["m4".to_string()]);
+
+        // What regenerate_last_response does around answer_question
+        add("m5", "assistant", "KES 4,500 on dining", "2025-01-01T00:00:05Z");
+        delete_messages(&conn, &exchange.stale_answer_ids).unwrap();
+
+        let turns: Vec<(String, String)> = conn
+            .prepare("SELECT role, content FROM conversation_messages WHERE session_id = 's1' ORDER BY created_at")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let user_turns = turns.iter().filter(|(role, _)| role == "user").count();
+        assert_eq!(user_turns, 2);
+        assert_eq!(turns.last().unwrap().1, "KES 4,500 on dining");
+        assert_eq!(turns.len(), 4);
+    }
+
+    #[test]
+    fn session_messages_are_chronological_and_paged() {
+        let conn = test_conn();
+        conn.execute(
+            "INSERT INTO conversation_sessions (id, created_at, updated_at) VALUES ('past', '2025-01-01T00:00:00Z', '2025-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        // Inserted out of order; created_at decides
+        for (id, role, content, at) in [
+            ("m3", "user", "And dining?", "2025-01-01T00:00:03Z"),
+            ("m1", "user", "How much on groceries?", "2025-01-01T00:00:01Z"),
+            ("m4", "assistant", "KES 4,500", "2025-01-01T00:00:04Z"),
+            ("m2", "assistant", "KES 12,000", "2025-01-01T00:00:02Z"),
+        ] {
+            conn.execute(
+                "INSERT INTO conversation_messages (id, session_id, role, content, created_at) VALUES (?1, 'past', ?2, ?3, ?4)",
+                [id, role, content, at],
+            )
+            .unwrap();
+        }
+
+        let all = session_messages(&conn, "past", None, 0).unwrap();
+        let contents: Vec<&str> = all.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["How much on groceries?", "KES 12,000", "And dining?", "KES 4,500"]);
+        assert_eq!(all[0].created_at.as_deref(), Some("2025-01-01T00:00:01Z"));
+
+        let page = session_messages(&conn, "past", Some(2), 1).unwrap();
+        let contents: Vec<&str> = page.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["KES 12,000", "And dining?"]);
+        assert!(session_messages(&conn, "past", Some(2), 4).unwrap().is_empty());
+
+        assert!(matches!(session_messages(&conn, "nope", None, 0), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn discrepant_import_is_flagged_until_reviewed() {
+        let mut conn = test_conn();
+        let statement = parse_transactions_json(
+            r#"[
+                {"date": "2025-03-01", "description": "Naivas", "amount": -2150.0, "category": "Groceries"},
+                {"date": "2025-03-02", "description": "Salary", "amount": 50000.0, "category": "Income"}
+            ]"#,
+        )
+        .unwrap();
+
+        // The statement's net change is 47,000 but the extracted rows only add up to 47,850
+        let options = ImportOptions { expected_total: Some(47000.0), ..Default::default() };
+        let summary = import_extracted_transactions(&mut conn, &statement, "default", None, "document", &options).unwrap();
+        assert_eq!((summary.inserted, summary.flagged_for_review), (2, 2));
+
+        // A reconciled import with one row the LLM wasn't sure about flags just that row
+        let next = parse_transactions_json(
+            r#"[
+                {"date": "2025-04-01", "description": "Java House", "amount": -900.0, "category": "Dining", "needs_review": true},
+                {"date": "2025-04-02", "description": "Uber", "amount": -450.0, "category": "Transportation"}
+            ]"#,
+        )
+        .unwrap();
+        let options = ImportOptions { expected_total: Some(-1350.0), ..Default::default() };
+        let summary = import_extracted_transactions(&mut conn, &next, "default", None, "document", &options).unwrap();
+        assert_eq!(summary.flagged_for_review, 1);
+
+        let flagged = transactions_needing_review(&conn).unwrap();
+        let descriptions: Vec<&str> = flagged.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["Java House", "Salary", "Naivas"]);
+        assert!(flagged.iter().all(|e| e.needs_review == Some(true)));
+
+        for entry in &flagged {
+            clear_review_flag(&conn, &entry.id).unwrap();
+        }
+        assert!(transactions_needing_review(&conn).unwrap().is_empty());
+        assert!(matches!(clear_review_flag(&conn, "missing"), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn statement_balances_supply_the_expected_total() {
+        let mut conn = test_conn();
+        // Newest-first with a running balance: 10,000 opening, 57,850 closing
+        let complete = parse_transactions_json(
+            r#"[
+                {"date": "2025-03-02", "description": "Salary", "amount": 50000.0, "category": "Income", "balance": 57850.0},
+                {"date": "2025-03-01", "description": "Naivas", "amount": -2150.0, "category": "Groceries", "balance": 7850.0}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(statement_total_from_balances(&complete), Some(47850.0));
+        let summary = import_extracted_transactions(&mut conn, &complete, "default", None, "document", &ImportOptions::default()).unwrap();
+        assert_eq!((summary.inserted, summary.flagged_for_review), (2, 0));
+
+        // Oldest-first, with the middle row missing from the extraction
+        let gappy = parse_transactions_json(
+            r#"[
+                {"date": "2025-04-01", "description": "Java House", "amount": -900.0, "category": "Dining", "balance": 56950.0},
+                {"date": "2025-04-09", "description": "Uber", "amount": -450.0, "category": "Transportation", "balance": 55000.0}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(statement_total_from_balances(&gappy), Some(-2850.0));
+        let summary = import_extracted_transactions(&mut conn, &gappy, "default", None, "document", &ImportOptions::default()).unwrap();
+        assert_eq!(summary.flagged_for_review, 2);
+
+        // An explicit total overrides the balances
+        let mut may = gappy.clone();
+        may.iter_mut().for_each(|t| t.date = t.date.replace("-04-", "-05-"));
+        let options = ImportOptions { expected_total: Some(-1350.0), ..Default::default() };
+        let summary = import_extracted_transactions(&mut conn, &may, "default", None, "document", &options).unwrap();
+        assert_eq!((summary.inserted, summary.flagged_for_review), (2, 0));
+
+        may.iter_mut().for_each(|t| t.balance = None);
+        assert_eq!(statement_total_from_balances(&may), None);
+    }
+
+    #[test]
+    fn converted_import_keeps_original_amount_and_currency() {
+        let mut conn = test_conn();
+        conn.execute(
+            "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at) VALUES ('usd-card', 'USD Card', 'credit', NULL, 'USD', 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+        // KES per unit: 1 USD = 130 KES and 1 EUR = 140 KES, so 100 EUR is 107.69 USD
+        conn.execute_batch(
+            "UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD';
+             UPDATE currencies SET conversion_rate = 140.0 WHERE code = 'EUR';",
+        )
+        .unwrap();
+        let transactions = parse_transactions_json(
+            r#"[{"date": "2025-04-02", "description": "Hotel Berlin", "amount": -100.0, "currency": "EUR", "category": "Travel"}]"#,
+        )
+        .unwrap();
+
+        let converted = ImportOptions { convert_to_account_currency: true, ..Default::default() };
+        let summary = import_extracted_transactions(&mut conn, &transactions, "usd-card", None, "manual", &converted).unwrap();
+        assert_eq!(summary.inserted, 1);
+
+        let (amount, currency, original_amount, original_currency): (f64, String, Option<f64>, Option<String>) = conn
+            .query_row(
+                "SELECT amount, currency, original_amount, original_currency FROM ledger WHERE description = 'Hotel Berlin'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(currency, "USD");
+        assert_eq!(amount, -107.69);
+        assert_eq!(original_amount, Some(-100.0));
+        assert_eq!(original_currency.as_deref(), Some("EUR"));
+
+        // Re-importing the same statement is a duplicate even though the stored amount changed
+        let again = import_extracted_transactions(&mut conn, &transactions, "usd-card", None, "manual", &converted).unwrap();
+        assert_eq!(again.skipped_duplicates, 1);
+    }
+
+    #[test]
+    fn top_transactions_rank_by_primary_currency_amount() {
+        let conn = test_conn();
+        // Make 1 USD worth 130 KES so a small USD figure outranks a larger KES one
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "kes-big", "2025-05-01", "Rent share", -5000.0, "housing", None);
+        insert_txn(&conn, "usd-small", "2025-05-02", "Flight", -60.0, "travel", None);
+        conn.execute("UPDATE ledger SET currency = 'USD' WHERE id = 'usd-small'", []).unwrap();
+        insert_txn(&conn, "salary", "2025-05-03", "Salary", 90000.0, "income", None);
+        insert_txn(&conn, "april", "2025-04-30", "Old", -99999.0, "other", None);
+
+        let expenses = top_transactions(&conn, Some("2025-05-01"), Some("2025-05-31"), 10, Some("expense")).unwrap();
+        let ids: Vec<&str> = expenses.iter().map(|t| t.entry.id.as_str()).collect();
+        assert_eq!(ids, vec!["usd-small", "kes-big"]);
+        assert_eq!(expenses[0].amount_in_primary, -7800.0);
+        assert_eq!(expenses[0].entry.amount, -60.0);
+
+        let both = top_transactions(&conn, Some("2025-05-01"), None, 1, None).unwrap();
+        assert_eq!(both[0].entry.id, "salary");
+
+        assert!(top_transactions(&conn, None, None, 5, Some("refunds")).is_err());
+    }
+
+    #[test]
+    fn diagnostics_flag_missing_provider_only() {
+        let conn = test_conn();
+
+        let report = diagnostics_report(local_diagnostics(&conn));
+        assert_eq!(report.status, "fail");
+        for check in &report.checks {
+            let expected = if check.name == "provider" { "fail" } else { "pass" };
+            assert_eq!(check.status, expected, "{}: {}", check.name, check.message);
+        }
+        assert_eq!(report.checks.len(), 5);
+
+        // Any table or column a migration adds counts, not just the newest one
+        conn.execute("ALTER TABLE ledger DROP COLUMN exchange_rate", []).unwrap();
+        conn.execute("DROP TABLE ignored_flags", []).unwrap();
+        let database = local_diagnostics(&conn).into_iter().find(|c| c.name == "database").unwrap();
+        assert_eq!(database.status, "warn");
+        assert!(database.message.contains("ignored_flags, ledger.exchange_rate"), "{}", database.message);
+    }
+
+    #[test]
+    fn backfill_sets_only_null_account_ids() {
+        let conn = test_conn();
+        insert_txn(&conn, "legacy1", "2024-11-02", "Naivas", -1200.0, "groceries", None);
+        insert_txn(&conn, "legacy2", "2024-11-09", "Uber", -600.0, "transportation", None);
+        insert_txn(&conn, "mpesa", "2025-01-04", "Java House", -800.0, "dining", None);
+        conn.execute("UPDATE ledger SET account_id = NULL WHERE id LIKE 'legacy%'", []).unwrap();
+        conn.execute(
+            "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at)
+             VALUES ('mpesa', 'M-Pesa', 'mobile_money', 'Safaricom', 'KES', 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+        conn.execute("UPDATE ledger SET account_id = 'mpesa' WHERE id = 'mpesa'", []).unwrap();
+
+        assert_eq!(backfill_null_account_ids(&conn).unwrap(), 2);
+        let accounts: Vec<(String, String)> = conn
+            .prepare("SELECT id, account_id FROM ledger ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                ("legacy1".to_string(), "default".to_string()),
+                ("legacy2".to_string(), "default".to_string()),
+                ("mpesa".to_string(), "mpesa".to_string()),
+            ]
+        );
+
+        // Nothing left to do the second time
+        assert_eq!(backfill_null_account_ids(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn recurring_template_posts_once_per_cycle() {
+        let mut conn = test_conn();
+        let template = |id: &str, merchant: &str, amount: f64, day_of_month: u32| RecurringTemplate {
+            id: id.to_string(),
+            merchant: merchant.to_string(),
+            amount,
+            category_id: "housing".to_string(),
+            account_id: "default".to_string(),
+            day_of_month,
+            active: true,
+            last_posted_cycle: None,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        insert_recurring_template(&conn, &template("rent", "Landlord", -45000.0, 1)).unwrap();
+        insert_recurring_template(&conn, &template("gym", "Gym", -3000.0, 31)).unwrap();
+        insert_recurring_template(&conn, &template("paused", "Netflix", -1100.0, 1)).unwrap();
+        conn.execute("UPDATE recurring_templates SET active = 0 WHERE id = 'paused'", []).unwrap();
+        assert!(insert_recurring_template(&conn, &template("bad", "Water", -500.0, 0)).is_err());
+        let ledger_dates = |conn: &Connection| -> Vec<(String, String)> {
+            conn.prepare("SELECT merchant, date FROM ledger WHERE source = 'recurring' ORDER BY date, merchant")
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect()
+        };
+
+        // Mid-February: rent is due, the gym (31st, so the 28th) isn't yet
+        let feb_10 = chrono::NaiveDate::from_ymd_opt(2025, 2, 10).unwrap();
+        assert_eq!(post_due_recurring_rows(&mut conn, feb_10).unwrap().posted, 1);
+        assert_eq!(post_due_recurring_rows(&mut conn, feb_10).unwrap().posted, 0);
+
+        let feb_28 = chrono::NaiveDate::from_ymd_opt(2025, 2, 28).unwrap();
+        assert_eq!(post_due_recurring_rows(&mut conn, feb_28).unwrap().posted, 1);
+        assert_eq!(post_due_recurring_rows(&mut conn, feb_28).unwrap().posted, 0);
+        assert_eq!(
+            ledger_dates(&conn),
+            vec![
+                ("Landlord".to_string(), "2025-02-01".to_string()),
+                ("Gym".to_string(), "2025-02-28".to_string()),
+            ]
+        );
+
+        // A new month is a new cycle
+        let mar_1 = chrono::NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        assert_eq!(post_due_recurring_rows(&mut conn, mar_1).unwrap().posted, 1);
+        assert_eq!(ledger_dates(&conn).len(), 3);
+        let rent = recurring_templates(&conn).unwrap().into_iter().find(|t| t.id == "rent").unwrap();
+        assert_eq!(rent.last_posted_cycle.as_deref(), Some("2025-03"));
+
+        // A template whose account is gone posts nothing and is reported, and
+        // keeps its cycle open for when the account is fixed
+        conn.execute(
+            "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at) VALUES ('closed', 'Closed', 'checking', NULL, 'KES', 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+        insert_recurring_template(
+            &conn,
+            &RecurringTemplate { account_id: "closed".to_string(), ..template("power", "KPLC", -2500.0, 1) },
+        )
+        .unwrap();
+        // Leave the template pointing at a removed account
+        conn.execute_batch("PRAGMA foreign_keys = OFF; DELETE FROM accounts WHERE id = 'closed'; PRAGMA foreign_keys = ON;")
+            .unwrap();
+        let summary = post_due_recurring_rows(&mut conn, mar_1).unwrap();
+        assert_eq!(summary.posted, 0);
+        assert_eq!(summary.skipped.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), vec!["power"]);
+        assert_eq!(ledger_dates(&conn).len(), 3);
+        let power = recurring_templates(&conn).unwrap().into_iter().find(|t| t.id == "power").unwrap();
+        assert_eq!(power.last_posted_cycle, None);
+
+        conn.execute("UPDATE recurring_templates SET account_id = 'default' WHERE id = 'power'", []).unwrap();
+        let summary = post_due_recurring_rows(&mut conn, mar_1).unwrap();
+        assert_eq!((summary.posted, summary.skipped.len()), (1, 0));
+    }
+
+    #[test]
+    fn uncategorized_queue_and_bulk_categorize() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "o1", "2025-03-01", "POS 44821", -350.0, "other", None);
+        insert_txn(&conn, "o2", "2025-03-04", "POS 44822", -1200.0, "other", None);
+        insert_txn(&conn, "o3", "2025-03-09", "MPESA 7781", -90.0, "other", None);
+        insert_txn(&conn, "g1", "2025-03-05", "Naivas", -2150.0, "groceries", Some("Naivas"));
+
+        let queue: Vec<String> = uncategorized_transactions(&conn, 50, 0).unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(queue, vec!["o3", "o2", "o1"]);
+        let page: Vec<String> = uncategorized_transactions(&conn, 1, 1).unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(page, vec!["o2"]);
+
+        assert_eq!(categorize_rows(&mut conn, &["o1".to_string(), "o2".to_string(), "o1".to_string()], "dining").unwrap(), 2);
+        let queue: Vec<String> = uncategorized_transactions(&conn, 50, 0).unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(queue, vec!["o3"]);
+        assert_eq!(transaction_by_id(&conn, "o2").unwrap().category_id, "dining");
+
+        // One bad id leaves every row as it was
+        assert!(matches!(
+            categorize_rows(&mut conn, &["o3".to_string(), "missing".to_string()], "shopping"),
+            Err(YukiError::NotFound(_))
+        ));
+        assert_eq!(transaction_by_id(&conn, "o3").unwrap().category_id, "other");
+        assert!(categorize_rows(&mut conn, &["o3".to_string()], "no-such-category").is_err());
+    }
+
+    #[test]
+    fn reassign_account_moves_all_or_a_date_bounded_subset() {
+        let mut conn = test_conn();
+        conn.execute(
+            "INSERT INTO accounts (id, name, account_type, institution, currency, is_default, created_at)
+             VALUES ('checking', 'Main Checking', 'checking', 'Equity', 'KES', 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+        insert_txn(&conn, "jan", "2025-01-15", "Naivas", -2150.0, "groceries", None);
+        insert_txn(&conn, "feb1", "2025-02-01", "Java House", -900.0, "dining", None);
+        insert_txn(&conn, "feb2", "2025-02-28", "Uber", -450.0, "transportation", None);
+        insert_txn(&conn, "mar", "2025-03-03", "KPLC", -3000.0, "utilities", None);
+        conn.execute("UPDATE ledger SET account_id = NULL WHERE id = 'mar'", []).unwrap();
+        let account_of = |conn: &Connection, id: &str| -> Option<String> {
+            conn.query_row("SELECT account_id FROM ledger WHERE id = ?1", [id], |row| row.get(0))
+                .unwrap()
+        };
+
+        // Only February's statement
+        let february = DateRange {
+            start_date: Some("2025-02-01".to_string()),
+            end_date: Some("2025-02-28".to_string()),
+        };
+        assert_eq!(reassign_account_rows(&mut conn, "default", "checking", &february).unwrap(), 2);
+        assert_eq!(account_of(&conn, "jan").as_deref(), Some("default"));
+        assert_eq!(account_of(&conn, "feb1").as_deref(), Some("checking"));
+        assert_eq!(account_of(&conn, "feb2").as_deref(), Some("checking"));
+
+        // Everything left, including the row with no account
+        assert_eq!(reassign_account_rows(&mut conn, "default", "checking", &DateRange::default()).unwrap(), 2);
+        for id in ["jan", "feb1", "feb2", "mar"] {
+            assert_eq!(account_of(&conn, id).as_deref(), Some("checking"));
+        }
+
+        assert!(matches!(
+            reassign_account_rows(&mut conn, "checking", "savings", &DateRange::default()),
+            Err(YukiError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn amounts_format_per_currency_rules() {
+        let conn = test_conn();
+        conn.execute(
+            "INSERT INTO currencies (code, name, symbol, conversion_rate, is_primary, created_at)
+             VALUES ('JPY', 'Japanese Yen', '¥', 0.86, 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+
+        let format = |amount: f64, code: &str| format_amount_in(&conn, amount, code).unwrap();
+        assert_eq!(format(1234.5, "USD"), "$1,234.50");
+        assert_eq!(format(-1234567.891, "USD"), "-$1,234,567.89");
+        assert_eq!(format(1234.56, "EUR"), "1.234,56 €");
+        assert_eq!(format(1234.56, "JPY"), "¥1,235");
+        assert_eq!(format(999.0, "JPY"), "¥999");
+        assert_eq!(format(-2150.0, "KES"), "-KSh 2,150.00");
+        assert_eq!(format(-0.001, "KES"), "KSh 0.00");
+
+        assert!(matches!(format_amount_in(&conn, 1.0, "XYZ"), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn rebasing_primary_currency_keeps_cross_rates() {
+        let mut conn = test_conn();
+        conn.execute("DELETE FROM currencies WHERE code = 'GBP'", []).unwrap();
+        let rates = |conn: &Connection| -> std::collections::HashMap<String, f64> {
+            conn.prepare("SELECT code, conversion_rate FROM currencies")
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect()
+        };
+        let before = rates(&conn);
+
+        rebase_primary_currency(&mut conn, "EUR").unwrap();
+        let after = rates(&conn);
+
+        assert_eq!(after["EUR"], 1.0);
+        let primaries: Vec<String> = conn
+            .prepare("SELECT code FROM currencies WHERE is_primary = 1")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(primaries, vec!["EUR".to_string()]);
+
+        // Converting KES -> USD gives the same result under either base
+        for (a, b) in [("KES", "USD"), ("USD", "EUR"), ("KES", "EUR")] {
+            let old_cross = before[a] / before[b];
+            let new_cross = after[a] / after[b];
+            assert!((old_cross - new_cross).abs() < 1e-9, "{}->{}", a, b);
+        }
+
+        let default: String = conn
+            .query_row("SELECT value FROM settings WHERE key = 'default_currency'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(default, "EUR");
+        assert!(matches!(rebase_primary_currency(&mut conn, "XYZ"), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn rebasing_primary_currency_rescales_transaction_rates() {
+        let mut conn = test_conn();
+        conn.execute("UPDATE currencies SET conversion_rate = 130.0 WHERE code = 'USD'", []).unwrap();
+        insert_txn(&conn, "kes", "2025-03-02", "Naivas", -1300.0, "groceries", None);
+        insert_txn(&conn, "usd", "2025-03-04", "AWS", -10.0, "other", None);
+        conn.execute("UPDATE ledger SET currency = 'USD' WHERE id = 'usd'", []).unwrap();
+        // Paid 125 KES per dollar rather than the stored 130
+        update_transaction_rate(&conn, "usd", Some(125.0)).unwrap();
+        assert_eq!(savings_rate(&conn, None, None).unwrap().expenses, 2550.0);
+
+        rebase_primary_currency(&mut conn, "USD").unwrap();
+        // 1,300 KES is $10, and $10 at 125 KES each is 1,250 KES, or $9.62
+        assert_eq!(savings_rate(&conn, None, None).unwrap().expenses, 19.62);
+    }
+
+    #[test]
+    fn orphan_report_finds_stray_files_and_dangling_rows() {
+        let conn = test_conn();
+        let dir = std::env::temp_dir().join(format!("yuki-orphans-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let kept = dir.join("doc1_statement.pdf");
+        fs::write(&kept, b"statement").unwrap();
+        fs::write(dir.join("stray_upload.pdf"), b"orphan").unwrap();
+        for (id, path) in [("doc1", kept.clone()), ("doc2", dir.join("doc2_gone.pdf"))] {
+            conn.execute(
+                "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES (?1, ?1, ?2, 'application/pdf', 'h', ?1)",
+                [id, path.to_string_lossy().as_ref()],
+            )
+            .unwrap();
+        }
+
+        let report = orphan_report(&conn, &dir).unwrap();
+        assert_eq!(report.orphaned_files, vec![dir.join("stray_upload.pdf").to_string_lossy().to_string()]);
+        assert_eq!(report.orphaned_bytes, 6);
+        assert_eq!(report.missing_files, vec!["doc2".to_string()]);
+        assert!(kept.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn current_context_shows_the_last_ten_messages_truncated() {
+        let conn = test_conn();
+        let label = format!("main-{}", uuid::Uuid::new_v4());
+        assert_eq!(current_context(&conn, &label).unwrap(), "");
+        assert!(current_session(&label).unwrap().is_none());
+
+        session_for_window(&conn, &label).unwrap();
+        for i in 1..=6 {
+            save_message(&conn, &label, "user", &format!("question {}", i)).unwrap();
+            let answer = if i == 6 { "x".repeat(600) } else { format!("answer {}", i) };
+            save_message(&conn, &label, "assistant", &answer).unwrap();
+        }
+
+        let context = current_context(&conn, &label).unwrap();
+        // The oldest exchange falls outside the last ten messages
+        assert!(!context.contains("question 1\n"));
+        assert!(!context.contains("answer 1\n"));
+        assert!(context.contains("User: question 2\nYuki: answer 2\n"));
+        assert!(context.contains(&format!("Yuki: {}...\n", "x".repeat(500))));
+        assert!(!context.contains(&"x".repeat(501)));
+        assert!(context.ends_with("Current message:\n"));
+    }
+
+    #[test]
+    fn windows_keep_independent_conversation_histories() {
+        let conn = test_conn();
+        // Labels are unique per test run because the session map is process-wide
+        let main = format!("main-{}", uuid::Uuid::new_v4());
+        let popout = format!("popout-{}", uuid::Uuid::new_v4());
+
+        let main_session = session_for_window(&conn, &main).unwrap();
+        let popout_session = session_for_window(&conn, &popout).unwrap();
+        assert_ne!(main_session, popout_session);
+        assert_eq!(session_for_window(&conn, &main).unwrap(), main_session);
+
+        save_message(&conn, &main, "user", "How much on dining?").unwrap();
+        save_message(&conn, &popout, "user", "Show my groceries").unwrap();
+        save_message(&conn, &main, "assistant", "KES 4,500 on dining").unwrap();
+
+        let contents = |label: &str| -> Vec<String> {
+            get_conversation_history(&conn, label, 10)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.content)
+                .collect()
+        };
+        assert_eq!(contents(&main), vec!["How much on dining?", "KES 4,500 on dining"]);
+        assert_eq!(contents(&popout), vec!["Show my groceries"]);
+
+        // Starting over in one window leaves the other's session alone
+        start_session(&conn, &main).unwrap();
+        assert!(contents(&main).is_empty());
+        assert_eq!(contents(&popout), vec!["Show my groceries"]);
+    }
+
+    /// Write a one-page PDF whose text layer is `text`
+    fn write_text_pdf(path: &std::path::Path, text: &str) {
+        write_pages_pdf(path, &[text]);
+    }
+
+    /// Write a PDF with one page per entry of `pages`, each with that text layer
+    fn write_pages_pdf(path: &std::path::Path, pages: &[&str]) {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{dictionary, Object, Stream};
+
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let mut kids: Vec<Object> = Vec::new();
+        for text in pages {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 10.into()]),
+                    Operation::new("Td", vec![40.into(), 700.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(*text)]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            let page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            });
+            kids.push(page_id.into());
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    /// Answer one OpenAI-style chat request with `content` and return the raw request
+    #[tokio::test]
+    async fn formatter_failure_falls_back_to_raw_table() {
+        let conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-01", "Naivas", -2150.5, "groceries", None);
+        insert_txn(&conn, "b", "2025-03-02", "Java House", -900.0, "dining", None);
+        let data = execute_query(
+            &conn,
+            "SELECT category_id, SUM(amount) AS total, COUNT(*) AS n FROM ledger GROUP BY category_id ORDER BY category_id",
+        )
+        .unwrap();
+
+        // Nothing listens on this port, so the formatting call fails
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Offline".to_string(),
+            endpoint: format!("http://127.0.0.1:{}", port),
+            api_key: Some("sk-test".to_string()),
+            model: "gpt-4o".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+
+        let response = format_results_or_raw_table(&provider, "spending by category", &data, &[]).await;
+        assert_eq!(response.cards.len(), 1);
+        match &response.cards[0] {
+            ResponseCard::Table(table) => {
+                assert_eq!(table.columns, vec!["category_id", "total", "n"]);
+                assert_eq!(
+                    table.rows,
+                    vec![vec!["dining", "-900.00", "1"], vec!["groceries", "-2150.50", "1"]]
+                );
+                assert!(table.summary.is_some());
+            }
+            _ => panic!("expected a table card"),
+        }
+    }
+
+    #[tokio::test]
+    async fn previewed_sql_runs_only_once_approved() {
+        let conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-01", "Naivas", -2150.5, "groceries", None);
+        insert_txn(&conn, "b", "2025-03-02", "Java House", -900.0, "dining", None);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "gpt-4o".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+
+        // Preview only generates SQL, and refuses SQL that execution would reject
+        let mut listener = listener;
+        for (sql, allowed) in [
+            ("SELECT SUM(amount) FROM ledger", true),
+            ("DELETE FROM ledger", false),
+            ("UPDATE ledger SET amount = 0", false),
+        ] {
+            let analysis = format!(r#"{{"needs_data": true, "sql_query": "{}", "query_type": "spending"}}"#, sql);
+            let server = tokio::spawn(async move {
+                serve_one_completion(&listener, &analysis).await;
+                listener
+            });
+            let preview = preview_sql(&provider, "what did I spend?", &[]).await;
+            listener = server.await.unwrap();
+            match preview {
+                Ok(analysis) => assert!(allowed && analysis.sql_query.as_deref() == Some(sql), "{}", sql),
+                Err(e) => assert!(!allowed && matches!(e, YukiError::Validation(_)), "{}: {}", sql, e),
+            }
+        }
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM ledger", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 2);
+
+        // The approved (edited) SQL goes through execution and formatting
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider { endpoint: format!("http://{}", listener.local_addr().unwrap()), ..provider };
+        let cards = r#"{"cards": [{"type": "text", "content": {"body": "You spent 3,050.50 in total."}}]}"#;
+        let server = tokio::spawn(async move { serve_one_completion(&listener, cards).await });
+        let response = answer_from_sql(
+            conn,
+            &provider,
+            "preview-test",
+            "how much did I spend?",
+            "SELECT SUM(amount) AS total FROM ledger",
+            &[],
+        )
+        .await;
+        let request = server.await.unwrap();
+
+        assert!(request.contains("-3050.5"));
+        match &response.cards[..] {
+            [ResponseCard::Text(text)] => assert_eq!(text.body, "You spent 3,050.50 in total."),
+            _ => panic!("expected the formatted text card"),
+        }
+    }
+
+    #[tokio::test]
+    async fn rows_before_the_cutoff_are_dropped_even_if_returned() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "gpt-4o".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+
+        // The model ignores the instruction and returns the whole statement
+        let rows = r#"[
+            {"date": "2025-03-02", "description": "Naivas", "amount": -2150.5, "category": "Groceries"},
+            {"date": "2025-03-15", "description": "Java House", "amount": -900, "category": "Dining"},
+            {"date": "2025-03-16", "description": "Uber", "amount": -640, "category": "Transportation"},
+            {"date": "16th March", "description": "Kiosk", "amount": -50, "category": "Other"}
+        ]"#;
+        let server = tokio::spawn(async move { serve_one_completion(&listener, rows).await });
+        let statement = "02/03 NAIVAS -2150.50\n15/03 JAVA HOUSE -900.00\n16/03 UBER -640.00";
+        let transactions = llm::parse_document_with_llm(&provider, statement, &["Groceries".to_string()], Some("2025-03-15"), None)
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+
+        assert!(request.contains("Only include transactions dated after 2025-03-15"));
+        let descriptions: Vec<&str> = transactions.iter().map(|t| t.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["Uber", "Kiosk"]);
+        // An unreadable date can't be checked against the cutoff, so it's kept for review
+        assert_eq!(transactions[1].needs_review, Some(true));
+        assert_eq!(transactions[0].needs_review, None);
+
+        assert!(validate_cutoff_date(Some("15/03/2025")).is_err());
+        assert_eq!(statement_period(Some("2024-12-15"), None).unwrap(), None);
+        assert!(statement_period(Some("2025-01-14"), Some("2024-12-15")).is_err());
+    }
+
+    #[tokio::test]
+    async fn pasted_yesterday_resolves_to_an_iso_date() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "llama3.2".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+
+        let server = tokio::spawn(async move {
+            serve_one_completion(
+                &listener,
+                r#"[{"date":"2025-03-13","description":"Chipotle","amount":-12,"currency":"USD","category":"dining","merchant":"Chipotle"}]"#,
+            )
+            .await
+        });
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+        let txns = llm::parse_pasted_text_with_llm(&provider, "Paid $12 at Chipotle yesterday", &["dining".to_string()], today)
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+
+        assert!(request.contains("Paid $12 at Chipotle 2025-03-13"));
+        assert!(!request.to_lowercase().contains("yesterday"));
+        assert_eq!(txns.len(), 1);
+        assert_eq!(txns[0].date, "2025-03-13");
+    }
+
+    #[tokio::test]
+    async fn french_statement_is_parsed_with_english_descriptions() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "llama3.2".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+
+        let server = tokio::spawn(async move {
+            serve_one_completion(
+                &listener,
+                r#"[{"date":"2025-03-03","description":"Card payment at bakery","amount":-4.2,"currency":"EUR","category":"dining","merchant":"Boulangerie du Coin"}]"#,
+            )
+            .await
+        });
+        let statement = "RELEVÉ DE COMPTE\n03/03/2025 PAIEMENT CARTE BOULANGERIE DU COIN -4,20\n05/03/2025 FRAIS DE TENUE DE COMPTE -2,50";
+        let txns = llm::parse_document_with_llm(&provider, statement, &["dining".to_string()], None, None).await.unwrap();
+        let request = server.await.unwrap();
+
+        assert!(request.contains("The document is in French: write each description in English"));
+        assert_eq!(txns[0].description, "Card payment at bakery");
+        assert_eq!(txns[0].merchant.as_deref(), Some("Boulangerie du Coin"));
+
+        let metadata = statement_metadata_from_text(statement);
+        assert_eq!(metadata.language.as_deref(), Some("French"));
+    }
+
+    #[tokio::test]
+    async fn empty_llm_response_is_retried_once_then_reported() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "llama3.2".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+
+        let server = tokio::spawn(async move {
+            serve_one_completion(&listener, "").await;
+            serve_one_completion(&listener, "  \n ").await;
+            // No third attempt
+            tokio::time::timeout(std::time::Duration::from_millis(300), listener.accept()).await.is_err()
+        });
+        let err = llm::call_llm(&provider, "how much on groceries?", None, None).await.unwrap_err();
+        assert!(server.await.unwrap());
+        assert_eq!(err.to_string(), llm::EMPTY_RESPONSE_MESSAGE);
+
+        // A reply on the retry is used as normal
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider { endpoint: format!("http://{}", listener.local_addr().unwrap()), ..provider };
+        let server = tokio::spawn(async move {
+            serve_one_completion(&listener, "").await;
+            serve_one_completion(&listener, "KSh 2,150").await;
+        });
+        assert_eq!(llm::call_llm(&provider, "q", None, None).await.unwrap(), "KSh 2,150");
+        server.await.unwrap();
+    }
+
+    async fn serve_one_completion(listener: &tokio::net::TcpListener, content: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if request.len() >= header_end + 4 + content_length {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+
+        let body = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": content }, "finish_reason": "stop" }]
+        })
+        .to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    }
+
+    #[tokio::test]
+    async fn receipt_folder_reports_each_file_separately() {
+        let dir = std::env::temp_dir().join(format!("yuki-receipts-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a_naivas.jpg"), b"\xFF\xD8\xFF\xE0 receipt scan").unwrap();
+        fs::write(dir.join("b_blank.png"), b"").unwrap();
+        fs::write(dir.join("notes.txt"), b"not a receipt").unwrap();
+        fs::write(dir.join(".DS_Store"), b"finder").unwrap();
+
+        let files = receipt_files_in(&dir).unwrap();
+        let names: Vec<String> = files.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, vec!["a_naivas.jpg", "b_blank.png"]);
+        assert!(matches!(receipt_files_in(&dir.join("missing")), Err(YukiError::NotFound(_))));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "gpt-4o".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+        let reply = r#"{"receipts": [{"merchant": "Naivas", "date": "2025-03-04", "items": [], "tax": null, "total": 2150.0, "category": "Groceries"}]}"#;
+        // Only the readable file reaches the provider
+        let server = tokio::spawn(async move { serve_one_completion(&listener, reply).await });
+
+        let progress = std::sync::Mutex::new(Vec::new());
+        let results = parse_receipt_files(provider, files, vec!["Groceries".to_string()], |p| {
+            progress.lock().unwrap().push((p.filename, p.completed, p.total));
+        })
+        .await;
+        server.await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].filename, "a_naivas.jpg");
+        assert_eq!(results[0].error, None);
+        assert_eq!(results[0].receipts[0].merchant, "Naivas");
+        assert_eq!(results[1].filename, "b_blank.png");
+        assert!(results[1].receipts.is_empty());
+        assert!(results[1].error.as_deref().unwrap().contains("is empty"));
+
+        let mut progress = progress.into_inner().unwrap();
+        progress.sort();
+        assert_eq!(progress.len(), 2);
+        assert!(progress.iter().all(|(_, _, total)| *total == 2));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parse_cost_is_estimated_from_text_size_and_pages() {
+        let provider = |model: &str, is_local: bool| LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Test".to_string(),
+            endpoint: "https://api.openai.com/v1".to_string(),
+            api_key: None,
+            model: model.to_string(),
+            is_local,
+            requests_per_minute: None,
+        };
+        // 40,000 characters: 10,000 tokens in, about 5,000 back
+        let text = "2025-03-01 NAIVAS WESTLANDS KES -215.50\n".repeat(1000);
+        assert_eq!(text.chars().count(), 40_000);
+
+        let hosted = parse_cost_estimate(Some(&text), 4, llm::model_pricing(&provider("gpt-4o-mini", false)));
+        assert_eq!(hosted.mode, "text");
+        assert_eq!(hosted.estimated_input_tokens, 10_000);
+        assert_eq!(hosted.estimated_output_tokens, 5_000);
+        assert!((hosted.estimated_cost_usd.unwrap() - 0.0045).abs() < 1e-9);
+        assert_eq!(hosted.recommended_chunking, 1);
+
+        // A local 8k model is free but can't take it in one go
+        let local = parse_cost_estimate(Some(&text), 4, llm::model_pricing(&provider("llama3.2", true)));
+        assert_eq!(local.estimated_cost_usd, Some(0.0));
+        assert_eq!(local.recommended_chunking, 3);
+
+        let scanned = parse_cost_estimate(None, 7, None);
+        assert_eq!(scanned.mode, "vision");
+        assert_eq!(scanned.estimated_input_tokens, 7 * VISION_TOKENS_PER_PAGE);
+        assert_eq!(scanned.estimated_cost_usd, None);
+        assert_eq!(scanned.recommended_chunking, 4);
+    }
+
+    #[test]
+    fn identical_upload_is_detected_by_hash() {
+        let conn = test_conn();
+        let statement = b"%PDF-1.5 March statement".to_vec();
+        check_not_imported(&conn, &statement).unwrap();
+
+        conn.execute(
+            "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES ('doc1', 'march.pdf', '/tmp/march.pdf', 'application/pdf', ?1, '2025-04-01')",
+            [hash_bytes(&statement)],
+        )
+        .unwrap();
+
+        match check_not_imported(&conn, &statement) {
+            Err(YukiError::AlreadyImported { existing_document_id }) => assert_eq!(existing_document_id, "doc1"),
+            other => panic!("expected AlreadyImported, got {:?}", other),
+        }
+        check_not_imported(&conn, b"%PDF-1.5 April statement").unwrap();
+    }
+
+    #[test]
+    fn pdf_text_is_extracted_once_then_cached() {
+        let conn = test_conn();
+        let dir = std::env::temp_dir().join(format!("yuki-text-cache-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let pdf_path = dir.join("doc1_statement.pdf");
+        write_text_pdf(&pdf_path, "2025-03-04 NAIVAS SUPERMARKET WESTLANDS NAIROBI purchase amount KES 2,150.00 card ending 4821");
+        conn.execute(
+            "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES ('doc1', 'statement.pdf', ?1, 'application/pdf', 'h', '2025-03-05')",
+            [pdf_path.to_string_lossy()],
+        )
+        .unwrap();
+        let document = document_by_path(&conn, &pdf_path.to_string_lossy()).unwrap().unwrap();
+
+        let extractions = std::cell::Cell::new(0);
+        let counting = |data: &[u8]| {
+            extractions.set(extractions.get() + 1);
+            pdf_extraction(data)
+        };
+        let first = cached_pdf_text(&conn, &document, counting).unwrap();
+        let second = cached_pdf_text(&conn, &document, counting).unwrap();
+
+        assert_eq!(extractions.get(), 1);
+        assert!(first.text.contains("NAIVAS SUPERMARKET"));
+        assert_eq!((second.text, second.is_scanned), (first.text, first.is_scanned));
+        let page_count: Option<i64> = conn
+            .query_row("SELECT page_count FROM document_text WHERE document_id = 'doc1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(page_count, Some(1));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn reparse_document_reads_stored_pdf_with_override_provider() {
+        let conn = test_conn();
+        let dir = std::env::temp_dir().join(format!("yuki-reparse-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let pdf_path = dir.join("doc1_statement.pdf");
+        write_text_pdf(&pdf_path, "2025-03-04 NAIVAS SUPERMARKET WESTLANDS NAIROBI purchase amount KES 2,150.00 card ending 4821");
+        conn.execute(
+            "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES ('doc1', 'statement.pdf', ?1, 'application/pdf', 'h', '2025-03-05')",
+            [pdf_path.to_string_lossy()],
+        )
+        .unwrap();
+        let document = document_by_id(&conn, "doc1").unwrap();
+        assert!(document_by_id(&conn, "missing").is_err());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let override_provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Override".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "override-model".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+        let reply = r#"[{"date": "2025-03-04", "description": "Naivas Supermarket", "amount": -2150.0, "currency": "KES", "category": "Groceries", "merchant": "Naivas"}]"#;
+        let server = tokio::spawn(async move { serve_one_completion(&listener, reply).await });
+
+        let categories = vec!["Groceries".to_string()];
+        let text = stored_document_text(&conn, &document).unwrap();
+        let transactions = reparse_stored_document(&override_provider, &document, text, &categories, None, None).await.unwrap();
+        let request = server.await.unwrap();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].amount, -2150.0);
+        assert!(request.contains("\"model\":\"override-model\""));
+        assert!(request.contains("NAIVAS SUPERMARKET"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn one_chunk_of_a_statement_reparses_on_its_own() {
+        use base64::Engine;
+
+        let dir = std::env::temp_dir().join(format!("yuki-chunk-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let pdf_path = dir.join("doc1_statement.pdf");
+        let pages = ["page 1", "page 2", "page 3", "page 4", "page 5", "page 6"];
+        write_pages_pdf(&pdf_path, &pages);
+        let document = Document {
+            id: "doc1".to_string(),
+            filename: "statement.pdf".to_string(),
+            filepath: pdf_path.to_string_lossy().to_string(),
+            filetype: "application/pdf".to_string(),
+            hash: "h".to_string(),
+            uploaded_at: "2025-03-05".to_string(),
+        };
+        assert_eq!(stored_pdf_page_count(&document).unwrap(), 6);
+        validate_page_range(3, 4, 6).unwrap();
+        for (start, end) in [(0, 2), (4, 3), (5, 7)] {
+            assert!(matches!(validate_page_range(start, end, 6), Err(YukiError::Validation(_))));
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider {
+            provider_type: "openai".to_string(),
+            name: "Mock".to_string(),
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: None,
+            model: "gpt-4o".to_string(),
+            is_local: true,
+            requests_per_minute: None,
+        };
+        let reply = r#"[{"date": "2025-03-14", "description": "Naivas", "amount": -2150.0, "currency": "KES", "category": "Groceries", "merchant": "Naivas"}]"#;
+        let server = tokio::spawn(async move { serve_one_completion(&listener, reply).await });
+
+        let categories = vec!["Groceries".to_string()];
+        let transactions =
+            llm::parse_statement_pages_with_vision_llm(&provider, &document.filepath, &categories, 3, 4, None, None)
+                .await
+                .unwrap();
+        let request = server.await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].amount, -2150.0);
+        assert!(request.contains("pages 3-4"));
+
+        // Only the two requested pages were sent
+        let data = request.split("base64,").nth(1).unwrap().split('"').next().unwrap();
+        let sent = base64::engine::general_purpose::STANDARD.decode(data).unwrap();
+        assert_eq!(lopdf::Document::load_mem(&sent).unwrap().get_pages().len(), 2);
+
+        // A chunk with no rows is suspect and one with an unreadable reply failed;
+        // neither passes for a clean page
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let provider = LLMProvider { endpoint: format!("http://{}", listener.local_addr().unwrap()), ..provider };
+        let server = tokio::spawn(async move {
+            for reply in [reply, "[]", "Sorry, I can't read this page"] {
+                serve_one_completion(&listener, reply).await;
+            }
+        });
+        let chunks = llm::parse_statement_chunks_with_vision_llm(&provider, &document.filepath, &categories, None, None)
+            .await
+            .unwrap();
+        server.await.unwrap();
+        let statuses: Vec<&str> = chunks.iter().map(|c| c.status.as_str()).collect();
+        assert_eq!(statuses, vec!["ok", "empty", "failed"]);
+        assert_eq!(chunks[0].transactions.len(), 1);
+        assert_eq!(chunks[1].error, None);
+        assert!(chunks[2].error.as_deref().unwrap().contains("pages 5-6"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn most_expensive_items_are_ordered_by_price() {
+        let conn = test_conn();
+        insert_txn(&conn, "naivas", "2025-03-01", "POS NAIVAS", -9000.0, "groceries", Some("Naivas"));
+        for (id, name, quantity, unit_price, total_price, ledger_id) in [
+            ("tv", "smart-tv-43in", 1.0, Some(45000.0), 45000.0, None),
+            ("rice", "basmati-rice  5kg", 4.0, Some(1200.0), 4800.0, Some("naivas")),
+            ("wine", "RED_WINE", 2.0, None, 3000.0, Some("naivas")),
+            ("milk", "whole-milk", 10.0, Some(65.0), 650.0, Some("naivas")),
+        ] {
+            conn.execute(
+                "INSERT INTO purchased_items (id, ledger_id, name, quantity, unit_price, total_price, purchased_at, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, '2025-03-01T10:00:00Z', '2025-03-01')",
+                rusqlite::params![id, ledger_id, name, quantity, unit_price, total_price],
+            )
+            .unwrap();
+        }
+
+        let items = most_expensive_items(&conn, 3, "total_price").unwrap();
+        let order: Vec<(&str, f64)> = items.iter().map(|i| (i.name.as_str(), i.total_price)).collect();
+        assert_eq!(order, vec![("Smart tv 43in", 45000.0), ("Basmati rice 5kg", 4800.0), ("Red wine", 3000.0)]);
+        assert_eq!(items[0].merchant, None);
+        assert_eq!(items[1].merchant.as_deref(), Some("Naivas"));
+        assert_eq!(items[1].raw_name, "basmati-rice  5kg");
+        assert_eq!(items[1].purchased_at, "2025-03-01");
+
+        // A bottle of wine (1,500) costs more per unit than a bag of rice (1,200)
+        let by_unit: Vec<String> = most_expensive_items(&conn, 10, "unit_price")
+            .unwrap()
+            .into_iter()
+            .map(|i| i.id)
+            .collect();
+        assert_eq!(by_unit, vec!["tv", "wine", "rice", "milk"]);
+
+        assert!(matches!(most_expensive_items(&conn, 10, "quantity"), Err(YukiError::Validation(_))));
+    }
+
+    #[test]
+    fn item_category_spending_groups_and_buckets_missing_categories() {
+        let conn = test_conn();
+        let insert_item = |id: &str, category: Option<&str>, total: f64, purchased_at: &str| {
+            conn.execute(
+                "INSERT INTO purchased_items (id, name, total_price, category, purchased_at, created_at) VALUES (?1, ?1, ?2, ?3, ?4, ?4)",
+                rusqlite::params![id, total, category, purchased_at],
+            )
+            .unwrap();
+        };
+        insert_item("apples", Some("produce"), 300.0, "2025-03-02");
+        insert_item("kale", Some("Produce"), 150.0, "2025-03-09T10:15:00Z");
+        insert_item("crisps", Some("snacks"), 200.0, "2025-03-05");
+        insert_item("batteries", None, 120.0, "2025-03-06");
+        insert_item("foil", Some(""), 60.0, "2025-03-07");
+        insert_item("old-milk", Some("dairy"), 90.0, "2025-02-20");
+
+        let points = item_category_spending(&conn, Some("2025-03-01"), Some("2025-03-31")).unwrap();
+        let pairs: Vec<(&str, f64)> = points.iter().map(|p| (p.label.as_str(), p.value)).collect();
+        assert_eq!(pairs, vec![("produce", 450.0), ("snacks", 200.0), ("other", 180.0)]);
+
+        let all = item_category_spending(&conn, None, None).unwrap();
+        assert_eq!(all.len(), 4);
+    }
+
+    #[test]
+    fn reset_removes_data_but_can_keep_settings() {
+        let mut conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-01", "Naivas", -2150.5, "groceries", None);
+        conn.execute(
+            "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at) VALUES ('doc1', 'statement.pdf', '/nonexistent/doc1.pdf', 'application/pdf', 'h', '2025-03-05')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO purchased_items (id, name, total_price, purchased_at, created_at) VALUES ('i1', 'Milk', 180.0, '2025-03-01', '2025-03-01')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO conversation_sessions (id, created_at, updated_at) VALUES ('s1', '2025-01-01T00:00:00Z', '2025-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO conversation_messages (id, session_id, role, content, created_at) VALUES ('m1', 's1', 'user', 'hi', '2025-01-01T00:00:01Z')",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO settings (key, value) VALUES ('provider', '{\"type\":\"ollama\"}')", []).unwrap();
+        conn.execute(
+            "INSERT INTO categories (id, name, is_default, created_at) VALUES ('pets', 'Pets', 0, '2025-01-01')",
+            [],
+        )
+        .unwrap();
+
+        let count = |conn: &Connection, table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+        };
+
+        let (summary, files) = reset_data_rows(&mut conn, true).unwrap();
+        assert_eq!((summary.transactions, summary.documents, summary.purchased_items, summary.conversation_messages), (1, 1, 1, 1));
+        assert_eq!(files, vec!["/nonexistent/doc1.pdf"]);
+        for table in ["ledger", "documents", "purchased_items", "conversation_messages", "conversation_sessions"] {
+            assert_eq!(count(&conn, table), 0, "{} should be empty", table);
+        }
+        let provider: String = conn.query_row("SELECT value FROM settings WHERE key = 'provider'", [], |row| row.get(0)).unwrap();
+        assert!(provider.contains("ollama"));
+        assert_eq!(count(&conn, "categories"), 16);
+
+        // Without keep_settings everything returns to the seeded defaults
+        reset_data_rows(&mut conn, false).unwrap();
+        assert_eq!(count(&conn, "settings"), 1);
+        assert_eq!(count(&conn, "categories"), 15);
+        assert_eq!(count(&conn, "accounts"), 1);
+    }
+
+    #[test]
+    fn pinned_categories_sort_first_then_custom_order() {
+        let mut conn = test_conn();
+        let ids = |conn: &Connection| -> Vec<String> { all_categories(conn).unwrap().into_iter().map(|c| c.id).collect() };
+
+        // Untouched categories are alphabetical
+        assert_eq!(&ids(&conn)[..3], ["dining", "education", "entertainment"]);
+
+        apply_category_order(&mut conn, &["groceries".to_string(), "transportation".to_string(), "dining".to_string()]).unwrap();
+        assert_eq!(&ids(&conn)[..4], ["groceries", "transportation", "dining", "education"]);
+
+        set_category_pinned(&conn, "utilities", true).unwrap();
+        let categories = all_categories(&conn).unwrap();
+        assert_eq!(categories[0].id, "utilities");
+        assert!(categories[0].is_pinned);
+        assert_eq!(categories[1].sort_order, Some(0));
+        assert_eq!(&ids(&conn)[..4], ["utilities", "groceries", "transportation", "dining"]);
+
+        // An unknown id leaves the previous order alone
+        assert!(matches!(
+            apply_category_order(&mut conn, &["dining".to_string(), "nope".to_string()]),
+            Err(YukiError::NotFound(_))
+        ));
+        assert!(apply_category_order(&mut conn, &["dining".to_string(), "dining".to_string()]).is_err());
+        assert_eq!(&ids(&conn)[..4], ["utilities", "groceries", "transportation", "dining"]);
+
+        set_category_pinned(&conn, "utilities", false).unwrap();
+        assert_eq!(ids(&conn)[0], "groceries");
+        assert!(matches!(set_category_pinned(&conn, "nope", true), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn exported_config_reproduces_categories_aliases_buckets_and_budgets() {
+        let source = test_conn();
+        let pets = insert_category(&source, "Pets", Some("#a16207"), Some("dog")).unwrap();
+        let kids = insert_category(&source, "Kids", None, Some("baby")).unwrap();
+        set_category_pinned(&source, &pets, true).unwrap();
+        update_category_fields(&source, "dining", Some("Eating out"), None, None).unwrap();
+        insert_item_alias(&source, "coca-cola*", "Coca-Cola").unwrap();
+        insert_item_alias(&source, "milk 500ml", "Milk").unwrap();
+        save_category_bucket(&source, &pets, Some("essential")).unwrap();
+        save_category_bucket(&source, "dining", Some("")).unwrap();
+        save_category_budget(&source, &kids, Some(5000.0)).unwrap();
+        save_category_budget(&source, "groceries", Some(20000.0)).unwrap();
+
+        let exported = config_snapshot(&source).unwrap();
+        let file: ConfigExport = serde_json::from_str(&serde_json::to_string(&exported).unwrap()).unwrap();
+
+        // A fresh machine that already made its own "kids" category under another id
+        let mut target = test_conn();
+        let local_kids = insert_category(&target, "kids", None, None).unwrap();
+        let unused = insert_category(&target, "Scratch", None, None).unwrap();
+
+        let summary = apply_config(&mut target, &file, false).unwrap();
+        assert_eq!(summary.categories_added, 1);
+        assert_eq!(summary.categories_matched_by_name, 1);
+        assert_eq!(summary.item_aliases, 2);
+
+        let imported = config_snapshot(&target).unwrap();
+        let shape = |config: &ConfigExport| -> Vec<(String, Option<String>, Option<String>, bool)> {
+            config.categories.iter().map(|c| (c.name.clone(), c.icon.clone(), c.color.clone(), c.is_pinned)).collect()
+        };
+        assert_eq!(shape(&imported), shape(&exported));
+        assert!(imported.categories.iter().any(|c| c.id == local_kids));
+        assert!(!imported.categories.iter().any(|c| c.id == kids || c.id == unused));
+        let aliases = |config: &ConfigExport| -> Vec<(String, String)> {
+            config.item_aliases.iter().map(|a| (a.pattern.clone(), a.canonical.clone())).collect()
+        };
+        assert_eq!(aliases(&imported), aliases(&exported));
+        assert_eq!(imported.spending_buckets, exported.spending_buckets);
+        // Budgets follow a category matched by name to its local id
+        assert_eq!(summary.category_budgets, 2);
+        assert_eq!(imported.category_budgets.get(&local_kids), Some(&5000.0));
+        assert_eq!(imported.category_budgets.get("groceries"), Some(&20000.0));
+        assert_eq!(imported.category_budgets.len(), 2);
+
+        // Merging keeps local choices and adds nothing twice
+        update_category_fields(&target, "dining", Some("Restaurants"), None, None).unwrap();
+        save_category_budget(&target, "groceries", Some(15000.0)).unwrap();
+        let summary = apply_config(&mut target, &file, true).unwrap();
+        assert_eq!((summary.categories_added, summary.categories_updated, summary.item_aliases), (0, 0, 0));
+        assert_eq!(summary.category_budgets, 0);
+        assert_eq!(category_budgets(&target).get("groceries"), Some(&15000.0));
+        assert_eq!(
+            all_categories(&target).unwrap().iter().find(|c| c.id == "dining").unwrap().name,
+            "Restaurants"
+        );
+    }
+
+    #[test]
+    fn gray_categories_get_distinct_colors() {
+        let mut conn = test_conn();
+        let ids: Vec<String> = ["Pets", "Kids", "Garden", "Hobbies", "Charity"]
+            .iter()
+            .map(|name| insert_category(&conn, name, None, None).unwrap())
+            .collect();
+        let teal = insert_category(&conn, "Side hustle", Some("#14b8a6"), None).unwrap();
+
+        assert_eq!(recolor_gray_categories(&mut conn).unwrap(), 5);
+        let color_of = |conn: &Connection, id: &str| -> String {
+            conn.query_row("SELECT color FROM categories WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        let all_colors: Vec<String> = conn
+            .prepare("SELECT color FROM categories")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let new_colors: Vec<String> = ids.iter().map(|id| color_of(&conn, id)).collect();
+        for color in &new_colors {
+            assert_ne!(color, DEFAULT_CATEGORY_COLOR);
+            assert_eq!(all_colors.iter().filter(|c| *c == color).count(), 1, "{} is used twice", color);
+        }
+
+        // Hues stay apart from each other and from the seeded palette
+        let mut hues: Vec<f64> = new_colors.iter().filter_map(|c| hex_hue(c)).collect();
+        assert_eq!(hues.len(), 5);
+        hues.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(hues.windows(2).all(|w| w[1] - w[0] >= 5.0));
+
+        // Colors already chosen, the seeded gray "Other" and a rerun are left alone
+        assert_eq!(color_of(&conn, &teal), "#14b8a6");
+        assert_eq!(color_of(&conn, "other"), DEFAULT_CATEGORY_COLOR);
+        assert_eq!(recolor_gray_categories(&mut conn).unwrap(), 0);
+        assert_eq!(hsl_hex(0.0, 1.0, 0.5), "#ff0000");
+        assert_eq!(hex_hue("#00ff00"), Some(120.0));
+    }
+
+    #[test]
+    fn partial_settings_save_keeps_other_fields() {
+        let conn = test_conn();
+        let full = merge_settings(
+            load_settings(&conn),
+            serde_json::json!({
+                "defaultCurrency": "KES",
+                "theme": "dark",
+                "localOnly": true,
+                "importWebhookUrl": "https://hooks.example.com/yuki",
+                "importWebhookSecret": "hush",
+                "importMinAmount": 5.0,
+            }),
+        )
+        .unwrap();
+        store_settings(&conn, &full).unwrap();
+
+        // What the setup wizard sends
+        let partial = serde_json::json!({ "provider": null, "defaultCurrency": "USD", "theme": "system" });
+        store_settings(&conn, &merge_settings(load_settings(&conn), partial).unwrap()).unwrap();
+
+        let saved = load_settings(&conn);
+        assert_eq!((saved.default_currency.as_str(), saved.theme.as_str()), ("USD", "system"));
+        assert!(saved.local_only);
+        assert_eq!(saved.import_webhook_url.as_deref(), Some("https://hooks.example.com/yuki"));
+        assert_eq!(saved.import_webhook_secret.as_deref(), Some("hush"));
+        assert_eq!(saved.import_min_amount, Some(5.0));
+
+        // An explicit null still clears an optional field
+        let cleared = merge_settings(saved, serde_json::json!({ "importWebhookUrl": null })).unwrap();
+        assert_eq!(cleared.import_webhook_url, None);
+        assert!(matches!(merge_settings(cleared, serde_json::json!([])), Err(YukiError::Validation(_))));
+    }
+
+    #[test]
+    fn settings_report_masks_secrets() {
+        let conn = test_conn();
+        let provider = LLMProvider {
+            provider_type: "anthropic".to_string(),
+            name: "Anthropic".to_string(),
+            endpoint: "https://api.anthropic.com".to_string(),
+            api_key: Some("sk-ant-REDACTED".to_string()),
+            model: "claude-sonnet-4".to_string(),
+            is_local: false,
+            requests_per_minute: None,
+        };
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('provider', ?1), ('import_webhook_secret', 'hush'),
+                ('import_webhook_url', 'https://hooks.slack.com/services/T0001/B0002/tokenXYZ?key=qs')",
+            [serde_json::to_string(&provider).unwrap()],
+        )
+        .unwrap();
+
+        let report = settings_report(&conn).unwrap();
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        assert!(!json.contains("abcdefghijklmnop"));
+        assert!(!json.contains("hush"));
+        assert!(!json.contains("tokenXYZ") && !json.contains("T0001") && !json.contains("key="));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let provider = &value["settings"]["provider"];
+        assert_eq!(provider["type"], "anthropic");
+        assert_eq!(provider["model"], "claude-sonnet-4");
+        assert_eq!(provider["apiKey"], "****1234");
+        assert_eq!(value["settings"]["importWebhookSecret"], "****");
+        assert_eq!(value["settings"]["importWebhookUrl"], "https://hooks.slack.com/…");
+        assert_eq!(url_origin("http://127.0.0.1:8080/hook?token=1"), "http://127.0.0.1:8080/…");
+        assert_eq!(url_origin("not a url"), "****");
+        assert_eq!(value["category_count"], report.category_count);
+        assert!(report.currencies.iter().any(|c| c.code == "KES"));
+    }
+
+    #[test]
+    fn category_color_and_icon_are_validated() {
+        let conn = test_conn();
+
+        let id = insert_category(&conn, "Pets", Some("#1A2b3C"), Some("dog")).unwrap();
+        let (color, icon): (String, Option<String>) = conn
+            .query_row("SELECT color, icon FROM categories WHERE id = ?1", [&id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((color.as_str(), icon.as_deref()), ("#1a2b3c", Some("dog")));
+
+        for bad in ["red", "#12345", "#12345g", "1a2b3c4"] {
+            assert!(matches!(insert_category(&conn, "Bad", Some(bad), None), Err(YukiError::Validation(_))), "{}", bad);
+        }
+        assert!(insert_category(&conn, "Bad", None, Some("unicorn")).is_err());
+
+        update_category_fields(&conn, &id, None, Some("#00ff00"), Some("")).unwrap();
+        let (color, icon): (String, Option<String>) = conn
+            .query_row("SELECT color, icon FROM categories WHERE id = ?1", [&id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((color.as_str(), icon), ("#00ff00", None));
+        assert!(update_category_fields(&conn, &id, None, Some("green"), None).is_err());
+        assert!(matches!(update_category_fields(&conn, "nope", Some("X"), None, None), Err(YukiError::NotFound(_))));
+    }
+
+    #[test]
+    fn aliased_item_variants_collapse_into_one_row() {
+        let conn = test_conn();
+        let insert_item = |id: &str, name: &str, quantity: f64, total: f64| {
+            conn.execute(
+                "INSERT INTO purchased_items (id, name, quantity, total_price, purchased_at, created_at) VALUES (?1, ?2, ?3, ?4, '2025-03-01', '2025-03-01')",
+                rusqlite::params![id, name, quantity, total],
+            )
+            .unwrap();
+        };
+        insert_item("i1", "Coca-Cola", 2.0, 200.0);
+        insert_item("i2", "coke", 1.0, 100.0);
+        insert_item("i3", "coca-cola-12pk", 12.0, 1100.0);
+        insert_item("i4", "Milk", 3.0, 180.0);
+
+        insert_item_alias(&conn, "Coke", "Coca-Cola").unwrap();
+        insert_item_alias(&conn, "coca-cola*", "Coca-Cola").unwrap();
+        assert!(insert_item_alias(&conn, "*", "Everything").is_err());
+
+        let spending = item_spending_normalized(&conn).unwrap();
+        assert_eq!(spending.len(), 2);
+        let coke = &spending[0];
+        assert_eq!(coke.name, "Coca-Cola");
+        assert_eq!((coke.quantity, coke.total_price, coke.purchase_count), (15.0, 1400.0, 3));
+        assert_eq!(coke.variants, vec!["Coca-Cola", "coca-cola-12pk", "coke"]);
+        assert_eq!((spending[1].name.as_str(), spending[1].quantity), ("milk", 3.0));
+
+        assert!(glob_matches("*cola*", "diet coca-cola zero"));
+        assert!(!glob_matches("coca-cola*", "diet coca-cola"));
+    }
+
+    #[test]
+    fn refund_is_matched_to_the_purchase_it_reverses() {
+        let conn = test_conn();
+        insert_txn(&conn, "shoes", "2025-02-01", "Jumia order 5521", -2500.0, "shopping", Some("Jumia"));
+        insert_txn(&conn, "cable", "2025-02-03", "Jumia order 5530", -800.0, "shopping", Some("Jumia"));
+        insert_txn(&conn, "lunch", "2025-02-04", "Java House", -2500.0, "dining", Some("Java House"));
+        insert_txn(&conn, "refund", "2025-02-10", "REFUND - Jumia order 5521", 2500.0, "income", None);
+        // Money in before any purchase and a salary are left alone
+        insert_txn(&conn, "early", "2025-01-15", "Naivas refund", 300.0, "income", Some("Naivas"));
+        insert_txn(&conn, "naivas", "2025-01-20", "Naivas", -300.0, "groceries", Some("Naivas"));
+        insert_txn(&conn, "salary", "2025-02-25", "Salary", 2500.0, "income", Some("Jumia"));
+
+        let matches = refund_matches(&conn).unwrap();
+        assert_eq!(matches.len(), 1);
+        let refund = &matches[0];
+        assert_eq!(refund.refund_id, "refund");
+        assert_eq!(refund.purchase_id, "shoes");
+        assert_eq!(refund.purchase_category_id, "shopping");
+        assert_eq!(refund.refund_category_id, "income");
+        assert_eq!(refund.days_after, 9);
+        assert!(refund.full_refund);
+
+        // The order already refunded isn't reused for a partial refund
+        insert_txn(&conn, "partial", "2025-02-12", "Jumia refund", 500.0, "income", Some("Jumia"));
+        let matches = refund_matches(&conn).unwrap();
+        assert_eq!(matches[0].refund_id, "partial");
+        assert_eq!(matches[0].purchase_id, "cable");
+        assert!(!matches[0].full_refund);
+
+        // A dismissed pairing is hidden, and its refund still isn't a sign error
+        insert_ignored_flag(&conn, "anomaly", "partial").unwrap();
+        let refund_ids: Vec<String> = refund_matches(&conn).unwrap().into_iter().map(|m| m.refund_id).collect();
+        assert_eq!(refund_ids, vec!["refund"]);
+        assert!(sign_errors(&conn).unwrap().iter().all(|e| e.transaction.id != "partial"));
+    }
+
+    #[test]
+    fn income_breakdown_separates_payroll_from_refunds() {
+        let conn = test_conn();
+        // Same employer every month, no "salary" in the description
+        insert_txn(&conn, "p1", "2025-01-28", "ACME LTD 00412", 150000.0, "income", None);
+        insert_txn(&conn, "p2", "2025-02-27", "ACME LTD 00588", 150000.0, "income", None);
+        insert_txn(&conn, "p3", "2025-03-28", "ACME LTD 00731", 150000.0, "income", None);
+        insert_txn(&conn, "r1", "2025-02-10", "REFUND - Jumia order 5521", 2500.0, "shopping", Some("Jumia"));
+        insert_txn(&conn, "i1", "2025-02-28", "Interest earned", 310.0, "income", None);
+        insert_txn(&conn, "t1", "2025-02-15", "Transfer from savings", 20000.0, "income", None);
+        insert_txn(&conn, "o1", "2025-02-20", "Sold old bike", 8000.0, "income", None);
+        insert_txn(&conn, "e1", "2025-02-11", "Naivas", -2150.0, "groceries", None);
+
+        let february = income_breakdown(&conn, Some("2025-02-01"), Some("2025-02-28")).unwrap();
+        let totals: Vec<(&str, f64, i64)> = february
+            .iter()
+            .map(|t| (t.income_type.as_str(), t.total, t.count))
+            .collect();
+        assert_eq!(
+            totals,
+            vec![
+                ("payroll", 150000.0, 1),
+                ("transfer", 20000.0, 1),
+                ("other", 8000.0, 1),
+                ("refund", 2500.0, 1),
+                ("interest", 310.0, 1),
+            ]
+        );
+
+        // Over the whole history every ACME deposit is payroll
+        let all = income_breakdown(&conn, None, None).unwrap();
+        let payroll = all.iter().find(|t| t.income_type == "payroll").unwrap();
+        assert_eq!((payroll.total, payroll.count), (450000.0, 3));
+    }
+
+    #[test]
+    fn spending_metrics_average_over_the_whole_window() {
+        let conn = test_conn();
+        insert_txn(&conn, "a", "2025-03-01", "Naivas", -300.0, "groceries", None);
+        insert_txn(&conn, "b", "2025-03-01", "Java House", -200.0, "dining", None);
+        insert_txn(&conn, "c", "2025-03-04", "Uber", -100.0, "transportation", None);
+        insert_txn(&conn, "d", "2025-03-10", "KPLC", -400.0, "utilities", None);
+        insert_txn(&conn, "pay", "2025-03-05", "Salary", 50000.0, "income", None);
+        insert_txn(&conn, "late", "2025-04-02", "Naivas", -999.0, "groceries", None);
+
+        // 1000 spent over 10 days in 4 transactions on 3 distinct days
+        let metrics = spending_metrics(&conn, Some("2025-03-01"), Some("2025-03-10")).unwrap();
+        assert_eq!(metrics.avg_daily_spend, 100.0);
+        assert_eq!(metrics.avg_transaction, 250.0);
+        assert_eq!(metrics.transaction_count, 4);
+        assert_eq!(metrics.days_with_spending, 3);
+        let largest = metrics.largest_day.unwrap();
+        assert_eq!((largest.date.as_str(), largest.total), ("2025-03-01", 500.0));
+
+        let quiet = spending_metrics(&conn, Some("2025-05-01"), Some("2025-05-31")).unwrap();
+        assert_eq!((quiet.avg_daily_spend, quiet.avg_transaction, quiet.transaction_count), (0.0, 0.0, 0));
+        assert!(quiet.largest_day.is_none());
+    }
+
+    #[test]
+    fn deductible_summary_counts_only_chosen_categories_in_the_year() {
+        let conn = test_conn();
+        insert_txn(&conn, "h1", "2024-03-10", "Aga Khan clinic", -4000.0, "healthcare", None);
+        insert_txn(&conn, "h2", "2024-12-31", "Goodlife pharmacy", -1500.0, "healthcare", None);
+        insert_txn(&conn, "e1", "2024-01-01", "School fees", -20000.0, "education", None);
+        // Outside the year on either side
+        insert_txn(&conn, "h3", "2023-12-31", "Pharmacy", -800.0, "healthcare", None);
+        insert_txn(&conn, "e2", "2025-01-01", "School fees", -20000.0, "education", None);
+        // Not a chosen category, and a refund into one that is
+        insert_txn(&conn, "d1", "2024-06-01", "Java House", -900.0, "dining", None);
+        insert_txn(&conn, "r1", "2024-05-01", "Clinic refund", 500.0, "healthcare", None);
+
+        let chosen = vec!["healthcare".to_string(), "education".to_string()];
+        let summary = deductible_summary(&conn, 2024, &chosen).unwrap();
+
+        let rows: Vec<(&str, &str, f64, i64)> = summary
+            .categories
+            .iter()
+            .map(|c| (c.category_id.as_str(), c.category_name.as_str(), c.total, c.count))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("education", "Education", 20000.0, 1), ("healthcare", "Healthcare", 5500.0, 2)]
+        );
+        assert_eq!(summary.total, 25500.0);
+
+        assert_eq!(deductible_summary(&conn, 2024, &[]).unwrap().total, 0.0);
+    }
+
+    fn set_import_webhook(conn: &rusqlite::Connection, listener: &tokio::net::TcpListener) {
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES ('import_webhook_url', ?1)", [url])
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_webhook_fires_from_the_shared_import_path() {
+        let mut conn = test_conn();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        set_import_webhook(&conn, &listener);
+
+        let transactions = parse_transactions_json(
+            r#"[{"date": "2025-03-04", "description": "Naivas", "amount": -2150.0, "category": "Groceries"}]"#,
+        )
+        .unwrap();
+        let summary = import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &ImportOptions::default()).unwrap();
+        assert_eq!(summary.inserted, 1);
+
+        let request = tokio::time::timeout(std::time::Duration::from_secs(5), serve_one_completion(&listener, ""))
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(json["event"], "transactions.imported");
+        assert_eq!(json["transactions"][0]["description"], "Naivas");
+        assert_eq!(json["transactions"][0]["category_id"], "groceries");
+    }
+
+    #[tokio::test]
+    async fn failed_import_webhook_leaves_saved_entries_in_place() {
+        let mut conn = test_conn();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        set_import_webhook(&conn, &listener);
+
+        let transactions = parse_transactions_json(
+            r#"[
+                {"date": "2025-03-04", "description": "Naivas", "amount": -2150.0, "category": "Groceries"},
+                {"date": "2025-03-05", "description": "Uber trip", "amount": -640.5, "category": "Transportation"}
+            ]"#,
+        )
+        .unwrap();
+        let summary = import_extracted_transactions(&mut conn, &transactions, "default", None, "manual", &ImportOptions::default()).unwrap();
+
+        // Hang up on the delivery without answering
+        let (socket, _) = tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        drop(socket);
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM ledger WHERE import_id = ?1", [summary.import_id.unwrap()], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+    }
 }
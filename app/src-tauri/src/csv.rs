@@ -0,0 +1,213 @@
+use anyhow::{anyhow, Result};
+
+use crate::models::ExtractedTransaction;
+
+/// CSV exports from other budgeting tools whose columns are known up front
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownFormat {
+    /// Date, Description, Amount, Transaction Type, Category. Amounts are
+    /// unsigned; the "debit"/"credit" type carries the sign.
+    Mint,
+    /// Date, Payee, Category, Memo, Outflow, Inflow, with the amount in
+    /// whichever of the two columns applies
+    Ynab,
+}
+
+impl KnownFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "mint" => Some(KnownFormat::Mint),
+            "ynab" => Some(KnownFormat::Ynab),
+            _ => None,
+        }
+    }
+
+    fn required_columns(self) -> &'static [&'static str] {
+        match self {
+            KnownFormat::Mint => &["Date", "Description", "Amount", "Transaction Type", "Category"],
+            KnownFormat::Ynab => &["Date", "Payee", "Category", "Memo", "Outflow", "Inflow"],
+        }
+    }
+}
+
+/// Parse an export in `format`. Columns are found by header name, so extra
+/// columns (Mint's "Account Name", YNAB's "Cleared") and their order don't matter.
+pub fn parse(text: &str, format: KnownFormat) -> Result<Vec<ExtractedTransaction>> {
+    let mut rows = records(text.trim_start_matches('\u{feff}')).into_iter();
+    let header = rows.next().ok_or_else(|| anyhow!("The file is empty"))?;
+
+    let find = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let missing: Vec<&str> = format.required_columns().iter().copied().filter(|c| find(c).is_none()).collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Not a {:?} export: missing column{} {}",
+            format,
+            if missing.len() == 1 { "" } else { "s" },
+            missing.join(", ")
+        ));
+    }
+    let col = |name: &str| find(name).unwrap_or_default();
+
+    let mut transactions = Vec::new();
+    for (index, row) in rows.enumerate() {
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        // Header is line 1
+        let line = index + 2;
+        let field = |name: &str| row.get(col(name)).map(|f| f.trim()).unwrap_or_default();
+
+        let date = parse_date(field("Date")).ok_or_else(|| anyhow!("Row {} has an invalid date '{}'", line, field("Date")))?;
+        let (description, merchant, amount) = match format {
+            KnownFormat::Mint => {
+                let amount = parse_amount(field("Amount"))
+                    .ok_or_else(|| anyhow!("Row {} has an invalid amount '{}'", line, field("Amount")))?
+                    .abs();
+                let amount = match field("Transaction Type").to_lowercase().as_str() {
+                    "debit" => -amount,
+                    "credit" => amount,
+                    other => return Err(anyhow!("Row {} has an unknown transaction type '{}'", line, other)),
+                };
+                let description = field("Description");
+                (description.to_string(), Some(description.to_string()), amount)
+            }
+            KnownFormat::Ynab => {
+                // One of the two columns is usually blank, which reads as zero
+                let column = |name: &str| match field(name) {
+                    "" => Ok(0.0),
+                    value => parse_amount(value)
+                        .map(f64::abs)
+                        .ok_or_else(|| anyhow!("Row {} has an invalid {} '{}'", line, name.to_lowercase(), value)),
+                };
+                let outflow = column("Outflow")?;
+                let inflow = column("Inflow")?;
+                let payee = field("Payee");
+                let memo = field("Memo");
+                let description = match (payee.is_empty(), memo.is_empty()) {
+                    (false, false) if !payee.eq_ignore_ascii_case(memo) => format!("{} - {}", payee, memo),
+                    (false, _) => payee.to_string(),
+                    (true, false) => memo.to_string(),
+                    (true, true) => "Unknown".to_string(),
+                };
+                (description, Some(payee.to_string()).filter(|p| !p.is_empty()), inflow - outflow)
+            }
+        };
+
+        transactions.push(ExtractedTransaction {
+            date,
+            description: if description.is_empty() { "Unknown".to_string() } else { description },
+            amount,
+            // Neither tool exports a currency; the import uses the account's
+            currency: String::new(),
+            category: field("Category").to_string(),
+            merchant: merchant.filter(|m| !m.is_empty()),
+            needs_review: None,
+            external_id: None,
+            balance: None,
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// Mint writes `M/D/YYYY`; YNAB follows the budget's date setting, of which
+/// ISO and US order are the common ones
+fn parse_date(value: &str) -> Option<String> {
+    ["%Y-%m-%d", "%m/%d/%Y", "%m/%d/%y"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(value, format).ok())
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// Currency-formatted amount such as "$1,234.50" or "(12.00)"; blank is None
+fn parse_amount(value: &str) -> Option<f64> {
+    let negative = (value.starts_with('(') && value.ends_with(')')) || value.starts_with('-');
+    let digits: String = value.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect();
+    let amount = digits.parse::<f64>().ok().filter(|a| a.is_finite())?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// Split CSV text into records of fields. Quoted fields may contain commas,
+/// doubled quotes and line breaks.
+fn records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mint_export_signs_amounts_by_transaction_type() {
+        let mint = "\u{feff}\"Date\",\"Description\",\"Original Description\",\"Amount\",\"Transaction Type\",\"Category\",\"Account Name\",\"Labels\",\"Notes\"
+\"3/04/2025\",\"Trader Joe's\",\"TRADER JOE'S #552\",\"54.20\",\"debit\",\"Groceries\",\"Checking\",\"\",\"\"
+\"3/15/2025\",\"Acme Payroll\",\"ACME CORP PAYROLL\",\"2,400.00\",\"credit\",\"Paycheck\",\"Checking\",\"\",\"\"
+";
+        let transactions = parse(mint, KnownFormat::Mint).unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        assert_eq!(transactions[0].date, "2025-03-04");
+        assert_eq!(transactions[0].amount, -54.20);
+        assert_eq!(transactions[0].description, "Trader Joe's");
+        assert_eq!(transactions[0].category, "Groceries");
+        assert_eq!(transactions[1].amount, 2400.0);
+        assert_eq!(transactions[1].merchant.as_deref(), Some("Acme Payroll"));
+
+        // A YNAB file isn't silently read as Mint
+        let err = parse("Date,Payee,Outflow,Inflow\n", KnownFormat::Mint).unwrap_err();
+        assert_eq!(err.to_string(), "Not a Mint export: missing columns Description, Amount, Transaction Type, Category");
+    }
+
+    #[test]
+    fn ynab_export_uses_outflow_and_inflow_columns() {
+        let ynab = "\"Account\",\"Flag\",\"Date\",\"Payee\",\"Category Group/Category\",\"Category Group\",\"Category\",\"Memo\",\"Outflow\",\"Inflow\",\"Cleared\"
+\"Checking\",\"\",\"03/02/2025\",\"Blue Bottle\",\"Food: Dining Out\",\"Food\",\"Dining Out\",\"latte, to go\",\"$5.75\",\"$0.00\",\"Cleared\"
+\"Checking\",\"\",\"03/05/2025\",\"Refund\",\"Inflow: Ready to Assign\",\"Inflow\",\"Ready to Assign\",\"\",\"$0.00\",\"$1,020.00\",\"Cleared\"
+";
+        let transactions = parse(ynab, KnownFormat::Ynab).unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        assert_eq!(transactions[0].date, "2025-03-02");
+        assert_eq!(transactions[0].amount, -5.75);
+        assert_eq!(transactions[0].description, "Blue Bottle - latte, to go");
+        assert_eq!(transactions[0].category, "Dining Out");
+        assert_eq!(transactions[1].amount, 1020.0);
+        assert_eq!(transactions[1].description, "Refund");
+
+        // A blank column is zero, but an unreadable one fails the row
+        let header = "Date,Payee,Category,Memo,Outflow,Inflow\n";
+        let transactions = parse(&format!("{}03/02/2025,Blue Bottle,Dining Out,,5.75,\n", header), KnownFormat::Ynab).unwrap();
+        assert_eq!(transactions[0].amount, -5.75);
+        let err = parse(&format!("{}03/02/2025,Blue Bottle,Dining Out,,5.75,\n03/03/2025,Cafe,Dining Out,,n/a,\n", header), KnownFormat::Ynab)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Row 3 has an invalid outflow 'n/a'");
+    }
+}
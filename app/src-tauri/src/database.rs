@@ -1,18 +1,193 @@
 use anyhow::Result;
 use rusqlite::Connection;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager};
 
-/// Get the path to the Yuki data directory
-pub fn get_data_dir(app: &AppHandle) -> Result<PathBuf> {
+/// File in the app data dir naming a relocated data directory. It can't live in
+/// the database, since the database is what moves.
+const LOCATION_FILE: &str = "data-location.txt";
+
+/// The platform app data dir, where Yuki keeps its data unless relocated
+fn default_data_dir(app: &AppHandle) -> Result<PathBuf> {
     let data_dir = app
         .path()
         .app_data_dir()
         .map_err(|e| anyhow::anyhow!("Failed to get app data dir: {}", e))?;
-    std::fs::create_dir_all(&data_dir)?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Get the path to the Yuki data directory
+pub fn get_data_dir(app: &AppHandle) -> Result<PathBuf> {
+    let data_dir = resolve_data_dir(&default_data_dir(app)?);
+    fs::create_dir_all(&data_dir)?;
     Ok(data_dir)
 }
 
+/// The directory named in `default_dir`'s location file, or `default_dir` itself
+fn resolve_data_dir(default_dir: &Path) -> PathBuf {
+    fs::read_to_string(default_dir.join(LOCATION_FILE))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_dir.to_path_buf())
+}
+
+/// Move yuki.db and documents/ to `target` and point the app at it. Connections
+/// are opened per command, so the next command after this returns uses the new
+/// location.
+pub fn set_data_dir(app: &AppHandle, target: &Path) -> Result<PathBuf> {
+    relocate_data_dir(&default_data_dir(app)?, target)
+}
+
+fn relocate_data_dir(default_dir: &Path, target: &Path) -> Result<PathBuf> {
+    // Stored document paths use the folder as resolved, so copy from that spelling
+    let current = resolve_data_dir(default_dir);
+    fs::create_dir_all(&current)?;
+    let canonical_current = current.canonicalize()?;
+
+    let resolved = resolve_path(target)?;
+    if resolved == canonical_current {
+        return Ok(resolved);
+    }
+    // The old folder is cleaned up after the move, which would take the new one with it
+    if resolved.starts_with(&canonical_current) {
+        return Err(anyhow::anyhow!(
+            "{} is inside the current data folder {}",
+            target.display(),
+            current.display()
+        ));
+    }
+    if resolved.exists() {
+        let mut entries = fs::read_dir(&resolved)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", target.display(), e))?;
+        if entries.next().is_some() {
+            return Err(anyhow::anyhow!("{} is not empty", target.display()));
+        }
+    }
+
+    fs::create_dir_all(target)
+        .map_err(|e| anyhow::anyhow!("Cannot create {}: {}", target.display(), e))?;
+    let target = target.canonicalize()?;
+
+    // Catch read-only mounts and locked vaults before anything is copied
+    let probe = target.join(".yuki-write-test");
+    fs::write(&probe, b"ok").map_err(|e| anyhow::anyhow!("{} is not writable: {}", target.display(), e))?;
+    fs::remove_file(&probe)?;
+
+    let mut copied = Vec::new();
+    let pointed = copy_data(&current, &target, &mut copied).and_then(|_| {
+        if target == default_dir.canonicalize()? {
+            let _ = fs::remove_file(default_dir.join(LOCATION_FILE));
+        } else {
+            fs::write(default_dir.join(LOCATION_FILE), target.to_string_lossy().as_bytes())?;
+        }
+        Ok(())
+    });
+    if let Err(e) = pointed {
+        // Leave the old location as the only copy
+        remove_copied(copied.iter().map(|(_, to)| to.as_path()));
+        return Err(e);
+    }
+
+    // The new copy is live; drop what was copied out of the old one
+    remove_copied(copied.iter().map(|(from, _)| from.as_path()));
+
+    log::info!("Moved data directory from {:?} to {:?}", current, target);
+    Ok(target)
+}
+
+/// `path` made absolute with symlinks resolved, even when its last components
+/// don't exist yet
+fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let absolute = if path.is_absolute() { path.to_path_buf() } else { std::env::current_dir()?.join(path) };
+    let mut missing = Vec::new();
+    let mut existing = absolute.as_path();
+    while !existing.exists() {
+        missing.push(existing.file_name().unwrap_or_default().to_os_string());
+        existing = existing
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("Cannot resolve {}", path.display()))?;
+    }
+    let mut resolved = existing.canonicalize()?;
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+/// Remove paths `copy_data` recorded, files first and folders only once empty, so
+/// nothing that was already there is touched
+fn remove_copied<'a>(paths: impl DoubleEndedIterator<Item = &'a Path>) {
+    // Recorded parents first, so reversed order empties each folder before removing it
+    for path in paths.rev() {
+        let _ = if path.is_dir() { fs::remove_dir(path) } else { fs::remove_file(path) };
+    }
+}
+
+/// Copy the database and documents from `from` into `to`, rewriting stored
+/// document paths to the new folder. Every (source, copy) pair created is
+/// pushed to `copied`, parents before their contents, even when a later step fails.
+fn copy_data(from: &Path, to: &Path, copied: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    let old_db = from.join("yuki.db");
+    let new_db = to.join("yuki.db");
+    if old_db.exists() {
+        // VACUUM INTO writes a consistent snapshot even if another connection is mid-write
+        let conn = Connection::open(&old_db)?;
+        copied.push((old_db.clone(), new_db.clone()));
+        conn.execute("VACUUM INTO ?1", [new_db.to_string_lossy()])?;
+    }
+
+    let old_documents = from.join("documents");
+    let new_documents = to.join("documents");
+    if old_documents.is_dir() {
+        copy_tree(&old_documents, &new_documents, copied)?;
+    } else {
+        fs::create_dir_all(&new_documents)?;
+    }
+
+    if new_db.exists() {
+        let conn = Connection::open(&new_db)?;
+        let rows: Vec<(String, String)> = {
+            let mut stmt = conn.prepare("SELECT id, filepath FROM documents")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            rows
+        };
+        for (id, old_path) in rows {
+            // Only files that lived in the old documents folder moved
+            let Ok(relative) = Path::new(&old_path).strip_prefix(&old_documents) else {
+                continue;
+            };
+            conn.execute(
+                "UPDATE documents SET filepath = ?1 WHERE id = ?2",
+                [new_documents.join(relative).to_string_lossy().as_ref(), id.as_str()],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Recursively copy the folder `from` to `to`, which must not exist yet
+fn copy_tree(from: &Path, to: &Path, copied: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    fs::create_dir(to)?;
+    copied.push((from.to_path_buf(), to.to_path_buf()));
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let (source, dest) = (entry.path(), to.join(entry.file_name()));
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_tree(&source, &dest, copied)?;
+        } else if file_type.is_file() {
+            fs::copy(&source, &dest)?;
+            copied.push((source, dest));
+        }
+    }
+    Ok(())
+}
+
 /// Get the path to the SQLite database
 pub fn get_db_path(app: &AppHandle) -> Result<PathBuf> {
     let data_dir = get_data_dir(app)?;
@@ -23,7 +198,70 @@ pub fn get_db_path(app: &AppHandle) -> Result<PathBuf> {
 pub async fn init_database(app: &AppHandle) -> Result<()> {
     let db_path = get_db_path(app)?;
     let conn = Connection::open(&db_path)?;
+    init_schema(&conn)?;
+
+    log::info!("Database initialized at {:?}", db_path);
+    Ok(())
+}
+
+/// Every table init_schema creates
+const SCHEMA_TABLES: &[&str] = &[
+    "documents",
+    "document_text",
+    "import_batches",
+    "categories",
+    "accounts",
+    "ledger",
+    "receipts",
+    "purchased_items",
+    "item_aliases",
+    "chat_history",
+    "conversation_sessions",
+    "conversation_messages",
+    "settings",
+    "currencies",
+    "recurring_templates",
+    "monthly_snapshots",
+    "snapshot_months",
+    "ignored_flags",
+];
+
+/// Columns init_schema adds to databases created before them
+const MIGRATED_COLUMNS: &[(&str, &str)] = &[
+    ("categories", "sort_order"),
+    ("categories", "is_pinned"),
+    ("ledger", "account_id"),
+    ("ledger", "original_amount"),
+    ("ledger", "original_currency"),
+    ("ledger", "needs_review"),
+    ("ledger", "external_id"),
+    ("ledger", "import_id"),
+    ("ledger", "exchange_rate"),
+    ("ledger", "document_attached"),
+];
 
+/// Tables (`name`) and migrated columns (`table.column`) init_schema would add
+/// to this database; empty when it is fully migrated
+pub fn missing_schema(conn: &Connection) -> Vec<String> {
+    let exists = |sql: &str, params: &[&str]| {
+        conn.query_row(sql, rusqlite::params_from_iter(params), |row| row.get::<_, i64>(0))
+            .map(|n| n > 0)
+            .unwrap_or(false)
+    };
+
+    let tables = SCHEMA_TABLES
+        .iter()
+        .filter(|table| !exists("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", &[table]))
+        .map(|table| table.to_string());
+    let columns = MIGRATED_COLUMNS
+        .iter()
+        .filter(|(table, column)| !exists("SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2", &[table, column]))
+        .map(|(table, column)| format!("{}.{}", table, column));
+    tables.chain(columns).collect()
+}
+
+/// Create tables, run migrations and seed default rows on an open connection
+pub fn init_schema(conn: &Connection) -> Result<()> {
     // Create documents table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS documents (
@@ -37,6 +275,31 @@ pub async fn init_database(app: &AppHandle) -> Result<()> {
         [],
     )?;
 
+    // Text extracted from each PDF document, so re-parsing doesn't extract it again
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS document_text (
+            document_id TEXT PRIMARY KEY,
+            text TEXT NOT NULL,
+            page_count INTEGER,
+            is_scanned INTEGER NOT NULL,
+            extracted_at TEXT NOT NULL,
+            FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // One row per statement or file import; ledger.import_id points back here
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_batches (
+            id TEXT PRIMARY KEY,
+            source TEXT NOT NULL,
+            document_id TEXT,
+            created_at TEXT NOT NULL,
+            transaction_count INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+
     // Create categories table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS categories (
@@ -50,6 +313,10 @@ pub async fn init_database(app: &AppHandle) -> Result<()> {
         [],
     )?;
 
+    // User ordering for the category list: pinned first, then sort_order, then name
+    let _ = conn.execute("ALTER TABLE categories ADD COLUMN sort_order INTEGER", []);
+    let _ = conn.execute("ALTER TABLE categories ADD COLUMN is_pinned INTEGER NOT NULL DEFAULT 0", []);
+
     // Create accounts table for multi-account support
     conn.execute(
         "CREATE TABLE IF NOT EXISTS accounts (
@@ -89,6 +356,29 @@ pub async fn init_database(app: &AppHandle) -> Result<()> {
     // Add account_id column if it doesn't exist (for existing databases)
     let _ = conn.execute("ALTER TABLE ledger ADD COLUMN account_id TEXT", []);
 
+    // Amount and currency as they appeared on the source document, when the
+    // import converted them into the account's currency
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN original_amount REAL", []);
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN original_currency TEXT", []);
+
+    // Set when an extracted row should be checked by hand; cleared by mark_reviewed
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN needs_review INTEGER", []);
+
+    // Id the bank gave the transaction (OFX FITID), unique within an account
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN external_id TEXT", []);
+
+    // Import batch the row arrived in, so a bad import can be rolled back as a whole
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN import_id TEXT", []);
+
+    // Rate into the primary currency actually paid on this row (set_transaction_rate);
+    // when set it takes precedence over the currency's conversion_rate
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN exchange_rate REAL", []);
+
+    // Set when document_id was attached afterwards (attach_document_to_transaction)
+    // rather than the row coming from that document; deleting the document then
+    // only unlinks the row
+    let _ = conn.execute("ALTER TABLE ledger ADD COLUMN document_attached INTEGER NOT NULL DEFAULT 0", []);
+
     // Migration: Drop old receipts/purchased_items tables if they have NOT NULL constraint on ledger_id
     // This is needed because SQLite doesn't support ALTER COLUMN to remove NOT NULL
     // Check if migration is needed by looking at table schema
@@ -145,6 +435,17 @@ pub async fn init_database(app: &AppHandle) -> Result<()> {
         [],
     )?;
 
+    // Maps purchased item names onto one canonical name for reporting.
+    // `pattern` is a lowercase name where `*` matches any run of characters.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS item_aliases (
+            pattern TEXT PRIMARY KEY,
+            canonical TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
     // Create chat_history table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS chat_history (
@@ -203,6 +504,90 @@ pub async fn init_database(app: &AppHandle) -> Result<()> {
         [],
     )?;
 
+    // Bills posted to the ledger automatically each month by post_due_recurring.
+    // last_posted_cycle (YYYY-MM) is the month the template was last posted for.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS recurring_templates (
+            id TEXT PRIMARY KEY,
+            merchant TEXT NOT NULL,
+            amount REAL NOT NULL,
+            category_id TEXT NOT NULL,
+            account_id TEXT NOT NULL DEFAULT 'default',
+            day_of_month INTEGER NOT NULL,
+            active INTEGER NOT NULL DEFAULT 1,
+            last_posted_cycle TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id),
+            FOREIGN KEY (category_id) REFERENCES categories(id)
+        )",
+        [],
+    )?;
+
+    // Per-month, per-category expense totals (primary currency) for closed months,
+    // built by build_snapshots. snapshot_months lists the months that are built,
+    // including ones with no spending.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS monthly_snapshots (
+            month TEXT NOT NULL,
+            category_id TEXT NOT NULL,
+            total REAL NOT NULL,
+            count INTEGER NOT NULL,
+            PRIMARY KEY (month, category_id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS snapshot_months (
+            month TEXT PRIMARY KEY,
+            built_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Any change to a ledger amount, date, currency, rate or category drops the snapshot
+    // of the month(s) it touches, whichever command made it. Adding, removing or re-rating a currency affects every month.
+    // The update trigger is recreated because databases from before exchange_rate
+    // have one that doesn't watch it.
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS ledger_insert_invalidates_snapshot AFTER INSERT ON ledger BEGIN
+            DELETE FROM snapshot_months WHERE month = strftime('%Y-%m', NEW.date);
+            DELETE FROM monthly_snapshots WHERE month = strftime('%Y-%m', NEW.date);
+        END;
+        DROP TRIGGER IF EXISTS ledger_update_invalidates_snapshot;
+        CREATE TRIGGER ledger_update_invalidates_snapshot
+            AFTER UPDATE OF date, amount, currency, exchange_rate, category_id ON ledger BEGIN
+            DELETE FROM snapshot_months WHERE month IN (strftime('%Y-%m', OLD.date), strftime('%Y-%m', NEW.date));
+            DELETE FROM monthly_snapshots WHERE month IN (strftime('%Y-%m', OLD.date), strftime('%Y-%m', NEW.date));
+        END;
+        CREATE TRIGGER IF NOT EXISTS ledger_delete_invalidates_snapshot AFTER DELETE ON ledger BEGIN
+            DELETE FROM snapshot_months WHERE month = strftime('%Y-%m', OLD.date);
+            DELETE FROM monthly_snapshots WHERE month = strftime('%Y-%m', OLD.date);
+        END;
+        CREATE TRIGGER IF NOT EXISTS currency_update_invalidates_snapshots AFTER UPDATE OF conversion_rate ON currencies BEGIN
+            DELETE FROM snapshot_months;
+            DELETE FROM monthly_snapshots;
+        END;
+        CREATE TRIGGER IF NOT EXISTS currency_insert_invalidates_snapshots AFTER INSERT ON currencies BEGIN
+            DELETE FROM snapshot_months;
+            DELETE FROM monthly_snapshots;
+        END;
+        CREATE TRIGGER IF NOT EXISTS currency_delete_invalidates_snapshots AFTER DELETE ON currencies BEGIN
+            DELETE FROM snapshot_months;
+            DELETE FROM monthly_snapshots;
+        END;",
+    )?;
+
+    // Create ignored_flags table for review flags the user has dismissed
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ignored_flags (
+            flag_type TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (flag_type, target_id)
+        )",
+        [],
+    )?;
+
     // Insert default categories if they don't exist
     let default_categories = vec![
         ("income", "Income", "#22c55e"),
@@ -259,7 +644,6 @@ pub async fn init_database(app: &AppHandle) -> Result<()> {
         [],
     )?;
 
-    log::info!("Database initialized at {:?}", db_path);
     Ok(())
 }
 
@@ -271,3 +655,92 @@ pub fn get_connection(app: &AppHandle) -> Result<Connection> {
     conn.busy_timeout(std::time::Duration::from_secs(30))?;
     Ok(conn)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relocating_moves_db_and_documents_and_later_opens_use_them() {
+        let root = std::env::temp_dir().join(format!("yuki-relocate-{}", uuid::Uuid::new_v4()));
+        let default_dir = root.join("app-data");
+        let vault = root.join("vault");
+        fs::create_dir_all(default_dir.join("documents")).unwrap();
+
+        let doc_path = default_dir.join("documents").join("doc1_statement.pdf");
+        fs::write(&doc_path, b"%PDF-1.4").unwrap();
+        {
+            let conn = Connection::open(default_dir.join("yuki.db")).unwrap();
+            init_schema(&conn).unwrap();
+            conn.execute(
+                "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at)
+                 VALUES ('doc1', 'statement.pdf', ?1, 'application/pdf', 'abc', '2025-01-01')",
+                [doc_path.to_string_lossy()],
+            )
+            .unwrap();
+        }
+
+        let moved_to = relocate_data_dir(&default_dir, &vault).unwrap();
+        assert_eq!(moved_to, vault.canonicalize().unwrap());
+        assert_eq!(resolve_data_dir(&default_dir), moved_to);
+        assert!(!default_dir.join("yuki.db").exists());
+        assert!(!doc_path.exists());
+
+        // What get_connection would open now
+        let conn = Connection::open(resolve_data_dir(&default_dir).join("yuki.db")).unwrap();
+        let filepath: String = conn
+            .query_row("SELECT filepath FROM documents WHERE id = 'doc1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(Path::new(&filepath), moved_to.join("documents").join("doc1_statement.pdf"));
+        assert!(Path::new(&filepath).exists());
+
+        // A folder that already holds anything is refused rather than overwritten
+        let other = root.join("other");
+        fs::create_dir_all(other.join("documents")).unwrap();
+        fs::write(other.join("documents").join("mine.txt"), b"keep").unwrap();
+        assert!(relocate_data_dir(&default_dir, &other).is_err());
+        assert!(other.join("documents").join("mine.txt").exists());
+        assert_eq!(resolve_data_dir(&default_dir), moved_to);
+
+        // So is a folder inside the current one, which the cleanup would delete
+        assert!(relocate_data_dir(&default_dir, &moved_to.join("documents").join("nested")).is_err());
+        assert!(!moved_to.join("documents").join("nested").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn relocating_copies_document_subfolders() {
+        let root = std::env::temp_dir().join(format!("yuki-relocate-tree-{}", uuid::Uuid::new_v4()));
+        let default_dir = root.join("app-data");
+        let nested = default_dir.join("documents").join("2025").join("receipt.jpg");
+        fs::create_dir_all(nested.parent().unwrap()).unwrap();
+        fs::write(&nested, b"jpeg").unwrap();
+        // Not Yuki's, so it stays behind
+        fs::write(default_dir.join("notes.txt"), b"mine").unwrap();
+        {
+            let conn = Connection::open(default_dir.join("yuki.db")).unwrap();
+            init_schema(&conn).unwrap();
+            conn.execute(
+                "INSERT INTO documents (id, filename, filepath, filetype, hash, uploaded_at)
+                 VALUES ('doc1', 'receipt.jpg', ?1, 'image/jpeg', 'abc', '2025-01-01')",
+                [nested.to_string_lossy()],
+            )
+            .unwrap();
+        }
+
+        let moved_to = relocate_data_dir(&default_dir, &root.join("vault")).unwrap();
+        let moved = moved_to.join("documents").join("2025").join("receipt.jpg");
+        assert_eq!(fs::read(&moved).unwrap(), b"jpeg");
+        assert!(!default_dir.join("documents").exists());
+        assert!(default_dir.join("notes.txt").exists());
+
+        let conn = Connection::open(moved_to.join("yuki.db")).unwrap();
+        let filepath: String = conn
+            .query_row("SELECT filepath FROM documents WHERE id = 'doc1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(Path::new(&filepath), moved);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
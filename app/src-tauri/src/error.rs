@@ -0,0 +1,132 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Error returned by every command.
+///
+/// Serialized for the frontend as `{ "kind": "...", "message": "..." }`, where
+/// `kind` is one of "noProvider", "database", "llm", "notFound", "validation" or
+/// "alreadyImported" and `message` is the human-readable text. "alreadyImported"
+/// also carries `existingDocumentId`. Both field names and kind values are part
+/// of the frontend contract; add kinds rather than renaming them.
+#[derive(Debug, thiserror::Error)]
+pub enum YukiError {
+    #[error("No LLM provider configured")]
+    NoProvider,
+    #[error("{0}")]
+    Database(String), // SQLite or data-directory failure
+    #[error("{0}")]
+    Llm(String), // Provider request, response or parsing failure
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Validation(String), // Bad input from the caller
+    #[error("This document was already imported")]
+    AlreadyImported { existing_document_id: String }, // Same file hash as a stored document
+}
+
+impl YukiError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            YukiError::NoProvider => "noProvider",
+            YukiError::Database(_) => "database",
+            YukiError::Llm(_) => "llm",
+            YukiError::NotFound(_) => "notFound",
+            YukiError::Validation(_) => "validation",
+            YukiError::AlreadyImported { .. } => "alreadyImported",
+        }
+    }
+
+    // Constructors for `map_err` on anyhow results, where the source doesn't say which kind it is
+
+    pub fn database(e: impl std::fmt::Display) -> Self {
+        YukiError::Database(e.to_string())
+    }
+
+    pub fn llm(e: impl std::fmt::Display) -> Self {
+        YukiError::Llm(e.to_string())
+    }
+
+    pub fn validation(e: impl std::fmt::Display) -> Self {
+        YukiError::Validation(e.to_string())
+    }
+}
+
+impl Serialize for YukiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = if matches!(self, YukiError::AlreadyImported { .. }) { 3 } else { 2 };
+        let mut state = serializer.serialize_struct("YukiError", fields)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let YukiError::AlreadyImported { existing_document_id } = self {
+            state.serialize_field("existingDocumentId", existing_document_id)?;
+        }
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for YukiError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => YukiError::NotFound("Record not found".to_string()),
+            other => YukiError::Database(other.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for YukiError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => YukiError::NotFound(e.to_string()),
+            _ => YukiError::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for YukiError {
+    fn from(e: serde_json::Error) -> Self {
+        YukiError::Validation(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_variant_serializes_with_its_own_kind() {
+        let errors = [
+            (YukiError::NoProvider, "noProvider", "No LLM provider configured"),
+            (YukiError::Database("disk I/O error".to_string()), "database", "disk I/O error"),
+            (YukiError::Llm("Anthropic API error: overloaded".to_string()), "llm", "Anthropic API error: overloaded"),
+            (YukiError::NotFound("Transaction 't1' not found".to_string()), "notFound", "Transaction 't1' not found"),
+            (YukiError::Validation("Merchant pattern must not be empty".to_string()), "validation", "Merchant pattern must not be empty"),
+        ];
+
+        let mut kinds = std::collections::HashSet::new();
+        for (error, kind, message) in errors {
+            let json = serde_json::to_value(&error).unwrap();
+            assert_eq!(json, serde_json::json!({ "kind": kind, "message": message }));
+            kinds.insert(kind);
+        }
+        assert_eq!(kinds.len(), 5);
+
+        let duplicate = YukiError::AlreadyImported { existing_document_id: "doc1".to_string() };
+        assert_eq!(
+            serde_json::to_value(&duplicate).unwrap(),
+            serde_json::json!({
+                "kind": "alreadyImported",
+                "message": "This document was already imported",
+                "existingDocumentId": "doc1"
+            })
+        );
+    }
+
+    #[test]
+    fn source_errors_map_to_the_right_kind() {
+        assert_eq!(YukiError::from(rusqlite::Error::QueryReturnedNoRows).kind(), "notFound");
+        assert_eq!(YukiError::from(rusqlite::Error::InvalidQuery).kind(), "database");
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(YukiError::from(missing).kind(), "notFound");
+        let bad_json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert_eq!(YukiError::from(bad_json).kind(), "validation");
+    }
+}
@@ -1,24 +1,40 @@
+mod backup;
+mod chart;
 mod commands;
+mod csv;
 mod database;
+mod error;
 mod llm;
+mod logging;
 mod models;
+mod ofx;
+mod plaintext;
+mod rate_limit;
+mod webhook;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(
             tauri_plugin_log::Builder::default()
-                .level(log::LevelFilter::Info)
+                // The effective level is set at runtime from the log_level setting
+                .level(log::LevelFilter::Trace)
                 .build()
         )
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
             // Initialize database on startup
+            log::set_max_level(log::LevelFilter::Info);
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 if let Err(e) = database::init_database(&app_handle).await {
                     log::error!("Failed to initialize database: {}", e);
+                    return;
+                }
+                if let Ok(settings) = commands::get_settings(app_handle.clone()).await {
+                    logging::apply_settings(&settings.log_level, settings.log_bodies);
+                    llm::set_local_only(settings.local_only);
                 }
             });
             Ok(())
@@ -28,23 +44,63 @@ pub fn run() {
             commands::has_llm_provider,
             commands::get_settings,
             commands::save_settings,
+            commands::export_settings_redacted,
+            commands::set_data_directory,
+            commands::get_provider_presets,
+            commands::get_provider_capabilities,
             commands::list_models,
             commands::test_llm_connection,
+            commands::run_diagnostics,
             // Document commands
             commands::save_uploaded_file,
             commands::save_document,
             commands::get_all_documents,
             commands::delete_document,
             commands::extract_pdf_text,
+            commands::extract_document_text,
+            commands::detect_statement_metadata,
+            commands::estimate_parse_cost,
+            commands::verify_documents,
+            commands::find_orphaned_files,
+            commands::cleanup_orphaned_files,
             // Ledger commands
             commands::save_ledger_entry,
             commands::save_ledger_entries_batch,
+            commands::list_import_batches,
+            commands::rollback_import,
+            commands::import_transactions_json,
+            commands::import_ofx,
+            commands::import_known_format,
+            commands::import_statement_transactions,
             commands::get_all_transactions,
+            commands::get_transaction,
             commands::delete_transaction,
+            commands::set_transaction_note,
+            commands::set_transaction_rate,
+            commands::get_transactions_needing_review,
+            commands::mark_reviewed,
+            commands::get_uncategorized_transactions,
+            commands::bulk_categorize,
+            commands::attach_document_to_transaction,
+            commands::recategorize_by_merchant,
+            commands::get_merchants,
+            commands::add_recurring_template,
+            commands::get_recurring_templates,
+            commands::set_recurring_template_active,
+            commands::delete_recurring_template,
+            commands::post_due_recurring,
+            commands::find_all_duplicate_groups,
+            commands::merge_duplicates,
+            commands::ignore_flag,
+            commands::list_ignored_flags,
             // Category commands
             commands::get_all_categories,
             commands::get_category_names,
             commands::add_category,
+            commands::update_category,
+            commands::assign_category_colors,
+            commands::set_category_order,
+            commands::pin_category,
             // Receipt commands
             commands::save_receipt,
             // Purchased items commands
@@ -52,29 +108,91 @@ pub fn run() {
             commands::save_purchased_items,
             commands::get_purchased_items,
             commands::delete_purchased_item,
+            commands::add_item_alias,
+            commands::get_item_spending_normalized,
+            commands::get_most_expensive_items,
             // Conversation commands
             commands::start_conversation,
             commands::get_or_create_session,
             commands::clear_conversation,
+            commands::get_session_messages,
+            commands::get_current_context,
             // Account commands
             commands::get_all_accounts,
             commands::add_account,
             commands::delete_account,
+            commands::reassign_account,
+            commands::backfill_account_ids,
+            commands::get_balance_as_of,
+            commands::get_cash_flow,
             // Currency commands
             commands::get_all_currencies,
             commands::add_currency,
             commands::update_currency,
             commands::delete_currency,
             commands::set_primary_currency,
+            commands::change_primary_currency,
             commands::get_default_currency,
             commands::set_default_currency,
+            commands::fix_invalid_currencies,
+            commands::format_amount,
+            commands::get_effective_exchange_rates,
+            // Analytics commands
+            commands::get_spending_by_weekday,
+            commands::get_spending_by_merchant_frequency,
+            commands::get_merchant_stats,
+            commands::get_category_month_comparison,
+            commands::get_year_over_year,
+            commands::get_category_trend,
+            commands::get_spending_trend_direction,
+            commands::build_snapshots,
+            commands::detect_price_increases,
+            commands::detect_missing_recurring,
+            commands::get_merchant_cadence,
+            commands::get_top_transactions,
+            commands::get_item_category_spending,
+            commands::get_income_breakdown,
+            commands::detect_refunds,
+            commands::detect_sign_errors,
+            commands::get_savings_rate,
+            commands::get_financial_runway,
+            commands::get_savings_goals,
+            commands::set_savings_goal,
+            commands::evaluate_purchase_impact,
+            commands::get_spending_to_income_ratios,
+            commands::get_essential_vs_discretionary,
+            commands::set_category_budget,
+            commands::get_weekly_allowance,
+            commands::get_budget_alerts,
+            commands::set_category_bucket,
+            commands::get_deductible_summary,
+            commands::get_daily_spending_calendar,
+            commands::get_spending_metrics,
+            commands::get_amount_percentiles,
+            commands::render_chart_image,
+            // Backup commands
+            commands::export_backup_bundle,
+            commands::import_backup_bundle,
+            commands::export_ledger_ndjson,
+            commands::export_config,
+            commands::import_config,
+            commands::reset_data,
             // Query commands
             commands::process_query,
+            commands::regenerate_last_response,
+            commands::preview_query_sql,
+            commands::execute_approved_query,
             commands::parse_document_text,
+            commands::parse_pasted_text,
             commands::parse_receipt_text,
+            commands::reparse_document,
+            commands::parse_statement_chunks,
+            commands::reparse_chunk,
             commands::parse_receipt_image,
+            commands::parse_receipt_folder,
             commands::parse_statement_image,
             commands::detect_expense,
+            commands::embed_texts,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
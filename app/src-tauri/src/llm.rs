@@ -2,19 +2,170 @@ use anyhow::Result;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
 use reqwest::Client;
 use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::logging;
+use crate::rate_limit::PROVIDER_LIMITER;
 use crate::models::{
     ConversationMessage, ExpenseDetectionResult, ExtractedTransaction, LLMProvider, ParsedReceipt,
-    ResponseCard, ResponseData, TextContent,
+    ProviderCapabilities, ProviderPreset, ResponseCard, ResponseData, StatementChunk, StatementMetadata,
+    TextContent,
 };
 
+// When set, only providers running on this machine may be called
+static LOCAL_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Apply the user's local-only setting
+pub fn set_local_only(local_only: bool) {
+    LOCAL_ONLY.store(local_only, Ordering::Relaxed);
+}
+
+/// Whether an endpoint points at this machine
+pub fn is_loopback_endpoint(endpoint: &str) -> bool {
+    let url = match reqwest::Url::parse(endpoint) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    match url.host_str() {
+        Some(host) => {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            host.eq_ignore_ascii_case("localhost")
+                || host
+                    .parse::<std::net::IpAddr>()
+                    .map(|ip| ip.is_loopback())
+                    .unwrap_or(false)
+        }
+        None => false,
+    }
+}
+
+/// Refuse to send anything off-machine while local-only mode is on
+pub fn check_provider_allowed(provider: &LLMProvider, local_only: bool) -> Result<()> {
+    if local_only && !(provider.is_local && is_loopback_endpoint(&provider.endpoint)) {
+        return Err(anyhow::anyhow!(
+            "Local-only mode is on: refusing to contact {} at {}",
+            provider.provider_type,
+            provider.endpoint
+        ));
+    }
+    Ok(())
+}
+
+/// One row per supported provider type; presets and capabilities both read
+/// from here. The flags are read off the dispatch in this module: vision from
+/// call_llm_with_vision, JSON mode from the request bodies and embeddings
+/// from embed_texts.
+struct ProviderSpec {
+    provider_type: &'static str,
+    name: &'static str,
+    endpoint: &'static str,
+    model: Option<&'static str>,
+    vision: bool,
+    json_mode: bool,
+    embeddings: bool,
+}
+
+// Anthropic does JSON mode via the assistant prefill; LM Studio rejects response_format
+const PROVIDER_SPECS: [ProviderSpec; 6] = [
+    ProviderSpec { provider_type: "ollama", name: "Ollama", endpoint: "http://localhost:11434", model: Some("llama3.2"), vision: false, json_mode: true, embeddings: true },
+    ProviderSpec { provider_type: "lmstudio", name: "LM Studio", endpoint: "http://localhost:1234/v1", model: None, vision: false, json_mode: false, embeddings: true },
+    ProviderSpec { provider_type: "anthropic", name: "Anthropic (Claude)", endpoint: "https://api.anthropic.com/v1", model: Some("claude-sonnet-4-20250514"), vision: true, json_mode: true, embeddings: false },
+    ProviderSpec { provider_type: "openai", name: "OpenAI", endpoint: "https://api.openai.com/v1", model: Some("gpt-4o"), vision: true, json_mode: true, embeddings: true },
+    ProviderSpec { provider_type: "google", name: "Google (Gemini)", endpoint: "https://generativelanguage.googleapis.com/v1", model: Some("gemini-2.0-flash"), vision: false, json_mode: true, embeddings: false },
+    ProviderSpec { provider_type: "openrouter", name: "OpenRouter", endpoint: "https://openrouter.ai/api/v1", model: Some("anthropic/claude-sonnet-4"), vision: true, json_mode: true, embeddings: true },
+];
+
+fn provider_spec(provider_type: &str) -> Option<&'static ProviderSpec> {
+    PROVIDER_SPECS.iter().find(|spec| spec.provider_type == provider_type)
+}
+
+/// Default endpoint, model and capabilities for every supported provider type
+pub fn provider_presets() -> Vec<ProviderPreset> {
+    // Local servers need no key; hosted APIs do
+    PROVIDER_SPECS
+        .iter()
+        .map(|spec| {
+            let is_local = is_loopback_endpoint(spec.endpoint);
+            ProviderPreset {
+                provider_type: spec.provider_type.to_string(),
+                name: spec.name.to_string(),
+                default_endpoint: spec.endpoint.to_string(),
+                default_model: spec.model.map(str::to_string),
+                requires_api_key: !is_local,
+                supports_vision: spec.vision,
+                is_local,
+            }
+        })
+        .collect()
+}
+
+pub fn provider_preset(provider_type: &str) -> Option<ProviderPreset> {
+    provider_presets().into_iter().find(|p| p.provider_type == provider_type)
+}
+
+/// What a provider type can do here. Every known type lists models through
+/// list_provider_models; none streams, every request waits for the whole response.
+pub fn provider_capabilities(provider_type: &str) -> ProviderCapabilities {
+    let spec = provider_spec(provider_type);
+    ProviderCapabilities {
+        vision: spec.is_some_and(|s| s.vision),
+        streaming: false,
+        json_mode: spec.is_some_and(|s| s.json_mode),
+        embeddings: spec.is_some_and(|s| s.embeddings),
+        model_listing: spec.is_some(),
+    }
+}
+
+/// Published list prices, in USD per million tokens, and the context window
+/// a model accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub context_tokens: usize,
+}
+
+/// Pricing for the provider's model, matched on the model name so OpenRouter's
+/// "anthropic/claude-sonnet-4" prices like Anthropic's own. Local models cost
+/// nothing; None means the model isn't one we know a price for.
+pub fn model_pricing(provider: &LLMProvider) -> Option<ModelPricing> {
+    let pricing = |input_per_million, output_per_million, context_tokens| ModelPricing {
+        input_per_million,
+        output_per_million,
+        context_tokens,
+    };
+    if provider.is_local {
+        // Most local servers default to an 8k context
+        return Some(pricing(0.0, 0.0, 8_192));
+    }
+
+    // More specific names first: "gpt-4o-mini" also contains "gpt-4o"
+    const PRICES: [(&str, f64, f64, usize); 10] = [
+        ("opus", 15.0, 75.0, 200_000),
+        ("sonnet", 3.0, 15.0, 200_000),
+        ("haiku", 0.8, 4.0, 200_000),
+        ("gpt-4o-mini", 0.15, 0.6, 128_000),
+        ("gpt-4o", 2.5, 10.0, 128_000),
+        ("gpt-4.1-mini", 0.4, 1.6, 1_000_000),
+        ("gpt-4.1", 2.0, 8.0, 1_000_000),
+        ("gemini-2.0-flash", 0.1, 0.4, 1_000_000),
+        ("flash", 0.3, 2.5, 1_000_000),
+        ("gemini", 1.25, 10.0, 1_000_000),
+    ];
+    let model = provider.model.to_lowercase();
+    PRICES
+        .iter()
+        .find(|(name, ..)| model.contains(name))
+        .map(|&(_, input, output, context)| pricing(input, output, context))
+}
+
 /// Encode bytes as base64 string
 fn base64_encode(data: &[u8]) -> String {
     BASE64_STANDARD.encode(data)
 }
 
 /// Build conversation context from message history for inclusion in prompts
-fn build_conversation_context(history: &[ConversationMessage]) -> String {
+pub fn build_conversation_context(history: &[ConversationMessage]) -> String {
     if history.is_empty() {
         return String::new();
     }
@@ -35,35 +186,104 @@ fn build_conversation_context(history: &[ConversationMessage]) -> String {
     context
 }
 
+/// Structured output mode to request from the provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    JsonObject,
+    JsonArray,
+}
+
+impl ResponseFormat {
+    /// Opening character used to prefill the assistant turn on Anthropic
+    fn prefill(self) -> &'static str {
+        match self {
+            ResponseFormat::JsonObject => "{",
+            ResponseFormat::JsonArray => "[",
+        }
+    }
+}
+
+/// Whether a provider error is the model rejecting the requested JSON mode,
+/// in which case the call is retried once as plain text
+fn is_unsupported_format_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["response_format", "json_object", "responsemimetype", "response_mime_type", "prefill"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+/// Shown when a model answers with nothing at all, e.g. an overloaded Ollama
+/// or a content filter that blanks the reply
+pub const EMPTY_RESPONSE_MESSAGE: &str = "The model returned no content. Please try again.";
+
+fn is_empty_response(result: &Result<String>) -> bool {
+    result.as_ref().is_ok_and(|text| text.trim().is_empty())
+}
+
 /// Build the appropriate request for different LLM providers
 pub async fn call_llm(
     provider: &LLMProvider,
     prompt: &str,
     system_prompt: Option<&str>,
+    response_format: Option<ResponseFormat>,
 ) -> Result<String> {
+    check_provider_allowed(provider, LOCAL_ONLY.load(Ordering::Relaxed))?;
+    PROVIDER_LIMITER
+        .acquire(&provider.provider_type, provider.requests_per_minute)
+        .await;
     let client = Client::new();
 
     log::info!("Calling LLM provider: {}", provider.provider_type);
-    log::debug!("Prompt: {}", prompt);
+    log::debug!("Prompt: {}", logging::body(prompt));
+
+    let mut format = response_format;
+    let mut result = send_llm_request(&client, provider, prompt, system_prompt, format).await;
+    if let Err(e) = &result {
+        if format.is_some() && is_unsupported_format_error(&e.to_string()) {
+            log::warn!("{} rejected JSON mode, retrying without it", provider.model);
+            format = None;
+            result = send_llm_request(&client, provider, prompt, system_prompt, format).await;
+        }
+    }
 
-    let result = match provider.provider_type.as_str() {
-        "anthropic" => call_anthropic(&client, provider, prompt, system_prompt).await,
-        "openai" | "openrouter" | "lmstudio" => {
-            call_openai_compatible(&client, provider, prompt, system_prompt).await
+    // An empty reply is usually transient; ask once more before giving up
+    if is_empty_response(&result) {
+        log::warn!("{} returned an empty response, retrying once", provider.model);
+        PROVIDER_LIMITER
+            .acquire(&provider.provider_type, provider.requests_per_minute)
+            .await;
+        result = send_llm_request(&client, provider, prompt, system_prompt, format).await;
+        if is_empty_response(&result) {
+            result = Err(anyhow::anyhow!(EMPTY_RESPONSE_MESSAGE));
         }
-        "ollama" => call_ollama(&client, provider, prompt, system_prompt).await,
-        "google" => call_google(&client, provider, prompt, system_prompt).await,
-        _ => Err(anyhow::anyhow!("Unsupported provider: {}", provider.provider_type)),
-    };
+    }
 
     match &result {
-        Ok(response) => log::debug!("LLM response: {}", response),
-        Err(e) => log::error!("LLM error: {}", e),
+        Ok(response) => log::debug!("LLM response: {}", logging::body(response)),
+        Err(e) => log::error!("LLM error: {}", logging::redact(&e.to_string())),
     }
 
     result
 }
 
+async fn send_llm_request(
+    client: &Client,
+    provider: &LLMProvider,
+    prompt: &str,
+    system_prompt: Option<&str>,
+    response_format: Option<ResponseFormat>,
+) -> Result<String> {
+    match provider.provider_type.as_str() {
+        "anthropic" => call_anthropic(client, provider, prompt, system_prompt, response_format).await,
+        "openai" | "openrouter" | "lmstudio" => {
+            call_openai_compatible(client, provider, prompt, system_prompt, response_format).await
+        }
+        "ollama" => call_ollama(client, provider, prompt, system_prompt, response_format).await,
+        "google" => call_google(client, provider, prompt, system_prompt, response_format).await,
+        _ => Err(anyhow::anyhow!("Unsupported provider: {}", provider.provider_type)),
+    }
+}
+
 /// Call LLM with vision (image/PDF input)
 pub async fn call_llm_with_vision(
     provider: &LLMProvider,
@@ -72,19 +292,37 @@ pub async fn call_llm_with_vision(
     media_type: &str,
     system_prompt: Option<&str>,
 ) -> Result<String> {
+    check_provider_allowed(provider, LOCAL_ONLY.load(Ordering::Relaxed))?;
+    PROVIDER_LIMITER
+        .acquire(&provider.provider_type, provider.requests_per_minute)
+        .await;
     let client = Client::new();
 
     log::info!("Calling LLM provider with vision: {} (media: {})", provider.provider_type, media_type);
 
-    let result = match provider.provider_type.as_str() {
-        "anthropic" => call_anthropic_vision(&client, provider, prompt, image_base64, media_type, system_prompt).await,
-        "openai" | "openrouter" => call_openai_vision(&client, provider, prompt, image_base64, media_type, system_prompt).await,
-        _ => Err(anyhow::anyhow!("Vision not supported for provider: {}", provider.provider_type)),
+    let send = || async {
+        match provider.provider_type.as_str() {
+            "anthropic" => call_anthropic_vision(&client, provider, prompt, image_base64, media_type, system_prompt).await,
+            "openai" | "openrouter" => call_openai_vision(&client, provider, prompt, image_base64, media_type, system_prompt).await,
+            _ => Err(anyhow::anyhow!("Vision not supported for provider: {}", provider.provider_type)),
+        }
     };
 
+    let mut result = send().await;
+    if is_empty_response(&result) {
+        log::warn!("{} returned an empty vision response, retrying once", provider.model);
+        PROVIDER_LIMITER
+            .acquire(&provider.provider_type, provider.requests_per_minute)
+            .await;
+        result = send().await;
+        if is_empty_response(&result) {
+            result = Err(anyhow::anyhow!(EMPTY_RESPONSE_MESSAGE));
+        }
+    }
+
     match &result {
-        Ok(response) => log::debug!("LLM vision response: {}", response),
-        Err(e) => log::error!("LLM vision error: {}", e),
+        Ok(response) => log::debug!("LLM vision response: {}", logging::body(response)),
+        Err(e) => log::error!("LLM vision error: {}", logging::redact(&e.to_string())),
     }
 
     result
@@ -165,20 +403,17 @@ async fn call_anthropic_vision(
     log::info!("[Anthropic Vision] Response status: {}", status);
 
     let response_body: serde_json::Value = response.json().await?;
-    log::debug!("[Anthropic Vision] Response body: {:?}", response_body);
+    log::debug!("[Anthropic Vision] Response body: {}", logging::body(&response_body.to_string()));
 
     if !status.is_success() {
         let error_msg = response_body["error"]["message"]
             .as_str()
             .unwrap_or("Unknown error");
-        log::error!("[Anthropic Vision] API error: {} - Full response: {:?}", error_msg, response_body);
+        log::error!("[Anthropic Vision] API error: {} - Full response: {}", logging::redact(error_msg), logging::body(&response_body.to_string()));
         return Err(anyhow::anyhow!("Anthropic Vision API error: {}", error_msg));
     }
 
-    response_body["content"][0]["text"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow::anyhow!("Invalid response from Anthropic Vision: {:?}", response_body))
+    extract_response_text("Anthropic Vision", &response_body)
 }
 
 async fn call_openai_vision(
@@ -236,49 +471,75 @@ async fn call_openai_vision(
     log::info!("[OpenAI Vision] Response status: {}", status);
 
     let response_body: serde_json::Value = response.json().await?;
-    log::debug!("[OpenAI Vision] Response body: {:?}", response_body);
+    log::debug!("[OpenAI Vision] Response body: {}", logging::body(&response_body.to_string()));
 
     if !status.is_success() {
         let error_msg = response_body["error"]["message"]
             .as_str()
             .unwrap_or("Unknown error");
-        log::error!("[OpenAI Vision] API error: {} - Full response: {:?}", error_msg, response_body);
+        log::error!("[OpenAI Vision] API error: {} - Full response: {}", logging::redact(error_msg), logging::body(&response_body.to_string()));
         return Err(anyhow::anyhow!("OpenAI Vision API error: {}", error_msg));
     }
 
-    response_body["choices"][0]["message"]["content"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow::anyhow!("Invalid response from OpenAI Vision: {:?}", response_body))
+    extract_response_text("OpenAI Vision", &response_body)
 }
 
-async fn call_anthropic(
-    client: &Client,
+/// A Messages API `content` array holding one text block
+fn anthropic_text_content(text: &str) -> serde_json::Value {
+    json!([{ "type": "text", "text": text }])
+}
+
+/// Messages API body with structured content. `prefill` starts the assistant's
+/// reply (the model continues from it); the API rejects one ending in whitespace.
+fn anthropic_body(
     provider: &LLMProvider,
     prompt: &str,
     system_prompt: Option<&str>,
-) -> Result<String> {
-    let api_key = provider
-        .api_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("API key required for Anthropic"))?;
+    prefill: Option<&str>,
+) -> serde_json::Value {
+    let mut messages = vec![json!({
+        "role": "user",
+        "content": anthropic_text_content(prompt)
+    })];
+
+    if let Some(prefill) = prefill {
+        messages.push(json!({
+            "role": "assistant",
+            "content": anthropic_text_content(prefill)
+        }));
+    }
 
     // Use higher max_tokens for document parsing to handle large bank statements
     let mut body = json!({
         "model": provider.model,
         "max_tokens": 16384,
-        "messages": [
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ]
+        "messages": messages
     });
 
     if let Some(sys) = system_prompt {
         body["system"] = json!(sys);
     }
 
+    body
+}
+
+async fn call_anthropic(
+    client: &Client,
+    provider: &LLMProvider,
+    prompt: &str,
+    system_prompt: Option<&str>,
+    response_format: Option<ResponseFormat>,
+) -> Result<String> {
+    let api_key = provider
+        .api_key
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("API key required for Anthropic"))?;
+
+    // Anthropic has no JSON mode; starting the reply for the model keeps it from
+    // opening with prose or a code fence
+    let prefill = response_format.map(ResponseFormat::prefill);
+    let body = anthropic_body(provider, prompt, system_prompt, prefill);
+
     let response = client
         .post(format!("{}/messages", provider.endpoint))
         .header("x-api-key", api_key)
@@ -298,18 +559,21 @@ async fn call_anthropic(
         return Err(anyhow::anyhow!("Anthropic API error: {}", error_msg));
     }
 
-    response_body["content"][0]["text"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow::anyhow!("Invalid response from Anthropic: {:?}", response_body))
+    let text = extract_response_text("Anthropic", &response_body)?;
+    if text.trim().is_empty() {
+        // Leave an empty reply empty rather than a lone prefilled "{"
+        return Ok(text);
+    }
+    // The prefilled opening isn't echoed back
+    Ok(format!("{}{}", prefill.unwrap_or_default(), text))
 }
 
-async fn call_openai_compatible(
-    client: &Client,
+fn openai_chat_body(
     provider: &LLMProvider,
     prompt: &str,
     system_prompt: Option<&str>,
-) -> Result<String> {
+    response_format: Option<ResponseFormat>,
+) -> serde_json::Value {
     let mut messages = vec![];
 
     if let Some(sys) = system_prompt {
@@ -325,12 +589,30 @@ async fn call_openai_compatible(
     }));
 
     // Use higher max_tokens for document parsing to handle large bank statements
-    let body = json!({
+    let mut body = json!({
         "model": provider.model,
         "messages": messages,
         "max_tokens": 16384
     });
 
+    // json_object mode only allows an object at the top level, and LM Studio
+    // rejects it outright, so arrays and LM Studio fall back to the prompt alone
+    if response_format == Some(ResponseFormat::JsonObject) && provider.provider_type != "lmstudio" {
+        body["response_format"] = json!({ "type": "json_object" });
+    }
+
+    body
+}
+
+async fn call_openai_compatible(
+    client: &Client,
+    provider: &LLMProvider,
+    prompt: &str,
+    system_prompt: Option<&str>,
+    response_format: Option<ResponseFormat>,
+) -> Result<String> {
+    let body = openai_chat_body(provider, prompt, system_prompt, response_format);
+
     let mut request = client
         .post(format!("{}/chat/completions", provider.endpoint))
         .header("content-type", "application/json")
@@ -351,25 +633,39 @@ async fn call_openai_compatible(
         return Err(anyhow::anyhow!("OpenAI API error: {}", error_msg));
     }
 
-    response_body["choices"][0]["message"]["content"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow::anyhow!("Invalid response from OpenAI-compatible API: {:?}", response_body))
+    extract_response_text("OpenAI-compatible API", &response_body)
 }
 
-async fn call_ollama(
-    client: &Client,
+fn ollama_body(
     provider: &LLMProvider,
     prompt: &str,
     system_prompt: Option<&str>,
-) -> Result<String> {
-    let body = json!({
+    response_format: Option<ResponseFormat>,
+) -> serde_json::Value {
+    let mut body = json!({
         "model": provider.model,
         "prompt": prompt,
         "system": system_prompt.unwrap_or(""),
         "stream": false
     });
 
+    // Ollama's JSON mode forces an object, like OpenAI's, so arrays rely on the prompt
+    if response_format == Some(ResponseFormat::JsonObject) {
+        body["format"] = json!("json");
+    }
+
+    body
+}
+
+async fn call_ollama(
+    client: &Client,
+    provider: &LLMProvider,
+    prompt: &str,
+    system_prompt: Option<&str>,
+    response_format: Option<ResponseFormat>,
+) -> Result<String> {
+    let body = ollama_body(provider, prompt, system_prompt, response_format);
+
     let response = client
         .post(format!("{}/api/generate", provider.endpoint))
         .header("content-type", "application/json")
@@ -384,23 +680,14 @@ async fn call_ollama(
         return Err(anyhow::anyhow!("Ollama error: {:?}", response_body));
     }
 
-    response_body["response"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow::anyhow!("Invalid response from Ollama: {:?}", response_body))
+    extract_response_text("Ollama", &response_body)
 }
 
-async fn call_google(
-    client: &Client,
-    provider: &LLMProvider,
+fn google_body(
     prompt: &str,
     system_prompt: Option<&str>,
-) -> Result<String> {
-    let api_key = provider
-        .api_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("API key required for Google"))?;
-
+    response_format: Option<ResponseFormat>,
+) -> serde_json::Value {
     let mut contents = vec![];
 
     // Add system instruction if provided
@@ -420,10 +707,31 @@ async fn call_google(
         "parts": [{ "text": prompt }]
     }));
 
-    let body = json!({
+    let mut body = json!({
         "contents": contents
     });
 
+    if response_format.is_some() {
+        body["generationConfig"] = json!({ "responseMimeType": "application/json" });
+    }
+
+    body
+}
+
+async fn call_google(
+    client: &Client,
+    provider: &LLMProvider,
+    prompt: &str,
+    system_prompt: Option<&str>,
+    response_format: Option<ResponseFormat>,
+) -> Result<String> {
+    let api_key = provider
+        .api_key
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("API key required for Google"))?;
+
+    let body = google_body(prompt, system_prompt, response_format);
+
     let response = client
         .post(format!(
             "{}/models/{}:generateContent?key={}",
@@ -444,109 +752,337 @@ async fn call_google(
         return Err(anyhow::anyhow!("Google API error: {}", error_msg));
     }
 
-    response_body["candidates"][0]["content"]["parts"][0]["text"]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| anyhow::anyhow!("Invalid response from Google: {:?}", response_body))
+    extract_response_text("Google", &response_body)
 }
 
-/// List available models for a provider
+/// Pull the generated text out of any supported provider's response body.
+/// Handles Anthropic content blocks, OpenAI choices (string or part arrays),
+/// Google candidates and Ollama, and turns refusals and safety stops into a
+/// readable error instead of a dump of the raw body.
+fn extract_response_text(provider_label: &str, body: &serde_json::Value) -> Result<String> {
+    let declined = |reason: &str| anyhow::anyhow!("{} declined to answer: {}", provider_label, reason);
+
+    // Concatenate every `text` part of an array of content blocks/parts
+    let join_text = |parts: &Vec<serde_json::Value>| -> Option<String> {
+        let texts: Vec<&str> = parts
+            .iter()
+            .filter(|part| part["type"].as_str().map_or(true, |t| t == "text"))
+            .filter_map(|part| part["text"].as_str())
+            .collect();
+        if texts.is_empty() {
+            None
+        } else {
+            Some(texts.join(""))
+        }
+    };
+
+    // Anthropic: {"content": [{"type": "text", ...}, {"type": "tool_use", ...}], "stop_reason": ...}
+    if let Some(blocks) = body["content"].as_array() {
+        if body["stop_reason"] == "refusal" {
+            return Err(declined("the request was refused"));
+        }
+        if let Some(text) = join_text(blocks) {
+            return Ok(text);
+        }
+        if blocks.iter().any(|b| b["type"] == "tool_use") {
+            return Err(anyhow::anyhow!("{} returned a tool call instead of text", provider_label));
+        }
+    }
+
+    // OpenAI-compatible: {"choices": [{"message": {"content" | "refusal"}, "finish_reason"}]}
+    if let Some(choice) = body["choices"].get(0) {
+        let message = &choice["message"];
+        if let Some(refusal) = message["refusal"].as_str() {
+            return Err(declined(refusal));
+        }
+        if choice["finish_reason"] == "content_filter" {
+            return Err(declined("the response was blocked by the content filter"));
+        }
+        if let Some(text) = message["content"].as_str() {
+            return Ok(text.to_string());
+        }
+        if let Some(text) = message["content"].as_array().and_then(join_text) {
+            return Ok(text);
+        }
+    }
+
+    // Google: {"candidates": [{"content": {"parts": [...]}, "finishReason"}], "promptFeedback"}
+    if let Some(reason) = body["promptFeedback"]["blockReason"].as_str() {
+        return Err(declined(&format!("the prompt was blocked ({})", reason)));
+    }
+    if let Some(candidate) = body["candidates"].get(0) {
+        if let Some(text) = candidate["content"]["parts"].as_array().and_then(join_text) {
+            return Ok(text);
+        }
+        if let Some(reason) = candidate["finishReason"].as_str() {
+            if matches!(reason, "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") {
+                return Err(declined(&format!("the response was blocked ({})", reason)));
+            }
+        }
+    }
+
+    // Ollama: {"response": "..."} from /api/generate, {"message": {"content"}} from /api/chat
+    if let Some(text) = body["response"].as_str().or_else(|| body["message"]["content"].as_str()) {
+        return Ok(text.to_string());
+    }
+
+    log::error!("[{}] Unrecognized response: {}", provider_label, logging::body(&body.to_string()));
+    Err(anyhow::anyhow!("{} returned a response with no text content", provider_label))
+}
+
+/// Inputs sent per request to OpenAI-compatible /embeddings endpoints
+const EMBEDDING_BATCH_SIZE: usize = 64;
+
+/// Embed `texts` with `provider.model`, returning one vector per input in order.
+/// OpenAI, OpenRouter and LM Studio take batches at `/embeddings`; Ollama's
+/// `/api/embeddings` takes a single prompt per request.
+pub async fn embed_texts(provider: &LLMProvider, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+    check_provider_allowed(provider, LOCAL_ONLY.load(Ordering::Relaxed))?;
+    let client = Client::new();
+    let mut embeddings = Vec::with_capacity(texts.len());
+
+    log::info!("Embedding {} texts with {}", texts.len(), provider.provider_type);
+
+    match provider.provider_type.as_str() {
+        "openai" | "openrouter" | "lmstudio" => {
+            for batch in texts.chunks(EMBEDDING_BATCH_SIZE) {
+                PROVIDER_LIMITER
+                    .acquire(&provider.provider_type, provider.requests_per_minute)
+                    .await;
+
+                let mut request = client
+                    .post(format!("{}/embeddings", provider.endpoint))
+                    .header("content-type", "application/json")
+                    .json(&json!({
+                        "model": provider.model,
+                        "input": batch
+                    }));
+                if let Some(api_key) = &provider.api_key {
+                    request = request.header("Authorization", format!("Bearer {}", api_key));
+                }
+
+                let response = request.send().await?;
+                let status = response.status();
+                let response_body: serde_json::Value = response.json().await?;
+                if !status.is_success() {
+                    let error_msg = response_body["error"]["message"]
+                        .as_str()
+                        .unwrap_or("Unknown error");
+                    return Err(anyhow::anyhow!("Embeddings API error: {}", error_msg));
+                }
+
+                let vectors = parse_embeddings_response(&response_body)?;
+                if vectors.len() != batch.len() {
+                    return Err(anyhow::anyhow!(
+                        "Embeddings API returned {} vectors for {} inputs",
+                        vectors.len(),
+                        batch.len()
+                    ));
+                }
+                embeddings.extend(vectors);
+            }
+        }
+        "ollama" => {
+            for text in &texts {
+                PROVIDER_LIMITER
+                    .acquire(&provider.provider_type, provider.requests_per_minute)
+                    .await;
+
+                let response = client
+                    .post(format!("{}/api/embeddings", provider.endpoint))
+                    .header("content-type", "application/json")
+                    .json(&json!({
+                        "model": provider.model,
+                        "prompt": text
+                    }))
+                    .send()
+                    .await?;
+
+                let status = response.status();
+                let response_body: serde_json::Value = response.json().await?;
+                if !status.is_success() {
+                    return Err(anyhow::anyhow!("Ollama embeddings error: {:?}", response_body));
+                }
+
+                embeddings.extend(parse_embeddings_response(&response_body)?);
+            }
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Embeddings not supported for provider: {}",
+                provider.provider_type
+            ))
+        }
+    }
+
+    Ok(embeddings)
+}
+
+/// Read vectors from either `{"data": [{"index", "embedding"}]}` (OpenAI-compatible)
+/// or `{"embedding": [...]}` (Ollama)
+fn parse_embeddings_response(body: &serde_json::Value) -> Result<Vec<Vec<f32>>> {
+    let to_vector = |value: &serde_json::Value| -> Option<Vec<f32>> {
+        value
+            .as_array()?
+            .iter()
+            .map(|n| n.as_f64().map(|f| f as f32))
+            .collect()
+    };
+
+    if let Some(data) = body["data"].as_array() {
+        // Entries carry an index and aren't guaranteed to come back in input order
+        let mut indexed: Vec<(u64, Vec<f32>)> = data
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let index = item["index"].as_u64().unwrap_or(i as u64);
+                to_vector(&item["embedding"])
+                    .map(|v| (index, v))
+                    .ok_or_else(|| anyhow::anyhow!("Invalid embedding at index {}", index))
+            })
+            .collect::<Result<_>>()?;
+        indexed.sort_by_key(|(index, _)| *index);
+        return Ok(indexed.into_iter().map(|(_, v)| v).collect());
+    }
+
+    if let Some(vector) = to_vector(&body["embedding"]) {
+        return Ok(vec![vector]);
+    }
+
+    Err(anyhow::anyhow!("Invalid embeddings response: {:?}", body))
+}
+
+/// List available models for a provider, optionally only those whose id contains `search`
 pub async fn list_provider_models(
     provider_type: &str,
     endpoint: &str,
     api_key: Option<&str>,
+    search: Option<&str>,
 ) -> Result<Vec<String>> {
+    if LOCAL_ONLY.load(Ordering::Relaxed) && !is_loopback_endpoint(endpoint) {
+        return Err(anyhow::anyhow!(
+            "Local-only mode is on: refusing to contact {} at {}",
+            provider_type,
+            endpoint
+        ));
+    }
+
     let client = Client::new();
 
-    match provider_type {
+    let models = match provider_type {
         "ollama" => {
-            let response = client
-                .get(format!("{}/api/tags", endpoint))
-                .send()
-                .await?;
-            let body: serde_json::Value = response.json().await?;
-            let models = body["models"]
+            let body = fetch_models_json(&client, &format!("{}/api/tags", endpoint), None).await?;
+            body["models"]
                 .as_array()
                 .map(|arr| {
                     arr.iter()
                         .filter_map(|m| m["name"].as_str().map(|s| s.to_string()))
                         .collect()
                 })
-                .unwrap_or_default();
-            Ok(models)
+                .unwrap_or_default()
         }
         "lmstudio" => {
-            let response = client
-                .get(format!("{}/models", endpoint))
-                .send()
-                .await?;
-            let body: serde_json::Value = response.json().await?;
-            let models = body["data"]
-                .as_array()
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
-                        .collect()
-                })
-                .unwrap_or_default();
-            Ok(models)
+            let body = fetch_models_json(&client, &format!("{}/models", endpoint), None).await?;
+            model_ids(&body)
         }
         "openai" => {
             let api_key = api_key.ok_or_else(|| anyhow::anyhow!("API key required"))?;
-            let response = client
-                .get(format!("{}/models", endpoint))
-                .header("Authorization", format!("Bearer {}", api_key))
-                .send()
-                .await?;
-            let body: serde_json::Value = response.json().await?;
-            let models = body["data"]
-                .as_array()
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
-                        .filter(|id| id.starts_with("gpt-"))
-                        .collect()
-                })
-                .unwrap_or_default();
-            Ok(models)
+            let body = fetch_models_json(&client, &format!("{}/models", endpoint), Some(api_key)).await?;
+            openai_chat_models(model_ids(&body))
         }
         "anthropic" => {
             // Anthropic doesn't have a models endpoint, return known models
-            Ok(vec![
+            vec![
                 "claude-sonnet-4-20250514".to_string(),
                 "claude-3-5-sonnet-20241022".to_string(),
                 "claude-3-5-haiku-20241022".to_string(),
                 "claude-3-opus-20240229".to_string(),
-            ])
+            ]
         }
         "google" => {
             // Return known Gemini models
-            Ok(vec![
+            vec![
                 "gemini-2.0-flash".to_string(),
                 "gemini-1.5-pro".to_string(),
                 "gemini-1.5-flash".to_string(),
-            ])
+            ]
         }
         "openrouter" => {
             let api_key = api_key.ok_or_else(|| anyhow::anyhow!("API key required"))?;
-            let response = client
-                .get(format!("{}/models", endpoint))
-                .header("Authorization", format!("Bearer {}", api_key))
-                .send()
-                .await?;
-            let body: serde_json::Value = response.json().await?;
-            let models = body["data"]
-                .as_array()
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
-                        .take(20) // Limit to top 20
-                        .collect()
-                })
-                .unwrap_or_default();
-            Ok(models)
+            let body = fetch_models_json(&client, &format!("{}/models", endpoint), Some(api_key)).await?;
+            // Hundreds of models come back in no useful order; sort so the list is scannable
+            let mut models = model_ids(&body);
+            models.sort();
+            models
+        }
+        _ => return Err(anyhow::anyhow!("Unsupported provider: {}", provider_type)),
+    };
+
+    Ok(filter_models(models, search))
+}
+
+/// GET a models listing, retrying once on connection errors, 429 and 5xx
+async fn fetch_models_json(client: &Client, url: &str, api_key: Option<&str>) -> Result<serde_json::Value> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let mut request = client.get(url);
+        if let Some(api_key) = api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
         }
-        _ => Err(anyhow::anyhow!("Unsupported provider: {}", provider_type)),
+
+        let retryable = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(response.json().await?),
+            Ok(response) => {
+                let status = response.status();
+                if attempt > 1 || !(status.is_server_error() || status.as_u16() == 429) {
+                    return Err(anyhow::anyhow!("Listing models failed: {}", status));
+                }
+                format!("status {}", status)
+            }
+            Err(e) if attempt == 1 && (e.is_connect() || e.is_timeout()) => e.to_string(),
+            Err(e) => return Err(e.into()),
+        };
+
+        log::warn!("Listing models from {} failed ({}), retrying", url, logging::redact(&retryable));
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+}
+
+/// The `id` of every entry in an OpenAI-style `{"data": [...]}` listing
+fn model_ids(body: &serde_json::Value) -> Vec<String> {
+    body["data"]
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Models from OpenAI's listing that can serve chat completions: the gpt-, chatgpt-
+/// and o-series (o1, o3, ...) families, minus the audio, realtime, image and
+/// completions-only variants that share those prefixes
+fn openai_chat_models(ids: Vec<String>) -> Vec<String> {
+    const NOT_CHAT: [&str; 6] = ["audio", "realtime", "transcribe", "tts", "image", "instruct"];
+
+    let mut models: Vec<String> = ids
+        .into_iter()
+        .filter(|id| {
+            let o_series = id.starts_with('o') && id[1..].starts_with(|c: char| c.is_ascii_digit());
+            (id.starts_with("gpt-") || id.starts_with("chatgpt-") || o_series)
+                && !NOT_CHAT.iter().any(|word| id.contains(word))
+        })
+        .collect();
+    models.sort();
+    models
+}
+
+fn filter_models(models: Vec<String>, search: Option<&str>) -> Vec<String> {
+    match search.map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty()) {
+        Some(needle) => models.into_iter().filter(|m| m.to_lowercase().contains(&needle)).collect(),
+        None => models,
     }
 }
 
@@ -555,6 +1091,8 @@ pub async fn parse_document_with_llm(
     provider: &LLMProvider,
     text: &str,
     categories: &[String],
+    only_after_date: Option<&str>,
+    period: Option<(&str, &str)>,
 ) -> Result<Vec<ExtractedTransaction>> {
     log::info!("[parse_document_with_llm] ========== STARTING TEXT PARSING ==========");
     log::info!("[parse_document_with_llm] Text length: {} chars", text.len());
@@ -566,7 +1104,7 @@ pub async fn parse_document_with_llm(
     } else {
         text.to_string()
     };
-    log::info!("[parse_document_with_llm] Text preview: {}", text_preview);
+    log::info!("[parse_document_with_llm] Text preview: {}", logging::body(&text_preview));
 
     let categories_str = categories.join(", ");
 
@@ -580,23 +1118,31 @@ Each transaction should have:
 - currency: Currency code (default USD)
 - category: One of: {}
 - merchant: Merchant name or null
+- needs_review: true if you had to guess the date, amount or sign (smudged, cut off or ambiguous); omit otherwise
+- balance: The running balance printed on the row, if the statement has a balance column; omit otherwise
 
 Rules:
 - Use negative amounts for expenses, positive for income
 - If date is ambiguous, use context to infer year
 - If category is unclear, use "Other"
-- Output only valid JSON array, no explanations"#,
-        categories_str
+- Output only valid JSON array, no explanations{}"#,
+        categories_str,
+        language_instruction(text)
     );
 
-    let prompt = format!("Parse transactions from this document:\n\n{}", text);
+    let prompt = format!(
+        "Parse transactions from this document:{}{}\n\n{}",
+        date_cutoff_instruction(only_after_date),
+        statement_period_instruction(period),
+        text
+    );
 
     log::info!("[parse_document_with_llm] Calling LLM...");
-    let response = call_llm(provider, &prompt, Some(&system_prompt)).await?;
+    let response = call_llm(provider, &prompt, Some(&system_prompt), Some(ResponseFormat::JsonArray)).await?;
 
     log::info!("[parse_document_with_llm] LLM response length: {} chars", response.len());
     log::info!("[parse_document_with_llm] LLM response preview: {}",
-        if response.len() > 1000 { format!("{}...", &response[..1000]) } else { response.clone() });
+        logging::body(&if response.len() > 1000 { format!("{}...", &response[..1000]) } else { response.clone() }));
 
     // Strip markdown code block wrapper if present
     let cleaned_response = response
@@ -619,7 +1165,7 @@ Rules:
                     let extracted = &cleaned_response[json_start..=json_end];
                     log::info!("[parse_document_with_llm] Trying to extract JSON from positions {}-{}", json_start, json_end);
                     log::info!("[parse_document_with_llm] Extracted JSON (first 500 chars): {}",
-                        if extracted.len() > 500 { format!("{}...", &extracted[..500]) } else { extracted.to_string() });
+                        logging::body(&if extracted.len() > 500 { format!("{}...", &extracted[..500]) } else { extracted.to_string() }));
                     return serde_json::from_str(extracted);
                 }
             }
@@ -631,21 +1177,285 @@ Rules:
             if e.to_string().contains("EOF") {
                 log::error!("[parse_document_with_llm] Response appears truncated! LLM may have run out of tokens.");
                 log::error!("[parse_document_with_llm] Last 200 chars: {}",
-                    if cleaned_response.len() > 200 { &cleaned_response[cleaned_response.len()-200..] } else { cleaned_response });
+                    logging::body(if cleaned_response.len() > 200 { &cleaned_response[cleaned_response.len()-200..] } else { cleaned_response }));
             } else {
-                log::error!("[parse_document_with_llm] Full response was: {}", cleaned_response);
+                log::error!("[parse_document_with_llm] Full response was: {}", logging::body(cleaned_response));
             }
             Vec::new()
         });
 
+    let transactions = assign_statement_years(transactions, period);
+    let transactions = drop_rows_before_cutoff(check_amount_signs(transactions), only_after_date);
     log::info!("[parse_document_with_llm] ========== RESULT: {} transactions ==========", transactions.len());
     if !transactions.is_empty() {
-        log::info!("[parse_document_with_llm] First transaction: {:?}", transactions[0]);
+        log::info!("[parse_document_with_llm] First transaction: {}", logging::body(&format!("{:?}", transactions[0])));
     }
 
     Ok(transactions)
 }
 
+/// Parse a few informal lines pasted from an email or SMS ("Paid $12 at Chipotle
+/// yesterday"). Relative dates are resolved against `today` before the text is
+/// sent, since models get "last Friday" wrong as often as right.
+pub async fn parse_pasted_text_with_llm(
+    provider: &LLMProvider,
+    text: &str,
+    categories: &[String],
+    today: chrono::NaiveDate,
+) -> Result<Vec<ExtractedTransaction>> {
+    let resolved = resolve_relative_dates(text, today);
+    let notes = format!(
+        "Informal notes pasted by the user, not a bank statement. Today is {today}. \
+Each line is usually one transaction: \"Paid $12 at Chipotle\" is an expense of 12 USD at merchant Chipotle, \
+\"got 500 from Sam\" is income of 500. Dates written without a year are in {year}; \
+lines with no date happened today.\n\n{resolved}",
+        today = today.format("%Y-%m-%d"),
+        year = chrono::Datelike::year(&today),
+        resolved = resolved.trim(),
+    );
+    parse_document_with_llm(provider, &notes, categories, None, None).await
+}
+
+lazy_static::lazy_static! {
+    static ref DAYS_AGO: regex::Regex = regex::Regex::new(r"(?i)\b(\d{1,2}|a|one|two|three|four|five|six) days? ago\b").unwrap();
+    static ref LAST_WEEKDAY: regex::Regex =
+        regex::Regex::new(r"(?i)\blast (monday|tuesday|wednesday|thursday|friday|saturday|sunday)\b").unwrap();
+    static ref DAY_BEFORE_YESTERDAY: regex::Regex = regex::Regex::new(r"(?i)\b(the )?day before yesterday\b").unwrap();
+    static ref YESTERDAY: regex::Regex = regex::Regex::new(r"(?i)\byesterday\b").unwrap();
+    static ref TODAY: regex::Regex = regex::Regex::new(r"(?i)\b(today|tonight|this morning)\b").unwrap();
+}
+
+/// Replace "yesterday", "3 days ago", "last Friday" and the like with ISO dates.
+/// "last Friday" is the most recent Friday before today.
+fn resolve_relative_dates(text: &str, today: chrono::NaiveDate) -> String {
+    use chrono::Datelike;
+
+    let iso = |days_back: i64| (today - chrono::Duration::days(days_back)).format("%Y-%m-%d").to_string();
+
+    let text = DAYS_AGO.replace_all(text, |caps: &regex::Captures| {
+        let days = match caps[1].to_lowercase().as_str() {
+            "a" | "one" => 1,
+            "two" => 2,
+            "three" => 3,
+            "four" => 4,
+            "five" => 5,
+            "six" => 6,
+            n => n.parse().unwrap_or(0),
+        };
+        iso(days)
+    });
+    let text = LAST_WEEKDAY.replace_all(&text, |caps: &regex::Captures| {
+        let target: chrono::Weekday = caps[1].parse().unwrap_or(chrono::Weekday::Mon);
+        let back = (today.weekday().num_days_from_monday() as i64 - target.num_days_from_monday() as i64 + 7) % 7;
+        iso(if back == 0 { 7 } else { back })
+    });
+    let text = DAY_BEFORE_YESTERDAY.replace_all(&text, iso(2).as_str()).into_owned();
+    let text = YESTERDAY.replace_all(&text, iso(1).as_str()).into_owned();
+    TODAY.replace_all(&text, iso(0).as_str()).into_owned()
+}
+
+/// Words that are common in statements in each language and rare in the others.
+/// English is scored too, so an English statement with a few foreign merchant
+/// names isn't mistaken for a foreign one.
+const LANGUAGE_MARKERS: [(&str, &[&str]); 6] = [
+    ("English", &["the", "and", "payment", "purchase", "transfer", "balance", "from", "withdrawal", "deposit", "fee"]),
+    ("French", &["le", "les", "du", "des", "et", "virement", "paiement", "retrait", "solde", "frais", "achat", "prélèvement", "carte", "relevé"]),
+    ("Spanish", &["el", "los", "del", "y", "pago", "compra", "transferencia", "retiro", "comisión", "cuenta", "abono"]),
+    ("Portuguese", &["do", "da", "pagamento", "transferência", "saque", "tarifa", "conta", "extrato", "débito"]),
+    ("German", &["der", "die", "das", "und", "zahlung", "überweisung", "lastschrift", "gutschrift", "kontostand", "gebühr"]),
+    ("Swahili", &["na", "ya", "wa", "kwa", "malipo", "kutoka", "salio", "kiasi", "tarehe", "umetuma", "umepokea", "muamala"]),
+];
+
+/// Best guess at the language of a statement's text, from how often each
+/// language's marker words appear. None when it reads as English or there is
+/// too little text to tell.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).collect();
+
+    // Reversed so a tie goes to English, which max_by_key then sees last
+    let (language, score) = LANGUAGE_MARKERS
+        .iter()
+        .rev()
+        .map(|(language, markers)| (*language, words.iter().filter(|w| markers.contains(w)).count()))
+        .max_by_key(|(_, score)| *score)?;
+    (language != "English" && score >= 3).then_some(language)
+}
+
+/// Extra prompt rule for a non-English statement: English descriptions keep the
+/// category hints working, and the original wording survives in `merchant`
+fn language_instruction(text: &str) -> String {
+    detect_language(text)
+        .map(|language| {
+            format!(
+                "\n- The document is in {}: write each description in English, and put the original merchant name or wording, untranslated, in merchant",
+                language
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Words that only describe money coming in
+const CREDIT_KEYWORDS: [&str; 8] = ["salary", "payroll", "deposit", "refund", "interest", "dividend", "reversal", "cashback"];
+
+/// Models often flip the sign of a row, turning a salary into an expense. Where
+/// neighbouring rows carry a running balance, the change in balance is the true
+/// signed amount: a row whose amount matches it with the opposite sign is
+/// flipped back. Rows the balances can't vouch for are flagged for review when
+/// they are negative but read like income.
+pub fn check_amount_signs(mut transactions: Vec<ExtractedTransaction>) -> Vec<ExtractedTransaction> {
+    let close = |a: f64, b: f64| (a - b).abs() < 0.005;
+    let balances: Vec<Option<f64>> = transactions.iter().map(|t| t.balance).collect();
+    let pairs: Vec<(usize, usize, f64, f64)> = (1..transactions.len())
+        .filter_map(|i| Some((i - 1, i, balances[i - 1]?, balances[i]?)))
+        .collect();
+
+    // Oldest-first statements reach row i's balance by adding row i's amount;
+    // newest-first ones reach row i-1's by adding row i-1's. Take whichever
+    // reading explains more of the balance changes.
+    let explained = |newest_first: bool| {
+        pairs
+            .iter()
+            .filter(|(prev, next, prev_balance, next_balance)| {
+                let row = if newest_first { *prev } else { *next };
+                close((next_balance - prev_balance).abs(), transactions[row].amount.abs())
+            })
+            .count()
+    };
+    let newest_first = explained(true) > explained(false);
+
+    let mut verified = vec![false; transactions.len()];
+    for (prev, next, prev_balance, next_balance) in pairs {
+        let (row, delta) = if newest_first {
+            (prev, prev_balance - next_balance)
+        } else {
+            (next, next_balance - prev_balance)
+        };
+        let txn = &mut transactions[row];
+        if close(delta, txn.amount) {
+            verified[row] = true;
+        } else if close(delta, -txn.amount) {
+            log::warn!(
+                "[check_amount_signs] Flipping the sign of '{}' on {}: the balance moved by {:.2}",
+                logging::body(&txn.description),
+                txn.date,
+                delta
+            );
+            txn.amount = -txn.amount;
+            verified[row] = true;
+        }
+    }
+
+    for (txn, verified) in transactions.iter_mut().zip(verified) {
+        let description = txn.description.to_lowercase();
+        let reads_like_income = description
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| CREDIT_KEYWORDS.iter().any(|k| word.starts_with(k)));
+        if !verified && txn.amount < 0.0 && reads_like_income {
+            txn.needs_review = Some(true);
+        }
+    }
+
+    transactions
+}
+
+/// Prompt sentence asking the model to leave out rows already imported
+fn date_cutoff_instruction(only_after_date: Option<&str>) -> String {
+    only_after_date
+        .map(|date| format!(" Only include transactions dated after {}; skip any on or before that date.", date))
+        .unwrap_or_default()
+}
+
+/// Prompt addition naming the statement period, so dates printed without a year
+/// get the right one
+fn statement_period_instruction(period: Option<(&str, &str)>) -> String {
+    period
+        .map(|(start, end)| {
+            format!(" The statement covers {} to {}; dates printed without a year fall within that period.", start, end)
+        })
+        .unwrap_or_default()
+}
+
+/// Move each row into the year that puts it inside the statement period
+/// (`period` is start and end, YYYY-MM-DD). Models infer the year of a date
+/// printed as "01/05" from elsewhere on the page, so the January rows of a
+/// December-to-January statement tend to land a year early. Rows already in the
+/// period, allowing a week either side for posting lag, are kept as they are, as
+/// are rows no year would fit. Year-less dates the model passed through as
+/// "MM/DD" or "MM-DD" are completed the same way.
+pub fn assign_statement_years(
+    transactions: Vec<ExtractedTransaction>,
+    period: Option<(&str, &str)>,
+) -> Vec<ExtractedTransaction> {
+    use chrono::Datelike;
+
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d.trim().get(..10).unwrap_or(d), "%Y-%m-%d").ok();
+    let Some((start, end)) = period.and_then(|(start, end)| Some((parse(start)?, parse(end)?))) else {
+        return transactions;
+    };
+    let earliest = start - chrono::Duration::days(7);
+    let latest = end + chrono::Duration::days(7);
+    let in_period = |d: &chrono::NaiveDate| *d >= earliest && *d <= latest;
+
+    // "MM/DD" or "MM-DD", month first as on the statements this comes up with
+    let month_day = |d: &str| -> Option<(u32, u32)> {
+        let (month, day) = d.trim().split_once(['/', '-'])?;
+        Some((month.parse().ok()?, day.parse().ok()?))
+    };
+
+    transactions
+        .into_iter()
+        .map(|mut txn| {
+            let (month, day) = match parse(&txn.date) {
+                Some(date) if in_period(&date) => return txn,
+                Some(date) => (date.month(), date.day()),
+                None => match month_day(&txn.date) {
+                    Some(month_day) => month_day,
+                    None => return txn,
+                },
+            };
+            let fitted = (earliest.year()..=latest.year())
+                .filter_map(|year| chrono::NaiveDate::from_ymd_opt(year, month, day))
+                .find(|date| in_period(date));
+            if let Some(date) = fitted {
+                log::debug!("[assign_statement_years] {} -> {}", txn.date, date);
+                txn.date = date.format("%Y-%m-%d").to_string();
+            }
+            txn
+        })
+        .collect()
+}
+
+/// Models don't reliably honor the cutoff instruction, so rows dated on or
+/// before it are dropped here as well. Rows whose date can't be read are kept
+/// and flagged for review rather than silently lost.
+pub fn drop_rows_before_cutoff(
+    transactions: Vec<ExtractedTransaction>,
+    only_after_date: Option<&str>,
+) -> Vec<ExtractedTransaction> {
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d.trim().get(..10).unwrap_or(d), "%Y-%m-%d").ok();
+    let Some(cutoff) = only_after_date.and_then(parse) else {
+        return transactions;
+    };
+
+    let before = transactions.len();
+    let kept: Vec<ExtractedTransaction> = transactions
+        .into_iter()
+        .filter_map(|mut txn| match parse(&txn.date) {
+            Some(date) if date <= cutoff => None,
+            Some(_) => Some(txn),
+            None => {
+                txn.needs_review = Some(true);
+                Some(txn)
+            }
+        })
+        .collect();
+    if kept.len() < before {
+        log::info!("Dropped {} transactions dated on or before {}", before - kept.len(), cutoff);
+    }
+    kept
+}
+
 /// Parse receipt text with detailed item extraction (for text/PDF receipts)
 pub async fn parse_receipt_text_with_llm(
     provider: &LLMProvider,
@@ -708,7 +1518,7 @@ Output only valid JSON."#,
 
     let prompt = format!("Analyze this receipt and extract detailed item information:\n\n{}", text);
 
-    let response = call_llm(provider, &prompt, Some(&system_prompt)).await?;
+    let response = call_llm(provider, &prompt, Some(&system_prompt), Some(ResponseFormat::JsonObject)).await?;
 
     // Try to parse JSON from response
     let receipt: ParsedReceipt = serde_json::from_str(&response)
@@ -725,22 +1535,27 @@ Output only valid JSON."#,
             tax: None,
             total: 0.0,
             category: "Other".to_string(),
+            date_corrected: false,
         });
 
     Ok(receipt)
 }
 
-/// Parse a receipt image/PDF with detailed item extraction using vision
+/// Parse a receipt image/PDF with detailed item extraction using vision.
+/// Several receipts photographed together come back as separate entries.
 pub async fn parse_receipt_with_llm(
     provider: &LLMProvider,
     image_path: &str,
     categories: &[String],
-) -> Result<ParsedReceipt> {
+) -> Result<Vec<ParsedReceipt>> {
     let categories_str = categories.join(", ");
 
     // Read the file and encode as base64
     let file_data = std::fs::read(image_path)
         .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", image_path, e))?;
+    if file_data.is_empty() {
+        return Err(anyhow::anyhow!("File {} is empty", image_path));
+    }
     let base64_data = base64_encode(&file_data);
 
     // Determine media type from extension
@@ -760,28 +1575,39 @@ pub async fn parse_receipt_with_llm(
     log::info!("[parse_receipt_with_llm] Base64 length: {}", base64_data.len());
 
     let system_prompt = format!(
-        r#"You are analyzing a receipt image or scanned document. Extract detailed item information for tracking purchases.
+        r#"You are analyzing a receipt image or scanned document. It may contain ONE receipt or SEVERAL separate receipts (photographed side by side, or one per page). Extract detailed item information for tracking purchases.
 
 Output JSON format:
 {{
-  "merchant": "Store name",
-  "date": "YYYY-MM-DD",
-  "items": [
+  "receipts": [
     {{
-      "name": "product-name-in-kebab-case",
-      "quantity": 2.5,
-      "unit": "lb" | "oz" | "kg" | "g" | "each" | "pack" | null,
-      "unit_price": 3.99,
-      "total_price": 9.97,
-      "category": "produce" | "dairy" | "meat" | "seafood" | "bakery" | "frozen" | "beverages" | "snacks" | "pantry" | "household" | "personal_care" | "alcohol" | "other",
-      "brand": "Brand name" | null
+      "merchant": "Store name",
+      "date": "YYYY-MM-DD",
+      "items": [
+        {{
+          "name": "product-name-in-kebab-case",
+          "quantity": 2.5,
+          "unit": "lb" | "oz" | "kg" | "g" | "each" | "pack" | null,
+          "unit_price": 3.99,
+          "total_price": 9.97,
+          "category": "produce" | "dairy" | "meat" | "seafood" | "bakery" | "frozen" | "beverages" | "snacks" | "pantry" | "household" | "personal_care" | "alcohol" | "other",
+          "brand": "Brand name" | null
+        }}
+      ],
+      "tax": 2.50,
+      "total": 45.67,
+      "category": "{}"
     }}
-  ],
-  "tax": 2.50,
-  "total": 45.67,
-  "category": "{}"
+  ]
 }}
 
+CRITICAL Multiple receipt rules:
+- Output one entry in "receipts" per physical receipt, in the order they appear
+- A new receipt starts where a different store header, date or total block begins
+- NEVER merge items, tax or totals from different receipts into one entry
+- Each entry's total is that receipt's own printed total
+- If there is only one receipt, "receipts" has exactly one entry
+
 CRITICAL Item extraction rules:
 - Extract EVERY individual line item from the receipt - DO NOT SUMMARIZE
 - Product names MUST be in lowercase kebab-case (e.g., "pumpkin-spice-latte", "chicken-sandwich", "iced-coffee")
@@ -814,30 +1640,89 @@ Output only valid JSON."#,
     // Call vision API with the image
     let response = call_llm_with_vision(
         provider,
-        "Analyze this receipt image and extract detailed item information.",
+        "Analyze this receipt image and extract detailed item information for every receipt in it.",
         &base64_data,
         media_type,
         Some(&system_prompt),
     ).await?;
 
-    // Try to parse JSON from response
-    let receipt: ParsedReceipt = serde_json::from_str(&response)
-        .or_else(|_| {
-            // Try to extract JSON from response
-            let json_start = response.find('{').unwrap_or(0);
-            let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
-            serde_json::from_str(&response[json_start..json_end])
-        })
-        .unwrap_or(ParsedReceipt {
+    let today = chrono::Local::now().date_naive();
+    Ok(parse_receipts_response(&response)
+        .into_iter()
+        .map(|receipt| check_receipt_date(receipt, today))
+        .collect())
+}
+
+/// How far back a receipt date can be before it's taken for a misread year
+const RECEIPT_MAX_AGE_YEARS: u32 = 10;
+
+/// Replace a receipt date that can't be right (unreadable, more than ten years old,
+/// or more than a day in the future, e.g. a misread 2099 or 1921) with today, and
+/// flag it so the user checks it instead of it skewing every time series.
+fn check_receipt_date(mut receipt: ParsedReceipt, today: chrono::NaiveDate) -> ParsedReceipt {
+    let earliest = today
+        .checked_sub_months(chrono::Months::new(RECEIPT_MAX_AGE_YEARS * 12))
+        .unwrap_or(chrono::NaiveDate::MIN);
+    let latest = today + chrono::Duration::days(1);
+    let plausible = chrono::NaiveDate::parse_from_str(receipt.date.trim(), "%Y-%m-%d")
+        .map(|date| (earliest..=latest).contains(&date))
+        .unwrap_or(false);
+
+    if !plausible {
+        log::warn!(
+            "[parse_receipt_with_llm] Implausible receipt date '{}' from {}, using {}",
+            receipt.date,
+            receipt.merchant,
+            today
+        );
+        receipt.date = today.to_string();
+        receipt.date_corrected = true;
+    }
+    receipt
+}
+
+/// Receipts from a vision response. Accepts `{"receipts": [...]}`, a bare array, or a
+/// single receipt object (what the prompt asked for before multi-receipt support, and
+/// what some models still return for one receipt). Falls back to one empty receipt
+/// when nothing parses, so the caller still gets a row to review.
+fn parse_receipts_response(response: &str) -> Vec<ParsedReceipt> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum ReceiptsShape {
+        Wrapped { receipts: Vec<ParsedReceipt> },
+        List(Vec<ParsedReceipt>),
+        Single(ParsedReceipt),
+    }
+
+    let parse = |text: &str| -> Option<Vec<ParsedReceipt>> {
+        match serde_json::from_str::<ReceiptsShape>(text).ok()? {
+            ReceiptsShape::Wrapped { receipts } | ReceiptsShape::List(receipts) => Some(receipts),
+            ReceiptsShape::Single(receipt) => Some(vec![receipt]),
+        }
+    };
+
+    let receipts = parse(response).or_else(|| {
+        // Try to extract JSON from response, whichever bracket opens first
+        let json_start = response.find(['{', '[']).unwrap_or(0);
+        let json_end = response.rfind(['}', ']']).map(|i| i + 1).unwrap_or(response.len());
+        parse(&response[json_start..json_end.max(json_start)])
+    });
+
+    match receipts {
+        Some(receipts) if !receipts.is_empty() => {
+            log::info!("[parse_receipt_with_llm] Found {} receipts", receipts.len());
+            receipts
+        }
+        _ => vec![ParsedReceipt {
             merchant: "Unknown".to_string(),
             date: chrono::Utc::now().format("%Y-%m-%d").to_string(),
             items: vec![],
             tax: None,
             total: 0.0,
             category: "Other".to_string(),
-        });
-
-    Ok(receipt)
+            date_corrected: false,
+        }],
+    }
 }
 
 /// Parse a bank statement image/PDF to extract transactions using vision
@@ -846,77 +1731,187 @@ pub async fn parse_statement_with_vision_llm(
     provider: &LLMProvider,
     image_path: &str,
     categories: &[String],
+    only_after_date: Option<&str>,
+    period: Option<(&str, &str)>,
 ) -> Result<Vec<ExtractedTransaction>> {
     let is_pdf = image_path.to_lowercase().ends_with(".pdf");
+    let instruction = date_cutoff_instruction(only_after_date) + &statement_period_instruction(period);
 
-    if is_pdf {
+    let transactions = if is_pdf {
         // For PDFs, process page by page
-        parse_pdf_statement_chunked(provider, image_path, categories).await
+        parse_pdf_statement_chunked(provider, image_path, categories, &instruction).await?
     } else {
         // For images, process directly
-        parse_single_page_statement(provider, image_path, categories).await
+        parse_single_page_statement(provider, image_path, categories, &instruction).await?
+    };
+
+    let transactions = assign_statement_years(transactions, period);
+    Ok(drop_rows_before_cutoff(check_amount_signs(transactions), only_after_date))
+}
+
+/// Parse a PDF statement with vision, keeping each chunk's result apart so the
+/// caller can see which page ranges failed or came back empty
+pub async fn parse_statement_chunks_with_vision_llm(
+    provider: &LLMProvider,
+    pdf_path: &str,
+    categories: &[String],
+    only_after_date: Option<&str>,
+    period: Option<(&str, &str)>,
+) -> Result<Vec<StatementChunk>> {
+    let instruction = date_cutoff_instruction(only_after_date) + &statement_period_instruction(period);
+    let mut chunks = parse_pdf_statement_chunks(provider, pdf_path, categories, &instruction).await?;
+    for chunk in &mut chunks {
+        let transactions = assign_statement_years(std::mem::take(&mut chunk.transactions), period);
+        chunk.transactions = drop_rows_before_cutoff(check_amount_signs(transactions), only_after_date);
     }
+    Ok(chunks)
 }
 
-/// Process a PDF statement page by page
-async fn parse_pdf_statement_chunked(
+/// Parse pages `start_page`..=`end_page` (1-indexed) of a PDF statement on their
+/// own, e.g. to retry one chunk of a chunked parse
+pub async fn parse_statement_pages_with_vision_llm(
     provider: &LLMProvider,
     pdf_path: &str,
     categories: &[String],
+    start_page: usize,
+    end_page: usize,
+    only_after_date: Option<&str>,
+    period: Option<(&str, &str)>,
 ) -> Result<Vec<ExtractedTransaction>> {
-    use lopdf::Document;
+    let doc = load_pdf(pdf_path)?;
+    let chunk_pdf = extract_pdf_pages(&doc, start_page, end_page)?;
+    let transactions = parse_statement_chunk(
+        provider,
+        &base64_encode(&chunk_pdf),
+        categories,
+        start_page,
+        end_page,
+        &(date_cutoff_instruction(only_after_date) + &statement_period_instruction(period)),
+    )
+    .await?;
+    let transactions = assign_statement_years(transactions, period);
+    Ok(drop_rows_before_cutoff(check_amount_signs(transactions), only_after_date))
+}
 
+fn load_pdf(pdf_path: &str) -> Result<lopdf::Document> {
     let file_data = std::fs::read(pdf_path)
         .map_err(|e| anyhow::anyhow!("Failed to read PDF {}: {}", pdf_path, e))?;
+    lopdf::Document::load_mem(&file_data)
+        .map_err(|e| anyhow::anyhow!("Failed to parse PDF: {}", e))
+}
 
-    // Load PDF to get page count
-    let doc = Document::load_mem(&file_data)
-        .map_err(|e| anyhow::anyhow!("Failed to parse PDF: {}", e))?;
+/// Process a PDF statement page by page. Any failed chunk fails the whole parse,
+/// naming its pages; a chunk with no transactions is only logged, since a page
+/// can hold nothing but terms and notices.
+async fn parse_pdf_statement_chunked(
+    provider: &LLMProvider,
+    pdf_path: &str,
+    categories: &[String],
+    cutoff_instruction: &str,
+) -> Result<Vec<ExtractedTransaction>> {
+    let chunks = parse_pdf_statement_chunks(provider, pdf_path, categories, cutoff_instruction).await?;
+
+    // A missing chunk would import a statement with a gap in it, so any failure
+    // fails the whole parse. Callers that want partial results use
+    // parse_statement_chunks_with_vision_llm and retry the failed pages.
+    if let Some(failed) = chunks.iter().find(|c| c.status == "failed") {
+        return Err(anyhow::anyhow!(
+            "Pages {}-{} could not be parsed: {}",
+            failed.start_page,
+            failed.end_page,
+            failed.error.as_deref().unwrap_or_default()
+        ));
+    }
 
-    let page_count = doc.get_pages().len();
-    log::info!("[parse_pdf_statement_chunked] PDF has {} pages", page_count);
+    for empty in chunks.iter().filter(|c| c.status == "empty") {
+        log::warn!(
+            "[parse_pdf_statement_chunked] Pages {}-{} returned no transactions",
+            empty.start_page,
+            empty.end_page
+        );
+    }
+
+    let all_transactions: Vec<ExtractedTransaction> = chunks.into_iter().flat_map(|c| c.transactions).collect();
+
+    log::info!("[parse_pdf_statement_chunked] Total extracted: {} transactions", all_transactions.len());
+    Ok(all_transactions)
+}
 
-    // For small PDFs (3 pages or less), process all at once
+/// Page ranges a statement of `page_count` pages is parsed in: all at once up to
+/// 3 pages, otherwise 2 pages at a time
+fn statement_chunk_ranges(page_count: usize) -> Vec<(usize, usize)> {
     if page_count <= 3 {
-        log::info!("[parse_pdf_statement_chunked] Small PDF, processing all pages at once");
-        return parse_single_page_statement(provider, pdf_path, categories).await;
+        return vec![(1, page_count.max(1))];
     }
-
-    // For larger PDFs, process in chunks of 2 pages
-    let mut all_transactions: Vec<ExtractedTransaction> = Vec::new();
     let chunk_size = 2;
-    let total_chunks = (page_count + chunk_size - 1) / chunk_size;
+    (1..=page_count)
+        .step_by(chunk_size)
+        .map(|start_page| (start_page, std::cmp::min(start_page + chunk_size - 1, page_count)))
+        .collect()
+}
 
-    log::info!("[parse_pdf_statement_chunked] Processing {} pages in {} chunks", page_count, total_chunks);
+/// Run every chunk of a PDF statement, recording each chunk's transactions or error
+async fn parse_pdf_statement_chunks(
+    provider: &LLMProvider,
+    pdf_path: &str,
+    categories: &[String],
+    cutoff_instruction: &str,
+) -> Result<Vec<StatementChunk>> {
+    let doc = load_pdf(pdf_path)?;
+    let page_count = doc.get_pages().len();
+    log::info!("[parse_pdf_statement_chunked] PDF has {} pages", page_count);
 
-    for chunk_idx in 0..total_chunks {
-        let start_page = chunk_idx * chunk_size + 1; // 1-indexed
-        let end_page = std::cmp::min(start_page + chunk_size - 1, page_count);
+    let ranges = statement_chunk_ranges(page_count);
+    let total_chunks = ranges.len();
+    log::info!("[parse_pdf_statement_chunked] Processing {} pages in {} chunks", page_count, total_chunks);
 
+    let mut chunks = Vec::with_capacity(total_chunks);
+    for (chunk_idx, (start_page, end_page)) in ranges.into_iter().enumerate() {
         log::info!("[parse_pdf_statement_chunked] Processing chunk {}/{}: pages {}-{}",
             chunk_idx + 1, total_chunks, start_page, end_page);
 
-        // Extract pages for this chunk
-        let chunk_pdf = extract_pdf_pages(&doc, start_page, end_page)?;
-        let base64_data = base64_encode(&chunk_pdf);
-
-        // Parse this chunk
-        let chunk_transactions = parse_statement_chunk(
-            provider,
-            &base64_data,
-            categories,
-            start_page,
-            end_page,
-        ).await?;
-
-        log::info!("[parse_pdf_statement_chunked] Chunk {}: extracted {} transactions",
-            chunk_idx + 1, chunk_transactions.len());
+        let result = if total_chunks == 1 {
+            // Small PDFs go whole, without re-encoding the pages
+            parse_single_page_statement(provider, pdf_path, categories, cutoff_instruction).await
+        } else {
+            match extract_pdf_pages(&doc, start_page, end_page) {
+                Ok(chunk_pdf) => {
+                    parse_statement_chunk(
+                        provider,
+                        &base64_encode(&chunk_pdf),
+                        categories,
+                        start_page,
+                        end_page,
+                        cutoff_instruction,
+                    ).await
+                }
+                Err(e) => Err(e),
+            }
+        };
 
-        all_transactions.extend(chunk_transactions);
+        let chunk = statement_chunk(start_page, end_page, result);
+        log::info!("[parse_pdf_statement_chunked] Chunk {}: {}, extracted {} transactions",
+            chunk_idx + 1, chunk.status, chunk.transactions.len());
+        chunks.push(chunk);
     }
 
-    log::info!("[parse_pdf_statement_chunked] Total extracted: {} transactions", all_transactions.len());
-    Ok(all_transactions)
+    Ok(chunks)
+}
+
+/// Record how one chunk went. A chunk the model found no rows in is "empty"
+/// rather than "ok": statement pages seldom have none, so it's more likely a bad
+/// reply and worth offering reparse_chunk for.
+pub fn statement_chunk(
+    start_page: usize,
+    end_page: usize,
+    result: Result<Vec<ExtractedTransaction>>,
+) -> StatementChunk {
+    let (transactions, status, error) = match result {
+        Ok(transactions) if transactions.is_empty() => (transactions, "empty", None),
+        Ok(transactions) => (transactions, "ok", None),
+        Err(e) => (Vec::new(), "failed", Some(e.to_string())),
+    };
+    StatementChunk { start_page, end_page, transactions, status: status.to_string(), error }
 }
 
 /// Extract specific pages from a PDF document into a new PDF buffer
@@ -949,6 +1944,7 @@ async fn parse_statement_chunk(
     categories: &[String],
     start_page: usize,
     end_page: usize,
+    cutoff_instruction: &str,
 ) -> Result<Vec<ExtractedTransaction>> {
     let categories_str = categories.join(", ");
 
@@ -962,6 +1958,8 @@ Output a JSON array of transactions. Each transaction should have:
 - currency: Currency code (default USD)
 - category: One of: {}
 - merchant: Merchant name extracted from description, or null
+- needs_review: true if you had to guess the date, amount or sign (blurry, cut off or ambiguous); omit otherwise
+- balance: The running balance printed on the row, if the statement has a balance column; omit otherwise
 
 Rules:
 - Extract EVERY transaction row - DO NOT SUMMARIZE OR SKIP ANY
@@ -986,8 +1984,8 @@ Output only valid JSON array, no explanations."#,
     );
 
     let prompt = format!(
-        "Extract ALL transactions from pages {}-{} of this bank statement. Return a JSON array with EVERY transaction.",
-        start_page, end_page
+        "Extract ALL transactions from pages {}-{} of this bank statement. Return a JSON array with EVERY transaction.{}",
+        start_page, end_page, cutoff_instruction
     );
 
     log::info!("[parse_statement_chunk] Calling LLM for pages {}-{}...", start_page, end_page);
@@ -1001,22 +1999,24 @@ Output only valid JSON array, no explanations."#,
     ).await?;
 
     log::info!("[parse_statement_chunk] Got LLM response, length: {} chars", response.len());
-    log::debug!("[parse_statement_chunk] Response preview: {}...", &response[..std::cmp::min(500, response.len())]);
+    log::debug!("[parse_statement_chunk] Response preview: {}...", logging::body(&response[..std::cmp::min(500, response.len())]));
 
     // Parse JSON from response
     log::info!("[parse_statement_chunk] Parsing JSON...");
+    // An unreadable reply fails the chunk rather than passing for a page without
+    // transactions, so it gets retried
     let transactions: Vec<ExtractedTransaction> = serde_json::from_str(&response)
         .or_else(|e| {
             log::warn!("[parse_statement_chunk] Direct JSON parse failed: {}, trying to extract array", e);
             let json_start = response.find('[').unwrap_or(0);
             let json_end = response.rfind(']').map(|i| i + 1).unwrap_or(response.len());
             log::info!("[parse_statement_chunk] Extracting JSON from positions {}-{}", json_start, json_end);
-            serde_json::from_str(&response[json_start..json_end])
+            serde_json::from_str(response.get(json_start..json_end.max(json_start)).unwrap_or_default())
         })
-        .unwrap_or_else(|e| {
+        .map_err(|e| {
             log::error!("[parse_statement_chunk] JSON parse FAILED completely: {}", e);
-            Vec::new()
-        });
+            anyhow::anyhow!("The reply for pages {}-{} was not a transaction list: {}", start_page, end_page, e)
+        })?;
 
     log::info!("[parse_statement_chunk] Parsed {} transactions from chunk", transactions.len());
     Ok(transactions)
@@ -1027,6 +2027,7 @@ async fn parse_single_page_statement(
     provider: &LLMProvider,
     image_path: &str,
     categories: &[String],
+    cutoff_instruction: &str,
 ) -> Result<Vec<ExtractedTransaction>> {
     let categories_str = categories.join(", ");
 
@@ -1058,6 +2059,8 @@ Output a JSON array of transactions. Each transaction should have:
 - currency: Currency code (default USD)
 - category: One of: {}
 - merchant: Merchant name extracted from description, or null
+- needs_review: true if you had to guess the date, amount or sign (blurry, cut off or ambiguous); omit otherwise
+- balance: The running balance printed on the row, if the statement has a balance column; omit otherwise
 
 Rules:
 - Extract EVERY transaction row - DO NOT SUMMARIZE
@@ -1072,9 +2075,13 @@ Output only valid JSON array, no explanations."#,
         categories_str
     );
 
+    let prompt = format!(
+        "Extract all transactions from this bank statement. Return a JSON array with every transaction.{}",
+        cutoff_instruction
+    );
     let response = call_llm_with_vision(
         provider,
-        "Extract all transactions from this bank statement. Return a JSON array with every transaction.",
+        &prompt,
         &base64_data,
         media_type,
         Some(&system_prompt),
@@ -1119,7 +2126,7 @@ Output only valid JSON."#;
 
     let prompt = format!("The user said: \"{}\"", message);
 
-    let response = call_llm(provider, &prompt, Some(system_prompt)).await?;
+    let response = call_llm(provider, &prompt, Some(system_prompt), Some(ResponseFormat::JsonObject)).await?;
 
     let result: ExpenseDetectionResult =
         serde_json::from_str(&response).unwrap_or(ExpenseDetectionResult {
@@ -1135,6 +2142,72 @@ Output only valid JSON."#;
     Ok(result)
 }
 
+const STATEMENT_METADATA_PROMPT: &str = r#"You read the header of a bank, card or mobile money statement.
+
+Extract:
+{
+  "institution": "Bank or provider name" or null,
+  "account_type": "checking" | "savings" | "credit" | "cash" | "investment" | "other" or null,
+  "account_last4": "last 4 digits of the account or card number" or null,
+  "period_start": "YYYY-MM-DD" or null,
+  "period_end": "YYYY-MM-DD" or null
+}
+
+Never return more than the last 4 digits of an account number.
+Output only valid JSON."#;
+
+/// Detect institution, account and period from the text of a statement's first page
+pub async fn detect_statement_metadata_with_llm(
+    provider: &LLMProvider,
+    header_text: &str,
+) -> Result<StatementMetadata> {
+    let prompt = format!("Statement header:\n\n{}", header_text);
+    let response = call_llm(provider, &prompt, Some(STATEMENT_METADATA_PROMPT), Some(ResponseFormat::JsonObject)).await?;
+    Ok(parse_statement_metadata(&response))
+}
+
+/// Detect statement metadata from a scanned PDF or image, sending only the first page
+pub async fn detect_statement_metadata_with_vision(
+    provider: &LLMProvider,
+    file_path: &str,
+) -> Result<StatementMetadata> {
+    let file_data = std::fs::read(file_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", file_path, e))?;
+
+    let lower = file_path.to_lowercase();
+    let (data, media_type) = if lower.ends_with(".pdf") {
+        let doc = lopdf::Document::load_mem(&file_data)
+            .map_err(|e| anyhow::anyhow!("Failed to parse PDF: {}", e))?;
+        (extract_pdf_pages(&doc, 1, 1)?, "application/pdf")
+    } else if lower.ends_with(".png") {
+        (file_data, "image/png")
+    } else if lower.ends_with(".webp") {
+        (file_data, "image/webp")
+    } else {
+        (file_data, "image/jpeg")
+    };
+
+    let response = call_llm_with_vision(
+        provider,
+        "Extract the institution, account type, account last 4 digits and statement period from this statement.",
+        &base64_encode(&data),
+        media_type,
+        Some(STATEMENT_METADATA_PROMPT),
+    )
+    .await?;
+    Ok(parse_statement_metadata(&response))
+}
+
+fn parse_statement_metadata(response: &str) -> StatementMetadata {
+    serde_json::from_str(response)
+        .or_else(|_| {
+            let json_start = response.find('{').unwrap_or(0);
+            let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
+            serde_json::from_str(&response[json_start..json_end.max(json_start)])
+        })
+        .unwrap_or_default()
+}
+
 /// Result of analyzing a user query
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct QueryAnalysis {
@@ -1149,7 +2222,7 @@ pub async fn analyze_query(
     question: &str,
     history: &[ConversationMessage],
 ) -> Result<QueryAnalysis> {
-    log::info!("Analyzing query: {}", question);
+    log::info!("Analyzing query: {}", logging::body(question));
 
     let system_prompt = r#"You are a query analyzer for a personal finance app using SQLite. Analyze the user's question and determine:
 1. Is this a data query that needs to retrieve information from the database?
@@ -1210,6 +2283,9 @@ CREATE TABLE ledger (
     notes TEXT,
     source TEXT NOT NULL,         -- "document", "image", "conversation", "manual"
     created_at TEXT NOT NULL,
+    original_amount REAL,         -- Amount as printed on the statement when converted at import (else NULL)
+    original_currency TEXT,       -- Currency of original_amount
+    exchange_rate REAL,           -- Rate to primary actually paid on this row; overrides currencies.conversion_rate when set
     FOREIGN KEY (account_id) REFERENCES accounts(id),
     FOREIGN KEY (category_id) REFERENCES categories(id)
 );
@@ -1252,7 +2328,7 @@ ITEM QUERIES (purchased_items table):
 CURRENCY HANDLING:
 - Transactions are stored with their original currency in the 'currency' column
 - The primary currency (is_primary=1) is the user's base currency for conversions
-- To convert amounts to primary currency: amount * (SELECT conversion_rate FROM currencies WHERE code = ledger.currency)
+- To convert amounts to primary currency: amount * COALESCE(ledger.exchange_rate, (SELECT conversion_rate FROM currencies WHERE code = ledger.currency))
 - When aggregating across currencies, convert to primary currency first
 - User's default currency can be found in settings table: SELECT value FROM settings WHERE key = 'default_currency'
 
@@ -1278,7 +2354,7 @@ Examples:
 - "what currencies do I have?" -> {"needs_data": true, "sql_query": "SELECT code, name, symbol, conversion_rate, is_primary FROM currencies ORDER BY is_primary DESC, name", "query_type": "data_query"}
 - "what is my default currency?" -> {"needs_data": true, "sql_query": "SELECT value as default_currency FROM settings WHERE key = 'default_currency'", "query_type": "data_query"}
 - "spending by currency" -> {"needs_data": true, "sql_query": "SELECT l.currency, c.symbol, SUM(ABS(l.amount)) as total FROM ledger l LEFT JOIN currencies c ON l.currency = c.code WHERE l.amount < 0 GROUP BY l.currency ORDER BY total DESC", "query_type": "data_query"}
-- "total spending in primary currency" -> {"needs_data": true, "sql_query": "SELECT SUM(ABS(l.amount) * COALESCE(c.conversion_rate, 1.0)) as total_in_primary FROM ledger l LEFT JOIN currencies c ON l.currency = c.code WHERE l.amount < 0", "query_type": "data_query"}
+- "total spending in primary currency" -> {"needs_data": true, "sql_query": "SELECT SUM(ABS(l.amount) * COALESCE(l.exchange_rate, c.conversion_rate, 1.0)) as total_in_primary FROM ledger l LEFT JOIN currencies c ON l.currency = c.code WHERE l.amount < 0", "query_type": "data_query"}
 
 Output ONLY valid JSON, no markdown."#;
 
@@ -1287,8 +2363,8 @@ Output ONLY valid JSON, no markdown."#;
     let full_prompt = format!("{}{}", context, question);
 
     log::info!("[ANALYZE] Sending query to LLM for analysis...");
-    let response_text = call_llm(provider, &full_prompt, Some(system_prompt)).await?;
-    log::info!("[ANALYZE] Raw LLM response: {}", response_text);
+    let response_text = call_llm(provider, &full_prompt, Some(system_prompt), Some(ResponseFormat::JsonObject)).await?;
+    log::info!("[ANALYZE] Raw LLM response: {}", logging::body(&response_text));
 
     // Parse the response
     let cleaned = response_text
@@ -1298,7 +2374,7 @@ Output ONLY valid JSON, no markdown."#;
         .trim_end_matches("```")
         .trim();
 
-    log::info!("[ANALYZE] Cleaned response: {}", cleaned);
+    log::info!("[ANALYZE] Cleaned response: {}", logging::body(cleaned));
 
     let analysis: QueryAnalysis = serde_json::from_str(cleaned)
         .or_else(|e| {
@@ -1307,7 +2383,7 @@ Output ONLY valid JSON, no markdown."#;
             if let Some(start) = response_text.find('{') {
                 if let Some(end) = response_text.rfind('}') {
                     let extracted = &response_text[start..=end];
-                    log::info!("[ANALYZE] Trying extracted JSON: {}", extracted);
+                    log::info!("[ANALYZE] Trying extracted JSON: {}", logging::body(extracted));
                     return serde_json::from_str(extracted);
                 }
             }
@@ -1339,15 +2415,15 @@ pub async fn format_query_results(
     history: &[ConversationMessage],
 ) -> Result<ResponseData> {
     log::info!("[FORMAT] Formatting query results...");
-    log::info!("[FORMAT] Original question: {}", question);
-    log::info!("[FORMAT] Data to format: {}", data);
+    log::info!("[FORMAT] Original question: {}", logging::body(question));
+    log::info!("[FORMAT] Data to format: {}", logging::body(data));
 
     let system_prompt = r#"You are Yuki, a friendly personal finance assistant. Format query results into clear, actionable responses.
 
 STYLE GUIDELINES:
 - Be concise: Get to the point quickly. No filler words.
 - Be specific: Use exact numbers. "You spent $1,234.56" not "You spent a lot."
-- Be insightful: Add brief context when helpful (e.g., "That's 15% more than last month")
+- Be insightful: Add brief context when helpful (e.g., "That's 15% more than last month"), but only state comparisons the query results actually contain
 - Use markdown: Bold key numbers, use bullet points for lists
 
 RESPONSE RULES:
@@ -1388,8 +2464,8 @@ Output ONLY valid JSON."#;
     );
 
     log::info!("[FORMAT] Sending to LLM for formatting...");
-    let response_text = call_llm(provider, &prompt, Some(system_prompt)).await?;
-    log::info!("[FORMAT] Raw LLM response: {}", response_text);
+    let response_text = call_llm(provider, &prompt, Some(system_prompt), Some(ResponseFormat::JsonObject)).await?;
+    log::info!("[FORMAT] Raw LLM response: {}", logging::body(&response_text));
 
     let result = parse_llm_response(&response_text)?;
     log::info!("[FORMAT] Parsed response with {} cards", result.cards.len());
@@ -1402,7 +2478,7 @@ pub async fn process_conversational_query(
     question: &str,
     history: &[ConversationMessage],
 ) -> Result<ResponseData> {
-    log::info!("[CONVO] Processing conversational query: {}", question);
+    log::info!("[CONVO] Processing conversational query: {}", logging::body(question));
 
     let system_prompt = r#"You are Yuki, a friendly personal finance assistant.
 
@@ -1442,14 +2518,19 @@ Output ONLY valid JSON."#;
     let full_prompt = format!("{}{}", context, question);
 
     log::info!("[CONVO] Sending to LLM...");
-    let response_text = call_llm(provider, &full_prompt, Some(system_prompt)).await?;
-    log::info!("[CONVO] Raw LLM response: {}", response_text);
+    let response_text = call_llm(provider, &full_prompt, Some(system_prompt), Some(ResponseFormat::JsonObject)).await?;
+    log::info!("[CONVO] Raw LLM response: {}", logging::body(&response_text));
 
     parse_llm_response(&response_text)
 }
 
 /// Parse LLM response, handling various formats
 fn parse_llm_response(response_text: &str) -> Result<ResponseData> {
+    // A blank text card would show the user nothing at all
+    if response_text.trim().is_empty() {
+        return Err(anyhow::anyhow!(EMPTY_RESPONSE_MESSAGE));
+    }
+
     // First, try direct JSON parse
     if let Ok(response) = serde_json::from_str::<ResponseData>(response_text) {
         return Ok(response);
@@ -1478,7 +2559,7 @@ fn parse_llm_response(response_text: &str) -> Result<ResponseData> {
     }
 
     // If all parsing fails, wrap the response as a text card
-    log::warn!("Could not parse LLM response as JSON, wrapping as text: {}", response_text);
+    log::warn!("Could not parse LLM response as JSON, wrapping as text: {}", logging::body(response_text));
     Ok(ResponseData {
         cards: vec![ResponseCard::Text(TextContent {
             body: response_text.to_string(),
@@ -1486,3 +2567,414 @@ fn parse_llm_response(response_text: &str) -> Result<ResponseData> {
         })],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_openai_embeddings_in_index_order() {
+        let body = json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.5, -0.25] },
+                { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "model": "text-embedding-3-small"
+        });
+
+        let vectors = parse_embeddings_response(&body).unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.5, -0.25]]);
+    }
+
+    #[test]
+    fn parses_ollama_embedding() {
+        let body = json!({ "embedding": [0.1, 0.2, 0.3] });
+
+        let vectors = parse_embeddings_response(&body).unwrap();
+        assert_eq!(vectors, vec![vec![0.1f32, 0.2, 0.3]]);
+    }
+
+    #[test]
+    fn rejects_unknown_embedding_shape() {
+        assert!(parse_embeddings_response(&json!({ "error": "model not found" })).is_err());
+    }
+
+    fn provider(provider_type: &str, endpoint: &str, is_local: bool) -> LLMProvider {
+        LLMProvider {
+            provider_type: provider_type.to_string(),
+            name: provider_type.to_string(),
+            endpoint: endpoint.to_string(),
+            api_key: None,
+            model: "test".to_string(),
+            is_local,
+            requests_per_minute: None,
+        }
+    }
+
+    #[test]
+    fn local_only_blocks_cloud_and_allows_loopback() {
+        let anthropic = provider("anthropic", "https://api.anthropic.com/v1", false);
+        let ollama = provider("ollama", "http://localhost:11434", true);
+        let lmstudio = provider("lmstudio", "http://127.0.0.1:1234/v1", true);
+        // Marked local but pointed at another machine
+        let remote_ollama = provider("ollama", "http://192.168.1.20:11434", true);
+
+        let err = check_provider_allowed(&anthropic, true).unwrap_err();
+        assert!(err.to_string().contains("Local-only mode"));
+        assert!(check_provider_allowed(&ollama, true).is_ok());
+        assert!(check_provider_allowed(&lmstudio, true).is_ok());
+        assert!(check_provider_allowed(&remote_ollama, true).is_err());
+        assert!(check_provider_allowed(&anthropic, false).is_ok());
+    }
+
+    #[test]
+    fn joins_all_anthropic_text_blocks() {
+        let body = json!({
+            "content": [
+                { "type": "text", "text": "[{\"date\": " },
+                { "type": "tool_use", "id": "toolu_1", "name": "noop", "input": {} },
+                { "type": "text", "text": "\"2025-01-01\"}]" }
+            ],
+            "stop_reason": "end_turn"
+        });
+
+        assert_eq!(extract_response_text("Anthropic", &body).unwrap(), "[{\"date\": \"2025-01-01\"}]");
+    }
+
+    #[test]
+    fn surfaces_refusals_as_readable_errors() {
+        let openai = json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": null, "refusal": "I can't help with that." },
+                "finish_reason": "stop"
+            }]
+        });
+        let err = extract_response_text("OpenAI-compatible API", &openai).unwrap_err().to_string();
+        assert_eq!(err, "OpenAI-compatible API declined to answer: I can't help with that.");
+
+        let filtered = json!({ "choices": [{ "message": { "content": null }, "finish_reason": "content_filter" }] });
+        assert!(extract_response_text("OpenAI", &filtered).unwrap_err().to_string().contains("content filter"));
+
+        let anthropic = json!({ "content": [], "stop_reason": "refusal" });
+        assert!(extract_response_text("Anthropic", &anthropic).unwrap_err().to_string().contains("declined"));
+
+        let tool_only = json!({ "content": [{ "type": "tool_use", "id": "t", "name": "x", "input": {} }] });
+        assert!(extract_response_text("Anthropic", &tool_only).unwrap_err().to_string().contains("tool call"));
+    }
+
+    #[test]
+    fn json_mode_sets_each_providers_field() {
+        let format = Some(ResponseFormat::JsonObject);
+
+        let body = openai_chat_body(&provider("openai", "https://api.openai.com/v1", false), "q", Some("sys"), format);
+        assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+
+        let body = google_body("q", Some("sys"), format);
+        assert_eq!(body["generationConfig"]["responseMimeType"], "application/json");
+
+        let ollama = provider("ollama", "http://localhost:11434", true);
+        assert_eq!(ollama_body(&ollama, "q", None, format)["format"], "json");
+        // An array can't be asked for through JSON mode
+        assert!(ollama_body(&ollama, "q", None, Some(ResponseFormat::JsonArray)).get("format").is_none());
+
+        let prefill = format.map(ResponseFormat::prefill);
+        let body = anthropic_body(&provider("anthropic", "https://api.anthropic.com/v1", false), "q", None, prefill);
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(
+            messages.last().unwrap(),
+            &json!({ "role": "assistant", "content": [{ "type": "text", "text": "{" }] })
+        );
+
+        // No format leaves every body as plain text
+        let openai = provider("openai", "https://api.openai.com/v1", false);
+        assert!(openai_chat_body(&openai, "q", None, None).get("response_format").is_none());
+        assert!(google_body("q", None, None).get("generationConfig").is_none());
+        assert_eq!(anthropic_body(&openai, "q", None, None)["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn anthropic_body_uses_structured_text_blocks() {
+        let anthropic = provider("anthropic", "https://api.anthropic.com/v1", false);
+
+        let body = anthropic_body(&anthropic, "How much on groceries?", Some("sys"), None);
+        assert_eq!(
+            body["messages"],
+            json!([{ "role": "user", "content": [{ "type": "text", "text": "How much on groceries?" }] }])
+        );
+        assert_eq!(body["system"], "sys");
+
+        // A longer prefill, e.g. to continue a truncated reply
+        let body = anthropic_body(&anthropic, "List my March purchases", None, Some(r#"[{"date": "2025-03-01""#));
+        assert_eq!(
+            body["messages"][1],
+            json!({ "role": "assistant", "content": [{ "type": "text", "text": "[{\"date\": \"2025-03-01\"" }] })
+        );
+        assert!(body.get("system").is_none());
+    }
+
+    #[test]
+    fn json_mode_degrades_where_unsupported() {
+        // LM Studio rejects json_object, and OpenAI's mode can't return a bare array
+        let lmstudio = provider("lmstudio", "http://localhost:1234/v1", true);
+        assert!(openai_chat_body(&lmstudio, "q", None, Some(ResponseFormat::JsonObject))
+            .get("response_format")
+            .is_none());
+        let openai = provider("openai", "https://api.openai.com/v1", false);
+        assert!(openai_chat_body(&openai, "q", None, Some(ResponseFormat::JsonArray))
+            .get("response_format")
+            .is_none());
+
+        // A model that refuses the mode is retried without it; other errors are not
+        assert!(is_unsupported_format_error(
+            "OpenAI API error: 'response_format' of type 'json_object' is not supported with this model."
+        ));
+        assert!(is_unsupported_format_error(
+            "Anthropic API error: This model does not support assistant message prefill."
+        ));
+        assert!(!is_unsupported_format_error("Anthropic API error: invalid x-api-key"));
+    }
+
+    #[test]
+    fn two_receipts_on_one_page_parse_separately() {
+        let response = r#"{"receipts": [
+            {"merchant": "Naivas", "date": "2025-03-04", "items": [
+                {"name": "whole-milk", "quantity": 2, "unit": "each", "unit_price": 65.0, "total_price": 130.0, "category": "dairy", "brand": "Brookside"},
+                {"name": "white-bread", "quantity": 1, "unit": null, "unit_price": 70.0, "total_price": 70.0, "category": "bakery", "brand": null}
+            ], "tax": 32.0, "total": 200.0, "category": "Groceries"},
+            {"merchant": "Java House", "date": "2025-03-04", "items": [
+                {"name": "cappuccino", "quantity": 1, "unit": null, "unit_price": 350.0, "total_price": 350.0, "category": "beverages", "brand": null}
+            ], "tax": null, "total": 350.0, "category": "Dining"}
+        ]}"#;
+
+        let receipts = parse_receipts_response(response);
+        assert_eq!(receipts.len(), 2);
+        assert_eq!((receipts[0].merchant.as_str(), receipts[0].total, receipts[0].items.len()), ("Naivas", 200.0, 2));
+        assert_eq!((receipts[1].merchant.as_str(), receipts[1].total, receipts[1].items.len()), ("Java House", 350.0, 1));
+    }
+
+    #[test]
+    fn single_receipt_object_still_parses() {
+        // The pre-multi-receipt shape, wrapped in prose the way some models answer
+        let response = r#"Here is the receipt:
+{"merchant": "Carrefour", "date": "2025-02-01", "items": [], "tax": null, "total": 1250.0, "category": "Groceries"}"#;
+        let receipts = parse_receipts_response(response);
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].merchant, "Carrefour");
+
+        let fallback = parse_receipts_response("I couldn't read this image.");
+        assert_eq!(fallback.len(), 1);
+        assert_eq!(fallback[0].merchant, "Unknown");
+    }
+
+    #[test]
+    fn statement_metadata_survives_stray_braces() {
+        let metadata = parse_statement_metadata(r#"Found it: {"institution": "KCB Bank", "period_start": "2025-01-01"}"#);
+        assert_eq!(metadata.institution.as_deref(), Some("KCB Bank"));
+        assert_eq!(metadata.period_start.as_deref(), Some("2025-01-01"));
+
+        // A closing brace before the opening one used to panic on the slice
+        for response in ["} oops {", "no braces at all", ""] {
+            assert_eq!(parse_statement_metadata(response).institution, None);
+        }
+    }
+
+    #[test]
+    fn implausible_receipt_dates_fall_back_to_today() {
+        let response = r#"{"receipts": [
+            {"merchant": "Naivas", "date": "2099-03-04", "items": [], "tax": null, "total": 200.0, "category": "Groceries"},
+            {"merchant": "Kiosk", "date": "1921-03-04", "items": [], "tax": null, "total": 50.0, "category": "Groceries"},
+            {"merchant": "Java House", "date": "2025-06-01", "items": [], "tax": null, "total": 350.0, "category": "Dining"}
+        ]}"#;
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+
+        let receipts: Vec<ParsedReceipt> =
+            parse_receipts_response(response).into_iter().map(|r| check_receipt_date(r, today)).collect();
+        let dates: Vec<(&str, bool)> = receipts.iter().map(|r| (r.date.as_str(), r.date_corrected)).collect();
+        assert_eq!(dates, vec![("2025-06-01", true), ("2025-06-01", true), ("2025-06-01", false)]);
+
+        // A day ahead is allowed for time zones
+        let receipt = ParsedReceipt { date: "2025-06-02".to_string(), ..receipts[2].clone() };
+        assert!(!check_receipt_date(receipt, today).date_corrected);
+    }
+
+    #[test]
+    fn every_supported_provider_has_a_usable_preset() {
+        // Every provider_type call_llm dispatches on
+        for provider_type in ["ollama", "lmstudio", "anthropic", "openai", "google", "openrouter"] {
+            let preset = provider_preset(provider_type).unwrap_or_else(|| panic!("no preset for {}", provider_type));
+
+            let url = reqwest::Url::parse(&preset.default_endpoint).unwrap();
+            assert!(matches!(url.scheme(), "http" | "https"), "{}", preset.default_endpoint);
+            assert!(!preset.default_endpoint.ends_with('/'), "endpoints are joined with '/path'");
+            // Local presets must pass local-only mode out of the box, remote ones use TLS
+            assert_eq!(is_loopback_endpoint(&preset.default_endpoint), preset.is_local);
+            assert_eq!(url.scheme() == "https", !preset.is_local);
+            assert_eq!(preset.requires_api_key, !preset.is_local);
+        }
+        assert_eq!(provider_presets().len(), 6);
+        assert!(provider_preset("unknown").is_none());
+    }
+
+    #[test]
+    fn blank_response_is_an_error_not_an_empty_card() {
+        let err = parse_llm_response(" \n\t").unwrap_err();
+        assert_eq!(err.to_string(), EMPTY_RESPONSE_MESSAGE);
+
+        let wrapped = parse_llm_response("You spent KSh 900 on dining.").unwrap();
+        assert!(matches!(&wrapped.cards[..], [ResponseCard::Text(text)] if text.body == "You spent KSh 900 on dining."));
+    }
+
+    #[test]
+    fn relative_dates_resolve_against_today() {
+        // A Friday
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+        assert_eq!(resolve_relative_dates("coffee Yesterday 250", today), "coffee 2025-03-13 250");
+        assert_eq!(resolve_relative_dates("the day before yesterday", today), "2025-03-12");
+        assert_eq!(resolve_relative_dates("3 days ago", today), "2025-03-11");
+        assert_eq!(resolve_relative_dates("last Friday", today), "2025-03-07");
+        assert_eq!(resolve_relative_dates("last monday", today), "2025-03-10");
+        assert_eq!(resolve_relative_dates("lunch today", today), "lunch 2025-03-14");
+        assert_eq!(resolve_relative_dates("2025-03-01 rent", today), "2025-03-01 rent");
+    }
+
+    #[test]
+    fn statement_language_is_detected_from_marker_words() {
+        let french = "RELEVÉ DE COMPTE\n03/03/2025 PAIEMENT CARTE BOULANGERIE DU COIN -4,20\n\
+            05/03/2025 VIREMENT SALAIRE +2 100,00\n07/03/2025 FRAIS DE TENUE DE COMPTE -2,50";
+        assert_eq!(detect_language(french), Some("French"));
+        assert!(language_instruction(french).contains("in French: write each description in English"));
+
+        let swahili = "Umetuma Ksh 1,500.00 kwa JOHN KAMAU tarehe 3/3/25. Salio lako ni Ksh 4,200.00";
+        assert_eq!(detect_language(swahili), Some("Swahili"));
+
+        let english = "03/03/2025 CARD PURCHASE LE CROISSANT -4.20\n05/03/2025 TRANSFER FROM SAVINGS 100.00\n\
+            Closing balance 2,100.00 after the monthly fee";
+        assert_eq!(detect_language(english), None);
+        assert_eq!(language_instruction(english), "");
+        assert_eq!(detect_language("Total 4,20"), None);
+    }
+
+    #[test]
+    fn flipped_salary_is_corrected_by_the_balance_or_flagged() {
+        let row = |date: &str, description: &str, amount: f64, balance: Option<f64>| ExtractedTransaction {
+            date: date.to_string(),
+            description: description.to_string(),
+            amount,
+            currency: "KES".to_string(),
+            category: String::new(),
+            merchant: None,
+            needs_review: None,
+            external_id: None,
+            balance,
+        };
+
+        // Oldest first: the balance rose by 85,000, so the salary was money in
+        let statement = vec![
+            row("2025-03-01", "Naivas Westlands", -2150.0, Some(10000.0)),
+            row("2025-03-25", "SALARY MARCH ACME LTD", -85000.0, Some(95000.0)),
+            row("2025-03-26", "KPLC prepaid", -3000.0, Some(92000.0)),
+        ];
+        let checked = check_amount_signs(statement.clone());
+        assert_eq!(checked[1].amount, 85000.0);
+        assert_eq!(checked[1].needs_review, None);
+        assert_eq!(checked[2].amount, -3000.0);
+
+        // The same rows newest first
+        let newest_first: Vec<ExtractedTransaction> = statement.into_iter().rev().collect();
+        let checked = check_amount_signs(newest_first);
+        assert_eq!(checked[1].amount, 85000.0);
+        assert_eq!(checked[0].amount, -3000.0);
+
+        // No balance column: the keyword can't prove the sign, so the row is flagged
+        let checked = check_amount_signs(vec![
+            row("2025-03-25", "Salary March", -85000.0, None),
+            row("2025-03-26", "Java House", -900.0, None),
+        ]);
+        assert_eq!(checked[0].amount, -85000.0);
+        assert_eq!(checked[0].needs_review, Some(true));
+        assert_eq!(checked[1].needs_review, None);
+    }
+
+    #[test]
+    fn january_rows_of_a_december_statement_get_the_next_year() {
+        let row = |date: &str| ExtractedTransaction {
+            date: date.to_string(),
+            description: "Purchase".to_string(),
+            amount: -100.0,
+            currency: "KES".to_string(),
+            category: String::new(),
+            merchant: None,
+            needs_review: None,
+            external_id: None,
+            balance: None,
+        };
+        let statement = vec![
+            row("2024-12-28"),
+            // The model took the year from the statement header
+            row("2024-01-05"),
+            // Passed through without a year
+            row("01/05"),
+            row("12/31"),
+            // Nowhere near the period in any year
+            row("2024-06-15"),
+            row("not a date"),
+        ];
+        let period = Some(("2024-12-15", "2025-01-14"));
+
+        let dates: Vec<String> = assign_statement_years(statement.clone(), period).into_iter().map(|t| t.date).collect();
+        assert_eq!(dates, vec!["2024-12-28", "2025-01-05", "2025-01-05", "2024-12-31", "2024-06-15", "not a date"]);
+
+        // Without a period nothing changes
+        let unchanged: Vec<String> = assign_statement_years(statement, None).into_iter().map(|t| t.date).collect();
+        assert_eq!(unchanged[1], "2024-01-05");
+        assert!(statement_period_instruction(period).contains("2024-12-15 to 2025-01-14"));
+    }
+
+    #[test]
+    fn capabilities_follow_the_provider_dispatch() {
+        // There is no call_ollama_vision, so Ollama can't take images
+        let ollama = provider_capabilities("ollama");
+        assert!(!ollama.vision);
+        assert!(ollama.json_mode && ollama.embeddings);
+
+        let anthropic = provider_capabilities("anthropic");
+        assert!(anthropic.vision && anthropic.json_mode);
+        assert!(!anthropic.embeddings);
+
+        assert!(!provider_capabilities("lmstudio").json_mode);
+        for provider_type in ["ollama", "lmstudio", "anthropic", "openai", "google", "openrouter"] {
+            let capabilities = provider_capabilities(provider_type);
+            assert!(!capabilities.streaming && capabilities.model_listing);
+            assert_eq!(provider_preset(provider_type).unwrap().supports_vision, capabilities.vision);
+        }
+        let unknown = provider_capabilities("unknown");
+        assert!(!unknown.model_listing && !unknown.vision && !unknown.json_mode && !unknown.embeddings);
+    }
+
+    #[test]
+    fn openai_listing_keeps_current_chat_models() {
+        let body = json!({
+            "object": "list",
+            "data": [
+                { "id": "gpt-4o" },
+                { "id": "o1-preview" },
+                { "id": "o3-mini" },
+                { "id": "chatgpt-4o-latest" },
+                { "id": "gpt-4o-realtime-preview" },
+                { "id": "gpt-4o-audio-preview" },
+                { "id": "gpt-3.5-turbo-instruct" },
+                { "id": "text-embedding-3-small" },
+                { "id": "whisper-1" },
+                { "id": "dall-e-3" },
+                { "id": "omni-moderation-latest" }
+            ]
+        });
+
+        let models = openai_chat_models(model_ids(&body));
+        assert_eq!(models, vec!["chatgpt-4o-latest", "gpt-4o", "o1-preview", "o3-mini"]);
+
+        assert_eq!(filter_models(models.clone(), Some(" O1 ")), vec!["o1-preview"]);
+        assert_eq!(filter_models(models, Some("")).len(), 4);
+    }
+}
@@ -0,0 +1,100 @@
+use regex::Regex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Whether prompts, LLM responses and raw provider bodies may be written to the log
+static LOG_BODIES: AtomicBool = AtomicBool::new(true);
+
+lazy_static::lazy_static! {
+    // Provider API keys and auth headers/params that can surface in request errors
+    static ref SECRET_PATTERNS: Vec<Regex> = vec![
+        Regex::new(r"sk-[A-Za-z0-9_\-]{16,}").unwrap(),
+        Regex::new(r"AIza[0-9A-Za-z_\-]{20,}").unwrap(),
+        Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._\-]{16,}").unwrap(),
+        Regex::new(r#"(?i)((?:x-api-key|api[_-]?key)["']?\s*[:=]\s*["']?)[A-Za-z0-9._\-]{8,}"#).unwrap(),
+        Regex::new(r"([?&]key=)[^&\s)]+").unwrap(),
+    ];
+    // Card and bank account numbers: three or more 4-digit groups, or long digit
+    // runs. Two groups would catch year ranges like "2024-2025".
+    static ref ACCOUNT_NUMBER: Regex =
+        Regex::new(r"\b(?:\d{4}[ -]?){2,4}\d{4}\b|\b\d{8,19}\b").unwrap();
+}
+
+/// Parse a log level name ("off", "error", "warn", "info", "debug", "trace")
+pub fn parse_log_level(level: &str) -> Option<log::LevelFilter> {
+    level.trim().parse::<log::LevelFilter>().ok()
+}
+
+/// Apply the user's log settings to the running logger
+pub fn apply_settings(level: &str, log_bodies: bool) {
+    let filter = parse_log_level(level).unwrap_or(log::LevelFilter::Info);
+    log::set_max_level(filter);
+    LOG_BODIES.store(log_bodies, Ordering::Relaxed);
+}
+
+/// Mask anything resembling an API key or account number
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    for pattern in SECRET_PATTERNS.iter() {
+        redacted = pattern
+            .replace_all(&redacted, |caps: &regex::Captures| {
+                // Keep the header/param name when the pattern captured one
+                let prefix = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+                format!("{}[REDACTED]", prefix)
+            })
+            .into_owned();
+    }
+
+    ACCOUNT_NUMBER
+        .replace_all(&redacted, |caps: &regex::Captures| {
+            let digits: String = caps[0].chars().filter(|c| c.is_ascii_digit()).collect();
+            // A compact date such as 20250131 is left readable
+            if digits.len() == 8 && chrono::NaiveDate::parse_from_str(&digits, "%Y%m%d").is_ok() {
+                return caps[0].to_string();
+            }
+            format!("****{}", &digits[digits.len() - 4..])
+        })
+        .into_owned()
+}
+
+/// Prepare a prompt/response body for logging: redacted, or withheld entirely
+/// when body logging is disabled
+pub fn body(text: &str) -> String {
+    if LOG_BODIES.load(Ordering::Relaxed) {
+        redact(text)
+    } else {
+        format!("[{} chars, body logging disabled]", text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_api_keys() {
+        let line = "request failed: x-api-key: sk-ant-REDACTED for https://generativelanguage.googleapis.com/v1beta/models/gemini:generateContent?key=AIzaSyA1234567890abcdefghijklmnop";
+        let redacted = redact(line);
+
+        assert!(!redacted.contains("sk-ant-api03"));
+        assert!(!redacted.contains("AIzaSy"));
+        assert!(redacted.contains("x-api-key: [REDACTED]"));
+        assert!(redacted.contains("?key=[REDACTED]"));
+    }
+
+    #[test]
+    fn masks_account_numbers_but_keeps_dates_and_amounts() {
+        let redacted = redact("2025-01-15 transfer from 4111 1111 1111 1234 acct 0012345678 amount 1500.25");
+
+        assert_eq!(redacted, "2025-01-15 transfer from ****1234 acct ****5678 amount 1500.25");
+
+        let redacted = redact("statements for 2024-2025, closing 20250131, ref 12345678");
+        assert_eq!(redacted, "statements for 2024-2025, closing 20250131, ref ****5678");
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!(parse_log_level("debug"), Some(log::LevelFilter::Debug));
+        assert_eq!(parse_log_level("WARN"), Some(log::LevelFilter::Warn));
+        assert_eq!(parse_log_level("loud"), None);
+    }
+}
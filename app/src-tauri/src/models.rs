@@ -8,6 +8,17 @@ pub struct PdfExtractionResult {
     pub is_scanned: bool, // True if PDF appears to be a scan (little/no text)
 }
 
+/// What parsing a document is likely to cost before it's sent to the LLM
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseCostEstimate {
+    pub mode: String, // "text", or "vision" for scanned PDFs and images
+    pub page_count: usize,
+    pub estimated_input_tokens: u64,
+    pub estimated_output_tokens: u64,
+    pub estimated_cost_usd: Option<f64>, // None when no provider is set or its model's price is unknown
+    pub recommended_chunking: usize,     // Requests to split the parse into; 1 sends it whole
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
     pub id: String,
@@ -30,11 +41,33 @@ pub struct Account {
     pub created_at: String,
 }
 
+/// Sum of an account's ledger rows up to and including a date, for reconciling
+/// against a statement's closing balance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountBalance {
+    pub account_id: String,
+    pub date: String,
+    pub balance: f64,     // In the account's currency
+    pub currency: String, // The account's currency
+    pub transaction_count: i64,
+}
+
+/// Money in and out of an account in one month, in the account's currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashFlowMonth {
+    pub month: String, // YYYY-MM
+    pub inflow: f64,   // Sum of positive amounts
+    pub outflow: f64,  // Sum of negative amounts, as a positive number
+    pub net: f64,
+}
+
 /// Conversation message for maintaining chat context
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationMessage {
     pub role: String, // "user" or "assistant"
     pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,10 +82,340 @@ pub struct LedgerEntry {
     pub category_id: String,
     pub merchant: Option<String>,
     pub notes: Option<String>,
-    pub source: String, // "document", "image", "conversation", "manual"
+    pub source: String, // "document", "image", "conversation", "manual", "recurring"
+    pub created_at: String,
+    #[serde(default)]
+    pub original_amount: Option<f64>, // Set when `amount` was converted at import
+    #[serde(default)]
+    pub original_currency: Option<String>,
+    #[serde(default)]
+    pub needs_review: Option<bool>, // Extraction was uncertain or didn't reconcile
+}
+
+/// A ledger row with its amount converted to the primary currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedTransaction {
+    #[serde(flatten)]
+    pub entry: LedgerEntry,
+    pub amount_in_primary: f64,
+}
+
+/// One line of export_ledger_ndjson
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedLedgerEntry {
+    #[serde(flatten)]
+    pub entry: LedgerEntry,
+    pub category_name: Option<String>, // None when the category no longer exists
+}
+
+/// Ledger rows that share date, amount and normalized description
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub date: String,
+    pub amount: f64,
+    pub description: String,
+    pub ids: Vec<String>, // Oldest first, so ids[0] is the natural row to keep
+    pub key: String,      // Stable id for ignore_flag("duplicate", key)
+}
+
+/// A flagged anomaly or duplicate the user has reviewed and dismissed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IgnoredFlag {
+    pub flag_type: String, // "anomaly" or "duplicate"
+    pub target_id: String,
     pub created_at: String,
 }
 
+/// Expense total for one day of the week
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeekdaySpending {
+    pub weekday: u32, // 0 = Sunday, matching SQLite's strftime('%w')
+    pub label: String,
+    pub total: f64, // Primary currency
+    pub count: i64,
+}
+
+/// How often a merchant shows up among expenses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantFrequency {
+    pub merchant: String,
+    pub visits: i64,
+    pub total: f64, // Primary currency
+}
+
+/// Spending at one merchant over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantStats {
+    pub merchant: String,
+    pub visit_count: i64,
+    pub total_spent: f64, // Primary currency
+    pub avg_per_visit: f64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// One category's spending this month against the month before
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryMonthComparison {
+    pub category_id: String,
+    pub month: String,          // YYYY-MM
+    pub previous_month: String, // YYYY-MM
+    pub current_total: f64,     // Primary currency
+    pub previous_total: f64,
+    pub change: f64,
+    pub percent_change: Option<f64>, // None when last month had no spending
+}
+
+/// Spending so far this month or quarter against the same days a year earlier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YearOverYear {
+    pub period: String,         // month or quarter
+    pub current_start: String,  // YYYY-MM-DD
+    pub current_end: String,    // Today
+    pub previous_start: String, // The same dates one year earlier
+    pub previous_end: String,
+    pub current_total: f64, // Primary currency
+    pub previous_total: f64,
+    pub percent_change: Option<f64>, // None when nothing was spent a year ago
+}
+
+/// Spending per category in one month, for stacked charts. Every category seen in
+/// the requested window appears in every month, with 0.0 where nothing was spent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryTrendMonth {
+    pub month: String,                                     // YYYY-MM
+    pub totals: std::collections::BTreeMap<String, f64>, // category_id -> primary currency
+}
+
+/// Direction of monthly spending from a least-squares fit (get_spending_trend_direction)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendingTrend {
+    pub direction: String,      // "up", "down" or "flat"
+    pub slope_per_month: f64,   // Change in monthly spending per month, primary currency
+    pub r_squared: Option<f64>, // How well the line fits, 0 to 1; None when every month is equal
+    pub average_monthly: f64,
+    pub months: Vec<ChartDataPoint>, // YYYY-MM totals the line was fitted to, oldest first
+}
+
+/// A recurring charge whose latest price is above what it used to cost
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceIncrease {
+    pub merchant: String,
+    pub old_amount: f64, // Typical charge before the increase, positive, in the charge's currency
+    pub new_amount: f64,
+    pub currency: String,
+    pub percent_increase: f64,
+    pub effective_date: String, // First charge at the new price
+    pub key: String,            // Stable id for ignore_flag("anomaly", key); a later increase gets a new one
+}
+
+/// A recurring charge that did not post when its cadence said it would
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissingRecurring {
+    pub merchant: String,
+    pub expected_around: String, // One typical gap after the last charge
+    pub days_overdue: i64,
+    pub typical_amount: f64, // Median charge, positive, in the charge's currency
+    pub currency: String,
+    pub key: String, // Stable id for ignore_flag("anomaly", key); the next missed charge gets a new one
+}
+
+/// How often a merchant charges (get_merchant_cadence)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantCadence {
+    pub merchant_pattern: String,
+    pub charge_days: usize, // Distinct days with a charge
+    pub average_days: Option<f64>, // Between consecutive charge days; None with fewer than two
+    pub min_days: Option<i64>,
+    pub max_days: Option<i64>,
+    pub frequency: String, // daily, weekly, biweekly, monthly, yearly or irregular
+    pub first_charge: Option<String>,
+    pub last_charge: Option<String>,
+}
+
+/// Income total for one kind of inflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncomeTypeTotal {
+    pub income_type: String, // payroll, refund, interest, transfer or other
+    pub total: f64,          // Primary currency
+    pub count: i64,
+}
+
+/// A positive row that looks like money back for an earlier purchase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundMatch {
+    pub refund_id: String,
+    pub refund_date: String,
+    pub refund_amount: f64, // Positive, in the shared currency
+    pub refund_category_id: String,
+    pub purchase_id: String,
+    pub purchase_date: String,
+    pub purchase_amount: f64, // Positive
+    pub purchase_category_id: String, // Suggested category for the refund
+    pub merchant: String,
+    pub currency: String,
+    pub days_after: i64,
+    pub full_refund: bool, // Refund equals the purchase to the cent
+}
+
+/// One category's spending measured against income over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryIncomeRatio {
+    pub category_id: String,
+    pub category_name: String,
+    pub total: f64,                     // Primary currency
+    pub percent_of_income: Option<f64>, // None when nothing came in
+}
+
+/// How long liquid money would cover spending with nothing coming in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancialRunway {
+    pub liquid_balance: f64,        // Checking, savings, cash and mobile money, primary currency
+    pub avg_monthly_expenses: f64,  // Over the last three full months
+    pub runway_months: Option<f64>, // None when nothing was spent: the money lasts indefinitely
+    pub account_count: i64,
+}
+
+/// Money being put aside toward a target, kept in the savings_goals setting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavingsGoal {
+    pub name: String,
+    pub target_amount: f64, // Primary currency
+    pub saved_amount: f64,
+    pub target_date: String, // YYYY-MM-DD
+}
+
+/// How far a purchase pushes back a savings goal (evaluate_purchase_impact)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurchaseImpact {
+    pub goal_id: String,
+    pub goal_name: String,
+    pub target_date: String,
+    pub remaining: f64,       // Still to save before the purchase
+    pub monthly_savings: f64, // Net income averaged over the last three full months
+    pub projected_date: Option<String>, // None when nothing is being saved
+    pub projected_date_with_purchase: Option<String>,
+    pub delay_days: Option<i64>,
+    pub delay_weeks: Option<f64>,
+    pub on_track: bool, // Projected to reach the goal by its target date
+    pub on_track_with_purchase: bool,
+}
+
+/// A transaction whose sign contradicts its category (detect_sign_errors)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignError {
+    pub transaction: LedgerEntry,
+    pub expected_sign: String, // "negative" for money in under an expense category, "positive" for money out under income
+}
+
+/// Income against spending over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavingsRate {
+    pub income: f64,   // Primary currency
+    pub expenses: f64, // Primary currency, positive
+    pub savings: f64,
+    pub savings_rate_percent: Option<f64>, // None when there was no income
+}
+
+/// Spending over a period split into essentials and discretionary spending.
+/// Categories in neither bucket are counted as unclassified.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendingSplit {
+    pub essential_total: f64,   // Primary currency
+    pub essential_percent: f64, // Share of all spending; 0 when nothing was spent
+    pub discretionary_total: f64,
+    pub discretionary_percent: f64,
+    pub unclassified_total: f64,
+    pub unclassified_percent: f64,
+    pub total: f64,
+}
+
+/// What is left of this month's budgets, spread over the days that remain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyAllowance {
+    pub monthly_budget: f64,   // Sum of category budgets, primary currency
+    pub spent_this_month: f64, // In budgeted categories, through today
+    pub remaining: f64,        // Negative once over budget
+    pub days_remaining: i64,   // Including today
+    pub weeks_remaining: f64,
+    pub per_week: f64,         // Safe to spend per week; 0 once over budget
+    pub today: f64,            // Safe to spend today
+}
+
+/// A budgeted category that is over, or heading over, its monthly budget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetAlert {
+    pub category_id: String,
+    pub category_name: String,
+    pub severity: String, // "critical" (over), "warning" (projected over) or "info" (nearly used up)
+    pub budget: f64,      // Primary currency
+    pub spent: f64,       // Month to date
+    pub projected: f64,   // Month end at the month-to-date daily rate
+    pub percent_used: f64,
+}
+
+/// Deductible spending in one category for a tax year
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeductibleCategoryTotal {
+    pub category_id: String,
+    pub category_name: String,
+    pub total: f64, // Primary currency
+    pub count: i64,
+}
+
+/// Year-end total of spending in the categories marked deductible
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeductibleSummary {
+    pub tax_year: i32,
+    pub categories: Vec<DeductibleCategoryTotal>, // Largest first
+    pub total: f64,
+}
+
+/// Inclusive YYYY-MM-DD bounds; either side may be open
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DateRange {
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub end_date: Option<String>,
+}
+
+/// Expense total for a single date
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayTotal {
+    pub date: String,
+    pub total: f64, // Primary currency
+}
+
+/// How fast money goes out over a window, in the primary currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendingMetrics {
+    pub avg_daily_spend: f64, // Over every day in the window, not just days with spending
+    pub avg_transaction: f64,
+    pub largest_day: Option<DayTotal>, // None when nothing was spent
+    pub transaction_count: i64,
+    pub days_with_spending: i64,
+}
+
+/// Spread of expense sizes in the primary currency; all zero when nothing was spent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmountPercentiles {
+    pub median: f64,
+    pub p75: f64,
+    pub p90: f64,
+    pub max: f64,
+    pub min: f64,
+    pub count: i64,
+}
+
+/// Mismatches between the documents table and the files on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanReport {
+    pub orphaned_files: Vec<String>, // Paths in documents/ with no row
+    pub orphaned_bytes: u64,
+    pub missing_files: Vec<String>, // Document ids whose file is gone
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Receipt {
     pub id: String,
@@ -87,6 +450,59 @@ pub struct PurchasedItem {
     pub created_at: String,
 }
 
+/// A bill posted to the ledger once a month on `day_of_month`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringTemplate {
+    pub id: String,
+    pub merchant: String,
+    pub amount: f64, // Signed like ledger amounts, so bills are negative
+    pub category_id: String,
+    pub account_id: String,
+    pub day_of_month: u32, // 1-31; later than the month's last day means the last day
+    pub active: bool,
+    pub last_posted_cycle: Option<String>, // YYYY-MM
+    pub created_at: String,
+}
+
+/// What post_due_recurring added to the ledger
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecurringPostSummary {
+    pub posted: usize,
+    pub skipped: Vec<RecurringTemplate>, // Due, but their account no longer exists
+}
+
+/// Maps item names matching `pattern` onto `canonical` in item reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemAlias {
+    pub pattern: String, // Lowercase; `*` matches any run of characters
+    pub canonical: String,
+    pub created_at: String,
+}
+
+/// Purchased-item spending for one canonical item name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalizedItemSpending {
+    pub name: String,
+    pub quantity: f64,
+    pub total_price: f64,
+    pub purchase_count: i64,
+    pub variants: Vec<String>, // Distinct original names folded into this row
+}
+
+/// One purchased item in get_most_expensive_items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpensiveItem {
+    pub id: String,
+    pub name: String,     // Cleaned up for display
+    pub raw_name: String, // As extracted from the receipt
+    pub quantity: f64,
+    pub unit_price: Option<f64>,
+    pub total_price: f64,
+    pub brand: Option<String>,
+    pub merchant: Option<String>, // From the receipt, else the linked transaction
+    pub purchased_at: String,     // YYYY-MM-DD
+}
+
 /// Parsed item from receipt with more detail for LLM extraction
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedReceiptItem {
@@ -107,6 +523,10 @@ pub struct Category {
     pub color: Option<String>,
     pub is_default: bool,
     pub created_at: String,
+    #[serde(default)]
+    pub sort_order: Option<i64>, // Position set by set_category_order; None sorts after ordered ones
+    #[serde(default)]
+    pub is_pinned: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,6 +539,50 @@ pub struct ChatHistoryEntry {
     pub created_at: String,
 }
 
+/// Summary of an exported or imported backup bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSummary {
+    pub transaction_count: i64,
+    pub document_count: usize,
+    pub created_at: String,
+}
+
+/// Categorization setup written by export_config, without any financial data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigExport {
+    pub format_version: u32,
+    pub exported_at: String,
+    pub categories: Vec<Category>,
+    pub item_aliases: Vec<ItemAlias>,
+    #[serde(default)]
+    pub spending_buckets: std::collections::HashMap<String, String>, // Category id to bucket overrides
+    #[serde(default)]
+    pub category_budgets: std::collections::HashMap<String, f64>, // Category id to monthly budget
+}
+
+/// What import_config changed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigImportSummary {
+    pub categories_added: usize,
+    pub categories_updated: usize,
+    pub categories_matched_by_name: usize, // Same name under a different id; the local id is kept
+    pub item_aliases: usize,
+    pub spending_buckets: usize,
+    pub category_budgets: usize,
+}
+
+/// Rows and files removed by reset_data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResetSummary {
+    pub transactions: usize,
+    pub purchased_items: usize,
+    pub receipts: usize,
+    pub documents: usize,
+    pub conversation_messages: usize,
+    pub chat_history: usize,
+    pub files_deleted: usize,
+}
+
 // Currency model
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -134,6 +598,22 @@ pub struct Currency {
     pub created_at: String,
 }
 
+/// Rate implied by a converted transaction against the stored currency rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveExchangeRate {
+    pub ledger_id: String,
+    pub date: String,
+    pub description: String,
+    pub original_amount: f64,
+    pub original_currency: String,
+    pub amount: f64,            // In `currency`, the account's
+    pub currency: String,
+    pub implied_rate: f64,      // `currency` units per `original_currency` unit
+    pub stored_rate: f64,       // Same, from the currencies table
+    pub deviation_percent: f64, // Positive when more was paid than the stored rate gives
+    pub flagged: bool,
+}
+
 // Settings models
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -147,6 +627,36 @@ pub struct LLMProvider {
     pub model: String,
     #[serde(rename = "isLocal")]
     pub is_local: bool,
+    #[serde(rename = "requestsPerMinute", default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>, // None or 0 disables rate limiting
+}
+
+/// Features a provider type supports, so the UI only offers what will work
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    pub vision: bool,
+    pub streaming: bool,
+    pub json_mode: bool,
+    pub embeddings: bool,
+    pub model_listing: bool,
+}
+
+/// Defaults the settings UI prefills for a provider type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderPreset {
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    pub name: String,
+    #[serde(rename = "defaultEndpoint")]
+    pub default_endpoint: String,
+    #[serde(rename = "defaultModel")]
+    pub default_model: Option<String>, // None where the model is whatever the user has loaded
+    #[serde(rename = "requiresApiKey")]
+    pub requires_api_key: bool,
+    #[serde(rename = "supportsVision")]
+    pub supports_vision: bool,
+    #[serde(rename = "isLocal")]
+    pub is_local: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -155,6 +665,52 @@ pub struct Settings {
     #[serde(rename = "defaultCurrency")]
     pub default_currency: String,
     pub theme: String,
+    #[serde(rename = "logLevel", default = "default_log_level")]
+    pub log_level: String, // "off", "error", "warn", "info", "debug", "trace"
+    #[serde(rename = "logBodies", default = "default_log_bodies")]
+    pub log_bodies: bool, // false keeps prompts and LLM responses out of the log
+    #[serde(rename = "localOnly", default)]
+    pub local_only: bool, // Refuse any provider that isn't running on this machine
+    #[serde(rename = "importWebhookUrl", default, skip_serializing_if = "Option::is_none")]
+    pub import_webhook_url: Option<String>, // Receives a POST of each imported batch
+    #[serde(rename = "importWebhookSecret", default, skip_serializing_if = "Option::is_none")]
+    pub import_webhook_secret: Option<String>, // Signs webhook bodies with HMAC-SHA256
+    #[serde(rename = "importMinAmount", default, skip_serializing_if = "Option::is_none")]
+    pub import_min_amount: Option<f64>, // Imports skip rows whose absolute amount is below this
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_bodies() -> bool {
+    true
+}
+
+/// Settings and surrounding configuration for a bug report, secrets masked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsReport {
+    pub app_version: String,
+    pub settings: Settings,
+    pub currencies: Vec<Currency>,
+    pub category_count: i64,
+    pub custom_category_count: i64,
+    pub account_count: i64,
+    pub transaction_count: i64,
+}
+
+/// Result of one setup check in the diagnostics report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: String, // "pass", "warn", "fail"
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    pub status: String, // Worst status among the checks
+    pub checks: Vec<DiagnosticCheck>,
 }
 
 // Response card types
@@ -221,9 +777,50 @@ pub struct ExtractedTransaction {
     pub date: String,
     pub description: String,
     pub amount: f64,
-    pub currency: String,
+    #[serde(default)]
+    pub currency: String, // Empty falls back to the target account's currency on import
+    #[serde(default)]
     pub category: String,
     pub merchant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub needs_review: Option<bool>, // Set by the LLM when it had to guess a field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>, // Bank-assigned id (OFX FITID); deduplicates re-imports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<f64>, // Running balance on the statement row, used to check signs
+}
+
+/// What one page range of a chunked statement parse produced. A "failed" or
+/// "empty" chunk can be re-run on its own with reparse_chunk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementChunk {
+    pub start_page: usize, // 1-indexed, inclusive
+    pub end_page: usize,
+    pub transactions: Vec<ExtractedTransaction>,
+    pub status: String, // "ok", "empty" (no rows came back, which is suspect), "failed"
+    pub error: Option<String>, // Why the chunk failed; its transactions are then empty
+}
+
+/// Outcome of importing extracted transactions into the ledger
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub skipped_duplicates: usize,
+    pub category_unmatched: usize, // Filed under "other"
+    pub flagged_for_review: usize,
+    pub below_threshold: usize, // Smaller than the minimum amount, not imported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_id: Option<String>, // Batch the inserted rows belong to; None when nothing was inserted
+}
+
+/// One import run, whose transactions can be rolled back together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportBatch {
+    pub id: String,
+    pub source: String,
+    pub document_id: Option<String>,
+    pub created_at: String,
+    pub transaction_count: i64, // Rows the import added
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -234,6 +831,25 @@ pub struct ParsedReceipt {
     pub tax: Option<f64>,
     pub total: f64,
     pub category: String,
+    #[serde(default)]
+    pub date_corrected: bool, // The read date was implausible and replaced with the parse date
+}
+
+/// What one file of a receipt folder produced (parse_receipt_folder)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptFileResult {
+    pub filename: String,
+    pub receipts: Vec<ParsedReceipt>,
+    pub error: Option<String>, // Why the file couldn't be parsed; receipts is then empty
+}
+
+/// Payload of the receipt-folder-progress event, sent as each file finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptFolderProgress {
+    pub filename: String,
+    pub completed: usize,
+    pub total: usize,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -252,3 +868,17 @@ pub struct ExpenseDetectionResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub confidence: Option<String>,
 }
+
+/// Account details read from a statement header
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatementMetadata {
+    pub institution: Option<String>,
+    pub account_type: Option<String>, // Same values as Account.account_type
+    pub account_last4: Option<String>,
+    pub period_start: Option<String>, // YYYY-MM-DD
+    pub period_end: Option<String>,
+    #[serde(default)]
+    pub suggested_account_id: Option<String>, // Existing account whose institution matches
+    #[serde(default)]
+    pub language: Option<String>, // e.g. "French"; None for English or too little text to tell
+}
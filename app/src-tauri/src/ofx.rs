@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Result};
+
+use crate::models::ExtractedTransaction;
+
+/// OFX 1.x files are often Windows-1252; anything that isn't valid UTF-8 is
+/// read as Latin-1, which covers the characters banks actually use
+pub fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Parse the `STMTTRN` records of an OFX statement. Handles both OFX 1.x SGML,
+/// where leaf elements are never closed, and OFX 2.x XML. Each record's FITID
+/// becomes its `external_id` so re-importing the same file is a no-op.
+pub fn parse(text: &str) -> Result<Vec<ExtractedTransaction>> {
+    let start = text
+        .to_ascii_uppercase()
+        .find("<OFX>")
+        .ok_or_else(|| anyhow!("Not an OFX file: no <OFX> element found"))?;
+
+    let mut transactions = Vec::new();
+    let mut currency = String::new();
+    let mut record: Option<Record> = None;
+
+    for element in elements(&text[start..]) {
+        match element {
+            Element::Open(tag) if tag == "STMTTRN" => record = Some(Record::default()),
+            Element::Close(tag) if tag == "STMTTRN" => {
+                if let Some(r) = record.take() {
+                    transactions.push(r.into_transaction(&currency, transactions.len() + 1)?);
+                }
+            }
+            Element::Leaf(tag, value) => match (&mut record, tag.as_str()) {
+                (Some(r), "DTPOSTED") => r.posted = Some(value),
+                (Some(r), "TRNAMT") => r.amount = Some(value),
+                (Some(r), "NAME") => r.name = Some(value),
+                (Some(r), "MEMO") => r.memo = Some(value),
+                (Some(r), "FITID") => r.fitid = Some(value),
+                // CURDEF precedes each statement's transaction list
+                (None, "CURDEF") => currency = value.to_uppercase(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    // SGML lets the last aggregate run to the end of the file
+    if let Some(r) = record {
+        transactions.push(r.into_transaction(&currency, transactions.len() + 1)?);
+    }
+
+    Ok(transactions)
+}
+
+#[derive(Default)]
+struct Record {
+    posted: Option<String>,
+    amount: Option<String>,
+    name: Option<String>,
+    memo: Option<String>,
+    fitid: Option<String>,
+}
+
+impl Record {
+    fn into_transaction(self, currency: &str, position: usize) -> Result<ExtractedTransaction> {
+        let label = match &self.fitid {
+            Some(fitid) => format!("transaction {} (FITID {})", position, fitid),
+            None => format!("transaction {}", position),
+        };
+
+        let date = self
+            .posted
+            .as_deref()
+            .and_then(parse_date)
+            .ok_or_else(|| anyhow!("{} has a missing or invalid DTPOSTED", label))?;
+        let amount = self
+            .amount
+            .as_deref()
+            .and_then(parse_amount)
+            .ok_or_else(|| anyhow!("{} has a missing or invalid TRNAMT", label))?;
+
+        let name = self.name.filter(|n| !n.is_empty());
+        let memo = self.memo.filter(|m| !m.is_empty());
+        let description = match (&name, &memo) {
+            (Some(n), Some(m)) if !n.eq_ignore_ascii_case(m) => format!("{} - {}", n, m),
+            (Some(n), _) => n.clone(),
+            (None, Some(m)) => m.clone(),
+            (None, None) => "Unknown".to_string(),
+        };
+
+        Ok(ExtractedTransaction {
+            date,
+            description,
+            amount,
+            currency: currency.to_string(),
+            category: String::new(),
+            merchant: name,
+            needs_review: None,
+            external_id: self.fitid.filter(|f| !f.is_empty()),
+            balance: None,
+        })
+    }
+}
+
+/// `YYYYMMDD[HHMMSS[.XXX]][[gmt offset:tz name]]` -> `YYYY-MM-DD`
+fn parse_date(value: &str) -> Option<String> {
+    let digits = value.get(..8)?;
+    chrono::NaiveDate::parse_from_str(digits, "%Y%m%d")
+        .ok()
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// Signed decimal, allowing the comma decimal separator some banks emit
+fn parse_amount(value: &str) -> Option<f64> {
+    let value = value.trim().trim_start_matches('+');
+    let normalized = if value.contains('.') { value.to_string() } else { value.replace(',', ".") };
+    normalized.parse::<f64>().ok().filter(|a| a.is_finite())
+}
+
+enum Element {
+    Open(String),
+    Close(String),
+    Leaf(String, String),
+}
+
+/// Flatten the markup into open/close/leaf events. A tag followed by text is a
+/// leaf whether or not a matching close tag follows, which is what lets the same
+/// walk read SGML and XML.
+fn elements(markup: &str) -> Vec<Element> {
+    let mut out = Vec::new();
+    let mut rest = markup;
+
+    while let Some(open) = rest.find('<') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('>') else { break };
+        let tag = after[..close].trim();
+        rest = &after[close + 1..];
+
+        // Processing instructions, comments and declarations carry no data
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            out.push(Element::Close(name.trim().to_uppercase()));
+            continue;
+        }
+
+        let name = tag.trim_end_matches('/').trim().to_uppercase();
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        let text = rest[..text_end].trim();
+        if text.is_empty() {
+            out.push(Element::Open(name));
+        } else {
+            out.push(Element::Leaf(name, unescape(text)));
+        }
+    }
+
+    out
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+CHARSET:1252
+
+<OFX>
+<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0<SEVERITY>INFO</STATUS><DTSERVER>20250402</SONRS></SIGNONMSGSRSV1>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>kes
+<BANKTRANLIST>
+<DTSTART>20250301<DTEND>20250331
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20250303120000.000[+3:EAT]
+<TRNAMT>-2150.50
+<FITID>2025030301
+<NAME>NAIVAS WESTLANDS
+<MEMO>POS PURCHASE
+</STMTTRN>
+<STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20250325<TRNAMT>+85000.00<FITID>2025032502<MEMO>Salary &amp; allowances</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>";
+
+    #[test]
+    fn sgml_statement_parses_unclosed_elements() {
+        let transactions = parse(SGML).unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        let purchase = &transactions[0];
+        assert_eq!(purchase.date, "2025-03-03");
+        assert_eq!(purchase.description, "NAIVAS WESTLANDS - POS PURCHASE");
+        assert_eq!(purchase.merchant.as_deref(), Some("NAIVAS WESTLANDS"));
+        assert_eq!(purchase.currency, "KES");
+        assert_eq!(purchase.external_id.as_deref(), Some("2025030301"));
+        assert_eq!(purchase.amount, -2150.5);
+        assert_eq!(parse_amount("-12,50"), Some(-12.5));
+
+        let salary = &transactions[1];
+        assert_eq!(salary.date, "2025-03-25");
+        assert_eq!(salary.amount, 85000.0);
+        assert_eq!(salary.description, "Salary & allowances");
+        assert_eq!(salary.merchant, None);
+    }
+
+    #[test]
+    fn xml_statement_parses_closed_elements() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX><CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+  <CURDEF>USD</CURDEF>
+  <BANKTRANLIST>
+    <STMTTRN>
+      <TRNTYPE>DEBIT</TRNTYPE>
+      <DTPOSTED>20250214</DTPOSTED>
+      <TRNAMT>-42.10</TRNAMT>
+      <FITID>cc-991</FITID>
+      <NAME>Bookshop</NAME>
+    </STMTTRN>
+  </BANKTRANLIST>
+</CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>"#;
+
+        let transactions = parse(xml).unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].date, "2025-02-14");
+        assert_eq!(transactions[0].amount, -42.10);
+        assert_eq!(transactions[0].currency, "USD");
+        assert_eq!(transactions[0].description, "Bookshop");
+        assert_eq!(transactions[0].external_id.as_deref(), Some("cc-991"));
+
+        assert!(parse("date,amount\n2025-01-01,5").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use regex::Regex;
+
+use crate::models::{ChartContent, ResponseCard, ResponseData, TableContent};
+
+lazy_static::lazy_static! {
+    static ref IMAGE: Regex = Regex::new(r"!\[([^\]]*)\]\([^)]*\)").unwrap();
+    static ref LINK: Regex = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
+    // Bold/italic markers hugging text; a spaced "3 * 4" is left alone
+    static ref EMPHASIS: Regex = Regex::new(r"(\*\*|__|\*)(\S(?:.*?\S)?)(\*\*|__|\*)").unwrap();
+    static ref HEADING: Regex = Regex::new(r"^#{1,6}\s+").unwrap();
+    static ref BULLET: Regex = Regex::new(r"^(\s*)[-*+]\s+").unwrap();
+    static ref RULE: Regex = Regex::new(r"^\s*([-*_])(\s*[-*_]){2,}\s*$").unwrap();
+}
+
+/// Flatten response cards to plain text for plaintext export and screen readers:
+/// markdown is stripped, tables become aligned columns and charts a
+/// "label: value" list. Cards are separated by a blank line.
+pub fn render_plaintext(response: &ResponseData) -> String {
+    response
+        .cards
+        .iter()
+        .map(|card| match card {
+            ResponseCard::Text(content) => strip_markdown(&content.body),
+            ResponseCard::Chart(content) => chart_text(content),
+            ResponseCard::Table(content) => table_text(content),
+            ResponseCard::Mixed(content) => format!("{}\n\n{}", strip_markdown(&content.body), chart_text(&content.chart)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn strip_markdown(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        // Code fences only mark where a block starts and ends
+        if trimmed.starts_with("```") {
+            continue;
+        }
+        if RULE.is_match(line) {
+            lines.push(String::new());
+            continue;
+        }
+
+        let line = HEADING.replace(trimmed.strip_prefix("> ").unwrap_or(line), "");
+        let line = BULLET.replace(&line, "$1");
+        let line = IMAGE.replace_all(&line, "$1");
+        let line = LINK.replace_all(&line, "$1 ($2)");
+        let line = EMPHASIS.replace_all(&line, "$2");
+        lines.push(line.replace('`', ""));
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn chart_text(chart: &ChartContent) -> String {
+    let mut lines = vec![chart.title.clone()];
+    lines.extend(chart.data.iter().map(|point| format!("{}: {:.2}", point.label, point.value)));
+    if let Some(caption) = &chart.caption {
+        lines.push(strip_markdown(caption));
+    }
+    lines.join("\n")
+}
+
+fn table_text(table: &TableContent) -> String {
+    let columns = table.columns.len().max(table.rows.iter().map(|r| r.len()).max().unwrap_or(0));
+    let cell = |row: &[String], i: usize| row.get(i).map(|c| strip_markdown(c)).unwrap_or_default();
+
+    let header: Vec<String> = (0..columns).map(|i| cell(&table.columns, i)).collect();
+    let rows: Vec<Vec<String>> = table.rows.iter().map(|row| (0..columns).map(|i| cell(row, i)).collect()).collect();
+
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(rows.iter())
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // Numbers line up on the right like they would in a spreadsheet
+    let numeric: Vec<bool> = (0..columns)
+        .map(|i| !rows.is_empty() && rows.iter().all(|row| row[i].is_empty() || is_number(&row[i])))
+        .collect();
+
+    let format_row = |row: &[String]| {
+        row.iter()
+            .enumerate()
+            .map(|(i, value)| {
+                if numeric[i] {
+                    format!("{:>width$}", value, width = widths[i])
+                } else {
+                    format!("{:<width$}", value, width = widths[i])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![table.title.clone(), format_row(&header)];
+    lines.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  "));
+    lines.extend(rows.iter().map(|row| format_row(row)));
+    if let Some(summary) = &table.summary {
+        lines.push(String::new());
+        lines.push(strip_markdown(summary));
+    }
+    lines.join("\n")
+}
+
+fn is_number(value: &str) -> bool {
+    value.replace([',', '%'], "").trim().parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChartDataPoint, TextContent};
+
+    #[test]
+    fn markdown_is_stripped_from_text_cards() {
+        let response = ResponseData {
+            cards: vec![ResponseCard::Text(TextContent {
+                body: "## March\n\nYou spent **KSh 12,500** on *groceries*:\n\n- Naivas: 8,000\n* Carrefour: 4,500\n\nSee [the ledger](yuki://ledger). 3 * 4 = 12".to_string(),
+                is_error: None,
+            })],
+        };
+
+        assert_eq!(
+            render_plaintext(&response),
+            "March\n\nYou spent KSh 12,500 on groceries:\n\nNaivas: 8,000\nCarrefour: 4,500\n\nSee the ledger (yuki://ledger). 3 * 4 = 12"
+        );
+    }
+
+    #[test]
+    fn tables_align_and_charts_list_values() {
+        let response = ResponseData {
+            cards: vec![
+                ResponseCard::Table(TableContent {
+                    title: "Top merchants".to_string(),
+                    columns: vec!["Merchant".to_string(), "Total".to_string()],
+                    rows: vec![
+                        vec!["Naivas".to_string(), "8,000.00".to_string()],
+                        vec!["**Java House**".to_string(), "900.00".to_string()],
+                    ],
+                    summary: None,
+                }),
+                ResponseCard::Chart(ChartContent {
+                    chart_type: "pie".to_string(),
+                    title: "By category".to_string(),
+                    data: vec![ChartDataPoint { label: "Dining".to_string(), value: 900.0 }],
+                    caption: None,
+                }),
+            ],
+        };
+
+        assert_eq!(
+            render_plaintext(&response),
+            "Top merchants\n\
+             Merchant       Total\n\
+             ----------  --------\n\
+             Naivas      8,000.00\n\
+             Java House    900.00\n\
+             \n\
+             By category\n\
+             Dining: 900.00"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static::lazy_static! {
+    // Shared by every LLM call so chunked/batch work queues behind the same quota
+    pub static ref PROVIDER_LIMITER: RateLimiter = RateLimiter::default();
+}
+
+/// Token bucket holding at most one request, refilled at `requests_per_minute`.
+/// Tokens may go negative: each caller reserves its slot up front and then
+/// sleeps off the deficit, so concurrent waiters are spaced rather than racing.
+struct Bucket {
+    tokens: f64,
+    per_second: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(requests_per_minute: u32) -> Self {
+        Bucket {
+            tokens: 1.0,
+            per_second: requests_per_minute as f64 / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token and return how long the caller must wait before using it
+    fn reserve(&mut self, requests_per_minute: u32) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.per_second = requests_per_minute as f64 / 60.0;
+        self.tokens = (self.tokens + elapsed * self.per_second).min(1.0);
+        self.last_refill = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+/// Per-key request limiter; keys are provider types ("anthropic", "openai", ...)
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Wait until a request for `key` is allowed. `None` or 0 means unlimited.
+    pub async fn acquire(&self, key: &str, requests_per_minute: Option<u32>) {
+        let rpm = match requests_per_minute {
+            Some(rpm) if rpm > 0 => rpm,
+            _ => return,
+        };
+
+        let wait = {
+            let mut buckets = match self.buckets.lock() {
+                Ok(buckets) => buckets,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            buckets
+                .entry(key.to_string())
+                .or_insert_with(|| Bucket::new(rpm))
+                .reserve(rpm)
+        };
+
+        if !wait.is_zero() {
+            log::debug!("Rate limiting {}: waiting {:?}", key, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn calls_are_spaced_by_the_configured_rate() {
+        let limiter = RateLimiter::default();
+        // 1200/min is one call every 50ms; the first call is free
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire("openai", Some(1200)).await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        // Other providers have their own bucket, and None never waits
+        let start = Instant::now();
+        limiter.acquire("anthropic", Some(1200)).await;
+        limiter.acquire("ollama", None).await;
+        limiter.acquire("ollama", None).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::json;
+use sha2::Sha256;
+use std::time::Duration;
+
+use crate::llm;
+use crate::logging;
+use crate::models::LedgerEntry;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when a webhook secret is set
+pub const SIGNATURE_HEADER: &str = "X-Yuki-Signature";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    // One client for every delivery so connections to the endpoint are reused
+    static ref CLIENT: Client = Client::new();
+}
+
+/// HMAC-SHA256 of `body` keyed with `secret`, formatted for SIGNATURE_HEADER
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn payload(transactions: &[LedgerEntry]) -> serde_json::Value {
+    json!({
+        "event": "transactions.imported",
+        "transactions": transactions,
+    })
+}
+
+/// POST the imported transactions to `url` and fail on any non-2xx response
+pub async fn deliver(url: &str, secret: Option<&str>, transactions: &[LedgerEntry]) -> Result<()> {
+    let body = serde_json::to_vec(&payload(transactions))?;
+
+    let mut request = CLIENT
+        .post(url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header("content-type", "application/json");
+    if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+        request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+    }
+
+    let response = request.body(body).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Webhook returned {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Deliver in the background; the import has already been committed, so a
+/// failing endpoint is only logged
+pub fn notify_imported(url: String, secret: Option<String>, local_only: bool, transactions: Vec<LedgerEntry>) {
+    if transactions.is_empty() {
+        return;
+    }
+    // Local-only mode keeps transaction data on this machine
+    if local_only && !llm::is_loopback_endpoint(&url) {
+        log::warn!("Skipping import webhook: {} is not a local address and local-only mode is on", url);
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        match deliver(&url, secret.as_deref(), &transactions).await {
+            Ok(()) => log::info!("Import webhook delivered {} transactions", transactions.len()),
+            Err(e) => log::warn!("Import webhook failed: {}", logging::redact(&e.to_string())),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn entry(id: &str, amount: f64) -> LedgerEntry {
+        LedgerEntry {
+            id: id.to_string(),
+            document_id: None,
+            account_id: Some("default".to_string()),
+            date: "2025-03-04".to_string(),
+            description: "Naivas".to_string(),
+            amount,
+            currency: "KES".to_string(),
+            category_id: "groceries".to_string(),
+            merchant: None,
+            notes: None,
+            source: "document".to_string(),
+            created_at: "2025-03-05".to_string(),
+            original_amount: None,
+            original_currency: None,
+            needs_review: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            socket.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        deliver(&url, Some("s3cret"), &[entry("t1", -2150.0)]).await.unwrap();
+        let request = server.await.unwrap();
+
+        let (headers, body) = request.split_once("\r\n\r\n").unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["event"], "transactions.imported");
+        assert_eq!(json["transactions"][0]["id"], "t1");
+        assert_eq!(json["transactions"][0]["amount"], -2150.0);
+
+        let signature = headers
+            .lines()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case(SIGNATURE_HEADER))
+            .map(|(_, value)| value.trim())
+            .unwrap();
+        assert_eq!(signature, sign("s3cret", body.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoint_is_an_error_not_a_panic() {
+        // Bind then drop to get a port nothing is listening on
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let result = deliver(&format!("http://127.0.0.1:{}/hook", port), None, &[entry("t1", -1.0)]).await;
+        assert!(result.is_err());
+    }
+}
@@ -15,6 +15,7 @@ import {
   Smartphone,
 } from "lucide-react";
 import type { Account } from "@/types";
+import { errorMessage } from "@/lib/tauri";
 import { Skeleton } from "./Skeleton";
 
 interface AccountsModalProps {
@@ -125,7 +126,7 @@ export function AccountsModal({ onClose, onAccountsChange }: AccountsModalProps)
       loadAccounts();
       onAccountsChange?.();
     } catch (err) {
-      setError(err instanceof Error ? err.message : "Failed to add account");
+      setError(errorMessage(err, "Failed to add account"));
     } finally {
       setIsAdding(false);
     }
@@ -150,7 +151,7 @@ export function AccountsModal({ onClose, onAccountsChange }: AccountsModalProps)
       loadAccounts();
       onAccountsChange?.();
     } catch (err) {
-      setError(err instanceof Error ? err.message : "Failed to delete account");
+      setError(errorMessage(err, "Failed to delete account"));
     }
   };
 
@@ -4,6 +4,7 @@ import { useState } from "react";
 import { Modal } from "./Modal";
 import { Loader2 } from "lucide-react";
 import type { Category, Account } from "@/types";
+import { errorMessage } from "@/lib/tauri";
 
 interface AddEntryModalProps {
   onClose: () => void;
@@ -67,7 +68,7 @@ export function AddEntryModal({ onClose, onSave, categories, accounts }: AddEntr
       await onSave({ ...formData, amount: finalAmount });
       onClose();
     } catch (err) {
-      setError(err instanceof Error ? err.message : "Failed to save entry");
+      setError(errorMessage(err, "Failed to save entry"));
     } finally {
       setIsSubmitting(false);
     }
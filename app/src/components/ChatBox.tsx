@@ -4,6 +4,7 @@ import { useState, useCallback, KeyboardEvent } from "react";
 import { Send, Loader2 } from "lucide-react";
 import { useAppStore } from "@/store/appStore";
 import { sendQuery } from "@/lib/llm";
+import { errorMessage } from "@/lib/tauri";
 
 interface ChatBoxProps {
   disabled?: boolean;
@@ -34,15 +35,14 @@ export function ChatBox({ disabled = false }: ChatBoxProps) {
         created_at: new Date().toISOString(),
       });
     } catch (err) {
-      const errorMessage =
-        err instanceof Error ? err.message : "An error occurred";
-      setError(errorMessage);
+      const message = errorMessage(err);
+      setError(message);
       setCurrentResponse({
         cards: [
           {
             type: "text",
             content: {
-              body: `Something went wrong: ${errorMessage}. Try rephrasing your question.`,
+              body: `Something went wrong: ${message}. Try rephrasing your question.`,
               is_error: true,
             },
           },
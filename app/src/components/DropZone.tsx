@@ -5,7 +5,7 @@ import { Upload } from "lucide-react";
 import { useAppStore } from "@/store/appStore";
 import { useToast } from "@/store/toastStore";
 import { processFile, type DocumentType } from "@/lib/fileProcessor";
-import { isTauri } from "@/lib/tauri";
+import { isTauri, errorMessage } from "@/lib/tauri";
 import { UploadTypeModal } from "./UploadTypeModal";
 
 // Play Yuki's thank you sound
@@ -83,9 +83,14 @@ export function DropZone({ children }: DropZoneProps) {
     try {
       setProcessingMessage(`Processing ${currentFile.filename}...`);
       console.log("[DropZone] Calling processFile...");
-      const result = await processFile(file, documentType, currency);
+      let result = await processFile(file, documentType, currency);
       console.log("[DropZone] processFile returned:", result);
 
+      // Re-importing the same statement is allowed, but only when asked for
+      if (result.alreadyImported && confirm(`${currentFile.filename} was already imported. Import it again anyway?`)) {
+        result = await processFile(file, documentType, currency, { allowDuplicate: true });
+      }
+
       // Show success and play sound
       setCurrentResponse({
         cards: [
@@ -101,14 +106,14 @@ export function DropZone({ children }: DropZoneProps) {
       playSound();
     } catch (err) {
       console.error("[DropZone] Processing error:", err);
-      const errorMessage = err instanceof Error ? err.message : "Failed to process file";
-      setError(errorMessage);
+      const message = errorMessage(err, "Failed to process file");
+      setError(message);
       setCurrentResponse({
         cards: [
           {
             type: "text",
             content: {
-              body: `Error: ${errorMessage}`,
+              body: `Error: ${message}`,
               is_error: true,
             },
           },
@@ -225,8 +230,8 @@ export function DropZone({ children }: DropZoneProps) {
 
             } catch (err) {
               console.error("[DropZone] Error reading files:", err);
-              const errorMessage = err instanceof Error ? err.message : "Failed to read file";
-              setError(errorMessage);
+              const message = errorMessage(err, "Failed to read file");
+              setError(message);
               toast.error("Failed to read file");
               setIsAnalyzing(false);
               setProcessingMessage(null);
@@ -156,13 +156,13 @@ export function LedgerModal({ onClose }: LedgerModalProps) {
           },
         ]);
         setCategories([
-          { id: "income", name: "Income", icon: null, color: "#22c55e", is_default: true, created_at: "2025-01-01T00:00:00Z" },
-          { id: "dining", name: "Dining", icon: null, color: "#f59e0b", is_default: true, created_at: "2025-01-01T00:00:00Z" },
-          { id: "groceries", name: "Groceries", icon: null, color: "#3b82f6", is_default: true, created_at: "2025-01-01T00:00:00Z" },
-          { id: "transportation", name: "Transportation", icon: null, color: "#8b5cf6", is_default: true, created_at: "2025-01-01T00:00:00Z" },
-          { id: "entertainment", name: "Entertainment", icon: null, color: "#ec4899", is_default: true, created_at: "2025-01-01T00:00:00Z" },
-          { id: "shopping", name: "Shopping", icon: null, color: "#06b6d4", is_default: true, created_at: "2025-01-01T00:00:00Z" },
-          { id: "other", name: "Other", icon: null, color: "#71717a", is_default: true, created_at: "2025-01-01T00:00:00Z" },
+          { id: "income", name: "Income", icon: null, color: "#22c55e", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
+          { id: "dining", name: "Dining", icon: null, color: "#f59e0b", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
+          { id: "groceries", name: "Groceries", icon: null, color: "#3b82f6", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
+          { id: "transportation", name: "Transportation", icon: null, color: "#8b5cf6", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
+          { id: "entertainment", name: "Entertainment", icon: null, color: "#ec4899", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
+          { id: "shopping", name: "Shopping", icon: null, color: "#06b6d4", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
+          { id: "other", name: "Other", icon: null, color: "#71717a", is_default: true, created_at: "2025-01-01T00:00:00Z", sort_order: null, is_pinned: false },
         ]);
         setAccounts([
           { id: "default", name: "Main Checking", account_type: "checking", institution: "Chase Bank", currency: "USD", is_default: true, created_at: "2025-01-01T00:00:00Z" },
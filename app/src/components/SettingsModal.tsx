@@ -5,6 +5,7 @@ import { Modal } from "./Modal";
 import { useAppStore } from "@/store/appStore";
 import { ThemeToggle } from "./ThemeToggle";
 import type { LLMProviderType, LLMProvider } from "@/types";
+import { errorMessage } from "@/lib/tauri";
 import { Loader2, Check, AlertCircle, Volume2, VolumeX } from "lucide-react";
 
 interface SettingsModalProps {
@@ -135,7 +136,7 @@ export function SettingsModal({ onClose }: SettingsModalProps) {
       }
     } catch (err) {
       setError(
-        err instanceof Error ? err.message : "Failed to fetch models"
+        errorMessage(err, "Failed to fetch models")
       );
     } finally {
       setIsLoadingModels(false);
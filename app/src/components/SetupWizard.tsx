@@ -4,7 +4,7 @@ import { useState, useEffect } from "react";
 import { useAppStore } from "@/store/appStore";
 import type { LLMProviderType, LLMProvider } from "@/types";
 import { Loader2, Check, AlertCircle, ChevronRight, Cloud, HardDrive } from "lucide-react";
-import { getTauriInvoke } from "@/lib/tauri";
+import { getTauriInvoke, errorMessage } from "@/lib/tauri";
 
 interface SetupWizardProps {
   onComplete: () => void;
@@ -133,7 +133,7 @@ export function SetupWizard({ onComplete }: SetupWizardProps) {
       setStep("model");
     } catch (err) {
       console.error("[fetchModels] Error:", err);
-      setError(err instanceof Error ? err.message : "Failed to fetch models");
+      setError(errorMessage(err, "Failed to fetch models"));
     } finally {
       setIsLoadingModels(false);
     }
@@ -174,7 +174,7 @@ export function SetupWizard({ onComplete }: SetupWizardProps) {
       onComplete();
     } catch (err) {
       console.error("[handleComplete] Failed to save settings:", err);
-      setError(err instanceof Error ? err.message : "Failed to save settings");
+      setError(errorMessage(err, "Failed to save settings"));
     } finally {
       setIsSaving(false);
     }
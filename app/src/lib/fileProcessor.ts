@@ -1,7 +1,7 @@
 import { v4 as uuidv4 } from "uuid";
 import { parseDocument, parseImage, parseReceiptText, parseStatementImage } from "./llm";
 import { getTauriInvoke } from "./tauri";
-import type { Document, LedgerEntry, ExtractedTransaction, PurchasedItem } from "@/types";
+import type { Document, LedgerEntry, ExtractedTransaction, PurchasedItem, ImportSummary, YukiError } from "@/types";
 
 export type DocumentType = "statement" | "receipt";
 
@@ -23,9 +23,15 @@ const SUPPORTED_IMAGE_TYPES = [
  * @param file The file to process
  * @param documentType Whether this is a "statement" (adds to ledger) or "receipt" (items only)
  * @param currency The currency code for the transactions (defaults to system default)
+ * @param options How a statement is imported (ignored for receipts)
  * Returns a summary of what was processed.
  */
-export async function processFile(file: File, documentType: DocumentType, currency?: string): Promise<ProcessingResult> {
+export async function processFile(
+  file: File,
+  documentType: DocumentType,
+  currency?: string,
+  options: StatementImportOptions = {}
+): Promise<ProcessingResult> {
   console.log("[processFile] Starting to process:", file.name, "type:", file.type, "documentType:", documentType, "currency:", currency);
   const fileType = file.type || getMimeTypeFromExtension(file.name);
 
@@ -37,7 +43,7 @@ export async function processFile(file: File, documentType: DocumentType, curren
       return result;
     } else {
       // Statement - extract transactions to ledger
-      const result = await processDocument(file, currency);
+      const result = await processDocument(file, currency, options);
       console.log("[processFile] Document processing complete:", result);
       return result;
     }
@@ -49,7 +55,7 @@ export async function processFile(file: File, documentType: DocumentType, curren
       return result;
     } else {
       // Image as statement - still add to ledger (rare case)
-      const result = await processImageAsStatement(file, currency);
+      const result = await processImageAsStatement(file, currency, options);
       console.log("[processFile] Image (statement) processing complete:", result);
       return result;
     }
@@ -60,90 +66,76 @@ export async function processFile(file: File, documentType: DocumentType, curren
   }
 }
 
+export interface StatementImportOptions {
+  allowDuplicate?: boolean; // Import again even though the same file was already imported
+  accountId?: string; // Account the transactions are imported into (defaults to "default")
+  convertToAccountCurrency?: boolean; // Store foreign-currency rows in the account's currency
+  expectedTotal?: number; // Statement's net change; derived from running balances when left out
+  minAmount?: number; // Skip rows below this absolute amount (defaults to the import_min_amount setting)
+}
+
 export interface ProcessingResult {
   filename: string;
   transactionCount: number;
   itemCount?: number;
   message: string;
+  alreadyImported?: boolean; // Skipped because the same file was imported before
 }
 
 /**
- * Save multiple ledger entries in a batch (more efficient for large documents).
- * Uses a single DB connection to avoid locking issues.
+ * Import a statement's transactions under its document in one batch, so the
+ * whole statement can be rolled back. Returns how many rows were saved.
  */
-async function saveLedgerEntriesBatch(
+async function importStatementTransactions(
   transactions: ExtractedTransaction[],
   documentId: string,
-  currencyOverride?: string
+  currencyOverride: string | undefined,
+  options: StatementImportOptions
 ): Promise<number> {
-  console.log("[saveLedgerEntriesBatch] Preparing", transactions.length, "entries for batch save");
-  console.log("[saveLedgerEntriesBatch] Document ID:", documentId);
-  console.log("[saveLedgerEntriesBatch] Currency override:", currencyOverride);
-
+  console.log("[importStatementTransactions] Importing", transactions.length, "transactions for document", documentId);
   if (transactions.length === 0) {
-    console.warn("[saveLedgerEntriesBatch] No transactions to save!");
+    console.warn("[importStatementTransactions] No transactions to save!");
     return 0;
   }
 
-  // Log first transaction for debugging
-  console.log("[saveLedgerEntriesBatch] First transaction:", JSON.stringify(transactions[0]));
-
-  // Convert all transactions to LedgerEntry format
-  const entries: LedgerEntry[] = transactions.map((txn, idx) => {
-    const categoryId = (txn.category || "other").toLowerCase().replace(/\s+/g, '-');
-    const entry = {
-      id: uuidv4(),
-      document_id: documentId,
-      account_id: "default",
-      date: txn.date || new Date().toISOString().split('T')[0],
-      description: txn.description || "Unknown transaction",
-      amount: typeof txn.amount === 'number' ? txn.amount : 0,
-      currency: currencyOverride || txn.currency || "KES",
-      category_id: categoryId,
-      merchant: txn.merchant || null,
-      notes: null,
-      source: "document" as const,
-      created_at: new Date().toISOString(),
-    };
-    if (idx === 0) {
-      console.log("[saveLedgerEntriesBatch] First entry after conversion:", JSON.stringify(entry));
-    }
-    return entry;
-  });
-
-  console.log("[saveLedgerEntriesBatch] Converted", entries.length, "entries");
+  // The backend falls back to the account's currency when a row has none
+  const rows = currencyOverride ? transactions.map((txn) => ({ ...txn, currency: currencyOverride })) : transactions;
 
   const invoke = await getTauriInvoke();
   if (invoke) {
-    console.log("[saveLedgerEntriesBatch] Invoking save_ledger_entries_batch with", entries.length, "entries...");
-    try {
-      const savedCount = await invoke<number>("save_ledger_entries_batch", { entries });
-      console.log("[saveLedgerEntriesBatch] Batch save complete:", savedCount, "entries saved");
-      return savedCount;
-    } catch (error) {
-      console.error("[saveLedgerEntriesBatch] Batch save failed:", error);
-      // Try saving one by one as fallback
-      console.log("[saveLedgerEntriesBatch] Attempting individual saves as fallback...");
-      let fallbackCount = 0;
-      for (const entry of entries) {
-        try {
-          await invoke("save_ledger_entry", { entry });
-          fallbackCount++;
-        } catch (e) {
-          console.error("[saveLedgerEntriesBatch] Individual save failed for:", entry.description, e);
-        }
-      }
-      console.log("[saveLedgerEntriesBatch] Fallback saved:", fallbackCount, "entries");
-      return fallbackCount;
-    }
-  } else {
-    // In browser mode, save to localStorage
-    console.log("[saveLedgerEntriesBatch] Mock batch save:", entries.length, "entries");
-    const existingEntries = JSON.parse(localStorage.getItem("yuki_ledger") || "[]");
-    existingEntries.push(...entries);
-    localStorage.setItem("yuki_ledger", JSON.stringify(existingEntries));
-    return entries.length;
+    const summary = await invoke<ImportSummary>("import_statement_transactions", {
+      documentId,
+      transactions: rows,
+      accountId: options.accountId,
+      convertToAccountCurrency: options.convertToAccountCurrency,
+      expectedTotal: options.expectedTotal,
+      minAmount: options.minAmount,
+    });
+    console.log("[importStatementTransactions] Import complete:", summary);
+    return summary.inserted;
   }
+
+  // In browser mode, save to localStorage
+  console.log("[importStatementTransactions] Mock import:", rows.length, "entries");
+  const entries: LedgerEntry[] = rows.map((txn) => ({
+    id: uuidv4(),
+    document_id: documentId,
+    account_id: options.accountId ?? "default",
+    date: txn.date,
+    description: txn.description,
+    amount: txn.amount,
+    currency: txn.currency || "KES",
+    category_id: (txn.category || "other").toLowerCase().replace(/\s+/g, '-'),
+    merchant: txn.merchant || null,
+    notes: null,
+    source: "document",
+    created_at: new Date().toISOString(),
+    needs_review: txn.needs_review ?? null,
+  }));
+  const existingEntries = JSON.parse(localStorage.getItem("yuki_ledger") || "[]");
+  existingEntries.push(...entries);
+  localStorage.setItem("yuki_ledger", JSON.stringify(existingEntries));
+  return entries.length;
 }
 
 /**
@@ -152,13 +144,20 @@ async function saveLedgerEntriesBatch(
  * @param file The file to process
  * @param currency Optional currency code (defaults to system default)
  */
-async function processDocument(file: File, currency?: string): Promise<ProcessingResult> {
+async function processDocument(
+  file: File,
+  currency: string | undefined,
+  options: StatementImportOptions
+): Promise<ProcessingResult> {
   console.log("[processDocument] Starting:", file.name, "currency:", currency);
   const documentId = uuidv4();
 
   // Save file to local storage
   console.log("[processDocument] Saving file...");
-  const savedPath = await saveFile(file, documentId);
+  const savedPath = await saveStatementFile(file, documentId, options.allowDuplicate);
+  if (savedPath === null) {
+    return alreadyImportedResult(file);
+  }
 
   // Create document record
   const document: Document = {
@@ -174,6 +173,19 @@ async function processDocument(file: File, currency?: string): Promise<Processin
   console.log("[processDocument] Saving document record...");
   await saveDocument(document);
 
+  // A statement that imported nothing mustn't block the file from being retried
+  return discardDocumentUnlessImported(documentId, file, () =>
+    parseAndImportDocument(file, documentId, savedPath, currency, options)
+  );
+}
+
+async function parseAndImportDocument(
+  file: File,
+  documentId: string,
+  savedPath: string,
+  currency: string | undefined,
+  options: StatementImportOptions
+): Promise<ProcessingResult> {
   // Extract text from file
   console.log("[processDocument] Extracting text...");
   const extraction = await extractText(file);
@@ -198,8 +210,7 @@ async function processDocument(file: File, currency?: string): Promise<Processin
     console.log("[processDocument] Vision extracted", transactions.length, "transactions");
     console.log("[processDocument] First few transactions:", JSON.stringify(transactions.slice(0, 3)));
 
-    // Use batch save for better performance and to avoid DB locking issues
-    const savedCount = await saveLedgerEntriesBatch(transactions, documentId, currency);
+    const savedCount = await importStatementTransactions(transactions, documentId, currency, options);
     console.log("[processDocument] Saved", savedCount, "of", transactions.length, "transactions");
 
     return {
@@ -215,14 +226,12 @@ async function processDocument(file: File, currency?: string): Promise<Processin
   console.log("[processDocument] Found", transactions.length, "transactions");
 
   // Save transactions to ledger
-  for (const txn of transactions) {
-    await saveLedgerEntry(txn, documentId, currency);
-  }
+  const savedCount = await importStatementTransactions(transactions, documentId, currency, options);
 
   return {
     filename: file.name,
-    transactionCount: transactions.length,
-    message: `Processed ${file.name}: found ${transactions.length} transaction${transactions.length !== 1 ? 's' : ''}.`,
+    transactionCount: savedCount,
+    message: `Processed ${file.name}: found ${transactions.length} transaction${transactions.length !== 1 ? 's' : ''}, saved ${savedCount}.`,
   };
 }
 
@@ -327,59 +336,66 @@ async function processImageAsReceiptWithPath(
   // Parse image with vision model
   console.log("[processImageAsReceiptWithPath] Parsing receipt with vision LLM for path:", savedPath);
 
-  let receiptData;
+  let receipts;
   try {
-    receiptData = await parseImage(savedPath, categories);
-    console.log("[processImageAsReceiptWithPath] Receipt data:", receiptData);
+    receipts = await parseImage(savedPath, categories);
+    console.log("[processImageAsReceiptWithPath] Found", receipts.length, "receipts:", receipts);
   } catch (error) {
     console.error("[processImageAsReceiptWithPath] Vision parsing failed:", error);
     throw error;
   }
 
-  const receiptId = uuidv4();
   const now = new Date().toISOString();
+  let itemCount = 0;
 
-  // Save receipt details (no ledger_id since we're not creating a ledger entry)
-  await saveReceipt({
-    id: receiptId,
-    document_id: documentId,
-    ledger_id: null, // No ledger entry for receipts
-    merchant: receiptData.merchant,
-    items: receiptData.items.map(item => ({
-      name: item.name,
-      amount: item.total_price,
-    })),
-    tax: receiptData.tax,
-    total: receiptData.total,
-  });
+  // Several receipts photographed together are saved as separate receipts
+  for (const receiptData of receipts) {
+    const receiptId = uuidv4();
 
-  // Save granular purchased items for detailed tracking
-  if (receiptData.items.length > 0) {
-    const purchasedItems: PurchasedItem[] = receiptData.items.map(item => ({
-      id: uuidv4(),
-      receipt_id: receiptId,
-      ledger_id: null, // No ledger entry
-      name: item.name,
-      quantity: item.quantity ?? 1,
-      unit: item.unit,
-      unit_price: item.unit_price,
-      total_price: item.total_price,
-      category: item.category,
-      brand: item.brand,
-      purchased_at: receiptData.date,
-      created_at: now,
-    }));
+    // Save receipt details (no ledger_id since we're not creating a ledger entry)
+    await saveReceipt({
+      id: receiptId,
+      document_id: documentId,
+      ledger_id: null, // No ledger entry for receipts
+      merchant: receiptData.merchant,
+      items: receiptData.items.map(item => ({
+        name: item.name,
+        amount: item.total_price,
+      })),
+      tax: receiptData.tax,
+      total: receiptData.total,
+    });
 
-    console.log("[processImageAsReceiptWithPath] Saving", purchasedItems.length, "purchased items...");
-    await savePurchasedItems(purchasedItems);
+    // Save granular purchased items for detailed tracking
+    if (receiptData.items.length > 0) {
+      const purchasedItems: PurchasedItem[] = receiptData.items.map(item => ({
+        id: uuidv4(),
+        receipt_id: receiptId,
+        ledger_id: null, // No ledger entry
+        name: item.name,
+        quantity: item.quantity ?? 1,
+        unit: item.unit,
+        unit_price: item.unit_price,
+        total_price: item.total_price,
+        category: item.category,
+        brand: item.brand,
+        purchased_at: receiptData.date,
+        created_at: now,
+      }));
+
+      console.log("[processImageAsReceiptWithPath] Saving", purchasedItems.length, "purchased items...");
+      await savePurchasedItems(purchasedItems);
+    }
+    itemCount += receiptData.items.length;
   }
 
-  const itemCount = receiptData.items.length;
+  const total = receipts.reduce((sum, r) => sum + r.total, 0);
+  const source = receipts.length === 1 ? receipts[0].merchant : `${receipts.length} receipts`;
   return {
     filename: savedPath.split('/').pop() || "receipt",
     transactionCount: 0, // No ledger entries
     itemCount: itemCount,
-    message: `Processed receipt from ${receiptData.merchant}: ${itemCount} item${itemCount !== 1 ? 's' : ''} ($${receiptData.total.toFixed(2)} total).`,
+    message: `Processed receipt from ${source}: ${itemCount} item${itemCount !== 1 ? 's' : ''} ($${total.toFixed(2)} total).`,
   };
 }
 
@@ -417,12 +433,19 @@ async function processImageAsReceipt(file: File): Promise<ProcessingResult> {
  * Process an image file as a statement (creates ledger entries).
  * This is for cases where someone uploads an image of a bank statement.
  */
-async function processImageAsStatement(file: File, currency?: string): Promise<ProcessingResult> {
+async function processImageAsStatement(
+  file: File,
+  currency: string | undefined,
+  options: StatementImportOptions
+): Promise<ProcessingResult> {
   console.log("[processImageAsStatement] Starting:", file.name, "currency:", currency);
   const documentId = uuidv4();
 
   // Save file to local storage
-  const savedPath = await saveFile(file, documentId);
+  const savedPath = await saveStatementFile(file, documentId, options.allowDuplicate);
+  if (savedPath === null) {
+    return alreadyImportedResult(file);
+  }
 
   // Create document record
   const document: Document = {
@@ -436,30 +459,58 @@ async function processImageAsStatement(file: File, currency?: string): Promise<P
 
   await saveDocument(document);
 
-  // Get categories for parsing
-  const categories = await getCategories();
+  return discardDocumentUnlessImported(documentId, file, async () => {
+    // Get categories for parsing
+    const categories = await getCategories();
 
-  // Parse image with vision model - use statement parser for multiple transactions
-  console.log("[processImageAsStatement] Using vision-based statement parser...");
-  const transactions = await parseStatementImage(savedPath, categories);
-  console.log("[processImageAsStatement] Extracted", transactions.length, "transactions");
+    // Parse image with vision model - use statement parser for multiple transactions
+    console.log("[processImageAsStatement] Using vision-based statement parser...");
+    const transactions = await parseStatementImage(savedPath, categories);
+    console.log("[processImageAsStatement] Extracted", transactions.length, "transactions");
+
+    // Save all transactions to ledger
+    const savedCount = await importStatementTransactions(transactions, documentId, currency, options);
+
+    return {
+      filename: file.name,
+      transactionCount: savedCount,
+      message: `Processed statement image: found ${transactions.length} transaction${transactions.length !== 1 ? 's' : ''}, saved ${savedCount}.`,
+    };
+  });
+}
 
-  // Save all transactions to ledger
-  for (const txn of transactions) {
-    await saveLedgerEntry(txn, documentId, currency);
+/**
+ * Run a statement import for a saved document, deleting the document (and its
+ * stored file) when the import throws or saves nothing. Otherwise its hash would
+ * mark the file as already imported and every retry would be skipped.
+ */
+async function discardDocumentUnlessImported(
+  documentId: string,
+  file: File,
+  importStatement: () => Promise<ProcessingResult>
+): Promise<ProcessingResult> {
+  let result: ProcessingResult;
+  try {
+    result = await importStatement();
+  } catch (error) {
+    await deleteDocument(documentId);
+    throw error;
   }
 
-  return {
-    filename: file.name,
-    transactionCount: transactions.length,
-    message: `Processed statement image: found ${transactions.length} transaction${transactions.length !== 1 ? 's' : ''}.`,
-  };
+  if (result.transactionCount === 0) {
+    await deleteDocument(documentId);
+    return {
+      ...result,
+      message: `No transactions were imported from ${file.name}. You can drop it again to retry.`,
+    };
+  }
+  return result;
 }
 
 /**
  * Save a file to local storage.
  */
-async function saveFile(file: File, documentId: string): Promise<string> {
+async function saveFile(file: File, documentId: string, allowDuplicate = true): Promise<string> {
   const invoke = await getTauriInvoke();
   if (invoke) {
     const arrayBuffer = await file.arrayBuffer();
@@ -468,6 +519,7 @@ async function saveFile(file: File, documentId: string): Promise<string> {
       filename: file.name,
       documentId,
       data: Array.from(bytes),
+      allowDuplicate,
     });
   }
 
@@ -476,6 +528,31 @@ async function saveFile(file: File, documentId: string): Promise<string> {
   return `/documents/${documentId}/${file.name}`;
 }
 
+/**
+ * Save a statement file, or return null when the same file was already imported.
+ */
+async function saveStatementFile(file: File, documentId: string, allowDuplicate = false): Promise<string | null> {
+  try {
+    return await saveFile(file, documentId, allowDuplicate);
+  } catch (error) {
+    const { kind, existingDocumentId } = error as Partial<YukiError>;
+    if (kind === "alreadyImported") {
+      console.log("[saveStatementFile] Already imported as document", existingDocumentId);
+      return null;
+    }
+    throw error;
+  }
+}
+
+function alreadyImportedResult(file: File): ProcessingResult {
+  return {
+    filename: file.name,
+    transactionCount: 0,
+    message: `${file.name} was already imported, so it was skipped.`,
+    alreadyImported: true,
+  };
+}
+
 interface TextExtractionResult {
   text: string;
   isScanned: boolean;  // True if PDF appears to be a scan (use vision instead)
@@ -552,6 +629,24 @@ async function saveDocument(document: Document): Promise<void> {
   }
 }
 
+/**
+ * Delete a document record and its stored file. Best effort: a failure is logged
+ * so it doesn't hide the error that led here.
+ */
+async function deleteDocument(documentId: string): Promise<void> {
+  const invoke = await getTauriInvoke();
+  try {
+    if (invoke) {
+      await invoke("delete_document", { documentId });
+    } else {
+      const docs: Document[] = JSON.parse(localStorage.getItem("yuki_documents") || "[]");
+      localStorage.setItem("yuki_documents", JSON.stringify(docs.filter((d) => d.id !== documentId)));
+    }
+  } catch (error) {
+    console.error("[deleteDocument] Failed to delete document", documentId, error);
+  }
+}
+
 /**
  * Get available categories.
  */
@@ -581,64 +676,6 @@ async function getCategories(): Promise<string[]> {
   ];
 }
 
-/**
- * Save a ledger entry from extracted transaction data.
- */
-async function saveLedgerEntry(
-  txn: ExtractedTransaction,
-  documentId: string,
-  currencyOverride?: string
-): Promise<void> {
-  console.log("[saveLedgerEntry] Saving transaction:", txn.description, "currency:", currencyOverride || txn.currency);
-
-  // Convert category name to lowercase ID to match database
-  // Database has IDs like "income", "housing", "dining" (lowercase)
-  // LLM returns names like "Income", "Housing", "Dining" (capitalized)
-  const categoryId = txn.category.toLowerCase().replace(/\s+/g, '-');
-
-  const entry: LedgerEntry = {
-    id: uuidv4(),
-    document_id: documentId,
-    account_id: "default",
-    date: txn.date,
-    description: txn.description,
-    amount: txn.amount,
-    currency: currencyOverride || txn.currency,
-    category_id: categoryId,
-    merchant: txn.merchant,
-    notes: null,
-    source: "document",
-    created_at: new Date().toISOString(),
-  };
-
-  console.log("[saveLedgerEntry] Entry:", JSON.stringify(entry));
-  await saveLedgerEntryDirect(entry);
-  console.log("[saveLedgerEntry] Saved successfully");
-}
-
-/**
- * Save a ledger entry directly.
- */
-async function saveLedgerEntryDirect(entry: LedgerEntry): Promise<void> {
-  const invoke = await getTauriInvoke();
-  if (invoke) {
-    console.log("[saveLedgerEntryDirect] Invoking save_ledger_entry...");
-    try {
-      await invoke("save_ledger_entry", { entry });
-      console.log("[saveLedgerEntryDirect] Invoke successful");
-    } catch (error) {
-      console.error("[saveLedgerEntryDirect] Invoke failed:", error);
-      throw error;
-    }
-  } else {
-    // In browser mode, save to localStorage
-    console.log("[saveLedgerEntryDirect] Mock save ledger entry:", entry.description);
-    const entries = JSON.parse(localStorage.getItem("yuki_ledger") || "[]");
-    entries.push(entry);
-    localStorage.setItem("yuki_ledger", JSON.stringify(entries));
-  }
-}
-
 /**
  * Save receipt details.
  */
@@ -5,7 +5,7 @@ import type {
   LLMProvider,
   ParsedReceiptItem,
 } from "@/types";
-import { isTauri, getTauriInvoke } from "./tauri";
+import { isTauri, getTauriInvoke, errorMessage } from "./tauri";
 
 /**
  * Send a natural language query to the LLM for processing.
@@ -32,7 +32,7 @@ export async function sendQuery(question: string): Promise<ResponseData> {
           {
             type: "text",
             content: {
-              body: `Error processing query: ${error instanceof Error ? error.message : String(error)}`,
+              body: `Error processing query: ${errorMessage(error)}`,
               is_error: true,
             },
           },
@@ -178,19 +178,20 @@ export async function parseReceiptText(
 
 /**
  * Parse an image (receipt) using vision model.
- * Returns detailed item information for granular tracking.
+ * Returns detailed item information for granular tracking, one entry per
+ * receipt when several receipts are photographed together.
  */
 export async function parseImage(
   imagePath: string,
   categories: string[]
-): Promise<{
+): Promise<Array<{
   merchant: string;
   date: string;
   items: ParsedReceiptItem[];
   tax: number | null;
   total: number;
   category: string;
-}> {
+}>> {
   console.log("[parseImage] Starting vision parsing for:", imagePath);
   const invoke = await getTauriInvoke();
   if (invoke) {
@@ -198,14 +199,14 @@ export async function parseImage(
       console.log("[parseImage] Calling parse_receipt_image...");
       const result = await invoke("parse_receipt_image", { imagePath, categories });
       console.log("[parseImage] Vision result:", result);
-      return result as {
+      return result as Array<{
         merchant: string;
         date: string;
         items: ParsedReceiptItem[];
         tax: number | null;
         total: number;
         category: string;
-      };
+      }>;
     } catch (error) {
       console.error("[parseImage] Parse receipt error:", error);
       // Re-throw to let caller handle it
@@ -214,7 +215,7 @@ export async function parseImage(
   }
 
   // Mock for browser development with detailed items
-  return {
+  return [{
     merchant: "Sample Grocery Store",
     date: new Date().toISOString().split("T")[0],
     items: [
@@ -240,7 +241,7 @@ export async function parseImage(
     tax: 0.98,
     total: 13.25,
     category: "Groceries",
-  };
+  }];
 }
 
 /**
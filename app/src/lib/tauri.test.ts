@@ -0,0 +1,20 @@
+import { describe, it, expect } from "vitest";
+import { errorMessage } from "./tauri";
+
+describe("errorMessage", () => {
+  it("reads the message of a rejected invoke's YukiError", () => {
+    expect(errorMessage({ kind: "validation", message: "Budget must be zero or more" })).toBe(
+      "Budget must be zero or more"
+    );
+  });
+
+  it("reads Errors and strings", () => {
+    expect(errorMessage(new Error("boom"))).toBe("boom");
+    expect(errorMessage("plain text")).toBe("plain text");
+  });
+
+  it("falls back when there is nothing readable", () => {
+    expect(errorMessage({ kind: "database" }, "Failed to save entry")).toBe("Failed to save entry");
+    expect(errorMessage(undefined)).toBe("An error occurred");
+  });
+});
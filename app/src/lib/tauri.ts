@@ -30,3 +30,17 @@ export async function getTauriInvoke(): Promise<typeof import("@tauri-apps/api/c
     return null;
   }
 }
+
+/**
+ * Readable text for a caught error. Rejected invokes carry a serialized YukiError
+ * ({ kind, message }) rather than an Error, so both shapes are read.
+ */
+export function errorMessage(err: unknown, fallback = "An error occurred"): string {
+  if (err instanceof Error) return err.message;
+  if (typeof err === "string") return err || fallback;
+  if (err && typeof err === "object" && "message" in err) {
+    const { message } = err as { message: unknown };
+    if (typeof message === "string" && message) return message;
+  }
+  return fallback;
+}
@@ -39,8 +39,11 @@ export interface LedgerEntry {
   category_id: string;
   merchant: string | null;
   notes: string | null;
-  source: "document" | "image" | "conversation" | "manual" | "scanned-pdf";
+  source: "document" | "image" | "conversation" | "manual" | "scanned-pdf" | "recurring";
   created_at: string;
+  original_amount?: number | null;
+  original_currency?: string | null;
+  needs_review?: boolean | null;
 }
 
 export interface Receipt {
@@ -90,6 +93,27 @@ export interface Category {
   color: string | null;
   is_default: boolean;
   created_at: string;
+  sort_order: number | null;
+  is_pinned: boolean;
+}
+
+// A bill posted to the ledger each month (post_due_recurring)
+export interface RecurringTemplate {
+  id: string;
+  merchant: string;
+  amount: number;
+  category_id: string;
+  account_id: string;
+  day_of_month: number;
+  active: boolean;
+  last_posted_cycle: string | null;
+  created_at: string;
+}
+
+// What post_due_recurring added; skipped templates point at a deleted account
+export interface RecurringPostSummary {
+  posted: number;
+  skipped: RecurringTemplate[];
 }
 
 export interface ChatHistoryEntry {
@@ -178,6 +202,27 @@ export interface LLMProvider {
   apiKey?: string;
   model: string;
   isLocal: boolean;
+  requestsPerMinute?: number;
+}
+
+// Defaults for prefilling provider setup (get_provider_presets)
+export interface ProviderPreset {
+  type: LLMProviderType;
+  name: string;
+  defaultEndpoint: string;
+  defaultModel: string | null;
+  requiresApiKey: boolean;
+  supportsVision: boolean;
+  isLocal: boolean;
+}
+
+// What the configured provider supports (get_provider_capabilities)
+export interface ProviderCapabilities {
+  vision: boolean;
+  streaming: boolean;
+  json_mode: boolean;
+  embeddings: boolean;
+  model_listing: boolean;
 }
 
 export interface Settings {
@@ -185,6 +230,12 @@ export interface Settings {
   defaultCurrency: string;
   theme: "light" | "dark" | "system";
   soundEnabled: boolean;
+  logLevel?: "off" | "error" | "warn" | "info" | "debug" | "trace";
+  logBodies?: boolean;
+  localOnly?: boolean;
+  importWebhookUrl?: string;
+  importWebhookSecret?: string;
+  importMinAmount?: number;
 }
 
 // Application state types
@@ -207,6 +258,15 @@ export interface CommandResult<T> {
   error?: string;
 }
 
+// Shape of every rejected invoke() (YukiError in src-tauri/src/error.rs)
+export type YukiErrorKind = "noProvider" | "database" | "llm" | "notFound" | "validation" | "alreadyImported";
+
+export interface YukiError {
+  kind: YukiErrorKind;
+  message: string;
+  existingDocumentId?: string; // Set when kind is "alreadyImported"
+}
+
 // Transaction extraction from LLM
 
 export interface ExtractedTransaction {
@@ -216,6 +276,28 @@ export interface ExtractedTransaction {
   currency: string;
   category: string;
   merchant: string | null;
+  needs_review?: boolean;
+  external_id?: string;
+  balance?: number;
+}
+
+// Outcome of an import (ImportSummary in src-tauri/src/models.rs)
+export interface ImportSummary {
+  inserted: number;
+  skipped_duplicates: number;
+  category_unmatched: number;
+  flagged_for_review: number;
+  below_threshold: number;
+  import_id?: string;
+}
+
+// One page range of a chunked statement parse (parse_statement_chunks, reparse_chunk)
+export interface StatementChunk {
+  start_page: number;
+  end_page: number;
+  transactions: ExtractedTransaction[];
+  status: "ok" | "empty" | "failed"; // "empty" is suspect and worth a reparse_chunk
+  error: string | null;
 }
 
 export interface ExpenseDetectionResult {